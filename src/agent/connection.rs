@@ -0,0 +1,195 @@
+//! Client side of the agent protocol: a persistent, authenticated
+//! connection shared by `AgentClient` and `AgentMailSender`.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf,
+    WriteHalf,
+};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::sync::Mutex;
+
+use crate::agent::AgentEndpoint;
+use crate::agent::protocol::{
+    AuthParams, JSONRPC_VERSION, MAX_FRAME_SIZE, Request, Response, methods,
+};
+use crate::error::{NotmuchError, Result};
+
+/// How long to wait for the agent to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+struct Framed {
+    reader: BufReader<ReadHalf<Box<dyn Stream>>>,
+    writer: WriteHalf<Box<dyn Stream>>,
+}
+
+/// A lazily established connection to a `whynot-agent`.
+///
+/// The connection is opened and authenticated on first use and kept open
+/// for subsequent calls. If it turns out to be stale, idempotent calls are
+/// retried once on a fresh connection.
+pub struct AgentConnection {
+    endpoint: AgentEndpoint,
+    token: String,
+    next_id: AtomicU64,
+    stream: Mutex<Option<Framed>>,
+}
+
+impl AgentConnection {
+    pub fn new(endpoint: AgentEndpoint, token: String) -> Self {
+        AgentConnection {
+            endpoint,
+            token,
+            next_id: AtomicU64::new(1),
+            stream: Mutex::new(None),
+        }
+    }
+
+    pub fn endpoint(&self) -> &AgentEndpoint {
+        &self.endpoint
+    }
+
+    /// Call a method on the agent and deserialize its result.
+    pub async fn call<P, R>(&self, method: &str, params: &P) -> Result<R>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params)?;
+        let mut guard = self.stream.lock().await;
+
+        let reused = guard.is_some();
+        let response = match self.exchange(&mut guard, method, params.clone()).await {
+            Err(NotmuchError::IoError(e)) if reused && methods::is_idempotent(method) => {
+                tracing::debug!("Agent connection went stale ({}), reconnecting", e);
+                self.exchange(&mut guard, method, params).await?
+            }
+            other => other?,
+        };
+
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+        let result = response.result.unwrap_or(serde_json::Value::Null);
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Send one request, connecting first if needed. Any I/O failure drops
+    /// the connection so the next call starts afresh.
+    async fn exchange(
+        &self,
+        guard: &mut Option<Framed>,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Response> {
+        if guard.is_none() {
+            *guard = Some(self.connect().await?);
+        }
+        let framed = guard.as_mut().expect("connection just established");
+
+        let result = self.round_trip(framed, method, params).await;
+        if matches!(result, Err(NotmuchError::IoError(_))) {
+            *guard = None;
+        }
+        result
+    }
+
+    async fn connect(&self) -> Result<Framed> {
+        let stream: Box<dyn Stream> = match &self.endpoint {
+            AgentEndpoint::Tcp(addr) => {
+                let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+                    .await
+                    .map_err(|_| {
+                        NotmuchError::IoError(std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            format!("Timed out connecting to agent at {}", addr),
+                        ))
+                    })??;
+                stream.set_nodelay(true)?;
+                Box::new(stream)
+            }
+            #[cfg(unix)]
+            AgentEndpoint::Unix(path) => Box::new(UnixStream::connect(path).await?),
+            #[cfg(not(unix))]
+            AgentEndpoint::Unix(_) => {
+                return Err(NotmuchError::ConfigError(
+                    "Unix sockets are not supported on this platform".to_string(),
+                ));
+            }
+        };
+
+        let (reader, writer) = tokio::io::split(stream);
+        let mut framed = Framed {
+            reader: BufReader::new(reader),
+            writer,
+        };
+
+        let auth = AuthParams {
+            token: self.token.clone(),
+        };
+        let response = self
+            .round_trip(&mut framed, methods::AUTH, serde_json::to_value(auth)?)
+            .await?;
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+
+        tracing::debug!("Connected to agent at {}", self.endpoint);
+        Ok(framed)
+    }
+
+    async fn round_trip(
+        &self,
+        framed: &mut Framed,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Response> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            method: method.to_string(),
+            params,
+        };
+
+        let mut bytes = serde_json::to_vec(&request)?;
+        bytes.push(b'\n');
+        framed.writer.write_all(&bytes).await?;
+        framed.writer.flush().await?;
+
+        let mut line = Vec::new();
+        let read = (&mut framed.reader)
+            .take(MAX_FRAME_SIZE as u64 + 1)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if read == 0 {
+            return Err(NotmuchError::IoError(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Agent closed the connection",
+            )));
+        }
+        if line.len() > MAX_FRAME_SIZE {
+            return Err(NotmuchError::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Agent response too large",
+            )));
+        }
+
+        let response: Response = serde_json::from_slice(&line)?;
+        if response.id.is_some_and(|rid| rid != id) {
+            return Err(NotmuchError::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Agent response id mismatch (expected {})", id),
+            )));
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,45 @@
+//! A small daemon protocol for reaching a notmuch database on another host.
+//!
+//! Instead of spawning one SSH session per notmuch command, a `whynot-agent`
+//! process runs next to the database and serves the full `NotmuchClient`
+//! API (and optionally `MailSender`) over newline-delimited JSON-RPC 2.0 on
+//! a TCP or Unix socket.
+//!
+//! # Overview
+//!
+//! - `AgentServer` - Serves a `NotmuchClient` (and `MailSender`) to agent clients
+//! - `AgentListener` - A bound TCP or Unix listening socket
+//! - `AgentEndpoint` - Parsed `host:port` or `unix:/path` address
+//! - `AgentConnection` - Client transport used by `client::AgentClient`
+//!   and `mail_sender::AgentMailSender`
+//!
+//! # Authentication
+//!
+//! Every connection must first call `auth` with the shared token; all other
+//! methods are rejected until it succeeds. The protocol itself is not
+//! encrypted, so TCP listeners should be bound to loopback or a private
+//! network (for example behind a WireGuard or SSH tunnel). Unix sockets are
+//! created with mode 0600.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use whynot::client::{create_client, ClientConfig, NotmuchClient};
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = create_client(ClientConfig::agent(
+//!     "mail.example.com:7878".to_string(),
+//!     "s3cret".to_string(),
+//! ))?;
+//! let unread = client.search("tag:unread").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod connection;
+pub mod protocol;
+pub mod server;
+
+pub use connection::AgentConnection;
+pub use protocol::AgentEndpoint;
+pub use server::{AgentListener, AgentServer};
@@ -0,0 +1,463 @@
+//! Wire types for the agent JSON-RPC protocol.
+//!
+//! Every request and response is a single JSON-RPC 2.0 object terminated by
+//! a newline. Binary payloads (raw messages, attachment parts) travel as
+//! base64 strings.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::client::{ExpungeOptions, TagOperation};
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::ComposableMessage;
+use crate::search::SearchItem;
+use crate::thread::Message;
+
+/// JSON-RPC protocol version sent in every envelope.
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Largest request or response line accepted, in bytes.
+///
+/// Large enough for inserting messages with sizeable attachments while
+/// keeping unauthenticated peers from exhausting memory.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Method names understood by the agent.
+pub mod methods {
+    pub const AUTH: &str = "auth";
+    pub const SEARCH: &str = "search";
+    pub const SEARCH_PAGINATED: &str = "search_paginated";
+    pub const SHOW: &str = "show";
+    pub const SHOW_HEADERS: &str = "show_headers";
+    pub const TAG: &str = "tag";
+    pub const REFRESH: &str = "refresh";
+    pub const INSERT: &str = "insert";
+    pub const CONFIG_GET: &str = "config_get";
+    pub const CONFIG_SET: &str = "config_set";
+    pub const LIST_TAGS: &str = "list_tags";
+    pub const PART: &str = "part";
+    pub const MOVE_TO_FOLDER: &str = "move_to_folder";
+    pub const EXPUNGE_REPORT: &str = "expunge_report";
+    pub const EXPUNGE: &str = "expunge";
+    pub const MAIL_SEND: &str = "mail.send";
+    pub const MAIL_REPLY: &str = "mail.reply";
+    pub const MAIL_FORWARD: &str = "mail.forward";
+    pub const MAIL_TEST_CONNECTION: &str = "mail.test_connection";
+    pub const MAIL_GET_FROM_ADDRESS: &str = "mail.get_from_address";
+
+    /// Methods that are safe to retry on a fresh connection when the
+    /// previous one turned out to be stale.
+    pub fn is_idempotent(method: &str) -> bool {
+        !matches!(
+            method,
+            INSERT | EXPUNGE | MAIL_SEND | MAIL_REPLY | MAIL_FORWARD | CONFIG_SET
+        )
+    }
+}
+
+/// JSON-RPC error codes used by the agent.
+pub mod codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// The operation itself failed; `data.kind` carries the error variant.
+    pub const OPERATION_FAILED: i64 = -32000;
+    pub const UNAUTHORIZED: i64 = -32001;
+}
+
+/// Where an agent listens, or where a client connects to.
+///
+/// Parsed from strings of the form `tcp:host:port`, `host:port`,
+/// `unix:/path/to/socket` or a bare absolute socket path.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::agent::AgentEndpoint;
+/// let tcp: AgentEndpoint = "127.0.0.1:7878".parse().unwrap();
+/// assert_eq!(tcp, AgentEndpoint::Tcp("127.0.0.1:7878".to_string()));
+///
+/// let unix: AgentEndpoint = "unix:/run/whynot/agent.sock".parse().unwrap();
+/// assert_eq!(unix.to_string(), "unix:/run/whynot/agent.sock");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentEndpoint {
+    /// A TCP `host:port` address.
+    Tcp(String),
+    /// A Unix domain socket path.
+    Unix(PathBuf),
+}
+
+impl std::str::FromStr for AgentEndpoint {
+    type Err = NotmuchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(NotmuchError::ConfigError(
+                    "Agent endpoint 'unix:' requires a socket path".to_string(),
+                ));
+            }
+            return Ok(AgentEndpoint::Unix(PathBuf::from(path)));
+        }
+        if s.starts_with('/') {
+            return Ok(AgentEndpoint::Unix(PathBuf::from(s)));
+        }
+
+        let addr = s.strip_prefix("tcp:").unwrap_or(s);
+        match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(AgentEndpoint::Tcp(addr.to_string()))
+            }
+            _ => Err(NotmuchError::ConfigError(format!(
+                "Invalid agent endpoint '{}': expected host:port or unix:/path",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for AgentEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentEndpoint::Tcp(addr) => write!(f, "tcp:{}", addr),
+            AgentEndpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A JSON-RPC request envelope.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    pub id: u64,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+/// A JSON-RPC response envelope carrying either a result or an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn success(id: u64, result: serde_json::Value) -> Self {
+        Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(id),
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Option<u64>, error: RpcError) -> Self {
+        Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<RpcErrorData>,
+}
+
+/// Extra error detail identifying which `NotmuchError` variant occurred.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcErrorData {
+    pub kind: String,
+    /// The SMTP reply code of a rejected message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_code: Option<u16>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<&NotmuchError> for RpcError {
+    fn from(error: &NotmuchError) -> Self {
+        let mut smtp_code = None;
+        let (kind, message) = match error {
+            NotmuchError::CommandFailed(msg) => ("command_failed", msg.clone()),
+            NotmuchError::SshError(msg) => ("ssh", msg.clone()),
+            NotmuchError::ParseError(e) => ("parse", e.to_string()),
+            NotmuchError::IoError(e) => ("io", e.to_string()),
+            NotmuchError::DatabaseError(msg) => ("database", msg.clone()),
+            NotmuchError::ConfigError(msg) => ("config", msg.clone()),
+            NotmuchError::MailSendError(msg) => ("mail_send", msg.clone()),
+            NotmuchError::SmtpRejected { code, message } => {
+                smtp_code = Some(*code);
+                ("smtp_rejected", message.clone())
+            }
+            NotmuchError::InvalidInput(msg) => ("invalid_input", msg.clone()),
+            NotmuchError::InvalidAddress { .. } => ("invalid_input", error.to_string()),
+        };
+        RpcError {
+            code: codes::OPERATION_FAILED,
+            message,
+            data: Some(RpcErrorData {
+                kind: kind.to_string(),
+                smtp_code,
+            }),
+        }
+    }
+}
+
+impl From<RpcError> for NotmuchError {
+    fn from(error: RpcError) -> Self {
+        let kind = error.data.as_ref().map(|d| d.kind.as_str());
+        let smtp_code = error.data.as_ref().and_then(|d| d.smtp_code);
+        match (error.code, kind) {
+            (codes::UNAUTHORIZED, _) => NotmuchError::ConfigError(format!(
+                "Agent rejected authentication: {}",
+                error.message
+            )),
+            (codes::OPERATION_FAILED, Some("ssh")) => NotmuchError::SshError(error.message),
+            (codes::OPERATION_FAILED, Some("io")) => {
+                NotmuchError::IoError(std::io::Error::other(error.message))
+            }
+            (codes::OPERATION_FAILED, Some("database")) => {
+                NotmuchError::DatabaseError(error.message)
+            }
+            (codes::OPERATION_FAILED, Some("config")) => NotmuchError::ConfigError(error.message),
+            (codes::OPERATION_FAILED, Some("mail_send")) => {
+                NotmuchError::MailSendError(error.message)
+            }
+            (codes::OPERATION_FAILED, Some("smtp_rejected")) => match smtp_code {
+                Some(code) => NotmuchError::SmtpRejected {
+                    code,
+                    message: error.message,
+                },
+                None => NotmuchError::MailSendError(error.message),
+            },
+            (codes::OPERATION_FAILED, Some("invalid_input")) => {
+                NotmuchError::InvalidInput(error.message)
+            }
+            (codes::OPERATION_FAILED, _) => NotmuchError::CommandFailed(error.message),
+            (code, _) => {
+                NotmuchError::CommandFailed(format!("Agent error {}: {}", code, error.message))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthParams {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryParams {
+    pub query: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchPaginatedParams {
+    pub query: String,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub items: Vec<SearchItem>,
+    pub total: Option<usize>,
+}
+
+/// Tag operations are sent in their command-line form (`+tag` / `-tag`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagParams {
+    pub query: String,
+    pub tags: Vec<String>,
+}
+
+impl TagParams {
+    pub fn new(query: &str, tags: &[TagOperation]) -> Self {
+        TagParams {
+            query: query.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    pub fn operations(&self) -> Result<Vec<TagOperation>> {
+        self.tags
+            .iter()
+            .map(|tag| {
+                if let Some(name) = tag.strip_prefix('+') {
+                    Ok(TagOperation::Add(name.to_string()))
+                } else if let Some(name) = tag.strip_prefix('-') {
+                    Ok(TagOperation::Remove(name.to_string()))
+                } else {
+                    Err(NotmuchError::InvalidInput(format!(
+                        "Tag operation must start with + or -: {}",
+                        tag
+                    )))
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InsertParams {
+    /// The RFC 822 message, base64 encoded.
+    pub message: String,
+    pub folder: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigGetParams {
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigSetParams {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartParams {
+    pub message_id: String,
+    pub part_id: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveToFolderParams {
+    pub query: String,
+    pub folder: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpungeReportParams {
+    pub query: String,
+    pub options: ExpungeOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpungeParams {
+    pub query: String,
+    pub options: ExpungeOptions,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendParams {
+    pub message: ComposableMessage,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplyParams {
+    pub original: Message,
+    pub reply: ComposableMessage,
+    pub reply_all: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForwardParams {
+    pub original: Message,
+    pub forward: ComposableMessage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoints() {
+        assert_eq!(
+            "tcp:mail.example.com:7878"
+                .parse::<AgentEndpoint>()
+                .unwrap(),
+            AgentEndpoint::Tcp("mail.example.com:7878".to_string())
+        );
+        assert_eq!(
+            "[::1]:7878".parse::<AgentEndpoint>().unwrap(),
+            AgentEndpoint::Tcp("[::1]:7878".to_string())
+        );
+        assert_eq!(
+            "/tmp/agent.sock".parse::<AgentEndpoint>().unwrap(),
+            AgentEndpoint::Unix(PathBuf::from("/tmp/agent.sock"))
+        );
+        assert!("mail.example.com".parse::<AgentEndpoint>().is_err());
+        assert!("unix:".parse::<AgentEndpoint>().is_err());
+        assert!("host:notaport".parse::<AgentEndpoint>().is_err());
+    }
+
+    #[test]
+    fn test_tag_params_roundtrip() {
+        let params = TagParams::new(
+            "tag:inbox",
+            &[
+                TagOperation::Add("todo".to_string()),
+                TagOperation::Remove("unread".to_string()),
+            ],
+        );
+        assert_eq!(params.tags, vec!["+todo", "-unread"]);
+
+        let ops = params.operations().unwrap();
+        assert!(matches!(&ops[0], TagOperation::Add(t) if t == "todo"));
+        assert!(matches!(&ops[1], TagOperation::Remove(t) if t == "unread"));
+
+        let bad = TagParams {
+            query: "*".to_string(),
+            tags: vec!["todo".to_string()],
+        };
+        assert!(bad.operations().is_err());
+    }
+
+    #[test]
+    fn test_error_kind_roundtrip() {
+        let original = NotmuchError::DatabaseError("locked".to_string());
+        let rpc = RpcError::from(&original);
+        assert_eq!(rpc.code, codes::OPERATION_FAILED);
+
+        match NotmuchError::from(rpc) {
+            NotmuchError::DatabaseError(msg) => assert_eq!(msg, "locked"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let rejected = NotmuchError::SmtpRejected {
+            code: 550,
+            message: "No such user".to_string(),
+        };
+        match NotmuchError::from(RpcError::from(&rejected)) {
+            NotmuchError::SmtpRejected { code, message } => {
+                assert_eq!(code, 550);
+                assert_eq!(message, "No such user");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let unauthorized = RpcError::new(codes::UNAUTHORIZED, "invalid token");
+        assert!(matches!(
+            NotmuchError::from(unauthorized),
+            NotmuchError::ConfigError(_)
+        ));
+    }
+}
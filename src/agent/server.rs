@@ -0,0 +1,383 @@
+//! The agent server, which answers JSON-RPC requests by delegating to a
+//! local `NotmuchClient` and, optionally, a `MailSender`.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+use crate::agent::AgentEndpoint;
+use crate::agent::protocol::{
+    AuthParams, ConfigGetParams, ConfigSetParams, ExpungeParams, ExpungeReportParams,
+    ForwardParams, InsertParams, JSONRPC_VERSION, MAX_FRAME_SIZE, MoveToFolderParams, PartParams,
+    QueryParams, ReplyParams, Request, Response, RpcError, SearchPage, SearchPaginatedParams,
+    SendParams, TagParams, codes, methods,
+};
+use crate::client::NotmuchClient;
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::MailSender;
+
+/// A bound listening socket for the agent.
+pub enum AgentListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl AgentListener {
+    /// Bind to the given endpoint.
+    ///
+    /// For Unix sockets a stale socket file is removed first and the new
+    /// socket is restricted to the current user (mode 0600).
+    pub async fn bind(endpoint: &AgentEndpoint) -> Result<Self> {
+        match endpoint {
+            AgentEndpoint::Tcp(addr) => Ok(AgentListener::Tcp(TcpListener::bind(addr).await?)),
+            #[cfg(unix)]
+            AgentEndpoint::Unix(path) => {
+                use std::os::unix::fs::PermissionsExt;
+
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+                Ok(AgentListener::Unix(listener))
+            }
+            #[cfg(not(unix))]
+            AgentEndpoint::Unix(_) => Err(NotmuchError::ConfigError(
+                "Unix sockets are not supported on this platform".to_string(),
+            )),
+        }
+    }
+
+    /// The endpoint actually bound, useful when binding TCP port 0.
+    pub fn local_endpoint(&self) -> Result<AgentEndpoint> {
+        match self {
+            AgentListener::Tcp(listener) => {
+                Ok(AgentEndpoint::Tcp(listener.local_addr()?.to_string()))
+            }
+            #[cfg(unix)]
+            AgentListener::Unix(listener) => {
+                let addr = listener.local_addr()?;
+                let path = addr.as_pathname().ok_or_else(|| {
+                    NotmuchError::ConfigError("Unix socket has no path".to_string())
+                })?;
+                Ok(AgentEndpoint::Unix(path.to_path_buf()))
+            }
+        }
+    }
+}
+
+/// Serves the `NotmuchClient` (and optionally `MailSender`) API to agent
+/// clients.
+///
+/// Each connection must authenticate with the shared token before any other
+/// method is accepted. Requests on a connection are handled in order;
+/// connections are handled concurrently.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use whynot::agent::{AgentEndpoint, AgentListener, AgentServer};
+/// # use whynot::client::{ClientConfig, LocalClient};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = LocalClient::new(ClientConfig::local())?;
+/// let server = AgentServer::new(Arc::new(client), "s3cret".to_string());
+///
+/// let endpoint: AgentEndpoint = "unix:/run/user/1000/whynot-agent.sock".parse()?;
+/// let listener = AgentListener::bind(&endpoint).await?;
+/// server.serve(listener).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AgentServer {
+    client: Arc<dyn NotmuchClient>,
+    mail_sender: Option<Arc<dyn MailSender>>,
+    token: Arc<String>,
+}
+
+impl AgentServer {
+    pub fn new(client: Arc<dyn NotmuchClient>, token: String) -> Self {
+        AgentServer {
+            client,
+            mail_sender: None,
+            token: Arc::new(token),
+        }
+    }
+
+    /// Also expose a mail sender through the `mail.*` methods.
+    pub fn with_mail_sender(mut self, mail_sender: Arc<dyn MailSender>) -> Self {
+        self.mail_sender = Some(mail_sender);
+        self
+    }
+
+    /// Accept connections forever, handling each on its own task.
+    pub async fn serve(self, listener: AgentListener) -> Result<()> {
+        loop {
+            match &listener {
+                AgentListener::Tcp(listener) => {
+                    let (stream, peer) = listener.accept().await?;
+                    tracing::debug!("Agent connection from {}", peer);
+                    let server = self.clone();
+                    tokio::spawn(async move { server.handle_connection(stream).await });
+                }
+                #[cfg(unix)]
+                AgentListener::Unix(listener) => {
+                    let (stream, _) = listener.accept().await?;
+                    tracing::debug!("Agent connection on unix socket");
+                    let server = self.clone();
+                    tokio::spawn(async move { server.handle_connection(stream).await });
+                }
+            }
+        }
+    }
+
+    /// Handle a single connection until the peer disconnects.
+    pub async fn handle_connection<S>(&self, stream: S)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        let mut authenticated = false;
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            let read = (&mut reader)
+                .take(MAX_FRAME_SIZE as u64 + 1)
+                .read_until(b'\n', &mut line)
+                .await;
+
+            let response = match read {
+                Ok(0) => break,
+                Ok(_) if line.len() > MAX_FRAME_SIZE => {
+                    let response = Response::failure(
+                        None,
+                        RpcError::new(codes::INVALID_REQUEST, "Request too large"),
+                    );
+                    let _ = write_response(&mut writer, &response).await;
+                    break;
+                }
+                Ok(_) => match serde_json::from_slice::<Request>(&line) {
+                    Ok(request) => self.handle_request(request, &mut authenticated).await,
+                    Err(e) => Response::failure(
+                        None,
+                        RpcError::new(codes::PARSE_ERROR, format!("Invalid request: {}", e)),
+                    ),
+                },
+                Err(e) => {
+                    tracing::debug!("Agent connection read failed: {}", e);
+                    break;
+                }
+            };
+
+            if let Err(e) = write_response(&mut writer, &response).await {
+                tracing::debug!("Agent connection write failed: {}", e);
+                break;
+            }
+        }
+    }
+
+    async fn handle_request(&self, request: Request, authenticated: &mut bool) -> Response {
+        if request.jsonrpc != JSONRPC_VERSION {
+            return Response::failure(
+                Some(request.id),
+                RpcError::new(codes::INVALID_REQUEST, "Unsupported jsonrpc version"),
+            );
+        }
+
+        if request.method == methods::AUTH {
+            return match serde_json::from_value::<AuthParams>(request.params) {
+                Ok(params) if constant_time_eq(params.token.as_bytes(), self.token.as_bytes()) => {
+                    *authenticated = true;
+                    Response::success(request.id, serde_json::Value::Bool(true))
+                }
+                Ok(_) => {
+                    tracing::warn!("Agent authentication failed");
+                    Response::failure(
+                        Some(request.id),
+                        RpcError::new(codes::UNAUTHORIZED, "Invalid token"),
+                    )
+                }
+                Err(e) => Response::failure(
+                    Some(request.id),
+                    RpcError::new(codes::INVALID_PARAMS, e.to_string()),
+                ),
+            };
+        }
+
+        if !*authenticated {
+            return Response::failure(
+                Some(request.id),
+                RpcError::new(codes::UNAUTHORIZED, "Authentication required"),
+            );
+        }
+
+        let id = request.id;
+        match self.dispatch(&request.method, request.params).await {
+            Ok(result) => Response::success(id, result),
+            Err(error) => Response::failure(Some(id), error),
+        }
+    }
+
+    async fn dispatch(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> std::result::Result<serde_json::Value, RpcError> {
+        let client = &self.client;
+        match method {
+            methods::SEARCH => {
+                let p: QueryParams = parse_params(params)?;
+                respond(client.search(&p.query).await)
+            }
+            methods::SEARCH_PAGINATED => {
+                let p: SearchPaginatedParams = parse_params(params)?;
+                let result = client.search_paginated(&p.query, p.offset, p.limit).await;
+                respond(result.map(|(items, total)| SearchPage { items, total }))
+            }
+            methods::SHOW => {
+                let p: QueryParams = parse_params(params)?;
+                respond(client.show(&p.query).await)
+            }
+            methods::SHOW_HEADERS => {
+                let p: QueryParams = parse_params(params)?;
+                respond(client.show_headers(&p.query).await)
+            }
+            methods::TAG => {
+                let p: TagParams = parse_params(params)?;
+                let ops = p.operations().map_err(|e| RpcError::from(&e))?;
+                respond(client.tag(&p.query, &ops).await)
+            }
+            methods::REFRESH => respond(client.refresh().await),
+            methods::INSERT => {
+                let p: InsertParams = parse_params(params)?;
+                let message = STANDARD.decode(&p.message).map_err(|e| {
+                    RpcError::new(codes::INVALID_PARAMS, format!("Invalid base64: {}", e))
+                })?;
+                let tags: Vec<&str> = p.tags.iter().map(String::as_str).collect();
+                respond(client.insert(&message, p.folder.as_deref(), &tags).await)
+            }
+            methods::CONFIG_GET => {
+                let p: ConfigGetParams = parse_params(params)?;
+                respond(client.config_get(&p.key).await)
+            }
+            methods::CONFIG_SET => {
+                let p: ConfigSetParams = parse_params(params)?;
+                respond(client.config_set(&p.key, &p.value).await)
+            }
+            methods::LIST_TAGS => respond(client.list_tags().await),
+            methods::PART => {
+                let p: PartParams = parse_params(params)?;
+                let result = client.part(&p.message_id, p.part_id).await;
+                respond(result.map(|bytes| STANDARD.encode(bytes)))
+            }
+            methods::MOVE_TO_FOLDER => {
+                let p: MoveToFolderParams = parse_params(params)?;
+                respond(client.move_to_folder(&p.query, &p.folder).await)
+            }
+            methods::EXPUNGE_REPORT => {
+                let p: ExpungeReportParams = parse_params(params)?;
+                respond(client.expunge_report(&p.query, &p.options).await)
+            }
+            methods::EXPUNGE => {
+                let p: ExpungeParams = parse_params(params)?;
+                respond(client.expunge(&p.query, &p.options, &p.token).await)
+            }
+            m if m.starts_with("mail.") => self.dispatch_mail(m, params).await,
+            _ => Err(RpcError::new(
+                codes::METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            )),
+        }
+    }
+
+    async fn dispatch_mail(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> std::result::Result<serde_json::Value, RpcError> {
+        let sender = self.mail_sender.as_ref().ok_or_else(|| {
+            RpcError::new(
+                codes::METHOD_NOT_FOUND,
+                "Mail sending is not enabled on this agent",
+            )
+        })?;
+
+        match method {
+            methods::MAIL_SEND => {
+                let p: SendParams = parse_params(params)?;
+                respond(sender.send(p.message).await)
+            }
+            methods::MAIL_REPLY => {
+                let p: ReplyParams = parse_params(params)?;
+                respond(sender.reply(&p.original, p.reply, p.reply_all).await)
+            }
+            methods::MAIL_FORWARD => {
+                let p: ForwardParams = parse_params(params)?;
+                respond(sender.forward(&p.original, p.forward).await)
+            }
+            methods::MAIL_TEST_CONNECTION => respond(sender.test_connection().await),
+            methods::MAIL_GET_FROM_ADDRESS => respond(sender.get_from_address().await),
+            _ => Err(RpcError::new(
+                codes::METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            )),
+        }
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(
+    params: serde_json::Value,
+) -> std::result::Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(codes::INVALID_PARAMS, e.to_string()))
+}
+
+fn respond<T: Serialize>(result: Result<T>) -> std::result::Result<serde_json::Value, RpcError> {
+    match result {
+        Ok(value) => {
+            serde_json::to_value(value).map_err(|e| RpcError::from(&NotmuchError::from(e)))
+        }
+        Err(e) => Err(RpcError::from(&e)),
+    }
+}
+
+async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    response: &Response,
+) -> std::io::Result<()> {
+    let mut bytes = serde_json::to_vec(response)?;
+    bytes.push(b'\n');
+    writer.write_all(&bytes).await?;
+    writer.flush().await
+}
+
+/// Compare tokens without short-circuiting on the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(!constant_time_eq(b"", b"x"));
+    }
+}
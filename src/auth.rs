@@ -0,0 +1,693 @@
+//! Sender authentication and phishing indicators.
+//!
+//! The receiving mail server records whether a message passed SPF, DKIM and
+//! DMARC in an `Authentication-Results` header (RFC 8601), and some servers
+//! add a `Received-SPF` header (RFC 7208) as well. [`Authentication`] reads
+//! these into a typed verdict, falling back to the `DKIM-Signature` header
+//! to at least name the signing domain when nothing verified it.
+//!
+//! Authentication says nothing about names that merely look trustworthy, so
+//! [`phishing_warnings`] also flags display names showing an address at
+//! another domain (`"support@bank.com" <x@evil.net>`) and From domains that
+//! resemble, but are not, a domain the user cares about.
+//!
+//! Anyone can add an `Authentication-Results` header to a message, so only
+//! results from the user's own servers count: those whose authserv-id is
+//! configured as `trusted_authserv_ids` (RFC 8601 section 5). notmuch only
+//! outputs the first occurrence of a header, which is the topmost one, added
+//! last. The headers have to be added to notmuch's `show.extra_headers`
+//! setting first:
+//!
+//! ```text
+//! notmuch config set show.extra_headers 'Authentication-Results;Received-SPF;DKIM-Signature'
+//! ```
+
+use std::fmt;
+
+use crate::common::Headers;
+
+/// Result of one authentication method (RFC 8601 section 2.7).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthResult {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+    /// The message was not signed, or the domain publishes no policy
+    None,
+    TempError,
+    PermError,
+    /// Authenticated, but local policy rejected the result
+    Policy,
+}
+
+impl AuthResult {
+    /// Parse a result keyword, case-insensitively.
+    pub fn parse(value: &str) -> Option<AuthResult> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pass" => Some(AuthResult::Pass),
+            "fail" | "hardfail" => Some(AuthResult::Fail),
+            "softfail" => Some(AuthResult::SoftFail),
+            "neutral" => Some(AuthResult::Neutral),
+            "none" => Some(AuthResult::None),
+            "temperror" => Some(AuthResult::TempError),
+            "permerror" => Some(AuthResult::PermError),
+            "policy" => Some(AuthResult::Policy),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthResult::Pass => "pass",
+            AuthResult::Fail => "fail",
+            AuthResult::SoftFail => "softfail",
+            AuthResult::Neutral => "neutral",
+            AuthResult::None => "none",
+            AuthResult::TempError => "temperror",
+            AuthResult::PermError => "permerror",
+            AuthResult::Policy => "policy",
+        }
+    }
+
+    /// Whether the check positively failed.
+    pub fn is_fail(&self) -> bool {
+        matches!(self, AuthResult::Fail | AuthResult::SoftFail)
+    }
+}
+
+impl fmt::Display for AuthResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Overall verdict of an [`Authentication`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthStatus {
+    /// DMARC passed, or without DMARC a DKIM signature of the From domain
+    /// verified
+    Verified,
+    /// DMARC failed, or SPF failed with no valid DKIM signature
+    Failed,
+    /// Nothing conclusive, including messages without any results
+    Unverified,
+}
+
+impl AuthStatus {
+    /// A short label for badges.
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuthStatus::Verified => "Verified",
+            AuthStatus::Failed => "Failed authentication",
+            AuthStatus::Unverified => "Unverified",
+        }
+    }
+
+    /// Lower-case name, used as CSS class suffix.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthStatus::Verified => "verified",
+            AuthStatus::Failed => "failed",
+            AuthStatus::Unverified => "unverified",
+        }
+    }
+}
+
+/// SPF, DKIM and DMARC results of a message.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Authentication {
+    pub spf: Option<AuthResult>,
+    pub dkim: Option<AuthResult>,
+    pub dmarc: Option<AuthResult>,
+    /// The DKIM signing domain (`header.d`, or `d=` of `DKIM-Signature`)
+    pub dkim_domain: Option<String>,
+    /// The server that produced the results
+    pub authserv_id: Option<String>,
+    /// Domain of the From address, for DKIM alignment
+    from_domain: Option<String>,
+}
+
+impl Authentication {
+    /// Read the authentication headers of a message.
+    ///
+    /// `Authentication-Results` are ignored unless their authserv-id is one
+    /// of `trusted_authserv_ids`, compared case-insensitively.
+    pub fn from_headers(headers: &Headers, trusted_authserv_ids: &[String]) -> Authentication {
+        let mut auth = Authentication {
+            from_domain: headers
+                .sender()
+                .and_then(|sender| domain_of(&sender.address).map(str::to_string)),
+            ..Default::default()
+        };
+
+        if let Some(value) = headers.get("Authentication-Results") {
+            auth.parse_authentication_results(value, trusted_authserv_ids);
+        }
+        if auth.spf.is_none() {
+            auth.spf = headers
+                .get("Received-SPF")
+                .and_then(|value| value.split_whitespace().next())
+                .and_then(AuthResult::parse);
+        }
+        if auth.dkim_domain.is_none() {
+            auth.dkim_domain = headers
+                .get("DKIM-Signature")
+                .and_then(|value| tag_value(value, "d"))
+                .map(|domain| domain.to_ascii_lowercase());
+        }
+        auth
+    }
+
+    /// Whether the message carried no authentication information at all.
+    pub fn is_empty(&self) -> bool {
+        self.spf.is_none() && self.dkim.is_none() && self.dmarc.is_none()
+    }
+
+    /// The overall verdict, see [`AuthStatus`].
+    pub fn status(&self) -> AuthStatus {
+        match self.dmarc {
+            Some(AuthResult::Pass) => return AuthStatus::Verified,
+            Some(result) if result.is_fail() => return AuthStatus::Failed,
+            _ => {}
+        }
+        let dkim_pass = self.dkim == Some(AuthResult::Pass);
+        let aligned = match (&self.dkim_domain, &self.from_domain) {
+            (Some(dkim), Some(from)) => same_organization(dkim, from),
+            _ => false,
+        };
+        if dkim_pass && aligned {
+            AuthStatus::Verified
+        } else if self.spf.is_some_and(|spf| spf.is_fail()) && !dkim_pass {
+            AuthStatus::Failed
+        } else {
+            AuthStatus::Unverified
+        }
+    }
+
+    /// The individual results, e.g. `SPF pass, DKIM pass (example.com),
+    /// DMARC pass`.
+    pub fn details(&self) -> String {
+        let result = |result: Option<AuthResult>| result.map_or("missing", |r| r.as_str());
+        let mut dkim = result(self.dkim).to_string();
+        if let Some(domain) = &self.dkim_domain {
+            dkim.push_str(&format!(" ({})", domain));
+        }
+        format!(
+            "SPF {}, DKIM {}, DMARC {}",
+            result(self.spf),
+            dkim,
+            result(self.dmarc)
+        )
+    }
+
+    /// Parse `authserv-id; method=result props...; method=result ...`, if
+    /// the authserv-id is trusted.
+    fn parse_authentication_results(&mut self, value: &str, trusted_authserv_ids: &[String]) {
+        let value = strip_comments(value);
+        let mut statements = split_unquoted(&value, ';').into_iter();
+        self.authserv_id = statements
+            .next()
+            .and_then(|id| id.split_whitespace().next().map(str::to_string));
+        let trusted = self.authserv_id.as_deref().is_some_and(|id| {
+            trusted_authserv_ids
+                .iter()
+                .any(|trusted| trusted.trim().eq_ignore_ascii_case(id))
+        });
+        if !trusted {
+            return;
+        }
+
+        for statement in statements {
+            let mut tokens = statement.split_whitespace();
+            let Some((method, result)) = tokens.next().and_then(|t| t.split_once('=')) else {
+                continue;
+            };
+            let Some(result) = AuthResult::parse(result) else {
+                continue;
+            };
+            // Methods may carry a version, as in `dkim/1`
+            let method = method.split('/').next().unwrap_or(method);
+            let props: Vec<(&str, &str)> = tokens.filter_map(|t| t.split_once('=')).collect();
+
+            match method.to_ascii_lowercase().as_str() {
+                "spf" => merge(&mut self.spf, result),
+                "dmarc" => merge(&mut self.dmarc, result),
+                // With several signatures, a passing one decides
+                "dkim" if self.dkim != Some(AuthResult::Pass) => {
+                    self.dkim = Some(result);
+                    self.dkim_domain = props
+                        .iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case("header.d"))
+                        .or_else(|| {
+                            props
+                                .iter()
+                                .find(|(key, _)| key.eq_ignore_ascii_case("header.i"))
+                        })
+                        .map(|(_, value)| {
+                            let value = value.trim_matches('"');
+                            value
+                                .rsplit_once('@')
+                                .map_or(value, |(_, domain)| domain)
+                                .to_ascii_lowercase()
+                        });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Keep the first result of a method reported more than once.
+fn merge(slot: &mut Option<AuthResult>, result: AuthResult) {
+    if slot.is_none() {
+        *slot = Some(result);
+    }
+}
+
+/// A reason to distrust the sender of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PhishingWarning {
+    /// The display name shows an address at another domain than the one
+    /// the message is from
+    DisplayNameAddress { shown: String, actual: String },
+    /// The From domain resembles, but is not, a domain the user knows
+    Lookalike { domain: String, resembles: String },
+}
+
+impl fmt::Display for PhishingWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhishingWarning::DisplayNameAddress { shown, actual } => write!(
+                f,
+                "The sender name shows {} but the message is from {}",
+                shown, actual
+            ),
+            PhishingWarning::Lookalike { domain, resembles } => write!(
+                f,
+                "The sender domain {} looks like {} but is not",
+                domain, resembles
+            ),
+        }
+    }
+}
+
+/// Warnings about the sender of a message.
+///
+/// `known_domains` are the domains a lookalike would imitate, typically the
+/// user's own and those configured as trusted.
+pub fn phishing_warnings(headers: &Headers, known_domains: &[String]) -> Vec<PhishingWarning> {
+    let Some(sender) = headers.sender() else {
+        return Vec::new();
+    };
+    let Some(from_domain) = domain_of(&sender.address).map(str::to_ascii_lowercase) else {
+        return Vec::new();
+    };
+    let mut warnings = Vec::new();
+
+    if let Some(name) = &sender.name {
+        for shown in addresses_in(name) {
+            let shown_domain = domain_of(shown).unwrap_or_default().to_ascii_lowercase();
+            if !same_organization(&shown_domain, &from_domain) {
+                warnings.push(PhishingWarning::DisplayNameAddress {
+                    shown: shown.to_string(),
+                    actual: sender.address.clone(),
+                });
+                break;
+            }
+        }
+    }
+
+    for known in known_domains {
+        let known = known.trim().to_ascii_lowercase();
+        if !known.is_empty() && is_lookalike(&from_domain, &known) {
+            warnings.push(PhishingWarning::Lookalike {
+                domain: from_domain.clone(),
+                resembles: known,
+            });
+            break;
+        }
+    }
+    warnings
+}
+
+/// Whether `domain` imitates `known`: equal after mapping confusable
+/// characters, one typo away, or `known` put in front of another domain.
+fn is_lookalike(domain: &str, known: &str) -> bool {
+    if same_organization(domain, known) {
+        return false;
+    }
+    // paypal.com.account-check.net
+    if domain.starts_with(&format!("{}.", known)) {
+        return true;
+    }
+    let (unicode, _) = idna::domain_to_unicode(domain);
+    if skeleton(&unicode) == skeleton(known) {
+        return true;
+    }
+    // Single typos in short names match too much by accident
+    known.chars().count() >= 8 && edit_distance(&unicode, known) == 1
+}
+
+/// Whether two domains are the same or one is a subdomain of the other.
+fn same_organization(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+    a == b || a.ends_with(&format!(".{}", b)) || b.ends_with(&format!(".{}", a))
+}
+
+/// Map characters that are easily mistaken for one another to a common
+/// form, after Unicode TR 39 skeletons but limited to common cases.
+fn skeleton(domain: &str) -> String {
+    domain
+        .to_lowercase()
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .replace("cl", "d")
+        .chars()
+        .map(|c| match c {
+            'а' | 'α' | '@' => 'a',
+            'е' | 'ε' | '3' => 'e',
+            'о' | 'ο' | 'σ' | '0' => 'o',
+            'р' | 'ρ' => 'p',
+            'с' | 'ϲ' => 'c',
+            'у' | 'γ' => 'y',
+            'х' | 'χ' => 'x',
+            'і' | 'ι' | 'ӏ' | 'l' | '1' | '|' | 'í' | 'ì' => 'i',
+            'ј' => 'j',
+            'ѕ' | '5' => 's',
+            'ԁ' => 'd',
+            'ν' => 'v',
+            'κ' => 'k',
+            'τ' => 't',
+            'ɡ' => 'g',
+            'ո' => 'n',
+            c => c,
+        })
+        .collect()
+}
+
+/// Levenshtein distance counting an adjacent transposition as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut before_previous = previous.clone();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+        before_previous = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// The domain of an address.
+fn domain_of(address: &str) -> Option<&str> {
+    address
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim().trim_end_matches('>'))
+        .filter(|domain| domain.contains('.'))
+}
+
+/// Things in a display name that look like email addresses.
+fn addresses_in(name: &str) -> impl Iterator<Item = &str> {
+    name.split(|c: char| c.is_whitespace() || "<>()[],;:\"'".contains(c))
+        .filter(|word| {
+            word.split_once('@')
+                .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'))
+        })
+}
+
+/// The value of a `tag=value` entry in a DKIM tag list.
+fn tag_value<'a>(value: &'a str, tag: &str) -> Option<&'a str> {
+    value.split(';').find_map(|entry| {
+        let (key, value) = entry.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(tag)
+            .then(|| value.trim())
+            .filter(|value| !value.is_empty())
+    })
+}
+
+/// Remove RFC 5322 comments, keeping quoted strings intact.
+fn strip_comments(value: &str) -> String {
+    let mut result = String::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+            if depth == 0 {
+                result.push(c);
+            }
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' if depth == 0 => quoted = !quoted,
+            '(' if !quoted => {
+                depth += 1;
+                continue;
+            }
+            ')' if !quoted && depth > 0 => {
+                depth -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Split on `separator` outside quoted strings.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(value[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn headers(from: &str, extra: &[(&str, &str)]) -> Headers {
+        Headers {
+            subject: Some("Invoice".to_string()),
+            from: from.to_string(),
+            to: Some("carol@example.com".to_string()),
+            reply_to: None,
+            date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+            additional: extra
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    fn trusted() -> Vec<String> {
+        vec!["MX.example.com".to_string()]
+    }
+
+    #[test]
+    fn test_authentication_results() {
+        let headers = headers(
+            "Bank <alerts@bank.example>",
+            &[(
+                "Authentication-Results",
+                "mx.example.com (mail filter);\r\n\tdkim=pass (2048-bit key) header.d=bank.example header.s=s1 header.b=abc;\r\n\tspf=pass (mx.example.com: domain of alerts@bank.example designates 192.0.2.1 as permitted sender) smtp.mailfrom=alerts@bank.example;\r\n\tdmarc=pass (p=REJECT sp=REJECT dis=NONE) header.from=bank.example",
+            )],
+        );
+        let auth = Authentication::from_headers(&headers, &trusted());
+        assert_eq!(auth.authserv_id.as_deref(), Some("mx.example.com"));
+        assert_eq!(auth.spf, Some(AuthResult::Pass));
+        assert_eq!(auth.dkim, Some(AuthResult::Pass));
+        assert_eq!(auth.dmarc, Some(AuthResult::Pass));
+        assert_eq!(auth.dkim_domain.as_deref(), Some("bank.example"));
+        assert_eq!(auth.status(), AuthStatus::Verified);
+        assert_eq!(
+            auth.details(),
+            "SPF pass, DKIM pass (bank.example), DMARC pass"
+        );
+    }
+
+    #[test]
+    fn test_dmarc_fail() {
+        let headers = headers(
+            "Bank <alerts@bank.example>",
+            &[(
+                "Authentication-Results",
+                "mx.example.com; dkim=none; spf=softfail smtp.mailfrom=evil.example; dmarc=fail header.from=bank.example",
+            )],
+        );
+        let auth = Authentication::from_headers(&headers, &trusted());
+        assert_eq!(auth.dkim, Some(AuthResult::None));
+        assert_eq!(auth.spf, Some(AuthResult::SoftFail));
+        assert_eq!(auth.status(), AuthStatus::Failed);
+    }
+
+    #[test]
+    fn test_multiple_dkim_signatures() {
+        let headers = headers(
+            "News <news@shop.example>",
+            &[(
+                "Authentication-Results",
+                "mx.example.com; dkim=fail header.i=@esp.example; dkim/1=pass header.i=@shop.example",
+            )],
+        );
+        let auth = Authentication::from_headers(&headers, &trusted());
+        assert_eq!(auth.dkim, Some(AuthResult::Pass));
+        assert_eq!(auth.dkim_domain.as_deref(), Some("shop.example"));
+        // Aligned DKIM without DMARC
+        assert_eq!(auth.status(), AuthStatus::Verified);
+    }
+
+    #[test]
+    fn test_untrusted_authserv_id_is_ignored() {
+        // Added by the sender, not by the user's server
+        let headers = headers(
+            "Bank <alerts@bank.example>",
+            &[(
+                "Authentication-Results",
+                "x; dkim=pass header.d=bank.example; dmarc=pass header.from=bank.example",
+            )],
+        );
+        let auth = Authentication::from_headers(&headers, &trusted());
+        assert_eq!(auth.authserv_id.as_deref(), Some("x"));
+        assert!(auth.is_empty());
+        assert_eq!(auth.status(), AuthStatus::Unverified);
+        let auth = Authentication::from_headers(&headers, &[]);
+        assert_eq!(auth.status(), AuthStatus::Unverified);
+    }
+
+    #[test]
+    fn test_received_spf_and_dkim_signature_fallback() {
+        let headers = headers(
+            "alice@example.com",
+            &[
+                (
+                    "Received-SPF",
+                    "fail (example.com: domain of alice@example.com does not designate 192.0.2.9 as permitted sender) client-ip=192.0.2.9;",
+                ),
+                (
+                    "DKIM-Signature",
+                    "v=1; a=rsa-sha256; c=relaxed/relaxed; d=Example.com; s=sel; h=from:to; bh=x; b=y",
+                ),
+            ],
+        );
+        let auth = Authentication::from_headers(&headers, &trusted());
+        assert_eq!(auth.spf, Some(AuthResult::Fail));
+        assert_eq!(auth.dkim, None);
+        assert_eq!(auth.dkim_domain.as_deref(), Some("example.com"));
+        assert_eq!(auth.status(), AuthStatus::Failed);
+    }
+
+    #[test]
+    fn test_no_authentication() {
+        let auth = Authentication::from_headers(&headers("alice@example.com", &[]), &trusted());
+        assert!(auth.is_empty());
+        assert_eq!(auth.status(), AuthStatus::Unverified);
+        assert_eq!(auth.details(), "SPF missing, DKIM missing, DMARC missing");
+    }
+
+    #[test]
+    fn test_display_name_address_warning() {
+        let known: Vec<String> = vec![];
+        let warnings = phishing_warnings(
+            &headers("\"support@bank.example\" <x@evil.example>", &[]),
+            &known,
+        );
+        assert_eq!(
+            warnings,
+            vec![PhishingWarning::DisplayNameAddress {
+                shown: "support@bank.example".to_string(),
+                actual: "x@evil.example".to_string(),
+            }]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "The sender name shows support@bank.example but the message is from x@evil.example"
+        );
+
+        // The same organization, or no address in the name, is fine
+        assert!(
+            phishing_warnings(
+                &headers("\"help@bank.example\" <alerts@mail.bank.example>", &[]),
+                &known
+            )
+            .is_empty()
+        );
+        assert!(phishing_warnings(&headers("Bank <x@evil.example>", &[]), &known).is_empty());
+    }
+
+    #[test]
+    fn test_lookalike_domains() {
+        let known = vec![
+            "paypal.com".to_string(),
+            "example.com".to_string(),
+            "microsoft.com".to_string(),
+        ];
+        let lookalike = |from: &str| {
+            phishing_warnings(&headers(from, &[]), &known)
+                .into_iter()
+                .find_map(|warning| match warning {
+                    PhishingWarning::Lookalike { resembles, .. } => Some(resembles),
+                    _ => None,
+                })
+        };
+
+        assert_eq!(lookalike("x@paypa1.com").as_deref(), Some("paypal.com"));
+        assert_eq!(
+            lookalike("x@rnicrosoft.com").as_deref(),
+            Some("microsoft.com")
+        );
+        // Cyrillic "а" in punycode
+        assert_eq!(
+            lookalike("x@xn--pypal-4ve.com").as_deref(),
+            Some("paypal.com")
+        );
+        assert_eq!(
+            lookalike("x@paypal.com.account-check.net").as_deref(),
+            Some("paypal.com")
+        );
+        assert_eq!(lookalike("x@exampel.com").as_deref(), Some("example.com"));
+        assert_eq!(lookalike("x@examp1e.com").as_deref(), Some("example.com"));
+
+        // The real domains and their subdomains are not lookalikes
+        assert_eq!(lookalike("x@paypal.com"), None);
+        assert_eq!(lookalike("x@mail.paypal.com"), None);
+        assert_eq!(lookalike("x@unrelated.org"), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("example", "example"), 0);
+        assert_eq!(edit_distance("example", "exmaple"), 1);
+        assert_eq!(edit_distance("example", "examples"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -32,6 +32,7 @@ async fn main() {
             initial_page_size: 5, // Small for testing
             pagination_size: 3,   // Small for testing
             infinite_scroll_enabled: true,
+            ..Default::default()
         };
 
         let state = AppState {
@@ -1,7 +1,9 @@
 use clap::{Parser, Subcommand};
 use colored::*;
 use std::path::PathBuf;
-use whynot::client::{ClientConfig, NotmuchClient, TagOperation, create_client};
+use whynot::client::{
+    ClientConfig, ExpungeOptions, NotmuchClient, OutputFormat, TagOperation, create_client,
+};
 use whynot::error::Result;
 
 #[derive(Parser)]
@@ -36,6 +38,18 @@ struct Cli {
     #[arg(long)]
     database_path: Option<PathBuf>,
 
+    /// Output format requested from notmuch: json or sexp
+    #[arg(long, default_value = "json")]
+    format: OutputFormat,
+
+    /// Connect to a whynot-agent (host:port or unix:/path) instead
+    #[arg(long, conflicts_with = "remote")]
+    agent: Option<String>,
+
+    /// whynot-agent authentication token
+    #[arg(long, env = "WHYNOT_AGENT_TOKEN", requires = "agent")]
+    agent_token: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -119,6 +133,24 @@ enum Commands {
         #[arg(long)]
         tags: Vec<String>,
     },
+
+    /// Permanently remove messages (dry run unless --confirm is given)
+    Expunge {
+        /// Query selecting the messages to remove, e.g. "tag:deleted AND date:..30d"
+        query: String,
+
+        /// Confirmation token printed by the dry run
+        #[arg(long)]
+        confirm: Option<String>,
+
+        /// Tags that protect messages from removal (default: flagged,important)
+        #[arg(long, value_delimiter = ',')]
+        protect: Vec<String>,
+
+        /// Move the files to this directory instead of deleting them
+        #[arg(long)]
+        trash_dir: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -126,7 +158,12 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     // Create client configuration
-    let config = if cli.remote {
+    let config = if let Some(endpoint) = cli.agent {
+        ClientConfig::Agent {
+            endpoint,
+            token: cli.agent_token.unwrap_or_default(),
+        }
+    } else if cli.remote {
         let host = cli.host.expect("Host is required for remote mode");
         ClientConfig::Remote {
             host,
@@ -134,12 +171,14 @@ async fn main() -> Result<()> {
             port: cli.port,
             identity_file: cli.identity_file,
             notmuch_path: cli.notmuch_path,
+            format: cli.format,
         }
     } else {
         ClientConfig::Local {
             notmuch_path: cli.notmuch_path,
             database_path: cli.database_path,
             mail_root: None,
+            format: cli.format,
         }
     };
 
@@ -188,6 +227,21 @@ async fn main() -> Result<()> {
             )
             .await?;
         }
+        Commands::Expunge {
+            query,
+            confirm,
+            protect,
+            trash_dir,
+        } => {
+            let mut options = ExpungeOptions {
+                trash_dir,
+                ..ExpungeOptions::default()
+            };
+            if !protect.is_empty() {
+                options.protected_tags = protect;
+            }
+            expunge(&*client, &query, &options, confirm.as_deref()).await?;
+        }
     }
 
     Ok(())
@@ -489,3 +543,79 @@ async fn insert(
 
     Ok(())
 }
+
+async fn expunge(
+    client: &dyn NotmuchClient,
+    query: &str,
+    options: &ExpungeOptions,
+    confirm: Option<&str>,
+) -> Result<()> {
+    if let Some(token) = confirm {
+        println!("{}", "Expunging messages...".dimmed());
+        let outcome = client.expunge(query, options, token).await?;
+        match &outcome.trash_dir {
+            Some(dir) => println!(
+                "{} Moved {} files of {} messages to {}",
+                "✓".green(),
+                outcome.files,
+                outcome.messages,
+                dir.display()
+            ),
+            None => println!(
+                "{} Deleted {} files of {} messages",
+                "✓".green(),
+                outcome.files,
+                outcome.messages
+            ),
+        }
+        return Ok(());
+    }
+
+    println!("{}", "Dry run, nothing will be removed.".dimmed());
+    let report = client.expunge_report(query, options).await?;
+
+    for message in &report.messages {
+        println!(
+            "{} {}",
+            "-".red(),
+            message.subject.as_deref().unwrap_or("(No subject)").bold()
+        );
+        println!("  {} {}", "Message-ID:".dimmed(), message.id.dimmed());
+        for file in &message.files {
+            println!("  {}", file);
+        }
+    }
+
+    for message in &report.protected {
+        println!(
+            "{} {} {}",
+            "=".yellow(),
+            message.subject.as_deref().unwrap_or("(No subject)"),
+            format!("(protected: {})", message.tags.join(", ")).yellow()
+        );
+    }
+
+    println!();
+    if report.is_empty() {
+        println!("{}", "Nothing to expunge.".yellow());
+        return Ok(());
+    }
+
+    let action = match &report.trash_dir {
+        Some(dir) => format!("move to {}", dir.display()),
+        None => "permanently delete".to_string(),
+    };
+    println!(
+        "Would {} {} files of {} messages ({} protected messages skipped).",
+        action,
+        report.file_count().to_string().red(),
+        report.messages.len(),
+        report.protected.len()
+    );
+    println!(
+        "Run again with {} to proceed.",
+        format!("--confirm {}", report.token).bold()
+    );
+
+    Ok(())
+}
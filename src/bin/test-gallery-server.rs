@@ -102,6 +102,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -0,0 +1,130 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::sync::Arc;
+use whynot::agent::{AgentEndpoint, AgentListener, AgentServer};
+use whynot::client::{ClientConfig, NotmuchClient, OutputFormat, create_client};
+use whynot::mail_sender::{MailSender, MailSenderConfig, create_mail_sender};
+
+#[derive(Parser)]
+#[command(name = "whynot-agent")]
+#[command(about = "Serve a notmuch database to whynot clients over JSON-RPC", long_about = None)]
+struct Cli {
+    /// Address to listen on: host:port, tcp:host:port or unix:/path
+    #[arg(long, env = "WHYNOT_AGENT_LISTEN", default_value = "127.0.0.1:7878")]
+    listen: String,
+
+    /// Shared authentication token
+    #[arg(long, env = "WHYNOT_AGENT_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Read the authentication token from a file
+    #[arg(long, conflicts_with = "token")]
+    token_file: Option<PathBuf>,
+
+    /// Path to notmuch binary
+    #[arg(long)]
+    notmuch_path: Option<PathBuf>,
+
+    /// Notmuch database path
+    #[arg(long)]
+    database_path: Option<PathBuf>,
+
+    /// Mail root directory
+    #[arg(long)]
+    mail_root: Option<PathBuf>,
+
+    /// Output format requested from notmuch: json or sexp
+    #[arg(long, default_value = "json")]
+    notmuch_format: OutputFormat,
+
+    /// Also expose mail sending via msmtp
+    #[arg(long)]
+    enable_send: bool,
+
+    /// Path to msmtp binary
+    #[arg(long)]
+    msmtp_path: Option<PathBuf>,
+
+    /// Path to msmtp configuration file
+    #[arg(long)]
+    msmtp_config: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "whynot=info".into()),
+        )
+        .init();
+
+    let cli = Cli::parse();
+
+    let token = match (&cli.token, &cli.token_file) {
+        (Some(token), _) => token.clone(),
+        (None, Some(path)) => std::fs::read_to_string(path)?.trim().to_string(),
+        (None, None) => {
+            eprintln!(
+                "ERROR: an authentication token is required (--token, --token-file or WHYNOT_AGENT_TOKEN)"
+            );
+            std::process::exit(2);
+        }
+    };
+    if token.is_empty() {
+        eprintln!("ERROR: the authentication token must not be empty");
+        std::process::exit(2);
+    }
+
+    let endpoint: AgentEndpoint = cli.listen.parse()?;
+
+    let client = create_client(ClientConfig::Local {
+        notmuch_path: cli.notmuch_path,
+        database_path: cli.database_path,
+        mail_root: cli.mail_root,
+        format: cli.notmuch_format,
+    })?;
+    let client = Arc::from(client) as Arc<dyn NotmuchClient>;
+
+    let mut server = AgentServer::new(client, token);
+
+    if cli.enable_send {
+        let sender = create_mail_sender(MailSenderConfig::Local {
+            msmtp_path: cli.msmtp_path,
+            config_path: cli.msmtp_config,
+            account: None,
+        })?;
+        if let Err(e) = sender.test_connection().await {
+            tracing::warn!("Mail sender test failed: {}", e);
+        }
+        server = server.with_mail_sender(Arc::from(sender) as Arc<dyn MailSender>);
+        tracing::info!("Mail sending enabled");
+    }
+
+    if let AgentEndpoint::Tcp(addr) = &endpoint {
+        let is_loopback = addr
+            .parse::<std::net::SocketAddr>()
+            .map(|a| a.ip().is_loopback())
+            .unwrap_or(addr.starts_with("localhost:"));
+        if !is_loopback {
+            tracing::warn!(
+                "Listening on {} without encryption; restrict access to a trusted network",
+                addr
+            );
+        }
+    }
+
+    let listener = AgentListener::bind(&endpoint).await?;
+    tracing::info!("whynot-agent listening on {}", listener.local_endpoint()?);
+
+    tokio::select! {
+        result = server.serve(listener) => result?,
+        _ = tokio::signal::ctrl_c() => tracing::info!("Shutting down"),
+    }
+
+    if let AgentEndpoint::Unix(path) = &endpoint {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Ok(())
+}
@@ -8,7 +8,8 @@ use std::{io, sync::Arc, time::Duration};
 use whynot::{
     client::create_client,
     config::{CliArgs, Config},
-    mail_sender::create_mail_sender,
+    mail_sender::{MailSender, create_mail_sender},
+    outbox::{self, Outbox},
     tui::{app::App, events::EventHandler, ui},
 };
 
@@ -26,9 +27,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = Arc::from(client) as Arc<dyn whynot::client::NotmuchClient>;
 
     // Create the mail sender (optional if not configured)
-    let mail_sender = match config.to_mail_sender_config() {
-        Ok(mail_sender_config) => match create_mail_sender(mail_sender_config) {
-            Ok(sender) => Some(sender),
+    let mail_sender: Option<Arc<dyn MailSender>> = match config.to_mail_sender_config() {
+        Ok(mail_sender_config) => match create_mail_sender(mail_sender_config)
+            .and_then(|sender| config.route_identities(sender))
+        {
+            Ok(sender) => Some(Arc::from(sender)),
             Err(e) => {
                 eprintln!("Warning: Mail sending not configured: {}", e);
                 None
@@ -40,6 +43,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    // Retry queued messages in the background
+    if let Some(sender) = &mail_sender {
+        outbox::spawn_worker(
+            Outbox::new(config.outbox_dir()),
+            sender.clone(),
+            client.clone(),
+            config.sent_folder(),
+        );
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -89,6 +102,16 @@ async fn run_app<B: ratatui::backend::Backend>(
                     break;
                 }
 
+                // Undo sending while the grace period lasts, from any view
+                // but another compose form, which it would replace
+                if app.pending_send.is_some()
+                    && app.state != whynot::tui::app::AppState::Compose
+                    && event.is_undo()
+                {
+                    app.undo_send().await;
+                    continue;
+                }
+
                 // State-specific event handling
                 match app.state {
                     whynot::tui::app::AppState::EmailList => {
@@ -104,6 +127,18 @@ async fn run_app<B: ratatui::backend::Backend>(
                             app.enter_search_mode();
                         } else if event.is_compose() {
                             app.start_compose_new();
+                        } else if event.is_drafts() {
+                            if let Err(e) = app.load_drafts().await {
+                                app.set_status(format!("Error loading drafts: {}", e));
+                            }
+                        } else if event.is_outbox() {
+                            if let Err(e) = app.show_outbox().await {
+                                app.set_status(format!("Error reading outbox: {}", e));
+                            }
+                        } else if event.is_archive() {
+                            if let Err(e) = app.archive_selected_thread().await {
+                                app.set_status(format!("Error archiving thread: {}", e));
+                            }
                         } else if event.is_help() {
                             app.show_help();
                         }
@@ -135,12 +170,56 @@ async fn run_app<B: ratatui::backend::Backend>(
                                     e
                                 ));
                             }
+                        } else if event.is_next_unread() {
+                            if let Err(e) = app.next_unread_in_thread().await {
+                                app.set_status(format!(
+                                    "Error navigating to unread message: {}",
+                                    e
+                                ));
+                            }
                         } else if event.is_reply() {
-                            app.start_compose_reply(false);
+                            app.start_compose_reply(false).await;
                         } else if event.is_reply_all() {
-                            app.start_compose_reply(true);
+                            app.start_compose_reply(true).await;
+                        } else if event.is_reply_list() {
+                            app.start_compose_reply_list().await;
+                        } else if event.is_unsubscribe() {
+                            if let Err(e) = app.unsubscribe_current_list().await {
+                                app.set_status(format!("Error unsubscribing: {}", e));
+                            }
+                        } else if let Some(partstat) = event.rsvp_response() {
+                            if let Err(e) = app.respond_to_invite(partstat).await {
+                                app.set_status(format!("Error responding to invitation: {}", e));
+                            }
                         } else if event.is_forward() {
                             app.start_compose_forward();
+                        } else if event.is_archive() {
+                            if let Err(e) = app.archive_selected_thread().await {
+                                app.set_status(format!("Error archiving thread: {}", e));
+                            }
+                        } else if event.is_help() {
+                            app.show_help();
+                        }
+                    }
+                    whynot::tui::app::AppState::Outbox => {
+                        if event.is_back() {
+                            app.go_back();
+                        } else if event.is_up() {
+                            app.outbox_up();
+                        } else if event.is_down() {
+                            app.outbox_down();
+                        } else if event.is_cancel() {
+                            if let Err(e) = app.cancel_selected_outbox_entry().await {
+                                app.set_status(format!("Error cancelling message: {}", e));
+                            }
+                        } else if event.is_edit() {
+                            if let Err(e) = app.edit_selected_outbox_entry().await {
+                                app.set_status(format!("Error editing message: {}", e));
+                            }
+                        } else if event.is_reply() {
+                            if let Err(e) = app.retry_selected_outbox_entry().await {
+                                app.set_status(format!("Error retrying message: {}", e));
+                            }
                         } else if event.is_help() {
                             app.show_help();
                         }
@@ -172,10 +251,44 @@ async fn run_app<B: ratatui::backend::Backend>(
                         // Check for markdown toggle first (using the event helper)
                         if event.is_markdown_toggle() {
                             app.toggle_compose_markdown_mode();
+                        } else if event.is_send_later() {
+                            app.compose_toggle_send_at();
+                        } else if event.is_next_identity() {
+                            app.compose_next_identity();
+                        } else if event.is_attach() {
+                            app.compose_toggle_attach();
+                        } else if event.is_pgp() {
+                            app.compose_cycle_pgp();
+                        } else if matches!(
+                            app.compose_form.current_field,
+                            whynot::tui::app::ComposeField::Attach
+                        ) && matches!(
+                            key.code,
+                            crossterm::event::KeyCode::Tab
+                                | crossterm::event::KeyCode::Enter
+                                | crossterm::event::KeyCode::Up
+                                | crossterm::event::KeyCode::Down
+                                | crossterm::event::KeyCode::Delete
+                        ) {
+                            // Tab completes paths and Enter attaches, rather
+                            // than moving between fields
+                            match key.code {
+                                crossterm::event::KeyCode::Tab => app.compose_complete_path(),
+                                crossterm::event::KeyCode::Enter => {
+                                    if let Err(e) = app.compose_attach().await {
+                                        app.set_status(format!("Error attaching: {}", e));
+                                    }
+                                }
+                                crossterm::event::KeyCode::Up => app.compose_attachment_up(),
+                                crossterm::event::KeyCode::Down => app.compose_attachment_down(),
+                                _ => app.compose_remove_attachment(),
+                            }
                         } else {
                             match key.code {
                                 crossterm::event::KeyCode::Esc => {
-                                    app.go_back();
+                                    if let Err(e) = app.close_compose().await {
+                                        app.set_status(format!("Error saving draft: {}", e));
+                                    }
                                 }
                                 crossterm::event::KeyCode::Tab => {
                                     app.compose_next_field();
@@ -212,6 +325,16 @@ async fn run_app<B: ratatui::backend::Backend>(
                 // Terminal was resized, redraw will happen on next loop
             }
             whynot::tui::Event::Tick => {
+                app.send_pending_when_due().await;
+                if let Err(e) = app.autosave_draft().await {
+                    app.set_status(format!("Error saving draft: {}", e));
+                }
+                // Pick up messages the background worker sent or retried
+                if app.state == whynot::tui::app::AppState::Outbox
+                    && let Err(e) = app.refresh_outbox().await
+                {
+                    app.set_status(format!("Error reading outbox: {}", e));
+                }
                 // Clear status message after some time
                 if app.status_message.is_some() {
                     // TODO: Implement timed status clearing
@@ -224,5 +347,8 @@ async fn run_app<B: ratatui::backend::Backend>(
         }
     }
 
+    // Don't leave a message waiting out its undo grace period behind
+    app.flush_pending_send().await;
+
     Ok(())
 }
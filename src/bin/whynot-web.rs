@@ -1,7 +1,8 @@
 use clap::Parser;
-use whynot::client::create_client;
+use whynot::client::{ClientConfig, create_client};
 use whynot::config::{CliArgs, Config};
 use whynot::mail_sender::create_mail_sender;
+use whynot::outbox::{self, Outbox};
 use whynot::web::{AppState, WebConfig, create_app};
 
 #[tokio::main]
@@ -22,18 +23,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client_config = config.to_client_config()?;
 
     // Log configuration mode
-    let is_remote = config.mail.reading.connection_type.as_deref() == Some("remote")
-        || config.mail.reading.host.is_some();
-
-    if is_remote {
-        tracing::info!(
-            "Using remote notmuch at {}@{}:{}",
-            config.mail.reading.user.as_deref().unwrap_or("(default)"),
-            config.mail.reading.host.as_deref().unwrap_or("(unknown)"),
-            config.mail.reading.port.unwrap_or(22)
-        );
-    } else {
-        tracing::info!("Using local notmuch");
+    match &client_config {
+        ClientConfig::Remote { .. } => {
+            tracing::info!(
+                "Using remote notmuch at {}@{}:{}",
+                config.mail.reading.user.as_deref().unwrap_or("(default)"),
+                config.mail.reading.host.as_deref().unwrap_or("(unknown)"),
+                config.mail.reading.port.unwrap_or(22)
+            );
+        }
+        ClientConfig::Agent { endpoint, .. } => {
+            tracing::info!("Using whynot-agent at {}", endpoint);
+        }
+        ClientConfig::Local { .. } => {
+            tracing::info!("Using local notmuch");
+        }
     }
 
     // Create the notmuch client
@@ -61,7 +65,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create mail sender if configured
     let mail_sender = if let Ok(mail_sender_config) = config.to_mail_sender_config() {
         tracing::info!("Creating mail sender...");
-        match create_mail_sender(mail_sender_config) {
+        match create_mail_sender(mail_sender_config)
+            .and_then(|sender| config.route_identities(sender))
+        {
             Ok(sender) => {
                 // Test the mail sender connection
                 match sender.test_connection().await {
@@ -70,11 +76,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         Some(sender)
                     }
                     Err(e) => {
+                        // Messages wait in the outbox until it is reachable
                         tracing::warn!(
-                            "Mail sender test failed: {}. Mail sending will be disabled.",
+                            "Mail sender test failed: {}. Messages will be queued in the outbox.",
                             e
                         );
-                        None
+                        Some(sender)
                     }
                 }
             }
@@ -100,15 +107,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         initial_page_size: config.ui.web.initial_page_size.unwrap_or(20),
         pagination_size: config.ui.web.pagination_size.unwrap_or(10),
         infinite_scroll_enabled: config.ui.web.infinite_scroll_enabled.unwrap_or(true),
+        archive_folder: config.mail.reading.archive_folder.clone(),
+        sent_folder: config.sent_folder(),
+        drafts_folder: config.drafts_folder(),
+        outbox_dir: Some(config.outbox_dir()),
+        undo_send_delay: config.undo_send_delay(),
+        expunge: config.expunge_options(),
+        attachment_limits: config.attachment_limits(),
+        gpg: config.gpg(),
     };
 
     let state = AppState {
         client: std::sync::Arc::from(client),
         mail_sender: mail_sender.map(std::sync::Arc::from),
         config: web_config.clone(),
-        user_config: config.user.clone(),
+        user_config: config.user_config(),
     };
 
+    // Retry queued messages in the background
+    if let (Some(sender), Some(dir)) = (&state.mail_sender, &web_config.outbox_dir) {
+        outbox::spawn_worker(
+            Outbox::new(dir),
+            sender.clone(),
+            state.client.clone(),
+            web_config.sent_folder.clone(),
+        );
+    }
+
     // Create the application
     let app = create_app(state);
 
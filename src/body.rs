@@ -1,5 +1,17 @@
-use serde::{Deserialize, Deserializer, Serialize};
-use serde_json;
+use crate::common::{
+    decode_charset, decode_encoded_words, decode_extended_value, decode_parameter_value,
+};
+use crate::flowed;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Preference order for showing a message as rich text.
+pub const PREFER_HTML: &[&str] = &["text/html", "text/plain"];
+
+/// Preference order for showing or quoting a message as plain text.
+pub const PREFER_PLAIN: &[&str] = &["text/plain", "text/html"];
 
 /// Represents a body part of an email (text, HTML, attachment, etc.)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -13,6 +25,10 @@ pub struct BodyPart {
     pub content_disposition: Option<String>,
     #[serde(rename = "content-id")]
     pub content_id: Option<String>,
+    #[serde(rename = "content-charset")]
+    pub content_charset: Option<String>,
+    /// Decoded filename, see [`decode_parameter_value`]
+    #[serde(default, deserialize_with = "deserialize_parameter_value")]
     pub filename: Option<String>,
     #[serde(rename = "content-transfer-encoding")]
     pub content_transfer_encoding: Option<String>,
@@ -29,10 +45,439 @@ impl BodyPart {
             false
         }
     }
+
+    /// The parsed Content-Type of this part.
+    pub fn media_type(&self) -> MediaType {
+        MediaType::parse(&self.content_type)
+    }
+
+    /// The charset of a text part, from notmuch or the Content-Type
+    /// parameters.
+    pub fn charset(&self) -> Option<String> {
+        self.content_charset
+            .clone()
+            .or_else(|| self.media_type().charset().map(str::to_string))
+    }
+
+    /// How this part should be presented, see [`PartRole`].
+    pub fn role(&self) -> PartRole {
+        let media_type = self.media_type();
+        if media_type.is_multipart() {
+            return PartRole::Container;
+        }
+
+        let disposition = self.content_disposition.as_deref().map(|d| {
+            d.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        });
+        match disposition.as_deref() {
+            Some("attachment") => PartRole::Attachment,
+            _ if media_type.is_text() && self.filename.is_none() => PartRole::Body,
+            Some("inline") => PartRole::Inline,
+            _ if self.content_id.is_some() => PartRole::Inline,
+            _ => PartRole::Attachment,
+        }
+    }
+
+    /// Child parts of a multipart container; empty for leaf parts.
+    pub fn parts(&self) -> &[BodyPart] {
+        match &self.content {
+            BodyContent::Multipart(parts) => parts,
+            _ => &[],
+        }
+    }
+
+    /// The decoded text of a leaf part, if notmuch included it.
+    pub fn text(&self) -> Option<&str> {
+        match &self.content {
+            BodyContent::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The text of a leaf part as it reads: `format=flowed` plain text is
+    /// reflowed into one line per paragraph, anything else is returned as it
+    /// is. Parts without Content-Type parameters are checked with
+    /// [`flowed::looks_flowed`], as notmuch leaves those out.
+    pub fn display_text(&self) -> Option<Cow<'_, str>> {
+        let text = self.text()?;
+        let media_type = self.media_type();
+        if media_type.essence() != "text/plain" {
+            return Some(Cow::Borrowed(text));
+        }
+        let is_flowed = match media_type.param("format") {
+            Some(format) => format.eq_ignore_ascii_case("flowed"),
+            None => flowed::looks_flowed(text),
+        };
+        if !is_flowed {
+            return Some(Cow::Borrowed(text));
+        }
+        let delsp = media_type
+            .param("delsp")
+            .is_some_and(|delsp| delsp.eq_ignore_ascii_case("yes"));
+        Some(Cow::Owned(flowed::decode(text, delsp)))
+    }
+
+    /// This part and all its descendants, depth-first.
+    pub fn walk(&self) -> Vec<&BodyPart> {
+        let mut parts = vec![self];
+        for part in self.parts() {
+            parts.extend(part.walk());
+        }
+        parts
+    }
+
+    /// Find the part to display, trying the media types in `preference`
+    /// order.
+    ///
+    /// Alternatives are chosen by preference rather than position,
+    /// `multipart/related` is represented by its root part, and attachments
+    /// are never picked.
+    pub fn find_body(&self, preference: &[&str]) -> Option<&BodyPart> {
+        preference
+            .iter()
+            .find_map(|media_type| self.find_body_of_type(media_type))
+    }
+
+    fn find_body_of_type(&self, wanted: &str) -> Option<&BodyPart> {
+        let media_type = self.media_type();
+        match self.role() {
+            PartRole::Body if media_type.essence() == wanted && self.text().is_some() => Some(self),
+            PartRole::Container if media_type.essence() == "multipart/related" => {
+                self.related_root()?.find_body_of_type(wanted)
+            }
+            // Later alternatives are the more faithful ones (RFC 2046)
+            PartRole::Container if media_type.essence() == "multipart/alternative" => self
+                .parts()
+                .iter()
+                .rev()
+                .find_map(|part| part.find_body_of_type(wanted)),
+            PartRole::Container => self
+                .parts()
+                .iter()
+                .find_map(|part| part.find_body_of_type(wanted)),
+            _ => None,
+        }
+    }
+
+    /// The best part of a `multipart/alternative` for `preference`.
+    ///
+    /// Returns `None` for other parts.
+    pub fn best_alternative(&self, preference: &[&str]) -> Option<&BodyPart> {
+        if self.media_type().essence() != "multipart/alternative" {
+            return None;
+        }
+        preference.iter().find_map(|wanted| {
+            self.parts()
+                .iter()
+                .rev()
+                .find(|part| part.media_type().essence() == *wanted)
+        })
+    }
+
+    /// The root part of a `multipart/related`: the one named by the `start`
+    /// parameter, or the first part.
+    fn related_root(&self) -> Option<&BodyPart> {
+        let parts = self.parts();
+        self.media_type()
+            .param("start")
+            .and_then(|start| {
+                parts.iter().find(|part| {
+                    part.content_id
+                        .as_deref()
+                        .is_some_and(|id| same_cid(id, start))
+                })
+            })
+            .or_else(|| parts.first())
+    }
+
+    /// Resolve a `cid:` reference from an HTML body to the part it names.
+    ///
+    /// Accepts the reference with or without the `cid:` prefix and angle
+    /// brackets, and with URL encoding as found in `src` attributes.
+    pub fn resolve_cid(&self, reference: &str) -> Option<&BodyPart> {
+        let reference = reference.strip_prefix("cid:").unwrap_or(reference);
+        let reference = urlencoding::decode(reference)
+            .map(|r| r.into_owned())
+            .unwrap_or_else(|_| reference.to_string());
+        self.walk().into_iter().find(|part| {
+            part.content_id
+                .as_deref()
+                .is_some_and(|id| same_cid(id, &reference))
+        })
+    }
+
+    /// Parts a user would save rather than read: attachments and inline
+    /// parts such as images, excluding the message body.
+    pub fn attachments(&self) -> Vec<&BodyPart> {
+        self.walk()
+            .into_iter()
+            .filter(|part| matches!(part.role(), PartRole::Attachment | PartRole::Inline))
+            .collect()
+    }
+}
+
+/// Find the part to display among the top-level parts of a message.
+///
+/// See [`BodyPart::find_body`].
+pub fn find_body<'a>(parts: &'a [BodyPart], preference: &[&str]) -> Option<&'a BodyPart> {
+    preference
+        .iter()
+        .find_map(|wanted| parts.iter().find_map(|part| part.find_body_of_type(wanted)))
+}
+
+/// Resolve a `cid:` reference among the top-level parts of a message.
+///
+/// See [`BodyPart::resolve_cid`].
+pub fn resolve_cid<'a>(parts: &'a [BodyPart], reference: &str) -> Option<&'a BodyPart> {
+    parts.iter().find_map(|part| part.resolve_cid(reference))
+}
+
+/// Compare Content-IDs, ignoring angle brackets and case.
+fn same_cid(a: &str, b: &str) -> bool {
+    let trim = |id: &str| {
+        id.trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string()
+    };
+    trim(a).eq_ignore_ascii_case(&trim(b))
+}
+
+/// How a body part is presented to the reader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartRole {
+    /// Readable text that forms (part of) the message body
+    Body,
+    /// Content meant to be shown inside the body, such as an image
+    /// referenced by `cid:`
+    Inline,
+    /// A file to be saved or opened separately
+    Attachment,
+    /// A multipart container
+    Container,
+}
+
+/// A parsed MIME media type such as `text/plain; charset=utf-8`.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::body::MediaType;
+/// let media_type = MediaType::parse("Text/HTML; charset=\"UTF-8\"");
+/// assert_eq!(media_type.essence(), "text/html");
+/// assert_eq!(media_type.charset(), Some("UTF-8"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    /// Top-level type, lowercased
+    pub kind: String,
+    /// Subtype, lowercased
+    pub subtype: String,
+    /// Parameters with lowercased names, in header order
+    pub params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// Parse a Content-Type value.
+    ///
+    /// A missing or malformed type is treated as `text/plain`, the RFC 2045
+    /// default.
+    pub fn parse(value: &str) -> Self {
+        let mut segments = split_params(value).into_iter();
+        let essence = segments.next().unwrap_or_default().to_ascii_lowercase();
+        let (kind, subtype) = match essence.split_once('/') {
+            Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() => {
+                (kind.to_string(), subtype.to_string())
+            }
+            _ => ("text".to_string(), "plain".to_string()),
+        };
+
+        let params = segments
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .map(|v| v.replace("\\\"", "\"").replace("\\\\", "\\"))
+                    .unwrap_or_else(|| value.to_string());
+                Some((name.trim().to_ascii_lowercase(), value))
+            })
+            .collect();
+        let params = merge_rfc2231_params(params);
+
+        MediaType {
+            kind,
+            subtype,
+            params,
+        }
+    }
+
+    /// The type without parameters, e.g. `text/plain`.
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.kind, self.subtype)
+    }
+
+    /// Look up a parameter by case-insensitive name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    pub fn is_multipart(&self) -> bool {
+        self.kind == "multipart"
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.kind == "text"
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.kind, self.subtype)?;
+        for (name, value) in &self.params {
+            write!(
+                f,
+                "; {}=\"{}\"",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a header value on `;`, ignoring separators inside quoted strings.
+fn split_params(value: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        match c {
+            _ if escaped => {
+                escaped = false;
+                current.push(c);
+            }
+            '\\' if quoted => {
+                escaped = true;
+                current.push(c);
+            }
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ';' if !quoted => segments.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current.trim().to_string());
+    segments
+}
+
+/// Combine RFC 2231 parameter sections (`name*0*=`, `name*1=`, ..) and
+/// extended values (`name*=`) into plain decoded parameters.
+///
+/// An extended value replaces a plain parameter of the same name, and
+/// encoded-words in plain values are decoded as well.
+fn merge_rfc2231_params(params: Vec<(String, String)>) -> Vec<(String, String)> {
+    // (name, value, from an extended or continued parameter)
+    let mut merged: Vec<(String, String, bool)> = Vec::new();
+    let mut sections: Vec<(String, Vec<ParamSection>)> = Vec::new();
+
+    for (name, value) in params {
+        let (name, extended) = match name.strip_suffix('*') {
+            Some(name) => (name.to_string(), true),
+            None => (name, false),
+        };
+        if let Some((base, number)) = name.split_once('*')
+            && let Ok(number) = number.parse::<u32>()
+        {
+            let section = ParamSection {
+                number,
+                extended,
+                value,
+            };
+            match sections.iter_mut().find(|(name, _)| name == base) {
+                Some((_, parts)) => parts.push(section),
+                None => sections.push((base.to_string(), vec![section])),
+            }
+            continue;
+        }
+
+        let decoded = if extended {
+            decode_extended_value(&value).unwrap_or(value)
+        } else {
+            decode_encoded_words(&value)
+        };
+        insert_param(&mut merged, name, decoded, extended);
+    }
+
+    for (name, mut parts) in sections {
+        parts.sort_by_key(|part| part.number);
+        let mut charset = String::new();
+        let mut bytes = Vec::new();
+        for part in parts {
+            if !part.extended {
+                bytes.extend_from_slice(part.value.as_bytes());
+                continue;
+            }
+            let mut encoded = part.value.as_str();
+            // Only the first section carries charset'language'
+            if part.number == 0
+                && let Some((set, rest)) = encoded.split_once('\'')
+                && let Some((_language, rest)) = rest.split_once('\'')
+            {
+                charset = set.to_string();
+                encoded = rest;
+            }
+            bytes.extend_from_slice(&urlencoding::decode_binary(encoded.as_bytes()));
+        }
+        let value = decode_charset(&charset, &bytes)
+            .unwrap_or_else(|| String::from_utf8_lossy(&bytes).into_owned());
+        insert_param(&mut merged, name, value, true);
+    }
+
+    merged
+        .into_iter()
+        .map(|(name, value, _)| (name, value))
+        .collect()
+}
+
+/// One `name*N=` or `name*N*=` section of a continued parameter.
+struct ParamSection {
+    number: u32,
+    extended: bool,
+    value: String,
+}
+
+fn insert_param(
+    merged: &mut Vec<(String, String, bool)>,
+    name: String,
+    value: String,
+    rfc2231: bool,
+) {
+    match merged.iter_mut().find(|(existing, _, _)| *existing == name) {
+        Some(entry) if rfc2231 || !entry.2 => *entry = (name, value, rfc2231),
+        Some(_) => {}
+        None => merged.push((name, value, rfc2231)),
+    }
 }
 
 /// Content of a body part - either text, multipart container, or empty
-#[derive(Debug, Clone, Serialize, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum BodyContent {
     Text(String),
     Multipart(Vec<BodyPart>),
@@ -45,15 +490,84 @@ impl<'de> Deserialize<'de> for BodyContent {
     where
         D: Deserializer<'de>,
     {
-        let value = serde_json::Value::deserialize(deserializer)?;
-        match value {
-            serde_json::Value::String(s) => Ok(BodyContent::Text(s)),
-            serde_json::Value::Array(arr) => {
-                let parts: Vec<BodyPart> = serde_json::from_value(serde_json::Value::Array(arr))
-                    .map_err(serde::de::Error::custom)?;
+        struct ContentVisitor;
+
+        impl<'de> Visitor<'de> for ContentVisitor {
+            type Value = BodyContent;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("text or a list of body parts")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<BodyContent, E> {
+                Ok(BodyContent::Text(value.to_string()))
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<BodyContent, E> {
+                Ok(BodyContent::Text(value))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<BodyContent, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut parts = Vec::new();
+                while let Some(part) = seq.next_element::<BodyPart>()? {
+                    parts.push(part);
+                }
                 Ok(BodyContent::Multipart(parts))
             }
-            _ => Ok(BodyContent::Empty),
+
+            fn visit_map<A>(self, mut map: A) -> Result<BodyContent, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                while map
+                    .next_entry::<de::IgnoredAny, de::IgnoredAny>()?
+                    .is_some()
+                {}
+                Ok(BodyContent::Empty)
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> Result<BodyContent, E> {
+                Ok(BodyContent::Empty)
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> Result<BodyContent, E> {
+                Ok(BodyContent::Empty)
+            }
+
+            fn visit_u64<E: de::Error>(self, _: u64) -> Result<BodyContent, E> {
+                Ok(BodyContent::Empty)
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> Result<BodyContent, E> {
+                Ok(BodyContent::Empty)
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<BodyContent, E> {
+                Ok(BodyContent::Empty)
+            }
+
+            fn visit_none<E: de::Error>(self) -> Result<BodyContent, E> {
+                Ok(BodyContent::Empty)
+            }
+        }
+
+        deserializer.deserialize_any(ContentVisitor)
+    }
+}
+
+impl Serialize for BodyContent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Mirror notmuch's JSON shape so serialized parts deserialize unchanged
+        match self {
+            BodyContent::Text(text) => serializer.serialize_str(text),
+            BodyContent::Multipart(parts) => parts.serialize(serializer),
+            BodyContent::Empty => serializer.serialize_none(),
         }
     }
 }
@@ -62,11 +576,14 @@ fn deserialize_body_content<'de, D>(deserializer: D) -> Result<BodyContent, D::E
 where
     D: Deserializer<'de>,
 {
-    let opt: Option<serde_json::Value> = Option::deserialize(deserializer)?;
-    match opt {
-        Some(value) => serde_json::from_value(value).map_err(serde::de::Error::custom),
-        None => Ok(BodyContent::Empty),
-    }
+    Ok(Option::<BodyContent>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn deserialize_parameter_value<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.map(|value| decode_parameter_value(&value)))
 }
 
 #[cfg(test)]
@@ -165,6 +682,7 @@ mod tests {
             content: BodyContent::Empty,
             content_disposition: Some("attachment".to_string()),
             content_id: None,
+            content_charset: None,
             filename: Some("image.png".to_string()),
             content_transfer_encoding: None,
             content_length: None,
@@ -178,6 +696,7 @@ mod tests {
             content: BodyContent::Empty,
             content_disposition: Some("inline".to_string()),
             content_id: None,
+            content_charset: None,
             filename: Some("image.png".to_string()),
             content_transfer_encoding: None,
             content_length: None,
@@ -191,6 +710,7 @@ mod tests {
             content: BodyContent::Text("Hello".to_string()),
             content_disposition: None,
             content_id: None,
+            content_charset: None,
             filename: None,
             content_transfer_encoding: None,
             content_length: None,
@@ -204,10 +724,248 @@ mod tests {
             content: BodyContent::Multipart(vec![]),
             content_disposition: None,
             content_id: None,
+            content_charset: None,
             filename: None,
             content_transfer_encoding: None,
             content_length: None,
         };
         assert!(!multipart.is_attachment());
     }
+    #[test]
+    fn test_serialize_body_part_roundtrip() {
+        let json_data = r#"{
+            "id": 1,
+            "content-type": "multipart/alternative",
+            "content": [
+                {"id": 2, "content-type": "text/plain", "content": "Hello"},
+                {"id": 3, "content-type": "image/png", "content-disposition": "attachment", "filename": "a.png"}
+            ]
+        }"#;
+
+        let body_part: BodyPart = serde_json::from_str(json_data).unwrap();
+        let serialized = serde_json::to_string(&body_part).unwrap();
+        let reparsed: BodyPart = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(body_part, reparsed);
+    }
+
+    fn part(id: u32, content_type: &str, content: BodyContent) -> BodyPart {
+        BodyPart {
+            id,
+            content_type: content_type.to_string(),
+            content,
+            content_disposition: None,
+            content_id: None,
+            content_charset: None,
+            filename: None,
+            content_transfer_encoding: None,
+            content_length: None,
+        }
+    }
+
+    fn text(id: u32, content_type: &str, text: &str) -> BodyPart {
+        part(id, content_type, BodyContent::Text(text.to_string()))
+    }
+
+    /// multipart/mixed
+    /// ├── multipart/alternative
+    /// │   ├── text/plain
+    /// │   └── multipart/related
+    /// │       ├── text/html referencing cid:logo@example.com
+    /// │       └── image/png (Content-ID <logo@example.com>)
+    /// └── application/pdf attachment
+    fn newsletter() -> BodyPart {
+        let mut logo = part(6, "image/png", BodyContent::Empty);
+        logo.content_id = Some("<logo@example.com>".to_string());
+        logo.content_disposition = Some("inline".to_string());
+        let mut pdf = part(7, "application/pdf", BodyContent::Empty);
+        pdf.content_disposition = Some("attachment".to_string());
+        pdf.filename = Some("report.pdf".to_string());
+
+        part(
+            1,
+            "multipart/mixed",
+            BodyContent::Multipart(vec![
+                part(
+                    2,
+                    "multipart/alternative",
+                    BodyContent::Multipart(vec![
+                        text(3, "text/plain", "Plain"),
+                        part(
+                            4,
+                            "multipart/related",
+                            BodyContent::Multipart(vec![
+                                text(5, "text/html", "<img src=\"cid:logo@example.com\">"),
+                                logo,
+                            ]),
+                        ),
+                    ]),
+                ),
+                pdf,
+            ]),
+        )
+    }
+
+    #[test]
+    fn test_media_type_parse() {
+        let media_type =
+            MediaType::parse("Multipart/Related; type=\"text/html\"; start=\"<a;b>\"; Boundary=x");
+        assert_eq!(media_type.essence(), "multipart/related");
+        assert_eq!(media_type.param("type"), Some("text/html"));
+        assert_eq!(media_type.param("start"), Some("<a;b>"));
+        assert_eq!(media_type.param("boundary"), Some("x"));
+        assert!(media_type.is_multipart());
+
+        assert_eq!(MediaType::parse("").essence(), "text/plain");
+        assert_eq!(MediaType::parse("garbage").essence(), "text/plain");
+        assert_eq!(
+            MediaType::parse("text/plain; charset=iso-8859-1").to_string(),
+            "text/plain; charset=\"iso-8859-1\""
+        );
+    }
+
+    #[test]
+    fn test_media_type_rfc2231_params() {
+        let media_type = MediaType::parse(
+            "application/pdf; name=\"fallback.pdf\"; name*0*=UTF-8''%E8%AB%8B%E6%B1%82; \
+             name*1*=%E6%9B%B8; name*2=\" 2024.pdf\"",
+        );
+        assert_eq!(media_type.param("name"), Some("請求書 2024.pdf"));
+
+        let media_type = MediaType::parse("text/plain; title*=iso-8859-1'fr'R%E9sum%E9");
+        assert_eq!(media_type.param("title"), Some("Résumé"));
+
+        let media_type =
+            MediaType::parse("image/png; name=\"=?ISO-2022-JP?B?GyRCRnxLXDhsGyhCLnBkZg==?=\"");
+        assert_eq!(media_type.param("name"), Some("日本語.pdf"));
+    }
+
+    #[test]
+    fn test_filename_decoded_on_deserialize() {
+        let part: BodyPart = serde_json::from_str(
+            r#"{"id": 2, "content-type": "application/pdf",
+                "content-disposition": "attachment",
+                "filename": "=?ISO-2022-JP?B?GyRCRnxLXDhsGyhCLnBkZg==?="}"#,
+        )
+        .unwrap();
+        assert_eq!(part.filename.as_deref(), Some("日本語.pdf"));
+
+        let part: BodyPart = serde_json::from_str(
+            r#"{"id": 2, "content-type": "application/pdf",
+                "filename": "UTF-8''%E8%AB%8B%E6%B1%82%E6%9B%B8.pdf"}"#,
+        )
+        .unwrap();
+        assert_eq!(part.filename.as_deref(), Some("請求書.pdf"));
+    }
+
+    #[test]
+    fn test_charset_prefers_notmuch_field() {
+        let mut plain = text(1, "text/plain; charset=us-ascii", "Hi");
+        assert_eq!(plain.charset().as_deref(), Some("us-ascii"));
+        plain.content_charset = Some("utf-8".to_string());
+        assert_eq!(plain.charset().as_deref(), Some("utf-8"));
+    }
+
+    #[test]
+    fn test_part_roles() {
+        let message = newsletter();
+        let roles: Vec<(u32, PartRole)> = message
+            .walk()
+            .into_iter()
+            .map(|part| (part.id, part.role()))
+            .collect();
+        assert_eq!(
+            roles,
+            vec![
+                (1, PartRole::Container),
+                (2, PartRole::Container),
+                (3, PartRole::Body),
+                (4, PartRole::Container),
+                (5, PartRole::Body),
+                (6, PartRole::Inline),
+                (7, PartRole::Attachment),
+            ]
+        );
+
+        // Text with an inline disposition is still body text
+        let mut inline_text = text(8, "text/plain", "Hi");
+        inline_text.content_disposition = Some("inline".to_string());
+        assert_eq!(inline_text.role(), PartRole::Body);
+
+        let ids: Vec<u32> = message.attachments().iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![6, 7]);
+    }
+
+    #[test]
+    fn test_find_body_by_preference() {
+        let message = newsletter();
+        assert_eq!(message.find_body(PREFER_HTML).map(|p| p.id), Some(5));
+        assert_eq!(message.find_body(PREFER_PLAIN).map(|p| p.id), Some(3));
+        assert_eq!(message.find_body(&["image/png"]).map(|p| p.id), None);
+
+        let alternative = &message.parts()[0];
+        assert_eq!(
+            alternative
+                .best_alternative(&["multipart/related", "text/plain"])
+                .map(|p| p.id),
+            Some(4)
+        );
+        assert!(message.best_alternative(PREFER_HTML).is_none());
+
+        let parts = vec![message.clone()];
+        assert_eq!(
+            find_body(&parts, PREFER_PLAIN).map(|p| p.text()),
+            Some(Some("Plain"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_cid() {
+        let message = newsletter();
+        assert_eq!(
+            message.resolve_cid("cid:logo@example.com").map(|p| p.id),
+            Some(6)
+        );
+        assert_eq!(
+            message.resolve_cid("<LOGO@example.com>").map(|p| p.id),
+            Some(6)
+        );
+        assert_eq!(
+            message.resolve_cid("cid:logo%40example.com").map(|p| p.id),
+            Some(6)
+        );
+        assert!(message.resolve_cid("cid:missing@example.com").is_none());
+    }
+
+    #[test]
+    fn test_display_text_reflows_flowed_text() {
+        let part = |content_type: &str, text: &str| BodyPart {
+            id: 1,
+            content_type: content_type.to_string(),
+            content: BodyContent::Text(text.to_string()),
+            content_disposition: None,
+            content_id: None,
+            content_charset: None,
+            filename: None,
+            content_transfer_encoding: None,
+            content_length: None,
+        };
+        let flowed = "One \nparagraph.\n> Quoted \n> too.\n";
+
+        let reflowed = part("text/plain; format=flowed", flowed);
+        assert_eq!(
+            reflowed.display_text().as_deref(),
+            Some("One paragraph.\n> Quoted too.\n")
+        );
+        let delsp = part("text/plain; format=flowed; delsp=yes", "Hyphen- \nated\n");
+        assert_eq!(delsp.display_text().as_deref(), Some("Hyphen-ated\n"));
+        // Fixed text and HTML stay as they are
+        let fixed = part("text/plain; format=fixed", flowed);
+        assert_eq!(fixed.display_text().as_deref(), Some(flowed));
+        let html = part("text/html", "<p>One \nparagraph.</p>");
+        assert_eq!(
+            html.display_text().as_deref(),
+            Some("<p>One \nparagraph.</p>")
+        );
+    }
 }
@@ -0,0 +1,1231 @@
+//! Calendar invitations.
+//!
+//! Meeting invitations arrive as `text/calendar` parts holding an
+//! iCalendar (RFC 5545) object whose `METHOD` says what the organizer wants
+//! (RFC 5546): `REQUEST` asks attendees to respond, `CANCEL` withdraws the
+//! meeting. [`Calendar::parse`] reads the `VEVENT`s of such a part, and
+//! [`Calendar::reply`] produces the `METHOD:REPLY` object an attendee sends
+//! back to accept, tentatively accept or decline. [`send_rsvp`] wraps that
+//! reply in a message as iMIP (RFC 6047) describes and sends it.
+//!
+//! Times are resolved to absolute instants where possible: UTC times
+//! directly, `TZID`s naming an IANA zone through the tz database, and
+//! other `TZID`s (as sent by Outlook, e.g. `W. Europe Standard Time`)
+//! through the `VTIMEZONE` definitions included in the object.
+
+use std::fmt::Write as _;
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc,
+    Weekday,
+};
+
+use crate::body::BodyPart;
+use crate::client::NotmuchClient;
+use crate::common::Mailbox;
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::{ComposableMessage, MailSender, MessageBuilder};
+use crate::thread::Message;
+
+/// Product identifier written into replies.
+const PRODID: &str = "-//whynot//whynot email client//EN";
+
+/// A parsed iCalendar object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calendar {
+    /// The iTIP method, e.g. `REQUEST`, `CANCEL` or `REPLY`
+    pub method: Option<String>,
+    /// The events, the recurring master first followed by any overridden
+    /// occurrences
+    pub events: Vec<Event>,
+    /// `VTIMEZONE` definitions, kept to resolve times and to copy into
+    /// replies
+    timezones: Vec<Component>,
+}
+
+/// A `VEVENT`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub organizer: Option<Mailbox>,
+    pub attendees: Vec<Attendee>,
+    pub start: Option<EventTime>,
+    pub end: Option<EventTime>,
+    pub recurrence: Option<Recurrence>,
+    /// Revision of the event; replies must refer to the one they answer
+    pub sequence: u32,
+    /// `STATUS`, e.g. `CANCELLED`
+    pub status: Option<String>,
+    /// The original properties, copied into replies
+    properties: Vec<Property>,
+}
+
+/// An `ATTENDEE` of an event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attendee {
+    pub mailbox: Mailbox,
+    /// `ROLE`, e.g. `REQ-PARTICIPANT` or `OPT-PARTICIPANT`
+    pub role: Option<String>,
+    pub partstat: PartStat,
+    /// Whether the organizer asked for a response
+    pub rsvp: bool,
+}
+
+/// Participation status of an attendee (`PARTSTAT`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartStat {
+    NeedsAction,
+    Accepted,
+    Tentative,
+    Declined,
+    Delegated,
+}
+
+impl PartStat {
+    /// Parse a `PARTSTAT` value; unknown values count as `NEEDS-ACTION`
+    /// as RFC 5545 requires.
+    pub fn parse(value: &str) -> PartStat {
+        match value.to_ascii_uppercase().as_str() {
+            "ACCEPTED" => PartStat::Accepted,
+            "TENTATIVE" => PartStat::Tentative,
+            "DECLINED" => PartStat::Declined,
+            "DELEGATED" => PartStat::Delegated,
+            _ => PartStat::NeedsAction,
+        }
+    }
+
+    /// Parse a response chosen by the user: `accept`, `tentative` or
+    /// `decline`.
+    pub fn from_response(response: &str) -> Option<PartStat> {
+        match response {
+            "accept" => Some(PartStat::Accepted),
+            "tentative" => Some(PartStat::Tentative),
+            "decline" => Some(PartStat::Declined),
+            _ => None,
+        }
+    }
+
+    /// The `PARTSTAT` value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartStat::NeedsAction => "NEEDS-ACTION",
+            PartStat::Accepted => "ACCEPTED",
+            PartStat::Tentative => "TENTATIVE",
+            PartStat::Declined => "DECLINED",
+            PartStat::Delegated => "DELEGATED",
+        }
+    }
+
+    /// A label for display, also used as the subject prefix of replies.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PartStat::NeedsAction => "Awaiting response",
+            PartStat::Accepted => "Accepted",
+            PartStat::Tentative => "Tentative",
+            PartStat::Declined => "Declined",
+            PartStat::Delegated => "Delegated",
+        }
+    }
+}
+
+/// Start or end of an event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventTime {
+    /// An all-day value
+    Date(NaiveDate),
+    /// A local time without a time zone, the same wall clock time wherever
+    /// the reader is
+    Floating(NaiveDateTime),
+    /// An absolute time
+    Zoned(DateTime<FixedOffset>),
+}
+
+impl EventTime {
+    /// The time as seen in `tz`; floating times are taken as they are.
+    fn naive_in<Tz: TimeZone>(&self, tz: &Tz) -> Option<NaiveDateTime> {
+        match self {
+            EventTime::Date(_) => None,
+            EventTime::Floating(time) => Some(*time),
+            EventTime::Zoned(time) => Some(time.with_timezone(tz).naive_local()),
+        }
+    }
+}
+
+/// A recurrence rule (`RRULE`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    /// `DAILY`, `WEEKLY`, `MONTHLY` or `YEARLY` (or a finer frequency)
+    pub frequency: String,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<EventTime>,
+    /// `BYDAY` entries, with the optional ordinal, e.g. `-1SU`
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+}
+
+impl Recurrence {
+    /// Parse an `RRULE` value such as `FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10`.
+    pub fn parse(value: &str) -> Option<Recurrence> {
+        let mut recurrence = Recurrence {
+            frequency: String::new(),
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+        };
+        for part in value.split(';') {
+            let (key, value) = part.split_once('=')?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => recurrence.frequency = value.to_ascii_uppercase(),
+                "INTERVAL" => recurrence.interval = value.parse().ok()?,
+                "COUNT" => recurrence.count = value.parse().ok(),
+                "UNTIL" => recurrence.until = parse_time(value, None, &[]),
+                "BYDAY" => recurrence.by_day = value.split(',').filter_map(parse_by_day).collect(),
+                "BYMONTHDAY" => {
+                    recurrence.by_month_day =
+                        value.split(',').filter_map(|d| d.parse().ok()).collect()
+                }
+                "BYMONTH" => {
+                    recurrence.by_month = value.split(',').filter_map(|m| m.parse().ok()).collect()
+                }
+                _ => {}
+            }
+        }
+        (!recurrence.frequency.is_empty()).then_some(recurrence)
+    }
+
+    /// A human readable description, e.g. `Every 2 weeks on Monday and
+    /// Wednesday, until Fri 1 Mar 2024`.
+    pub fn summary(&self) -> String {
+        let (single, unit) = match self.frequency.as_str() {
+            "SECONDLY" => ("Every second", "seconds"),
+            "MINUTELY" => ("Every minute", "minutes"),
+            "HOURLY" => ("Hourly", "hours"),
+            "DAILY" => ("Daily", "days"),
+            "WEEKLY" => ("Weekly", "weeks"),
+            "MONTHLY" => ("Monthly", "months"),
+            _ => ("Yearly", "years"),
+        };
+        let mut summary = if self.interval > 1 {
+            format!("Every {} {}", self.interval, unit)
+        } else {
+            single.to_string()
+        };
+
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|(ordinal, day)| match ordinal {
+                    Some(n) => format!("the {} {}", ordinal_name(*n), weekday_name(*day)),
+                    None => weekday_name(*day).to_string(),
+                })
+                .collect();
+            let _ = write!(summary, " on {}", join_and(&days));
+        } else if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self
+                .by_month_day
+                .iter()
+                .map(|day| match day {
+                    -1 => "the last day".to_string(),
+                    day => format!("day {}", day),
+                })
+                .collect();
+            let _ = write!(summary, " on {}", join_and(&days));
+        }
+
+        if let Some(count) = self.count {
+            let _ = write!(summary, ", {} times", count);
+        } else if let Some(until) = &self.until {
+            let date = match until {
+                EventTime::Date(date) => *date,
+                EventTime::Floating(time) => time.date(),
+                EventTime::Zoned(time) => time.with_timezone(&Local).date_naive(),
+            };
+            let _ = write!(summary, ", until {}", date.format("%a %-d %b %Y"));
+        }
+        summary
+    }
+}
+
+impl Calendar {
+    /// Parse an iCalendar object.
+    ///
+    /// Returns [`NotmuchError::InvalidInput`] when the text is not a
+    /// well-formed `VCALENDAR`.
+    pub fn parse(text: &str) -> Result<Calendar> {
+        let lines = unfold(text);
+        let mut stack: Vec<Component> = Vec::new();
+        let mut root = None;
+
+        for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+            let property = Property::parse(line).ok_or_else(|| {
+                NotmuchError::InvalidInput(format!("Invalid calendar line: {}", line))
+            })?;
+            match property.name.as_str() {
+                "BEGIN" => stack.push(Component {
+                    name: property.value.to_ascii_uppercase(),
+                    properties: Vec::new(),
+                    components: Vec::new(),
+                }),
+                "END" => {
+                    let component = stack
+                        .pop()
+                        .filter(|c| c.name.eq_ignore_ascii_case(&property.value))
+                        .ok_or_else(|| {
+                            NotmuchError::InvalidInput(format!(
+                                "Unexpected END:{} in calendar",
+                                property.value
+                            ))
+                        })?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.components.push(component),
+                        None => {
+                            root = Some(component);
+                            break;
+                        }
+                    }
+                }
+                _ => stack
+                    .last_mut()
+                    .ok_or_else(|| {
+                        NotmuchError::InvalidInput(
+                            "Calendar property outside BEGIN/END".to_string(),
+                        )
+                    })?
+                    .properties
+                    .push(property),
+            }
+        }
+
+        let root = root
+            .filter(|c| c.name == "VCALENDAR")
+            .ok_or_else(|| NotmuchError::InvalidInput("Not a complete VCALENDAR".to_string()))?;
+        let timezones: Vec<Component> = root
+            .components
+            .iter()
+            .filter(|c| c.name == "VTIMEZONE")
+            .cloned()
+            .collect();
+        let mut events: Vec<Event> = root
+            .components
+            .iter()
+            .filter(|c| c.name == "VEVENT")
+            .map(|c| Event::from_component(c, &timezones))
+            .collect();
+        // The master event first, then overridden occurrences
+        events.sort_by_key(|event| event.property("RECURRENCE-ID").is_some());
+
+        Ok(Calendar {
+            method: root
+                .property("METHOD")
+                .map(|p| p.value.to_ascii_uppercase()),
+            events,
+            timezones,
+        })
+    }
+
+    /// The event to show: the recurring master, or the only occurrence.
+    pub fn event(&self) -> Option<&Event> {
+        self.events.first()
+    }
+
+    /// Whether the organizer asks attendees to respond.
+    pub fn expects_reply(&self) -> bool {
+        self.method.as_deref() == Some("REQUEST")
+    }
+
+    /// Whether this withdraws a meeting.
+    pub fn is_cancelled(&self) -> bool {
+        self.method.as_deref() == Some("CANCEL")
+            || self
+                .event()
+                .and_then(|e| e.status.as_deref())
+                .is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED"))
+    }
+
+    /// Build the `METHOD:REPLY` object with which `attendee` responds to
+    /// this request.
+    ///
+    /// Each event is answered with its identifying properties and only the
+    /// attendee's own `ATTENDEE` line, as RFC 5546 asks. The attendee's
+    /// name is taken from the invitation when they are listed; otherwise
+    /// the bare address is used, as happens for invitations sent to a list.
+    pub fn reply(&self, attendee: &str, partstat: PartStat) -> Result<String> {
+        if self.events.is_empty() {
+            return Err(NotmuchError::InvalidInput(
+                "Calendar has no event to reply to".to_string(),
+            ));
+        }
+        let dtstamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut out = String::new();
+        write_line(&mut out, "BEGIN:VCALENDAR");
+        write_line(&mut out, &format!("PRODID:{}", PRODID));
+        write_line(&mut out, "VERSION:2.0");
+        write_line(&mut out, "METHOD:REPLY");
+
+        // Only the time zones the copied times refer to
+        let tzids: Vec<&str> = self
+            .events
+            .iter()
+            .flat_map(|event| event.properties.iter())
+            .filter_map(|p| p.param("TZID"))
+            .collect();
+        for timezone in &self.timezones {
+            if timezone
+                .property("TZID")
+                .is_some_and(|tzid| tzids.contains(&tzid.value.as_str()))
+            {
+                timezone.write(&mut out);
+            }
+        }
+
+        for event in &self.events {
+            write_line(&mut out, "BEGIN:VEVENT");
+            for property in &event.properties {
+                if matches!(
+                    property.name.as_str(),
+                    "UID"
+                        | "SEQUENCE"
+                        | "RECURRENCE-ID"
+                        | "DTSTART"
+                        | "DTEND"
+                        | "DURATION"
+                        | "SUMMARY"
+                        | "ORGANIZER"
+                ) {
+                    property.write(&mut out);
+                }
+            }
+            write_line(&mut out, &format!("DTSTAMP:{}", dtstamp));
+
+            let name = event
+                .attendees
+                .iter()
+                .find(|a| a.mailbox.has_address(attendee))
+                .and_then(|a| a.mailbox.name.clone());
+            let mut params = vec![("PARTSTAT".to_string(), partstat.as_str().to_string())];
+            if let Some(name) = name {
+                params.push(("CN".to_string(), name));
+            }
+            Property {
+                name: "ATTENDEE".to_string(),
+                params,
+                value: format!("mailto:{}", attendee.trim()),
+            }
+            .write(&mut out);
+            write_line(&mut out, "END:VEVENT");
+        }
+        write_line(&mut out, "END:VCALENDAR");
+        Ok(out)
+    }
+
+    /// A message answering the invitation in `original` on behalf of
+    /// `from`, addressed to the organizer.
+    pub fn reply_builder(
+        &self,
+        original: &Message,
+        from: &str,
+        partstat: PartStat,
+    ) -> Result<MessageBuilder> {
+        let event = self.event().ok_or_else(|| {
+            NotmuchError::InvalidInput("Calendar has no event to reply to".to_string())
+        })?;
+        let organizer = event
+            .organizer
+            .as_ref()
+            .ok_or_else(|| NotmuchError::InvalidInput("Invitation has no organizer".to_string()))?;
+        let attendee = Mailbox::parse(from)
+            .map(|mailbox| mailbox.address)
+            .unwrap_or_else(|| from.to_string());
+        let summary = event.summary.as_deref().unwrap_or("Invitation");
+        let verb = match partstat {
+            PartStat::Accepted => "accepted",
+            PartStat::Tentative => "tentatively accepted",
+            PartStat::Declined => "declined",
+            _ => "responded to",
+        };
+
+        Ok(ComposableMessage::builder()
+            .from(from.to_string())
+            .to(organizer.to_string())
+            .subject(format!("{}: {}", partstat.label(), summary))
+            .in_reply_to(original.id.clone())
+            .add_reference(original.id.clone())
+            .body(format!(
+                "{} has {} this invitation.\n\n{}\n{}\n",
+                attendee,
+                verb,
+                summary,
+                event.when()
+            ))
+            .calendar("REPLY".to_string(), self.reply(&attendee, partstat)?))
+    }
+}
+
+impl Event {
+    fn from_component(component: &Component, timezones: &[Component]) -> Event {
+        let text = |name: &str| component.property(name).map(|p| unescape(&p.value));
+        let time = |name: &str| {
+            component
+                .property(name)
+                .and_then(|p| parse_time(&p.value, p.param("TZID"), timezones))
+        };
+        let start = time("DTSTART");
+        let end = time("DTEND").or_else(|| {
+            let duration = parse_duration(&component.property("DURATION")?.value)?;
+            match start.as_ref()? {
+                EventTime::Date(date) => date.checked_add_signed(duration).map(EventTime::Date),
+                EventTime::Floating(time) => {
+                    time.checked_add_signed(duration).map(EventTime::Floating)
+                }
+                EventTime::Zoned(time) => time.checked_add_signed(duration).map(EventTime::Zoned),
+            }
+        });
+
+        Event {
+            uid: text("UID"),
+            summary: text("SUMMARY"),
+            description: text("DESCRIPTION"),
+            location: text("LOCATION"),
+            organizer: component.property("ORGANIZER").map(Property::mailbox),
+            attendees: component
+                .properties
+                .iter()
+                .filter(|p| p.name == "ATTENDEE")
+                .map(|p| Attendee {
+                    mailbox: p.mailbox(),
+                    role: p.param("ROLE").map(str::to_string),
+                    partstat: p
+                        .param("PARTSTAT")
+                        .map(PartStat::parse)
+                        .unwrap_or(PartStat::NeedsAction),
+                    rsvp: p
+                        .param("RSVP")
+                        .is_some_and(|v| v.eq_ignore_ascii_case("TRUE")),
+                })
+                .collect(),
+            start,
+            end,
+            recurrence: component
+                .property("RRULE")
+                .and_then(|p| Recurrence::parse(&p.value)),
+            sequence: text("SEQUENCE")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(0),
+            status: text("STATUS").map(|s| s.to_ascii_uppercase()),
+            properties: component.properties.clone(),
+        }
+    }
+
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    /// The attendee with this address, if listed.
+    pub fn attendee(&self, address: &str) -> Option<&Attendee> {
+        self.attendees
+            .iter()
+            .find(|a| a.mailbox.has_address(address))
+    }
+
+    /// When the event takes place, in the local time zone.
+    pub fn when(&self) -> String {
+        self.when_in(&Local)
+    }
+
+    /// When the event takes place, in `tz`, e.g. `Mon 5 Feb 2024
+    /// 14:00–15:00` or `Mon 5 Feb 2024 (all day)`.
+    pub fn when_in<Tz: TimeZone>(&self, tz: &Tz) -> String {
+        const DATE: &str = "%a %-d %b %Y";
+        const DATE_TIME: &str = "%a %-d %b %Y %H:%M";
+
+        let Some(start) = &self.start else {
+            return "Unknown time".to_string();
+        };
+        if let EventTime::Date(start) = start {
+            // DTEND of an all-day event is the day after it ends
+            let last = match &self.end {
+                Some(EventTime::Date(end)) if *end > *start + Duration::days(1) => {
+                    Some(*end - Duration::days(1))
+                }
+                _ => None,
+            };
+            return match last {
+                Some(last) => format!("{} – {} (all day)", start.format(DATE), last.format(DATE)),
+                None => format!("{} (all day)", start.format(DATE)),
+            };
+        }
+
+        let start = start.naive_in(tz).unwrap_or_default();
+        match self.end.as_ref().and_then(|end| end.naive_in(tz)) {
+            Some(end) if end.date() == start.date() => {
+                format!("{}–{}", start.format(DATE_TIME), end.format("%H:%M"))
+            }
+            Some(end) => format!("{} – {}", start.format(DATE_TIME), end.format(DATE_TIME)),
+            None => start.format(DATE_TIME).to_string(),
+        }
+    }
+}
+
+/// The `text/calendar` part of a message, if it has one.
+pub fn calendar_part(message: &Message) -> Option<&BodyPart> {
+    message
+        .body
+        .iter()
+        .flat_map(|part| part.walk())
+        .find(|part| part.media_type().essence() == "text/calendar")
+}
+
+/// Parse the invitation in a message, fetching the calendar part with
+/// [`NotmuchClient::part`] when notmuch left out its content.
+pub async fn load_invite(
+    client: &dyn NotmuchClient,
+    message: &Message,
+) -> Result<Option<Calendar>> {
+    let Some(part) = calendar_part(message) else {
+        return Ok(None);
+    };
+    let text = match part.text() {
+        Some(text) => text.to_string(),
+        None => String::from_utf8_lossy(&client.part(&message.id, part.id).await?).into_owned(),
+    };
+    Calendar::parse(&text).map(Some)
+}
+
+/// Respond to the invitation in `original` and send the reply to the
+/// organizer, returning its Message-ID.
+///
+/// The reply is sent from `from`, or the sender's own address if `None`;
+/// it has to be the address the invitation went to.
+pub async fn send_rsvp(
+    sender: &dyn MailSender,
+    original: &Message,
+    calendar: &Calendar,
+    partstat: PartStat,
+    from: Option<&str>,
+) -> Result<String> {
+    if !calendar.expects_reply() {
+        return Err(NotmuchError::InvalidInput(
+            "This calendar message does not ask for a response".to_string(),
+        ));
+    }
+    let from = match from {
+        Some(from) => from.to_string(),
+        None => sender.get_from_address().await?,
+    };
+    let message = calendar.reply_builder(original, &from, partstat)?.build()?;
+    sender.send(message).await
+}
+
+/// A calendar component such as `VEVENT` with its properties and
+/// subcomponents.
+#[derive(Debug, Clone, PartialEq)]
+struct Component {
+    name: String,
+    properties: Vec<Property>,
+    components: Vec<Component>,
+}
+
+impl Component {
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    fn write(&self, out: &mut String) {
+        write_line(out, &format!("BEGIN:{}", self.name));
+        for property in &self.properties {
+            property.write(out);
+        }
+        for component in &self.components {
+            component.write(out);
+        }
+        write_line(out, &format!("END:{}", self.name));
+    }
+}
+
+/// A content line: `NAME;PARAM=value:value`.
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    /// Upper-cased name
+    name: String,
+    /// Parameters with upper-cased names and unquoted values
+    params: Vec<(String, String)>,
+    /// The raw value, still escaped
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Property> {
+        let name_end = line.find([';', ':'])?;
+        let name = line[..name_end].trim().to_ascii_uppercase();
+        let mut params = Vec::new();
+        let mut rest = &line[name_end..];
+
+        while let Some(param) = rest.strip_prefix(';') {
+            let (param_name, after) = param.split_once('=')?;
+            let mut values = Vec::new();
+            let mut after = after;
+            loop {
+                if let Some(quoted) = after.strip_prefix('"') {
+                    let end = quoted.find('"')?;
+                    values.push(&quoted[..end]);
+                    after = &quoted[end + 1..];
+                } else {
+                    let end = after.find([',', ';', ':'])?;
+                    values.push(&after[..end]);
+                    after = &after[end..];
+                }
+                match after.strip_prefix(',') {
+                    Some(next) => after = next,
+                    None => break,
+                }
+            }
+            params.push((param_name.trim().to_ascii_uppercase(), values.join(",")));
+            rest = after;
+        }
+
+        Some(Property {
+            name,
+            params,
+            value: rest.strip_prefix(':')?.to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The mailbox of an `ORGANIZER` or `ATTENDEE`: the `mailto:` address
+    /// with the `CN` parameter as its name.
+    fn mailbox(&self) -> Mailbox {
+        let value = self.value.trim();
+        let address = match value.get(..7) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+            _ => value,
+        };
+        Mailbox::new(self.param("CN").map(str::to_string), address)
+    }
+
+    fn write(&self, out: &mut String) {
+        let mut line = self.name.clone();
+        for (name, value) in &self.params {
+            if value.contains([':', ';', ',']) {
+                let _ = write!(line, ";{}=\"{}\"", name, value.replace('"', ""));
+            } else {
+                let _ = write!(line, ";{}={}", name, value);
+            }
+        }
+        line.push(':');
+        line.push_str(&self.value);
+        write_line(out, &line);
+    }
+}
+
+/// Join folded lines: a CRLF followed by a space or tab continues the
+/// previous line.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Write a content line, folded to 75 octets without splitting characters.
+fn write_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Undo TEXT value escaping.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parse a DATE or DATE-TIME value, resolving `TZID` through the tz
+/// database or the calendar's own `VTIMEZONE`s.
+fn parse_time(value: &str, tzid: Option<&str>, timezones: &[Component]) -> Option<EventTime> {
+    let value = value.trim();
+    if !value.contains('T') {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(EventTime::Date);
+    }
+    if let Some(utc) = value.strip_suffix(['Z', 'z']) {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(EventTime::Zoned(
+            Utc.from_utc_datetime(&time).fixed_offset(),
+        ));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let Some(tzid) = tzid else {
+        return Some(EventTime::Floating(time));
+    };
+
+    let zoned = match tzid.trim_start_matches('/').parse::<chrono_tz::Tz>() {
+        Ok(tz) => tz
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|t| t.fixed_offset()),
+        Err(_) => timezones
+            .iter()
+            .find(|tz| tz.property("TZID").is_some_and(|p| p.value == tzid))
+            .and_then(|tz| vtimezone_offset(tz, time))
+            .and_then(|offset| offset.from_local_datetime(&time).single()),
+    };
+    // An unknown zone is better shown as the wall clock time than not at all
+    Some(
+        zoned
+            .map(EventTime::Zoned)
+            .unwrap_or(EventTime::Floating(time)),
+    )
+}
+
+/// The UTC offset a `VTIMEZONE` gives a local time: that of the observance
+/// (`STANDARD` or `DAYLIGHT`) with the latest onset before it.
+fn vtimezone_offset(timezone: &Component, time: NaiveDateTime) -> Option<FixedOffset> {
+    let mut best: Option<(NaiveDateTime, FixedOffset)> = None;
+    for observance in &timezone.components {
+        let Some(offset) = observance
+            .property("TZOFFSETTO")
+            .and_then(|p| parse_utc_offset(&p.value))
+        else {
+            continue;
+        };
+        let Some(first) = observance
+            .property("DTSTART")
+            .and_then(|p| NaiveDateTime::parse_from_str(p.value.trim(), "%Y%m%dT%H%M%S").ok())
+        else {
+            continue;
+        };
+        let rule = observance
+            .property("RRULE")
+            .and_then(|p| Recurrence::parse(&p.value));
+
+        let onset = match rule {
+            Some(rule) => [time.year(), time.year() - 1]
+                .into_iter()
+                .filter_map(|year| yearly_onset(&rule, year, first))
+                .find(|onset| *onset <= time && *onset >= first),
+            None => Some(first).filter(|onset| *onset <= time),
+        };
+        if let Some(onset) = onset
+            && best.is_none_or(|(latest, _)| onset > latest)
+        {
+            best = Some((onset, offset));
+        }
+    }
+    best.map(|(_, offset)| offset)
+}
+
+/// The onset of a yearly observance rule such as
+/// `FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU` in `year`.
+fn yearly_onset(rule: &Recurrence, year: i32, first: NaiveDateTime) -> Option<NaiveDateTime> {
+    let month = rule.by_month.first().copied().unwrap_or(first.month());
+    let date = match rule.by_day.first() {
+        Some((ordinal, weekday)) => nth_weekday(year, month, *weekday, ordinal.unwrap_or(1))?,
+        None => NaiveDate::from_ymd_opt(year, month, first.day())?,
+    };
+    Some(date.and_time(first.time()))
+}
+
+/// The `n`th `weekday` of a month, counting from the end when negative.
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: i32) -> Option<NaiveDate> {
+    if n > 0 {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let offset =
+            (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+        let date = first + Duration::days(i64::from(offset) + 7 * i64::from(n - 1));
+        (date.month() == month).then_some(date)
+    } else {
+        let next = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        let last = next.pred_opt()?;
+        let offset =
+            (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        let date = last - Duration::days(i64::from(offset) + 7 * i64::from(-n - 1));
+        (date.month() == month).then_some(date)
+    }
+}
+
+/// Parse a UTC offset such as `+0100` or `-053000`.
+fn parse_utc_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let sign = match value.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    let hours: i32 = digits.get(0..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    let seconds: i32 = digits.get(4..6).map_or(Some(0), |s| s.parse().ok())?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Parse a DURATION value such as `PT1H30M` or `P1D`, or `None` if it is
+/// invalid or out of range.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut rest = value.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut in_time = false;
+    while !rest.is_empty() {
+        if let Some(time) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = time;
+            continue;
+        }
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount: i64 = rest[..end].parse().ok()?;
+        let part = match (rest[end..].chars().next()?, in_time) {
+            ('W', false) => Duration::try_weeks(amount),
+            ('D', false) => Duration::try_days(amount),
+            ('H', true) => Duration::try_hours(amount),
+            ('M', true) => Duration::try_minutes(amount),
+            ('S', true) => Duration::try_seconds(amount),
+            _ => return None,
+        };
+        total = total.checked_add(&part?)?;
+        rest = &rest[end + 1..];
+    }
+    total.checked_mul(sign)
+}
+
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let value = value.trim();
+    let split = value.char_indices().rev().nth(1)?.0;
+    let weekday = match value[split..].to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let ordinal = &value[..split];
+    if ordinal.is_empty() {
+        Some((None, weekday))
+    } else {
+        Some((Some(ordinal.trim_start_matches('+').parse().ok()?), weekday))
+    }
+}
+
+fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+fn ordinal_name(n: i32) -> String {
+    match n {
+        1 => "first".to_string(),
+        2 => "second".to_string(),
+        3 => "third".to_string(),
+        4 => "fourth".to_string(),
+        -1 => "last".to_string(),
+        -2 => "second to last".to_string(),
+        n => format!("{}.", n),
+    }
+}
+
+fn join_and(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "BEGIN:VCALENDAR\r\n\
+PRODID:-//Google Inc//Google Calendar 70.9054//EN\r\n\
+VERSION:2.0\r\n\
+METHOD:REQUEST\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART;TZID=Europe/Stockholm:20240205T140000\r\n\
+DTEND;TZID=Europe/Stockholm:20240205T150000\r\n\
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20240301T000000Z\r\n\
+DTSTAMP:20240101T120000Z\r\n\
+ORGANIZER;CN=Alice Smith:mailto:alice@example.com\r\n\
+UID:abc123@google.com\r\n\
+ATTENDEE;CUTYPE=INDIVIDUAL;ROLE=REQ-PARTICIPANT;PARTSTAT=ACCEPTED;CN=Alice Smith:\r\n\
+\x20mailto:alice@example.com\r\n\
+ATTENDEE;ROLE=OPT-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE;\r\n\
+\x20CN=\"Carol, the reviewer\";X-NUM-GUESTS=0:mailto:carol@example.com\r\n\
+SEQUENCE:2\r\n\
+SUMMARY:Design review\\, round 2\r\n\
+LOCATION:Room 4\\; second floor\r\n\
+DESCRIPTION:Agenda:\\n- API\\n- UI\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+    const OUTLOOK: &str = "BEGIN:VCALENDAR\r\n\
+METHOD:REQUEST\r\n\
+VERSION:2.0\r\n\
+BEGIN:VTIMEZONE\r\n\
+TZID:W. Europe Standard Time\r\n\
+BEGIN:STANDARD\r\n\
+DTSTART:16010101T030000\r\n\
+TZOFFSETFROM:+0200\r\n\
+TZOFFSETTO:+0100\r\n\
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=10\r\n\
+END:STANDARD\r\n\
+BEGIN:DAYLIGHT\r\n\
+DTSTART:16010101T020000\r\n\
+TZOFFSETFROM:+0100\r\n\
+TZOFFSETTO:+0200\r\n\
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=3\r\n\
+END:DAYLIGHT\r\n\
+END:VTIMEZONE\r\n\
+BEGIN:VEVENT\r\n\
+ORGANIZER;CN=Bob:mailto:bob@example.com\r\n\
+ATTENDEE;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:carol@example.com\r\n\
+SUMMARY:Planning\r\n\
+DTSTART;TZID=W. Europe Standard Time:20240612T093000\r\n\
+DURATION:PT45M\r\n\
+UID:040000008200E00074C5B7101A82E008\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+    fn utc(value: &str) -> EventTime {
+        EventTime::Zoned(
+            Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap())
+                .fixed_offset(),
+        )
+    }
+
+    #[test]
+    fn test_parse_invite() {
+        let calendar = Calendar::parse(INVITE).unwrap();
+        assert_eq!(calendar.method.as_deref(), Some("REQUEST"));
+        assert!(calendar.expects_reply());
+        assert!(!calendar.is_cancelled());
+
+        let event = calendar.event().unwrap();
+        assert_eq!(event.uid.as_deref(), Some("abc123@google.com"));
+        assert_eq!(event.summary.as_deref(), Some("Design review, round 2"));
+        assert_eq!(event.location.as_deref(), Some("Room 4; second floor"));
+        assert_eq!(event.description.as_deref(), Some("Agenda:\n- API\n- UI"));
+        assert_eq!(event.sequence, 2);
+        assert_eq!(
+            event.organizer,
+            Some(Mailbox::new(
+                Some("Alice Smith".to_string()),
+                "alice@example.com"
+            ))
+        );
+
+        // Folded lines are joined, quoted parameters keep their commas
+        assert_eq!(event.attendees.len(), 2);
+        let carol = event.attendee("Carol@Example.com").unwrap();
+        assert_eq!(carol.mailbox.name.as_deref(), Some("Carol, the reviewer"));
+        assert_eq!(carol.role.as_deref(), Some("OPT-PARTICIPANT"));
+        assert_eq!(carol.partstat, PartStat::NeedsAction);
+        assert!(carol.rsvp);
+        assert_eq!(event.attendees[0].partstat, PartStat::Accepted);
+
+        // Stockholm is at +01:00 in February
+        assert_eq!(event.start, Some(utc("2024-02-05 13:00")));
+        assert_eq!(event.end, Some(utc("2024-02-05 14:00")));
+        assert_eq!(event.when_in(&Utc), "Mon 5 Feb 2024 13:00–14:00");
+        assert_eq!(
+            event.recurrence.as_ref().unwrap().summary(),
+            "Weekly on Monday and Wednesday, until Fri 1 Mar 2024"
+        );
+    }
+
+    #[test]
+    fn test_vtimezone_and_duration() {
+        let calendar = Calendar::parse(OUTLOOK).unwrap();
+        let event = calendar.event().unwrap();
+        // Summer time per the VTIMEZONE rules, +02:00
+        assert_eq!(event.start, Some(utc("2024-06-12 07:30")));
+        assert_eq!(event.end, Some(utc("2024-06-12 08:15")));
+
+        let winter = OUTLOOK.replace("20240612T093000", "20241204T093000");
+        let event = Calendar::parse(&winter).unwrap().events.remove(0);
+        assert_eq!(event.start, Some(utc("2024-12-04 08:30")));
+    }
+
+    #[test]
+    fn test_duration_out_of_range() {
+        for duration in ["P99999999D", "P99999999999999W", "P9999999999DT9999999999H"] {
+            let text = OUTLOOK.replace("DURATION:PT45M", &format!("DURATION:{}", duration));
+            let event = Calendar::parse(&text).unwrap().events.remove(0);
+            assert_eq!(event.start, Some(utc("2024-06-12 07:30")));
+            assert_eq!(event.end, None);
+        }
+    }
+
+    #[test]
+    fn test_all_day_and_floating() {
+        let text = INVITE
+            .replace(
+                "DTSTART;TZID=Europe/Stockholm:20240205T140000",
+                "DTSTART;VALUE=DATE:20240205",
+            )
+            .replace(
+                "DTEND;TZID=Europe/Stockholm:20240205T150000",
+                "DTEND;VALUE=DATE:20240208",
+            );
+        let event = Calendar::parse(&text).unwrap().events.remove(0);
+        assert_eq!(
+            event.when_in(&Utc),
+            "Mon 5 Feb 2024 – Wed 7 Feb 2024 (all day)"
+        );
+
+        // Without a zone the wall clock time is shown as is
+        let text = INVITE
+            .replace("DTSTART;TZID=Europe/Stockholm:", "DTSTART:")
+            .replace("DTEND;TZID=Europe/Stockholm:", "DTEND:");
+        let event = Calendar::parse(&text).unwrap().events.remove(0);
+        assert_eq!(event.when_in(&Utc), "Mon 5 Feb 2024 14:00–15:00");
+    }
+
+    #[test]
+    fn test_recurrence_summary() {
+        let summary = |rule: &str| Recurrence::parse(rule).unwrap().summary();
+        assert_eq!(summary("FREQ=DAILY;COUNT=10"), "Daily, 10 times");
+        assert_eq!(
+            summary("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU"),
+            "Every 2 weeks on Tuesday"
+        );
+        assert_eq!(
+            summary("FREQ=MONTHLY;BYDAY=-1FR"),
+            "Monthly on the last Friday"
+        );
+        assert_eq!(summary("FREQ=MONTHLY;BYMONTHDAY=15"), "Monthly on day 15");
+        assert_eq!(summary("FREQ=YEARLY"), "Yearly");
+        assert!(Recurrence::parse("COUNT=3").is_none());
+    }
+
+    #[test]
+    fn test_reply() {
+        let calendar = Calendar::parse(OUTLOOK).unwrap();
+        let reply = calendar
+            .reply("carol@example.com", PartStat::Tentative)
+            .unwrap();
+
+        assert!(reply.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(reply.contains("\r\nMETHOD:REPLY\r\n"));
+        assert!(reply.contains("\r\nUID:040000008200E00074C5B7101A82E008\r\n"));
+        assert!(reply.contains("\r\nDURATION:PT45M\r\n"));
+        assert!(reply.contains("\r\nORGANIZER;CN=Bob:mailto:bob@example.com\r\n"));
+        assert!(reply.contains("\r\nATTENDEE;PARTSTAT=TENTATIVE:mailto:carol@example.com\r\n"));
+        // The VTIMEZONE the start refers to is included
+        assert!(reply.contains("\r\nTZID:W. Europe Standard Time\r\n"));
+        assert!(reply.contains("\r\nDTSTART;TZID=W. Europe Standard Time:20240612T093000\r\n"));
+        assert!(reply.contains("DTSTAMP:"));
+        assert!(!reply.contains("RSVP"));
+
+        // And it parses again
+        let parsed = Calendar::parse(&reply).unwrap();
+        assert_eq!(parsed.method.as_deref(), Some("REPLY"));
+        assert_eq!(
+            parsed.event().unwrap().attendees[0].partstat,
+            PartStat::Tentative
+        );
+    }
+
+    #[test]
+    fn test_reply_keeps_name_and_folds() {
+        let calendar = Calendar::parse(INVITE).unwrap();
+        let reply = calendar
+            .reply("carol@example.com", PartStat::Accepted)
+            .unwrap();
+        assert!(reply.lines().all(|line| line.len() <= 76));
+        let parsed = Calendar::parse(&reply).unwrap();
+        let attendee = &parsed.event().unwrap().attendees[0];
+        assert_eq!(
+            attendee.mailbox.name.as_deref(),
+            Some("Carol, the reviewer")
+        );
+        assert_eq!(attendee.partstat, PartStat::Accepted);
+        assert_eq!(parsed.event().unwrap().sequence, 2);
+    }
+
+    #[test]
+    fn test_non_ascii_values() {
+        let text = INVITE
+            .replace(
+                "mailto:alice@example.com\r\nUID",
+                "abcdeföx@example.com\r\nUID",
+            )
+            .replace("BYDAY=MO,WE", "BYDAY=éx");
+        let event = Calendar::parse(&text).unwrap().events.remove(0);
+        assert_eq!(event.organizer.unwrap().address, "abcdeföx@example.com");
+        // Days that do not parse are left out
+        assert_eq!(
+            event.recurrence.unwrap().summary(),
+            "Weekly, until Fri 1 Mar 2024"
+        );
+        assert_eq!(
+            Recurrence::parse("FREQ=MONTHLY;BYDAY=1é,2TU")
+                .unwrap()
+                .summary(),
+            "Monthly on the second Tuesday"
+        );
+    }
+
+    #[test]
+    fn test_invalid_calendar() {
+        assert!(Calendar::parse("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n").is_err());
+        assert!(Calendar::parse("BEGIN:VEVENT\r\nEND:VCALENDAR\r\n").is_err());
+        assert!(Calendar::parse("hello").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::agent::AgentConnection;
+use crate::agent::protocol::{
+    ConfigGetParams, ConfigSetParams, ExpungeParams, ExpungeReportParams, InsertParams,
+    MoveToFolderParams, PartParams, QueryParams, SearchPage, SearchPaginatedParams, TagParams,
+    methods,
+};
+use crate::client::{
+    ClientConfig, ExpungeOptions, ExpungeOutcome, ExpungeReport, NotmuchClient, TagOperation,
+};
+use crate::error::{NotmuchError, Result};
+use crate::search::SearchItem;
+use crate::thread::Thread;
+
+/// A notmuch client that talks to a `whynot-agent` daemon.
+///
+/// `AgentClient` keeps a single authenticated connection to the agent open
+/// and forwards every `NotmuchClient` call over it as a JSON-RPC request,
+/// avoiding the cost of an SSH session per command.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use whynot::client::{AgentClient, ClientConfig};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // Over TCP
+/// let client = AgentClient::new(ClientConfig::Agent {
+///     endpoint: "mail.example.com:7878".to_string(),
+///     token: "s3cret".to_string(),
+/// })?;
+///
+/// // Over a Unix socket (e.g. forwarded with `ssh -L`)
+/// let client = AgentClient::new(ClientConfig::Agent {
+///     endpoint: "unix:/run/user/1000/whynot-agent.sock".to_string(),
+///     token: "s3cret".to_string(),
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AgentClient {
+    connection: AgentConnection,
+}
+
+impl AgentClient {
+    pub fn new(config: ClientConfig) -> Result<Self> {
+        match config {
+            ClientConfig::Agent { endpoint, token } => Ok(AgentClient {
+                connection: AgentConnection::new(endpoint.parse()?, token),
+            }),
+            _ => Err(NotmuchError::ConfigError(
+                "Invalid config type for AgentClient".to_string(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl NotmuchClient for AgentClient {
+    async fn search(&self, query: &str) -> Result<Vec<SearchItem>> {
+        let params = QueryParams {
+            query: query.to_string(),
+        };
+        self.connection.call(methods::SEARCH, &params).await
+    }
+
+    async fn search_paginated(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<SearchItem>, Option<usize>)> {
+        let params = SearchPaginatedParams {
+            query: query.to_string(),
+            offset,
+            limit,
+        };
+        let page: SearchPage = self
+            .connection
+            .call(methods::SEARCH_PAGINATED, &params)
+            .await?;
+        Ok((page.items, page.total))
+    }
+
+    async fn show(&self, query: &str) -> Result<Thread> {
+        let params = QueryParams {
+            query: query.to_string(),
+        };
+        self.connection.call(methods::SHOW, &params).await
+    }
+
+    async fn show_headers(&self, query: &str) -> Result<Thread> {
+        let params = QueryParams {
+            query: query.to_string(),
+        };
+        self.connection.call(methods::SHOW_HEADERS, &params).await
+    }
+
+    async fn tag(&self, query: &str, tags: &[TagOperation]) -> Result<()> {
+        if tags.is_empty() {
+            return Ok(());
+        }
+        self.connection
+            .call(methods::TAG, &TagParams::new(query, tags))
+            .await
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        self.connection.call(methods::REFRESH, &()).await
+    }
+
+    async fn insert(&self, message: &[u8], folder: Option<&str>, tags: &[&str]) -> Result<String> {
+        let params = InsertParams {
+            message: STANDARD.encode(message),
+            folder: folder.map(str::to_string),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        self.connection.call(methods::INSERT, &params).await
+    }
+
+    async fn config_get(&self, key: &str) -> Result<String> {
+        let params = ConfigGetParams {
+            key: key.to_string(),
+        };
+        self.connection.call(methods::CONFIG_GET, &params).await
+    }
+
+    async fn config_set(&self, key: &str, value: &str) -> Result<()> {
+        let params = ConfigSetParams {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        self.connection.call(methods::CONFIG_SET, &params).await
+    }
+
+    async fn list_tags(&self) -> Result<Vec<String>> {
+        self.connection.call(methods::LIST_TAGS, &()).await
+    }
+
+    async fn part(&self, message_id: &str, part_id: u32) -> Result<Vec<u8>> {
+        let params = PartParams {
+            message_id: message_id.to_string(),
+            part_id,
+        };
+        let encoded: String = self.connection.call(methods::PART, &params).await?;
+        STANDARD.decode(encoded).map_err(|e| {
+            NotmuchError::CommandFailed(format!("Invalid part data from agent: {}", e))
+        })
+    }
+
+    fn can_move_files(&self) -> bool {
+        true
+    }
+
+    async fn move_to_folder(&self, query: &str, folder: &str) -> Result<usize> {
+        let params = MoveToFolderParams {
+            query: query.to_string(),
+            folder: folder.to_string(),
+        };
+        self.connection.call(methods::MOVE_TO_FOLDER, &params).await
+    }
+
+    async fn expunge_report(&self, query: &str, options: &ExpungeOptions) -> Result<ExpungeReport> {
+        let params = ExpungeReportParams {
+            query: query.to_string(),
+            options: options.clone(),
+        };
+        self.connection.call(methods::EXPUNGE_REPORT, &params).await
+    }
+
+    async fn expunge(
+        &self,
+        query: &str,
+        options: &ExpungeOptions,
+        token: &str,
+    ) -> Result<ExpungeOutcome> {
+        let params = ExpungeParams {
+            query: query.to_string(),
+            options: options.clone(),
+            token: token.to_string(),
+        };
+        self.connection.call(methods::EXPUNGE, &params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_client_config() {
+        let config = ClientConfig::agent("127.0.0.1:7878".to_string(), "token".to_string());
+        let client = AgentClient::new(config).unwrap();
+        assert_eq!(
+            client.connection.endpoint().to_string(),
+            "tcp:127.0.0.1:7878"
+        );
+    }
+
+    #[test]
+    fn test_agent_client_invalid_config() {
+        assert!(AgentClient::new(ClientConfig::local()).is_err());
+
+        let config = ClientConfig::agent("no-port".to_string(), "token".to_string());
+        assert!(AgentClient::new(config).is_err());
+    }
+}
@@ -1,8 +1,13 @@
+use serde::de::DeserializeOwned;
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::{NotmuchError, Result};
 
 /// Configuration for creating notmuch clients.
 ///
-/// This enum specifies whether to create a local or remote client,
+/// This enum specifies whether to create a local, remote or agent client,
 /// along with the necessary connection and path information.
 ///
 /// # Examples
@@ -21,6 +26,12 @@ use std::path::PathBuf;
 /// // Remote configuration
 /// let remote = ClientConfig::remote("mail.example.com".to_string());
 ///
+/// // Agent daemon configuration
+/// let agent = ClientConfig::agent(
+///     "mail.example.com:7878".to_string(),
+///     "s3cret".to_string()
+/// );
+///
 /// // Remote with full options
 /// let remote_full = ClientConfig::remote_full(
 ///     "mail.example.com".to_string(),
@@ -41,6 +52,8 @@ pub enum ClientConfig {
         /// Path to the mail root directory. Currently unused but reserved
         /// for future functionality.
         mail_root: Option<PathBuf>,
+        /// Output format requested from `notmuch search` and `notmuch show`.
+        format: OutputFormat,
     },
     /// Configuration for remote notmuch execution via SSH.
     Remote {
@@ -54,6 +67,16 @@ pub enum ClientConfig {
         identity_file: Option<PathBuf>,
         /// Path to notmuch binary on the remote host. If None, uses "notmuch" from PATH.
         notmuch_path: Option<PathBuf>,
+        /// Output format requested from `notmuch search` and `notmuch show`.
+        format: OutputFormat,
+    },
+    /// Configuration for talking to a `whynot-agent` daemon.
+    Agent {
+        /// Agent address: `host:port`, `tcp:host:port`, `unix:/path` or an
+        /// absolute socket path.
+        endpoint: String,
+        /// Shared token the agent was started with.
+        token: String,
     },
 }
 
@@ -63,6 +86,7 @@ impl Default for ClientConfig {
             notmuch_path: None,
             database_path: None,
             mail_root: None,
+            format: Default::default(),
         }
     }
 }
@@ -85,6 +109,7 @@ impl ClientConfig {
             notmuch_path: None,
             database_path: Some(database_path),
             mail_root: None,
+            format: Default::default(),
         }
     }
 
@@ -102,6 +127,7 @@ impl ClientConfig {
             port: None,
             identity_file: None,
             notmuch_path: None,
+            format: Default::default(),
         }
     }
 
@@ -118,6 +144,7 @@ impl ClientConfig {
             port: None,
             identity_file: None,
             notmuch_path: None,
+            format: Default::default(),
         }
     }
 
@@ -136,6 +163,92 @@ impl ClientConfig {
             port: Some(port),
             identity_file: Some(identity_file),
             notmuch_path: None,
+            format: Default::default(),
+        }
+    }
+    /// Create a configuration for a `whynot-agent` daemon.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - Agent address (`host:port` or `unix:/path`)
+    /// * `token` - Shared authentication token
+    pub fn agent(endpoint: String, token: String) -> Self {
+        ClientConfig::Agent { endpoint, token }
+    }
+
+    /// Use `format` for search and show output.
+    ///
+    /// Agent configurations are returned unchanged; the agent picks the
+    /// format of its own client.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use whynot::client::{ClientConfig, OutputFormat};
+    /// let config = ClientConfig::remote("mail.example.com".to_string())
+    ///     .with_format(OutputFormat::Sexp);
+    /// ```
+    pub fn with_format(mut self, output_format: OutputFormat) -> Self {
+        match &mut self {
+            ClientConfig::Local { format, .. } | ClientConfig::Remote { format, .. } => {
+                *format = output_format
+            }
+            ClientConfig::Agent { .. } => {}
+        }
+        self
+    }
+}
+
+/// Structured output format of notmuch commands.
+///
+/// Both formats deserialize into the same types. `Sexp` is cheaper for
+/// notmuch to produce on large threads and is handled better by some older
+/// notmuch versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Sexp,
+}
+
+impl OutputFormat {
+    /// The `--format=` argument for notmuch.
+    pub fn arg(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "--format=json",
+            OutputFormat::Sexp => "--format=sexp",
+        }
+    }
+
+    /// Deserialize notmuch output produced with [`OutputFormat::arg`].
+    pub fn parse<T: DeserializeOwned>(self, output: &str) -> Result<T> {
+        match self {
+            OutputFormat::Json => Ok(serde_json::from_str(output)?),
+            OutputFormat::Sexp => Ok(crate::sexp::from_str(output)?),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Sexp => write!(f, "sexp"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = NotmuchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "sexp" => Ok(OutputFormat::Sexp),
+            _ => Err(NotmuchError::ConfigError(format!(
+                "Unknown notmuch output format '{}', expected json or sexp",
+                s
+            ))),
         }
     }
 }
@@ -0,0 +1,207 @@
+//! Types for permanently removing messages from the mail store.
+//!
+//! Expunging is a two step process. A dry run with
+//! [`NotmuchClient::expunge_report`](crate::client::NotmuchClient::expunge_report)
+//! lists the messages and files that would be removed together with a
+//! confirmation token. Passing that token to
+//! [`NotmuchClient::expunge`](crate::client::NotmuchClient::expunge) performs
+//! the removal, but only if the set of files is still exactly the one from
+//! the report.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+/// Tags that protect a message from being expunged unless configured
+/// otherwise.
+pub const DEFAULT_PROTECTED_TAGS: &[&str] = &["flagged", "important"];
+
+/// Safeguards applied when expunging messages.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::client::ExpungeOptions;
+/// // Never delete flagged mail, and keep a copy of everything removed
+/// let options = ExpungeOptions {
+///     protected_tags: vec!["flagged".to_string()],
+///     trash_dir: Some("/home/user/.mail-trash".into()),
+/// };
+/// assert!(options.is_protected(&["deleted".to_string(), "flagged".to_string()]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpungeOptions {
+    /// Messages carrying any of these tags are never removed.
+    pub protected_tags: Vec<String>,
+    /// Move files here instead of deleting them.
+    pub trash_dir: Option<PathBuf>,
+}
+
+impl Default for ExpungeOptions {
+    fn default() -> Self {
+        Self {
+            protected_tags: DEFAULT_PROTECTED_TAGS
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+            trash_dir: None,
+        }
+    }
+}
+
+impl ExpungeOptions {
+    /// Check whether a message with `tags` is protected from removal.
+    pub fn is_protected(&self, tags: &[String]) -> bool {
+        tags.iter().any(|tag| self.protected_tags.contains(tag))
+    }
+}
+
+/// A message matched by an expunge query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpungeCandidate {
+    pub id: String,
+    pub subject: Option<String>,
+    pub tags: Vec<String>,
+    /// Every file of the message; duplicates are removed together.
+    pub files: Vec<String>,
+}
+
+/// The result of an expunge dry run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpungeReport {
+    pub query: String,
+    /// Messages whose files would be removed.
+    pub messages: Vec<ExpungeCandidate>,
+    /// Matching messages skipped because of a protected tag.
+    pub protected: Vec<ExpungeCandidate>,
+    /// Where the files would be moved, if not deleted.
+    pub trash_dir: Option<PathBuf>,
+    /// Token that must be passed back to confirm this exact removal.
+    pub token: String,
+}
+
+impl ExpungeReport {
+    /// Build a report, splitting `candidates` into removable and protected
+    /// messages.
+    pub fn new(query: &str, candidates: Vec<ExpungeCandidate>, options: &ExpungeOptions) -> Self {
+        let (protected, messages): (Vec<_>, Vec<_>) = candidates
+            .into_iter()
+            .partition(|candidate| options.is_protected(&candidate.tags));
+
+        let token = confirmation_token(query, &messages, options);
+        ExpungeReport {
+            query: query.to_string(),
+            messages,
+            protected,
+            trash_dir: options.trash_dir.clone(),
+            token,
+        }
+    }
+
+    /// Number of files that would be removed.
+    pub fn file_count(&self) -> usize {
+        self.messages.iter().map(|m| m.files.len()).sum()
+    }
+
+    /// Whether there is nothing to remove.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Summary of a completed expunge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpungeOutcome {
+    pub messages: usize,
+    pub files: usize,
+    /// Set when the files were moved rather than deleted.
+    pub trash_dir: Option<PathBuf>,
+}
+
+/// Derive the confirmation token for a removal.
+///
+/// The token changes whenever the query, the files to remove or the trash
+/// directory change, so a stale confirmation cannot remove mail that arrived
+/// after the dry run. It guards against mistakes, not against attackers.
+fn confirmation_token(
+    query: &str,
+    messages: &[ExpungeCandidate],
+    options: &ExpungeOptions,
+) -> String {
+    let mut files: Vec<&str> = messages
+        .iter()
+        .flat_map(|m| m.files.iter().map(String::as_str))
+        .collect();
+    files.sort_unstable();
+
+    let mut hasher = DefaultHasher::new();
+    query.hash(&mut hasher);
+    files.hash(&mut hasher);
+    options.trash_dir.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: &str, tags: &[&str], files: &[&str]) -> ExpungeCandidate {
+        ExpungeCandidate {
+            id: id.to_string(),
+            subject: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_report_skips_protected_messages() {
+        let report = ExpungeReport::new(
+            "tag:deleted",
+            vec![
+                candidate("a", &["deleted"], &["/mail/cur/a", "/mail/Other/cur/a"]),
+                candidate("b", &["deleted", "flagged"], &["/mail/cur/b"]),
+            ],
+            &ExpungeOptions::default(),
+        );
+
+        assert_eq!(report.messages.len(), 1);
+        assert_eq!(report.messages[0].id, "a");
+        assert_eq!(report.protected.len(), 1);
+        assert_eq!(report.protected[0].id, "b");
+        assert_eq!(report.file_count(), 2);
+        assert!(!report.is_empty());
+    }
+
+    #[test]
+    fn test_token_tracks_the_removal() {
+        let options = ExpungeOptions::default();
+        let messages = || vec![candidate("a", &["deleted"], &["/mail/cur/a"])];
+        let token = ExpungeReport::new("tag:deleted", messages(), &options).token;
+
+        // Stable for the same removal
+        assert_eq!(
+            ExpungeReport::new("tag:deleted", messages(), &options).token,
+            token
+        );
+
+        // Changes when more files would be removed
+        let mut more = messages();
+        more.push(candidate("c", &["deleted"], &["/mail/cur/c"]));
+        assert_ne!(
+            ExpungeReport::new("tag:deleted", more, &options).token,
+            token
+        );
+
+        // Changes when the files would be deleted instead of trashed
+        let trash = ExpungeOptions {
+            trash_dir: Some(PathBuf::from("/trash")),
+            ..ExpungeOptions::default()
+        };
+        assert_ne!(
+            ExpungeReport::new("tag:deleted", messages(), &trash).token,
+            token
+        );
+    }
+}
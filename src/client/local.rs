@@ -1,9 +1,13 @@
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
 
-use crate::client::{ClientConfig, NotmuchClient, TagOperation};
+use crate::client::maildir::{self, MaildirFile};
+use crate::client::{
+    ClientConfig, ExpungeCandidate, ExpungeOptions, ExpungeOutcome, ExpungeReport, NotmuchClient,
+    OutputFormat, TagOperation, insert_tag_arg,
+};
 use crate::error::{NotmuchError, Result};
 use crate::search::{SearchItem, SearchResult};
 use crate::thread::Thread;
@@ -25,6 +29,7 @@ use crate::thread::Thread;
 ///     notmuch_path: None,
 ///     database_path: None,
 ///     mail_root: None,
+///     format: Default::default(),
 /// };
 /// let client = LocalClient::new(config)?;
 ///
@@ -33,6 +38,7 @@ use crate::thread::Thread;
 ///     notmuch_path: Some(PathBuf::from("/usr/local/bin/notmuch")),
 ///     database_path: Some(PathBuf::from("/home/user/.mail/.notmuch")),
 ///     mail_root: Some(PathBuf::from("/home/user/.mail")),
+///     format: Default::default(),
 /// };
 /// let client = LocalClient::new(config)?;
 /// # Ok(())
@@ -41,8 +47,8 @@ use crate::thread::Thread;
 pub struct LocalClient {
     notmuch_path: PathBuf,
     database_path: Option<PathBuf>,
-    #[allow(dead_code)]
     mail_root: Option<PathBuf>,
+    format: OutputFormat,
 }
 
 impl LocalClient {
@@ -52,10 +58,12 @@ impl LocalClient {
                 notmuch_path,
                 database_path,
                 mail_root,
+                format,
             } => Ok(LocalClient {
                 notmuch_path: notmuch_path.unwrap_or_else(|| PathBuf::from("notmuch")),
                 database_path,
                 mail_root,
+                format,
             }),
             _ => Err(NotmuchError::ConfigError(
                 "Invalid config type for LocalClient".to_string(),
@@ -122,15 +130,98 @@ impl LocalClient {
 
         Ok(output.stdout)
     }
+
+    /// Read the files, tags and subjects of the messages matching `query`.
+    async fn message_files(&self, query: &str) -> Result<Vec<maildir::MessageFiles>> {
+        let output = self
+            .execute_command(&[
+                "show",
+                "--format=json",
+                "--body=false",
+                "--entire-thread=false",
+                query,
+            ])
+            .await?;
+        maildir::message_files(&output)
+    }
+
+    /// Resolve the maildir root, asking notmuch when it was not configured.
+    async fn resolve_mail_root(&self) -> Result<PathBuf> {
+        if let Some(mail_root) = &self.mail_root {
+            return Ok(mail_root.clone());
+        }
+
+        // database.mail_root is only set when it differs from database.path
+        for key in ["database.mail_root", "database.path"] {
+            if let Ok(value) = self.config_get(key).await
+                && !value.is_empty()
+            {
+                return Ok(PathBuf::from(value));
+            }
+        }
+
+        Err(NotmuchError::ConfigError(
+            "Could not determine the mail root directory".to_string(),
+        ))
+    }
+}
+
+/// `path` made absolute with symlinks resolved, as far as it exists.
+async fn resolve_path(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        let base = if existing.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            existing
+        };
+        if let Ok(resolved) = tokio::fs::canonicalize(base).await {
+            return missing
+                .iter()
+                .rev()
+                .fold(resolved, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Pick a path in `dir` for `file_name` that does not exist yet.
+async fn unused_path(dir: &Path, file_name: &str) -> Result<PathBuf> {
+    let mut candidate = dir.join(file_name);
+    let mut counter = 1;
+    while tokio::fs::try_exists(&candidate).await? {
+        candidate = dir.join(format!("{}.{}", file_name, counter));
+        counter += 1;
+    }
+    Ok(candidate)
+}
+
+/// Rename a file, falling back to copy and remove across filesystems.
+async fn move_file(from: &Path, to: &Path) -> Result<()> {
+    match tokio::fs::rename(from, to).await {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            tokio::fs::copy(from, to).await?;
+            tokio::fs::remove_file(from).await?;
+            Ok(())
+        }
+        result => Ok(result?),
+    }
 }
 
 #[async_trait]
 impl NotmuchClient for LocalClient {
     async fn search(&self, query: &str) -> Result<Vec<SearchItem>> {
         let output = self
-            .execute_command(&["search", "--format=json", query])
+            .execute_command(&["search", self.format.arg(), query])
             .await?;
-        let result: SearchResult = serde_json::from_str(&output)?;
+        let result: SearchResult = self.format.parse(&output)?;
         Ok(result.0)
     }
 
@@ -147,7 +238,7 @@ impl NotmuchClient for LocalClient {
         let output = self
             .execute_command(&[
                 "search",
-                "--format=json",
+                self.format.arg(),
                 "--offset",
                 &offset_str,
                 "--limit",
@@ -156,7 +247,7 @@ impl NotmuchClient for LocalClient {
             ])
             .await?;
 
-        let result: SearchResult = serde_json::from_str(&output)?;
+        let result: SearchResult = self.format.parse(&output)?;
 
         // Get total count by running a count command (separate query)
         // This is optional since it requires an extra query
@@ -172,13 +263,27 @@ impl NotmuchClient for LocalClient {
         let output = self
             .execute_command(&[
                 "show",
-                "--format=json",
+                self.format.arg(),
                 "--include-html",
                 "--entire-thread",
                 query,
             ])
             .await?;
-        let thread: Thread = serde_json::from_str(&output)?;
+        let thread: Thread = self.format.parse(&output)?;
+        Ok(thread)
+    }
+
+    async fn show_headers(&self, query: &str) -> Result<Thread> {
+        let output = self
+            .execute_command(&[
+                "show",
+                self.format.arg(),
+                "--body=false",
+                "--entire-thread=false",
+                query,
+            ])
+            .await?;
+        let thread: Thread = self.format.parse(&output)?;
         Ok(thread)
     }
 
@@ -210,10 +315,11 @@ impl NotmuchClient for LocalClient {
         if let Some(folder) = folder {
             args.push("--folder".to_string());
             args.push(folder.to_string());
+            args.push("--create-folder".to_string());
         }
 
         for tag in tags {
-            args.push(format!("+{}", tag));
+            args.push(insert_tag_arg(tag));
         }
 
         let mut cmd = Command::new(&self.notmuch_path);
@@ -275,6 +381,157 @@ impl NotmuchClient for LocalClient {
         self.execute_command_bytes(&["show", "--format=raw", &part_arg, message_id])
             .await
     }
+
+    fn can_move_files(&self) -> bool {
+        true
+    }
+
+    async fn move_to_folder(&self, query: &str, folder: &str) -> Result<usize> {
+        maildir::validate_folder(folder)?;
+        let target = self.resolve_mail_root().await?.join(folder);
+
+        let messages = self.message_files(query).await?;
+        if messages.is_empty() {
+            return Ok(0);
+        }
+
+        for subdir in ["cur", "new", "tmp"] {
+            tokio::fs::create_dir_all(target.join(subdir)).await?;
+        }
+
+        // Check every file before moving any, so a conflict leaves them all
+        // where the index has them
+        let mut moves = Vec::new();
+        for message in &messages {
+            for filename in &message.filenames {
+                let source = Path::new(filename);
+                let file = MaildirFile::parse(source).ok_or_else(|| {
+                    NotmuchError::InvalidInput(format!("Not a maildir message file: {}", filename))
+                })?;
+                if file.folder == target {
+                    continue;
+                }
+
+                let destination = file.destination(&target, &message.tags);
+                if tokio::fs::try_exists(&destination).await?
+                    || moves.iter().any(|(_, planned)| *planned == destination)
+                {
+                    return Err(NotmuchError::CommandFailed(format!(
+                        "Refusing to overwrite existing file {}",
+                        destination.display()
+                    )));
+                }
+                moves.push((source, destination));
+            }
+        }
+
+        let mut moved = 0;
+        let mut result = Ok(());
+        for (source, destination) in &moves {
+            if let Err(e) = move_file(source, destination).await {
+                result = Err(e);
+                break;
+            }
+            moved += 1;
+        }
+
+        // Let notmuch pick up the renamed files, also those moved before a
+        // failure
+        if moved > 0 {
+            self.refresh().await?;
+        }
+        result?;
+
+        Ok(moved)
+    }
+
+    async fn expunge_report(&self, query: &str, options: &ExpungeOptions) -> Result<ExpungeReport> {
+        if query.trim().is_empty() {
+            return Err(NotmuchError::InvalidInput(
+                "An expunge query must not be empty".to_string(),
+            ));
+        }
+
+        // `notmuch new` would index a trash inside the mail root again, and
+        // the expunged mail would come back as new
+        if let Some(trash_dir) = &options.trash_dir {
+            let mail_root = resolve_path(&self.resolve_mail_root().await?).await;
+            if resolve_path(trash_dir).await.starts_with(&mail_root) {
+                return Err(NotmuchError::ConfigError(format!(
+                    "The trash directory {} must be outside the mail root {}",
+                    trash_dir.display(),
+                    mail_root.display()
+                )));
+            }
+        }
+
+        let candidates = self
+            .message_files(query)
+            .await?
+            .into_iter()
+            .map(|message| ExpungeCandidate {
+                id: message.id,
+                subject: message.subject,
+                tags: message.tags,
+                files: message.filenames,
+            })
+            .collect();
+
+        Ok(ExpungeReport::new(query, candidates, options))
+    }
+
+    async fn expunge(
+        &self,
+        query: &str,
+        options: &ExpungeOptions,
+        token: &str,
+    ) -> Result<ExpungeOutcome> {
+        let report = self.expunge_report(query, options).await?;
+        if report.token != token {
+            return Err(NotmuchError::InvalidInput(
+                "Confirmation token does not match the messages currently selected; run a new dry run"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(trash_dir) = &report.trash_dir {
+            tokio::fs::create_dir_all(trash_dir).await?;
+        }
+
+        let mut files = 0;
+        for message in &report.messages {
+            for filename in &message.files {
+                let source = Path::new(filename);
+                let result = match &report.trash_dir {
+                    Some(trash_dir) => {
+                        let name = source
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .unwrap_or(&message.id);
+                        let destination = unused_path(trash_dir, name).await?;
+                        move_file(source, &destination).await
+                    }
+                    None => tokio::fs::remove_file(source).await.map_err(Into::into),
+                };
+
+                match result {
+                    Ok(()) => files += 1,
+                    // Already gone, e.g. removed by a sync in the meantime
+                    Err(NotmuchError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        // notmuch drops messages whose files have all disappeared
+        self.refresh().await?;
+
+        Ok(ExpungeOutcome {
+            messages: report.messages.len(),
+            files,
+            trash_dir: report.trash_dir,
+        })
+    }
 }
 
 #[cfg(test)]
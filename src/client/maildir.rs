@@ -0,0 +1,339 @@
+//! Maildir helpers used when moving messages between folders.
+//!
+//! A maildir message lives in either `new/` (not yet seen by a mail reader)
+//! or `cur/`. Files in `cur/` carry an info suffix such as `:2,RS` whose
+//! letters are the maildir flags. notmuch synchronizes a handful of these
+//! flags with tags, so a moved file has to get a name that agrees with the
+//! message's current tags or the next `notmuch new` would undo tag changes.
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::error::{NotmuchError, Result};
+
+/// Separator between the unique part of a maildir filename and its info.
+const INFO_SEPARATOR: char = ':';
+
+/// Maildir flags synchronized with notmuch tags, in the order notmuch uses.
+/// `S` (seen) is handled separately since it maps to the absence of `unread`.
+const FLAG_TAGS: &[(char, &str)] = &[
+    ('D', "draft"),
+    ('F', "flagged"),
+    ('P', "passed"),
+    ('R', "replied"),
+];
+
+/// The maildir subdirectory a message file is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Subdir {
+    Cur,
+    New,
+}
+
+impl Subdir {
+    fn as_str(self) -> &'static str {
+        match self {
+            Subdir::Cur => "cur",
+            Subdir::New => "new",
+        }
+    }
+}
+
+/// A message file inside a maildir, split into its naming components.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MaildirFile {
+    /// The maildir folder containing `cur/`, `new/` and `tmp/`.
+    pub folder: PathBuf,
+    pub subdir: Subdir,
+    /// The unique part of the filename, before the info separator.
+    pub unique: String,
+    /// The flag letters from a `:2,FLAGS` suffix, if there was one.
+    pub flags: Option<String>,
+}
+
+impl MaildirFile {
+    /// Split a message path into its maildir components.
+    ///
+    /// Returns `None` when the file is not stored in a `cur/` or `new/`
+    /// directory.
+    pub fn parse(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let parent = path.parent()?;
+        let subdir = match parent.file_name()?.to_str()? {
+            "cur" => Subdir::Cur,
+            "new" => Subdir::New,
+            _ => return None,
+        };
+        let folder = parent.parent()?.to_path_buf();
+
+        let (unique, flags) = match name.split_once(INFO_SEPARATOR) {
+            Some((unique, info)) => (
+                unique.to_string(),
+                info.strip_prefix("2,").map(str::to_string),
+            ),
+            None => (name.to_string(), None),
+        };
+
+        Some(MaildirFile {
+            folder,
+            subdir,
+            unique,
+            flags,
+        })
+    }
+
+    /// Compute where this file should be stored in `target`.
+    ///
+    /// The flags are synchronized with `tags` and any mbsync `,U=<uid>` is
+    /// dropped, since the UID only has meaning in the original folder. A
+    /// message without flags that was still in `new/` stays there;
+    /// everything else goes to `cur/`.
+    pub fn destination(&self, target: &Path, tags: &[String]) -> PathBuf {
+        let unique = strip_uid(&self.unique);
+        let flags = sync_flags(self.flags.as_deref().unwrap_or(""), tags);
+
+        if self.subdir == Subdir::New && flags.is_empty() {
+            target.join(Subdir::New.as_str()).join(unique)
+        } else {
+            target
+                .join(Subdir::Cur.as_str())
+                .join(format!("{}{}2,{}", unique, INFO_SEPARATOR, flags))
+        }
+    }
+}
+
+/// Return `existing` flags updated to match `tags`.
+///
+/// Flags that notmuch maps to tags are set or cleared accordingly; any other
+/// flags (for example `T`, trashed) are kept. The result is sorted, as the
+/// maildir specification requires.
+pub(crate) fn sync_flags(existing: &str, tags: &[String]) -> String {
+    let has_tag = |tag: &str| tags.iter().any(|t| t == tag);
+
+    let mut flags: Vec<char> = existing
+        .chars()
+        .filter(|c| *c != 'S' && !FLAG_TAGS.iter().any(|(flag, _)| flag == c))
+        .collect();
+
+    for (flag, tag) in FLAG_TAGS {
+        if has_tag(tag) {
+            flags.push(*flag);
+        }
+    }
+    if !has_tag("unread") {
+        flags.push('S');
+    }
+
+    flags.sort_unstable();
+    flags.dedup();
+    flags.into_iter().collect()
+}
+
+/// Remove an mbsync `,U=<uid>` field from the unique part of a filename.
+pub(crate) fn strip_uid(unique: &str) -> String {
+    unique
+        .split(',')
+        .filter(|field| !field.starts_with("U="))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Check that `folder` is a plain path relative to the mail root.
+pub(crate) fn validate_folder(folder: &str) -> Result<()> {
+    let path = Path::new(folder);
+    let valid = !folder.trim().is_empty()
+        && path.is_relative()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(NotmuchError::InvalidInput(format!(
+            "Invalid maildir folder '{}': must be a path relative to the mail root",
+            folder
+        )))
+    }
+}
+
+/// Files and tags of one message from `notmuch show --format=json`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MessageFiles {
+    pub id: String,
+    pub subject: Option<String>,
+    pub filenames: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+/// Collect the files and tags of every matching message in `notmuch show`
+/// JSON output.
+///
+/// Only the `id`, `filename`, `tags`, `match` and `Subject` header fields
+/// are read, so the output
+/// may be produced with `--body=false`. Both the array form of `filename`
+/// (notmuch 0.29+) and the older single string are accepted.
+pub(crate) fn message_files(json: &str) -> Result<Vec<MessageFiles>> {
+    let value: Value = serde_json::from_str(json)?;
+    let mut messages = Vec::new();
+    collect_message_files(&value, &mut messages);
+    Ok(messages)
+}
+
+fn collect_message_files(value: &Value, messages: &mut Vec<MessageFiles>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_message_files(item, messages);
+            }
+        }
+        Value::Object(map) if map.contains_key("id") => {
+            if map.get("match").and_then(Value::as_bool) == Some(false) {
+                return;
+            }
+            let id = map
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let subject = map
+                .get("headers")
+                .and_then(|headers| headers.get("Subject"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let filenames = match map.get("filename") {
+                Some(Value::String(name)) => vec![name.clone()],
+                Some(Value::Array(names)) => names
+                    .iter()
+                    .filter_map(|n| n.as_str().map(str::to_string))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let tags = map
+                .get("tags")
+                .and_then(Value::as_array)
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(|t| t.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default();
+            messages.push(MessageFiles {
+                id,
+                subject,
+                filenames,
+                tags,
+            });
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_cur_file() {
+        let file = MaildirFile::parse(Path::new(
+            "/mail/INBOX/cur/1748424311.690968_1.icarus,U=182875:2,RS",
+        ))
+        .unwrap();
+        assert_eq!(file.folder, PathBuf::from("/mail/INBOX"));
+        assert_eq!(file.subdir, Subdir::Cur);
+        assert_eq!(file.unique, "1748424311.690968_1.icarus,U=182875");
+        assert_eq!(file.flags.as_deref(), Some("RS"));
+    }
+
+    #[test]
+    fn test_parse_new_file() {
+        let file = MaildirFile::parse(Path::new("/mail/INBOX/new/1748424311.1.host")).unwrap();
+        assert_eq!(file.subdir, Subdir::New);
+        assert_eq!(file.unique, "1748424311.1.host");
+        assert_eq!(file.flags, None);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_maildir_paths() {
+        assert!(MaildirFile::parse(Path::new("/mail/INBOX/tmp/123.host")).is_none());
+        assert!(MaildirFile::parse(Path::new("/mail/mbox")).is_none());
+    }
+
+    #[test]
+    fn test_sync_flags() {
+        assert_eq!(sync_flags("", &tags(&["inbox", "unread"])), "");
+        assert_eq!(sync_flags("", &tags(&["inbox"])), "S");
+        assert_eq!(sync_flags("S", &tags(&["unread", "flagged"])), "F");
+        assert_eq!(sync_flags("RST", &tags(&["replied", "draft"])), "DRST");
+        assert_eq!(sync_flags("FS", &tags(&[])), "S");
+    }
+
+    #[test]
+    fn test_strip_uid() {
+        assert_eq!(
+            strip_uid("1748424311.690968_1.icarus,U=182875"),
+            "1748424311.690968_1.icarus"
+        );
+        assert_eq!(strip_uid("123.host,S=2048,U=7"), "123.host,S=2048");
+        assert_eq!(strip_uid("123.host"), "123.host");
+    }
+
+    #[test]
+    fn test_destination() {
+        let target = Path::new("/mail/Archive");
+
+        let seen = MaildirFile::parse(Path::new("/mail/INBOX/cur/1.host,U=5:2,S")).unwrap();
+        assert_eq!(
+            seen.destination(target, &tags(&["replied"])),
+            PathBuf::from("/mail/Archive/cur/1.host:2,RS")
+        );
+
+        let unseen = MaildirFile::parse(Path::new("/mail/INBOX/new/2.host")).unwrap();
+        assert_eq!(
+            unseen.destination(target, &tags(&["unread"])),
+            PathBuf::from("/mail/Archive/new/2.host")
+        );
+        assert_eq!(
+            unseen.destination(target, &tags(&[])),
+            PathBuf::from("/mail/Archive/cur/2.host:2,S")
+        );
+    }
+
+    #[test]
+    fn test_validate_folder() {
+        assert!(validate_folder("Archive").is_ok());
+        assert!(validate_folder("work/Archive").is_ok());
+        assert!(validate_folder("").is_err());
+        assert!(validate_folder("/tmp/Archive").is_err());
+        assert!(validate_folder("../Archive").is_err());
+        assert!(validate_folder("Archive/../..").is_err());
+    }
+
+    #[test]
+    fn test_message_files() {
+        let json = r#"[[[{"id": "a@example.com", "match": true, "filename": ["/mail/INBOX/cur/1:2,S", "/mail/Other/cur/1:2,S"], "tags": ["inbox"]},
+            [[{"id": "b@example.com", "match": false, "filename": ["/mail/INBOX/cur/2:2,S"], "tags": []}, []]]]]]"#;
+        let messages = message_files(json).unwrap();
+        assert_eq!(
+            messages,
+            vec![MessageFiles {
+                id: "a@example.com".to_string(),
+                subject: None,
+                filenames: vec![
+                    "/mail/INBOX/cur/1:2,S".to_string(),
+                    "/mail/Other/cur/1:2,S".to_string()
+                ],
+                tags: vec!["inbox".to_string()],
+            }]
+        );
+
+        let legacy = r#"[[[{"id": "c@example.com", "match": true, "filename": "/mail/INBOX/new/3", "tags": ["unread"], "headers": {"Subject": "Hello"}}, []]]]"#;
+        let legacy = message_files(legacy).unwrap();
+        assert_eq!(legacy[0].filenames, vec!["/mail/INBOX/new/3".to_string()]);
+        assert_eq!(legacy[0].subject.as_deref(), Some("Hello"));
+    }
+}
@@ -1,8 +1,8 @@
 //! Notmuch client implementations for local and remote command execution.
 //!
 //! This module provides a unified interface for interacting with the notmuch
-//! email indexer, supporting both local command execution and remote execution
-//! via SSH.
+//! email indexer, supporting local command execution, remote execution via
+//! SSH, and a `whynot-agent` daemon running next to the database.
 //!
 //! # Overview
 //!
@@ -10,7 +10,10 @@
 //! - `NotmuchClient` trait - The main interface for all client implementations
 //! - `LocalClient` - Executes notmuch commands locally
 //! - `RemoteClient` - Executes notmuch commands on a remote host via SSH
+//! - `AgentClient` - Forwards calls to a `whynot-agent` over JSON-RPC
 //! - `ClientConfig` - Configuration for creating clients
+//! - `ExpungeOptions` / `ExpungeReport` - Safeguards and dry runs for
+//!   permanently deleting messages
 //! - `TagOperation` - Represents tag add/remove operations
 //!
 //! # Version Compatibility
@@ -44,6 +47,7 @@
 //!     notmuch_path: None,
 //!     database_path: None,
 //!     mail_root: None,
+//!     format: Default::default(),
 //! };
 //! let client = create_client(config)?;
 //!
@@ -65,15 +69,20 @@
 use async_trait::async_trait;
 use std::fmt;
 
-use crate::error::Result;
+use crate::error::{NotmuchError, Result};
 use crate::search::SearchItem;
 use crate::thread::Thread;
 
+pub mod agent;
 pub mod config;
+pub mod expunge;
 pub mod local;
+mod maildir;
 pub mod remote;
 
-pub use config::ClientConfig;
+pub use agent::AgentClient;
+pub use config::{ClientConfig, OutputFormat};
+pub use expunge::{ExpungeCandidate, ExpungeOptions, ExpungeOutcome, ExpungeReport};
 pub use local::LocalClient;
 pub use remote::RemoteClient;
 
@@ -114,6 +123,16 @@ impl fmt::Display for TagOperation {
     }
 }
 
+/// Turn an `insert` tag into a notmuch tag operation: `-tag` is passed
+/// through as a removal and anything else is added.
+pub(crate) fn insert_tag_arg(tag: &str) -> String {
+    if tag.starts_with(['+', '-']) {
+        tag.to_string()
+    } else {
+        format!("+{}", tag)
+    }
+}
+
 /// A client for interacting with notmuch email indexer.
 ///
 /// This trait provides a unified interface for executing notmuch commands
@@ -140,6 +159,7 @@ impl fmt::Display for TagOperation {
 ///     notmuch_path: None,
 ///     database_path: None,
 ///     mail_root: None,
+///     format: Default::default(),
 /// };
 /// let client = create_client(config)?;
 ///
@@ -256,6 +276,31 @@ pub trait NotmuchClient: Send + Sync {
     /// ```
     async fn show(&self, query: &str) -> Result<Thread>;
 
+    /// Show the headers of the messages matching a query, without bodies.
+    ///
+    /// Unlike [`show`](Self::show) only matching messages are returned, not
+    /// their whole threads. This is much cheaper when only header values
+    /// are needed for many messages at once, such as the mailing list
+    /// headers of a page of search results.
+    ///
+    /// The default implementation falls back to `show`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use whynot::client::NotmuchClient;
+    /// # async fn example(client: &dyn NotmuchClient) -> Result<(), Box<dyn std::error::Error>> {
+    /// let thread = client.show_headers("tag:inbox and date:today..").await?;
+    /// for message in thread.get_messages() {
+    ///     println!("{:?}", message.headers.get("List-Id"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn show_headers(&self, query: &str) -> Result<Thread> {
+        self.show(query).await
+    }
+
     /// Add or remove tags from messages matching a query.
     ///
     /// This executes `notmuch tag` with the specified tag operations.
@@ -318,8 +363,9 @@ pub trait NotmuchClient: Send + Sync {
     /// # Arguments
     ///
     /// * `message` - The complete email message in RFC 822 format
-    /// * `folder` - Optional folder name (relative to mail root)
-    /// * `tags` - Initial tags to apply to the message
+    /// * `folder` - Optional folder name (relative to mail root), created if missing
+    /// * `tags` - Initial tags to apply to the message; a tag written as
+    ///   `-tag` is removed instead, e.g. `-unread` to undo notmuch's `new.tags`
     ///
     /// # Returns
     ///
@@ -446,16 +492,143 @@ pub trait NotmuchClient: Send + Sync {
     /// # }
     /// ```
     async fn part(&self, message_id: &str, part_id: u32) -> Result<Vec<u8>>;
+
+    /// Move the files of all messages matching a query into a maildir folder.
+    ///
+    /// This is for setups where an external tool such as mbsync mirrors the
+    /// folder structure to a server, so archiving needs a physical move
+    /// rather than just a tag change. Files are moved between `cur/` and
+    /// `new/` as appropriate, their maildir flags are synchronized with the
+    /// message tags, and the notmuch index is updated afterwards.
+    ///
+    /// Only clients with direct access to the maildir support this; the
+    /// default implementation returns a configuration error.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A notmuch query selecting the messages to move
+    /// * `folder` - Target maildir folder, relative to the mail root
+    ///
+    /// # Returns
+    ///
+    /// The number of files that were moved.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use whynot::client::NotmuchClient;
+    /// # async fn example(client: &dyn NotmuchClient) -> Result<(), Box<dyn std::error::Error>> {
+    /// // Archive a whole thread
+    /// let moved = client.move_to_folder("thread:0000000000000001", "Archive").await?;
+    /// println!("Moved {} files", moved);
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn move_to_folder(&self, _query: &str, _folder: &str) -> Result<usize> {
+        Err(NotmuchError::ConfigError(
+            "Moving messages between folders requires a local notmuch database".to_string(),
+        ))
+    }
+
+    /// Whether this client supports [`move_to_folder`](Self::move_to_folder).
+    ///
+    /// Used to offer archiving only where it can work.
+    fn can_move_files(&self) -> bool {
+        false
+    }
+
+    /// List what [`expunge`](Self::expunge) would remove, without touching
+    /// any files.
+    ///
+    /// Messages matching `query` that carry one of the protected tags are
+    /// listed separately and will not be removed. The returned report
+    /// contains the confirmation token needed to perform the removal.
+    ///
+    /// Only clients with direct access to the maildir support this; the
+    /// default implementation returns a configuration error.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A notmuch query selecting the messages to remove
+    /// * `options` - Protected tags and an optional trash directory
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use whynot::client::{ExpungeOptions, NotmuchClient};
+    /// # async fn example(client: &dyn NotmuchClient) -> Result<(), Box<dyn std::error::Error>> {
+    /// let options = ExpungeOptions::default();
+    /// let report = client
+    ///     .expunge_report("tag:deleted AND date:..30d", &options)
+    ///     .await?;
+    ///
+    /// for message in &report.messages {
+    ///     println!("{} ({} files)", message.id, message.files.len());
+    /// }
+    /// println!("Confirm with token {}", report.token);
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn expunge_report(
+        &self,
+        _query: &str,
+        _options: &ExpungeOptions,
+    ) -> Result<ExpungeReport> {
+        Err(NotmuchError::ConfigError(
+            "Expunging messages requires a local notmuch database".to_string(),
+        ))
+    }
+
+    /// Permanently remove the files of all messages matching a query.
+    ///
+    /// The removal only happens when `token` matches the token of a fresh
+    /// [`expunge_report`](Self::expunge_report) for the same query and
+    /// options, so the files removed are exactly the ones that were
+    /// reviewed. Protected messages are never touched. With a trash
+    /// directory configured the files are moved there instead of being
+    /// deleted. The notmuch index is updated afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A notmuch query selecting the messages to remove
+    /// * `options` - Protected tags and an optional trash directory
+    /// * `token` - The confirmation token from the dry run
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use whynot::client::{ExpungeOptions, NotmuchClient};
+    /// # async fn example(client: &dyn NotmuchClient) -> Result<(), Box<dyn std::error::Error>> {
+    /// let query = "tag:deleted AND date:..30d";
+    /// let options = ExpungeOptions::default();
+    /// let report = client.expunge_report(query, &options).await?;
+    ///
+    /// let outcome = client.expunge(query, &options, &report.token).await?;
+    /// println!("Removed {} messages", outcome.messages);
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn expunge(
+        &self,
+        _query: &str,
+        _options: &ExpungeOptions,
+        _token: &str,
+    ) -> Result<ExpungeOutcome> {
+        Err(NotmuchError::ConfigError(
+            "Expunging messages requires a local notmuch database".to_string(),
+        ))
+    }
 }
 
 /// Create a new notmuch client based on the provided configuration.
 ///
-/// This factory function creates either a `LocalClient` or `RemoteClient`
-/// based on the configuration variant, returning it as a trait object.
+/// This factory function creates a `LocalClient`, `RemoteClient` or
+/// `AgentClient` based on the configuration variant, returning it as a
+/// trait object.
 ///
 /// # Arguments
 ///
-/// * `config` - The client configuration specifying local or remote execution
+/// * `config` - The client configuration specifying local, remote or agent execution
 ///
 /// # Returns
 ///
@@ -472,6 +645,7 @@ pub trait NotmuchClient: Send + Sync {
 ///     notmuch_path: None,
 ///     database_path: None,
 ///     mail_root: None,
+///     format: Default::default(),
 /// };
 /// let local_client = create_client(local_config)?;
 ///
@@ -482,8 +656,16 @@ pub trait NotmuchClient: Send + Sync {
 ///     port: None,
 ///     identity_file: Some(PathBuf::from("/home/alice/.ssh/id_rsa")),
 ///     notmuch_path: None,
+///     format: Default::default(),
 /// };
 /// let remote_client = create_client(remote_config)?;
+///
+/// // Connect to a whynot-agent daemon
+/// let agent_config = ClientConfig::Agent {
+///     endpoint: "mail.example.com:7878".to_string(),
+///     token: "s3cret".to_string(),
+/// };
+/// let agent_client = create_client(agent_config)?;
 /// # Ok(())
 /// # }
 /// ```
@@ -491,5 +673,6 @@ pub fn create_client(config: ClientConfig) -> Result<Box<dyn NotmuchClient>> {
     match &config {
         ClientConfig::Local { .. } => Ok(Box::new(LocalClient::new(config)?)),
         ClientConfig::Remote { .. } => Ok(Box::new(RemoteClient::new(config)?)),
+        ClientConfig::Agent { .. } => Ok(Box::new(AgentClient::new(config)?)),
     }
 }
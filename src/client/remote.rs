@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use std::process::Stdio;
 use tokio::process::Command;
 
-use crate::client::{ClientConfig, NotmuchClient, TagOperation};
+use crate::client::{ClientConfig, NotmuchClient, OutputFormat, TagOperation, insert_tag_arg};
 use crate::error::{NotmuchError, Result};
 use crate::search::{SearchItem, SearchResult};
 use crate::thread::Thread;
@@ -35,6 +35,7 @@ use crate::thread::Thread;
 ///     port: None,
 ///     identity_file: None,
 ///     notmuch_path: None,
+///     format: Default::default(),
 /// };
 /// let client = RemoteClient::new(config)?;
 ///
@@ -45,6 +46,7 @@ use crate::thread::Thread;
 ///     port: Some(2222),
 ///     identity_file: Some(PathBuf::from("/home/alice/.ssh/mail_key")),
 ///     notmuch_path: Some(PathBuf::from("/usr/local/bin/notmuch")),
+///     format: Default::default(),
 /// };
 /// let client = RemoteClient::new(config)?;
 /// # Ok(())
@@ -56,6 +58,7 @@ pub struct RemoteClient {
     port: Option<u16>,
     identity_file: Option<PathBuf>,
     notmuch_path: PathBuf,
+    format: OutputFormat,
 }
 
 impl RemoteClient {
@@ -67,12 +70,14 @@ impl RemoteClient {
                 port,
                 identity_file,
                 notmuch_path,
+                format,
             } => Ok(RemoteClient {
                 host,
                 user,
                 port,
                 identity_file,
                 notmuch_path: notmuch_path.unwrap_or_else(|| PathBuf::from("notmuch")),
+                format,
             }),
             _ => Err(NotmuchError::ConfigError(
                 "Invalid config type for RemoteClient".to_string(),
@@ -305,9 +310,9 @@ impl RemoteClient {
 impl NotmuchClient for RemoteClient {
     async fn search(&self, query: &str) -> Result<Vec<SearchItem>> {
         let output = self
-            .execute_ssh_command(&["search", "--format=json", query])
+            .execute_ssh_command(&["search", self.format.arg(), query])
             .await?;
-        let result: SearchResult = serde_json::from_str(&output)?;
+        let result: SearchResult = self.format.parse(&output)?;
         Ok(result.0)
     }
 
@@ -324,7 +329,7 @@ impl NotmuchClient for RemoteClient {
         let output = self
             .execute_ssh_command(&[
                 "search",
-                "--format=json",
+                self.format.arg(),
                 "--offset",
                 &offset_str,
                 "--limit",
@@ -332,7 +337,7 @@ impl NotmuchClient for RemoteClient {
                 query,
             ])
             .await?;
-        let result: SearchResult = serde_json::from_str(&output)?;
+        let result: SearchResult = self.format.parse(&output)?;
 
         // Get total count by running a count command (separate query)
         // This is optional since it requires an extra query
@@ -348,13 +353,27 @@ impl NotmuchClient for RemoteClient {
         let output = self
             .execute_ssh_command(&[
                 "show",
-                "--format=json",
+                self.format.arg(),
                 "--include-html",
                 "--entire-thread",
                 query,
             ])
             .await?;
-        let thread: Thread = serde_json::from_str(&output)?;
+        let thread: Thread = self.format.parse(&output)?;
+        Ok(thread)
+    }
+
+    async fn show_headers(&self, query: &str) -> Result<Thread> {
+        let output = self
+            .execute_ssh_command(&[
+                "show",
+                self.format.arg(),
+                "--body=false",
+                "--entire-thread=false",
+                query,
+            ])
+            .await?;
+        let thread: Thread = self.format.parse(&output)?;
         Ok(thread)
     }
 
@@ -386,10 +405,11 @@ impl NotmuchClient for RemoteClient {
         if let Some(folder) = folder {
             args.push("--folder".to_string());
             args.push(folder.to_string());
+            args.push("--create-folder".to_string());
         }
 
         for tag in tags {
-            args.push(format!("+{}", tag));
+            args.push(insert_tag_arg(tag));
         }
 
         let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
@@ -464,6 +484,7 @@ mod tests {
             port: Some(2222),
             identity_file: Some(PathBuf::from("/home/user/.ssh/id_rsa")),
             notmuch_path: None,
+            format: Default::default(),
         };
 
         let client = RemoteClient::new(config);
@@ -482,6 +503,7 @@ mod tests {
             notmuch_path: None,
             database_path: None,
             mail_root: None,
+            format: Default::default(),
         };
 
         let result = RemoteClient::new(config);
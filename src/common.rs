@@ -1,5 +1,9 @@
+use chrono::{DateTime, FixedOffset, TimeZone};
+use mail_parser::HeaderValue;
+use mail_parser::parsers::MessageStream;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 
 /// Placeholder for cryptographic information (currently empty)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -11,7 +15,11 @@ pub struct CryptoInfo {
 /// Email headers containing standard fields
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Headers {
-    #[serde(rename = "Subject")]
+    #[serde(
+        rename = "Subject",
+        default,
+        deserialize_with = "deserialize_encoded_words"
+    )]
     pub subject: Option<String>,
     #[serde(rename = "From")]
     pub from: String,
@@ -35,9 +43,297 @@ impl Headers {
             "to" => self.to.as_ref(),
             "reply-to" => self.reply_to.as_ref(),
             "date" => Some(&self.date),
-            _ => self.additional.get(&key_lower),
+            _ => self
+                .additional
+                .iter()
+                .find(|(name, _)| name.to_lowercase() == key_lower)
+                .map(|(_, value)| value),
         }
     }
+
+    /// Mailboxes in the From header.
+    pub fn from_mailboxes(&self) -> Vec<Mailbox> {
+        Mailbox::parse_list(&self.from)
+    }
+
+    /// Mailboxes in the To header.
+    pub fn to_mailboxes(&self) -> Vec<Mailbox> {
+        self.to
+            .as_deref()
+            .map(Mailbox::parse_list)
+            .unwrap_or_default()
+    }
+
+    /// Mailboxes in the Cc header.
+    pub fn cc_mailboxes(&self) -> Vec<Mailbox> {
+        self.get("cc")
+            .map(|cc| Mailbox::parse_list(cc))
+            .unwrap_or_default()
+    }
+
+    /// Mailboxes in the Reply-To header.
+    pub fn reply_to_mailboxes(&self) -> Vec<Mailbox> {
+        self.reply_to
+            .as_deref()
+            .map(Mailbox::parse_list)
+            .unwrap_or_default()
+    }
+
+    /// The first mailbox in the From header, if it could be parsed.
+    pub fn sender(&self) -> Option<Mailbox> {
+        self.from_mailboxes().into_iter().next()
+    }
+
+    /// The From header normalized through [`Mailbox`], or the raw value if it
+    /// cannot be parsed.
+    pub fn formatted_from(&self) -> String {
+        let from = self.from_mailboxes();
+        if from.is_empty() {
+            self.from.clone()
+        } else {
+            Mailbox::format_list(&from)
+        }
+    }
+
+    /// Where replies should go: Reply-To when present, otherwise From.
+    pub fn reply_recipients(&self) -> Vec<Mailbox> {
+        let reply_to = self.reply_to_mailboxes();
+        if reply_to.is_empty() {
+            self.from_mailboxes()
+        } else {
+            reply_to
+        }
+    }
+
+    /// The Date header parsed as RFC 5322, keeping the sender's timezone.
+    pub fn parsed_date(&self) -> Option<DateTime<FixedOffset>> {
+        let date = mail_parser::DateTime::parse_rfc822(&format!("{}\r\n", self.date))?;
+        if !date.is_valid() {
+            return None;
+        }
+        let offset = (date.tz_hour as i32 * 3600) + (date.tz_minute as i32 * 60);
+        let offset = if date.tz_before_gmt {
+            FixedOffset::west_opt(offset)?
+        } else {
+            FixedOffset::east_opt(offset)?
+        };
+        offset
+            .with_ymd_and_hms(
+                date.year as i32,
+                date.month as u32,
+                date.day as u32,
+                date.hour as u32,
+                date.minute as u32,
+                date.second as u32,
+            )
+            .single()
+    }
+}
+
+/// A single email address with an optional display name.
+///
+/// Parsing follows RFC 5322, so quoted names containing commas, group
+/// syntax and RFC 2047 encoded words are handled.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::common::Mailbox;
+/// let list = Mailbox::parse_list("\"Doe, John\" <john@example.com>, jane@example.com");
+/// assert_eq!(list.len(), 2);
+/// assert_eq!(list[0].name.as_deref(), Some("Doe, John"));
+/// assert_eq!(list[1].address, "jane@example.com");
+/// assert_eq!(list[0].to_string(), "\"Doe, John\" <john@example.com>");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+impl Mailbox {
+    pub fn new(name: Option<String>, address: impl Into<String>) -> Self {
+        Mailbox {
+            name: name.filter(|n| !n.trim().is_empty()),
+            address: address.into(),
+        }
+    }
+
+    /// Parse an address list header value.
+    ///
+    /// Groups are flattened into their members and entries without an
+    /// address are dropped.
+    pub fn parse_list(value: &str) -> Vec<Mailbox> {
+        let input = format!("{}\r\n", value.trim());
+        match MessageStream::new(input.as_bytes()).parse_address() {
+            HeaderValue::Address(address) => address
+                .into_list()
+                .into_iter()
+                .filter_map(|addr| {
+                    let address = addr.address?.trim().to_string();
+                    if address.is_empty() {
+                        return None;
+                    }
+                    let name = addr.name.map(|n| n.trim().to_string());
+                    Some(Mailbox::new(name, address))
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Parse a single mailbox, returning the first one in `value`.
+    pub fn parse(value: &str) -> Option<Mailbox> {
+        Mailbox::parse_list(value).into_iter().next()
+    }
+
+    /// Format mailboxes as a comma separated header value.
+    pub fn format_list(mailboxes: &[Mailbox]) -> String {
+        mailboxes
+            .iter()
+            .map(Mailbox::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The name to show for this mailbox, falling back to the address.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.address)
+    }
+
+    /// Compare addresses case-insensitively, ignoring display names.
+    pub fn has_address(&self, address: &str) -> bool {
+        self.address.eq_ignore_ascii_case(address.trim())
+    }
+}
+
+impl fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) if name.chars().any(|c| "()<>[]:;@\\,.\"".contains(c)) => {
+                let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{}\" <{}>", escaped, self.address)
+            }
+            Some(name) => write!(f, "{} <{}>", name, self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+/// Decode RFC 2047 encoded-words such as `=?ISO-2022-JP?B?...?=`.
+///
+/// Text outside encoded-words is kept and whitespace between adjacent
+/// encoded-words is dropped. Values without encoded-words are returned
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::common::decode_encoded_words;
+/// assert_eq!(decode_encoded_words("=?UTF-8?Q?caf=C3=A9?= menu"), "café menu");
+/// assert_eq!(decode_encoded_words("plain"), "plain");
+/// ```
+pub fn decode_encoded_words(value: &str) -> String {
+    if !value.contains("=?") {
+        return value.to_string();
+    }
+    let input = format!("{}\r\n", value);
+    match MessageStream::new(input.as_bytes()).parse_unstructured() {
+        HeaderValue::Text(text) => text.into_owned(),
+        _ => value.to_string(),
+    }
+}
+
+/// Deserialize an optional header value through [`decode_encoded_words`].
+///
+/// notmuch decodes headers itself, but subjects with encoded-words in
+/// places RFC 2047 forbids (inside quotes, for example) come through raw.
+pub(crate) fn deserialize_encoded_words<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.map(|value| decode_encoded_words(&value)))
+}
+
+/// Decode an RFC 2231 extended parameter value, `charset'language'%XX..`.
+///
+/// Returns `None` when `value` is not in that form or names a charset that
+/// cannot be decoded.
+pub fn decode_extended_value(value: &str) -> Option<String> {
+    let (charset, rest) = value.split_once('\'')?;
+    let (_language, encoded) = rest.split_once('\'')?;
+    decode_charset(charset, &urlencoding::decode_binary(encoded.as_bytes()))
+}
+
+/// Decode a MIME parameter value such as an attachment filename.
+///
+/// Both RFC 2231 extended values and RFC 2047 encoded-words are accepted.
+/// Encoded-words are not allowed in parameters, but Outlook and many
+/// Japanese mailers use them anyway.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::common::decode_parameter_value;
+/// assert_eq!(decode_parameter_value("UTF-8''%E2%82%AC%20rates.pdf"), "€ rates.pdf");
+/// assert_eq!(decode_parameter_value("=?UTF-8?B?4oKsIHJhdGVzLnBkZg==?="), "€ rates.pdf");
+/// assert_eq!(decode_parameter_value("it's.pdf"), "it's.pdf");
+/// ```
+pub fn decode_parameter_value(value: &str) -> String {
+    decode_extended_value(value).unwrap_or_else(|| decode_encoded_words(value))
+}
+
+/// Decode `bytes` in the named charset. An empty charset is read as UTF-8.
+pub fn decode_charset(charset: &str, bytes: &[u8]) -> Option<String> {
+    if charset.is_empty()
+        || charset.eq_ignore_ascii_case("utf-8")
+        || charset.eq_ignore_ascii_case("us-ascii")
+    {
+        return Some(String::from_utf8_lossy(bytes).into_owned());
+    }
+    mail_parser::decoders::charsets::map::charset_decoder(charset.as_bytes())
+        .map(|decode| decode(bytes))
+}
+
+/// Encode `value` as an RFC 5987 extended value, `UTF-8''%XX..`.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::common::encode_extended_value;
+/// assert_eq!(encode_extended_value("€ rates.pdf"), "UTF-8''%E2%82%AC%20rates.pdf");
+/// ```
+pub fn encode_extended_value(value: &str) -> String {
+    let mut encoded = String::from("UTF-8''");
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Format a MIME parameter, using the RFC 2231 extended form when `value`
+/// is not plain ASCII.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::common::format_parameter;
+/// assert_eq!(format_parameter("filename", "a \"b\".txt"), "filename=\"a \\\"b\\\".txt\"");
+/// assert_eq!(format_parameter("filename", "é.txt"), "filename*=UTF-8''%C3%A9.txt");
+/// ```
+pub fn format_parameter(name: &str, value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("{}=\"{}\"", name, escaped)
+    } else {
+        format!("{}*={}", name, encode_extended_value(value))
+    }
 }
 
 #[cfg(test)]
@@ -127,4 +423,119 @@ mod tests {
         assert_eq!(headers.reply_to, None);
         assert_eq!(headers.date, "Mon, 1 Jan 2024 12:00:00 +0000");
     }
+
+    fn headers(from: &str, to: Option<&str>) -> Headers {
+        Headers {
+            subject: None,
+            from: from.to_string(),
+            to: to.map(str::to_string),
+            reply_to: None,
+            date: "Wed, 28 May 2025 09:20:35 +0200".to_string(),
+            additional: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_mailbox_list_with_comma_in_name() {
+        let headers = headers(
+            "\"Doe, John\" <john@example.com>",
+            Some("\"Doe, John\" <john@example.com>, Jane <jane@example.com>, bob@example.com"),
+        );
+
+        assert_eq!(
+            headers.from_mailboxes(),
+            vec![Mailbox::new(
+                Some("Doe, John".to_string()),
+                "john@example.com"
+            )]
+        );
+        let to = headers.to_mailboxes();
+        assert_eq!(to.len(), 3);
+        assert_eq!(to[1].name.as_deref(), Some("Jane"));
+        assert_eq!(to[2], Mailbox::new(None, "bob@example.com"));
+        assert_eq!(
+            Mailbox::format_list(&to),
+            "\"Doe, John\" <john@example.com>, Jane <jane@example.com>, bob@example.com"
+        );
+    }
+
+    #[test]
+    fn test_mailbox_groups_and_encoded_words() {
+        let list = Mailbox::parse_list(
+            "Team: alice@example.com, =?UTF-8?B?SsO2cmdlbg==?= <jorgen@example.com>;, undisclosed-recipients:;",
+        );
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].address, "alice@example.com");
+        assert_eq!(list[1].name.as_deref(), Some("Jörgen"));
+        assert_eq!(list[1].display_name(), "Jörgen");
+        assert!(list[1].has_address("JORGEN@example.com"));
+    }
+
+    #[test]
+    fn test_cc_and_reply_to() {
+        let mut headers = headers("Bob <bob@example.com>", None);
+        headers
+            .additional
+            .insert("Cc".to_string(), "carol@example.com".to_string());
+        assert_eq!(headers.cc_mailboxes()[0].address, "carol@example.com");
+        assert_eq!(headers.reply_recipients()[0].address, "bob@example.com");
+
+        headers.reply_to = Some("list@example.com".to_string());
+        assert_eq!(headers.reply_recipients()[0].address, "list@example.com");
+    }
+
+    #[test]
+    fn test_parsed_date_keeps_timezone() {
+        let date = headers("a@example.com", None).parsed_date().unwrap();
+        assert_eq!(date.to_rfc3339(), "2025-05-28T09:20:35+02:00");
+
+        let mut invalid = headers("a@example.com", None);
+        invalid.date = "not a date".to_string();
+        assert_eq!(invalid.parsed_date(), None);
+    }
+
+    #[test]
+    fn test_decode_encoded_words() {
+        assert_eq!(
+            decode_encoded_words("=?ISO-2022-JP?B?GyRCRnxLXDhsGyhCLnBkZg==?="),
+            "日本語.pdf"
+        );
+        assert_eq!(
+            decode_encoded_words("=?utf-8?q?Re=3A_caf=C3=A9?= =?utf-8?q?_plans?="),
+            "Re: café plans"
+        );
+        assert_eq!(decode_encoded_words("=?bogus"), "=?bogus");
+    }
+
+    #[test]
+    fn test_decode_extended_value() {
+        assert_eq!(
+            decode_extended_value("UTF-8'ja'%E8%AB%8B%E6%B1%82%E6%9B%B8%202024.pdf").as_deref(),
+            Some("請求書 2024.pdf")
+        );
+        assert_eq!(
+            decode_extended_value("iso-8859-1''R%E9sum%E9").as_deref(),
+            Some("Résumé")
+        );
+        assert_eq!(decode_extended_value("no quotes"), None);
+        assert_eq!(decode_extended_value("it''s unknown"), None);
+    }
+
+    #[test]
+    fn test_encode_extended_value_round_trips() {
+        let name = "請求書 \"2024\".pdf";
+        assert_eq!(
+            decode_extended_value(&encode_extended_value(name)).as_deref(),
+            Some(name)
+        );
+    }
+
+    #[test]
+    fn test_subject_encoded_words_decoded() {
+        let headers: Headers = serde_json::from_str(
+            r#"{"Subject": "=?UTF-8?B?5pel5pys6Kqe?=", "From": "a@example.com", "Date": "x"}"#,
+        )
+        .unwrap();
+        assert_eq!(headers.subject.as_deref(), Some("日本語"));
+    }
 }
@@ -29,6 +29,10 @@ pub struct Config {
 
     #[serde(default)]
     pub general: GeneralConfig,
+
+    /// Sending identities from `[[identities]]`
+    #[serde(default)]
+    pub identities: Vec<IdentityConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -43,23 +47,42 @@ pub struct MailConfig {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MailReadingConfig {
     #[serde(rename = "type")]
-    pub connection_type: Option<String>, // "local" or "remote"
+    pub connection_type: Option<String>, // "local", "remote" or "agent"
     pub host: Option<String>,
     pub user: Option<String>,
     pub port: Option<u16>,
     pub notmuch_path: Option<String>,
+    pub format: Option<String>, // "json" (default) or "sexp"
     pub database_path: Option<String>,
+    pub agent_endpoint: Option<String>,
+    pub agent_token: Option<String>,
+    pub archive_folder: Option<String>, // Maildir folder the archive action moves threads to
+    pub expunge_protected_tags: Option<Vec<String>>, // Tags that prevent permanent deletion
+    pub trash_dir: Option<String>,      // Expunged files are moved here instead of deleted
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MailSendingConfig {
     #[serde(rename = "type")]
-    pub connection_type: Option<String>, // "local" or "remote"
+    pub connection_type: Option<String>, // "local", "remote", "smtp" or "agent"
     pub host: Option<String>,
     pub user: Option<String>,
     pub port: Option<u16>,
     pub msmtp_path: Option<String>,
     pub config_path: Option<String>,
+    pub agent_endpoint: Option<String>,
+    pub agent_token: Option<String>,
+    pub tls: Option<String>, // SMTP: "starttls" (default), "tls" or "none"
+    pub password_command: Option<String>,
+    pub password_env: Option<String>,
+    pub ca_cert: Option<String>,
+    pub allow_insecure_auth: Option<bool>,
+    pub sent_folder: Option<String>, // Maildir folder for sent copies ("" disables)
+    pub drafts_folder: Option<String>, // Maildir folder drafts are saved in
+    pub outbox_dir: Option<String>,  // Spool directory for outgoing mail
+    pub undo_send_seconds: Option<u64>, // Grace period to undo sending (0 disables)
+    pub max_attachment_mb: Option<u64>, // Limit on the total size of attachments
+    pub gpg_path: Option<String>,    // gpg binary for OpenPGP signing and encryption
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -95,6 +118,159 @@ pub struct UserConfig {
     pub name: Option<String>,
     pub email: Option<String>,
     pub signature: Option<String>,
+    pub trusted_domains: Option<Vec<String>>, // Domains whose lookalikes are flagged as phishing
+    pub trusted_authserv_ids: Option<Vec<String>>, // Own servers whose Authentication-Results count
+    pub signing_key: Option<String>,          // OpenPGP key to sign mail with
+    pub reply_attribution: Option<String>,    // Line above quotes, e.g. "On {date}, {name} wrote:"
+    pub reply_position: Option<String>,       // "bottom" (default) or "top" of the quote
+    pub trim_quoted_signature: Option<bool>,  // Leave signatures out of quotes (default true)
+    #[serde(skip)]
+    pub identities: Vec<IdentityConfig>, // Filled in from [[identities]] by Config::user_config
+}
+
+impl UserConfig {
+    /// The user's own email addresses, used to recognize sent mail.
+    pub fn addresses(&self) -> Vec<&str> {
+        let mut addresses: Vec<&str> = self.email.as_deref().into_iter().collect();
+        for identity in &self.identities {
+            if !addresses
+                .iter()
+                .any(|address| address.eq_ignore_ascii_case(&identity.email))
+            {
+                addresses.push(&identity.email);
+            }
+        }
+        addresses
+    }
+
+    /// The identities mail can be sent as, the default first. Without
+    /// `[[identities]]` this is the `[user]` name, address and signature.
+    pub fn sending_identities(&self) -> Vec<IdentityConfig> {
+        if !self.identities.is_empty() {
+            return self.identities.clone();
+        }
+        self.email
+            .iter()
+            .map(|email| IdentityConfig {
+                name: self.name.clone(),
+                email: email.clone(),
+                signature: self.signature.clone(),
+                signing_key: self.signing_key.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// The identity mail is sent as unless another one is picked.
+    pub fn default_identity(&self) -> Option<IdentityConfig> {
+        self.sending_identities().into_iter().next()
+    }
+
+    /// The identity to send as: the one whose address is in `from`, or the
+    /// default one when `from` is empty. An unknown address is an error, so
+    /// mail is never sent from an address that was not configured.
+    pub fn identity(&self, from: &str) -> Result<Option<IdentityConfig>> {
+        if from.trim().is_empty() {
+            return Ok(self.default_identity());
+        }
+        let mailbox = crate::common::Mailbox::parse(from)
+            .ok_or_else(|| NotmuchError::InvalidInput(format!("Invalid From address: {}", from)))?;
+        self.sending_identities()
+            .into_iter()
+            .find(|identity| mailbox.has_address(&identity.email))
+            .map(Some)
+            .ok_or_else(|| {
+                NotmuchError::InvalidInput(format!("No identity configured for {}", from))
+            })
+    }
+
+    /// The identity to reply to a message as: the first one whose address
+    /// the message was sent to, going by its To, Cc and Delivered-To
+    /// headers, or the default one.
+    ///
+    /// notmuch only outputs `Delivered-To` when it is listed in
+    /// `show.extra_headers`.
+    pub fn reply_identity(&self, headers: &crate::common::Headers) -> Option<IdentityConfig> {
+        let identities = self.sending_identities();
+        let recipients: Vec<crate::common::Mailbox> = ["To", "Cc", "Delivered-To"]
+            .iter()
+            .filter_map(|name| headers.get(name))
+            .flat_map(|value| crate::common::Mailbox::parse_list(value))
+            .collect();
+        recipients
+            .iter()
+            .find_map(|recipient| {
+                identities
+                    .iter()
+                    .find(|identity| recipient.has_address(&identity.email))
+            })
+            .or(identities.first())
+            .cloned()
+    }
+
+    /// Domains that lookalike senders imitate: that of the user's own
+    /// address and the configured trusted domains.
+    pub fn known_domains(&self) -> Vec<String> {
+        let own = self
+            .email
+            .as_deref()
+            .and_then(|email| email.rsplit_once('@'))
+            .map(|(_, domain)| domain.trim().to_ascii_lowercase());
+        own.into_iter()
+            .chain(
+                self.trusted_domains
+                    .iter()
+                    .flatten()
+                    .map(|domain| domain.trim().to_ascii_lowercase()),
+            )
+            .filter(|domain| !domain.is_empty())
+            .collect()
+    }
+
+    /// The authserv-ids whose `Authentication-Results` are trusted.
+    pub fn trusted_authserv_ids(&self) -> &[String] {
+        self.trusted_authserv_ids.as_deref().unwrap_or_default()
+    }
+
+    /// How replies quote the original message.
+    pub fn reply_quoting(&self) -> crate::mail_sender::ReplyQuoting {
+        let default = crate::mail_sender::ReplyQuoting::default();
+        crate::mail_sender::ReplyQuoting {
+            attribution: self
+                .reply_attribution
+                .clone()
+                .unwrap_or(default.attribution),
+            top_posting: self
+                .reply_position
+                .as_deref()
+                .is_some_and(|position| position.eq_ignore_ascii_case("top")),
+            trim_signature: self.trim_quoted_signature.unwrap_or(default.trim_signature),
+        }
+    }
+}
+
+/// An address mail can be sent as, from `[[identities]]`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct IdentityConfig {
+    pub name: Option<String>,
+    pub email: String,
+    pub signature: Option<String>,
+    pub msmtp_account: Option<String>, // msmtp account to send through (-a)
+    pub sending: Option<MailSendingConfig>, // Own sender instead of [mail.sending]
+    pub sent_folder: Option<String>,   // Maildir folder for sent copies ("" disables)
+    pub signing_key: Option<String>,   // OpenPGP key to sign mail with
+}
+
+impl IdentityConfig {
+    /// The From header value for this identity.
+    pub fn mailbox(&self) -> String {
+        crate::common::Mailbox::new(self.name.clone(), self.email.clone()).to_string()
+    }
+
+    /// Whether mail from this identity needs its own sender or Sent folder.
+    fn is_routed(&self) -> bool {
+        self.msmtp_account.is_some() || self.sending.is_some() || self.sent_folder.is_some()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -178,6 +354,13 @@ pub struct CliArgs {
     #[arg(long, env = "WHYNOT_NOTMUCH_PATH", help = "Path to notmuch executable")]
     pub notmuch_path: Option<String>,
 
+    #[arg(
+        long,
+        env = "WHYNOT_NOTMUCH_FORMAT",
+        help = "Output format requested from notmuch: json or sexp"
+    )]
+    pub notmuch_format: Option<String>,
+
     #[arg(
         long,
         env = "WHYNOT_NOTMUCH_DATABASE",
@@ -185,6 +368,42 @@ pub struct CliArgs {
     )]
     pub notmuch_database: Option<String>,
 
+    #[arg(
+        long,
+        env = "WHYNOT_AGENT_ENDPOINT",
+        help = "whynot-agent address for mail reading (host:port or unix:/path)"
+    )]
+    pub agent_endpoint: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_AGENT_TOKEN",
+        help = "Authentication token for the whynot-agent"
+    )]
+    pub agent_token: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_ARCHIVE_FOLDER",
+        help = "Maildir folder to move archived threads to (local databases)"
+    )]
+    pub archive_folder: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_EXPUNGE_PROTECTED_TAGS",
+        value_delimiter = ',',
+        help = "Comma-separated tags that protect messages from being expunged"
+    )]
+    pub expunge_protected_tags: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_TRASH_DIR",
+        help = "Directory to move expunged files to instead of deleting them"
+    )]
+    pub trash_dir: Option<String>,
+
     // Mail sending options
     #[arg(long, env = "WHYNOT_MSMTP_HOST", help = "Remote msmtp server hostname")]
     pub msmtp_host: Option<String>,
@@ -205,6 +424,110 @@ pub struct CliArgs {
     )]
     pub msmtp_config_path: Option<String>,
 
+    #[arg(
+        long,
+        env = "WHYNOT_SENDING_AGENT_ENDPOINT",
+        help = "whynot-agent address for mail sending (defaults to --agent-endpoint)"
+    )]
+    pub sending_agent_endpoint: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SENDING_AGENT_TOKEN",
+        help = "Authentication token for the sending whynot-agent (defaults to --agent-token)"
+    )]
+    pub sending_agent_token: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SMTP_HOST",
+        help = "SMTP submission server (sends without msmtp)"
+    )]
+    pub smtp_host: Option<String>,
+
+    #[arg(long, env = "WHYNOT_SMTP_PORT", help = "SMTP submission port")]
+    pub smtp_port: Option<u16>,
+
+    #[arg(long, env = "WHYNOT_SMTP_USER", help = "SMTP login name")]
+    pub smtp_user: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SMTP_TLS",
+        help = "SMTP transport security: starttls, tls or none"
+    )]
+    pub smtp_tls: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SMTP_PASSWORD_COMMAND",
+        help = "Command printing the SMTP password"
+    )]
+    pub smtp_password_command: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SMTP_PASSWORD_ENV",
+        help = "Environment variable holding the SMTP password"
+    )]
+    pub smtp_password_env: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SMTP_CA_CERT",
+        help = "Extra PEM certificate to trust for the SMTP server"
+    )]
+    pub smtp_ca_cert: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SMTP_ALLOW_INSECURE_AUTH",
+        help = "Send the SMTP password even without TLS"
+    )]
+    pub smtp_allow_insecure_auth: Option<bool>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_SENT_FOLDER",
+        help = "Maildir folder to file sent messages in (default: Sent, empty to disable)"
+    )]
+    pub sent_folder: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_DRAFTS_FOLDER",
+        help = "Maildir folder to save drafts in (default: Drafts)"
+    )]
+    pub drafts_folder: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_OUTBOX_DIR",
+        help = "Spool directory for outgoing mail (default: whynot/outbox in the data directory)"
+    )]
+    pub outbox_dir: Option<String>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_UNDO_SEND_SECONDS",
+        help = "Seconds a sent message can still be undone (default: 10, 0 disables)"
+    )]
+    pub undo_send_seconds: Option<u64>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_MAX_ATTACHMENT_MB",
+        help = "Largest total size of a message's attachments in MB (default: 25)"
+    )]
+    pub max_attachment_mb: Option<u64>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_GPG_PATH",
+        help = "Path to the gpg binary used to sign and encrypt mail (default: gpg)"
+    )]
+    pub gpg_path: Option<String>,
+
     // User identity options
     #[arg(long, env = "WHYNOT_USER_NAME", help = "User's full name for email")]
     pub user_name: Option<String>,
@@ -215,6 +538,22 @@ pub struct CliArgs {
     #[arg(long, env = "WHYNOT_USER_SIGNATURE", help = "User's email signature")]
     pub user_signature: Option<String>,
 
+    #[arg(
+        long,
+        env = "WHYNOT_USER_TRUSTED_DOMAINS",
+        value_delimiter = ',',
+        help = "Comma-separated domains whose lookalikes are flagged as phishing"
+    )]
+    pub user_trusted_domains: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        env = "WHYNOT_USER_TRUSTED_AUTHSERV_IDS",
+        value_delimiter = ',',
+        help = "Comma-separated authserv-ids whose Authentication-Results are trusted"
+    )]
+    pub user_trusted_authserv_ids: Option<Vec<String>>,
+
     // General options
     #[arg(
         long,
@@ -414,9 +753,33 @@ impl Config {
         if let Ok(path) = env::var("WHYNOT_NOTMUCH_PATH") {
             config.mail.reading.notmuch_path = Some(path);
         }
+        if let Ok(format) = env::var("WHYNOT_NOTMUCH_FORMAT") {
+            config.mail.reading.format = Some(format);
+        }
         if let Ok(db) = env::var("WHYNOT_NOTMUCH_DATABASE") {
             config.mail.reading.database_path = Some(db);
         }
+        if let Ok(endpoint) = env::var("WHYNOT_AGENT_ENDPOINT") {
+            config.mail.reading.agent_endpoint = Some(endpoint);
+            config.mail.reading.connection_type = Some("agent".to_string());
+        }
+        if let Ok(token) = env::var("WHYNOT_AGENT_TOKEN") {
+            config.mail.reading.agent_token = Some(token);
+        }
+        if let Ok(folder) = env::var("WHYNOT_ARCHIVE_FOLDER") {
+            config.mail.reading.archive_folder = Some(folder);
+        }
+        if let Ok(tags) = env::var("WHYNOT_EXPUNGE_PROTECTED_TAGS") {
+            config.mail.reading.expunge_protected_tags = Some(
+                tags.split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect(),
+            );
+        }
+        if let Ok(dir) = env::var("WHYNOT_TRASH_DIR") {
+            config.mail.reading.trash_dir = Some(dir);
+        }
 
         // Mail sending configuration
         if let Ok(host) = env::var("WHYNOT_MSMTP_HOST") {
@@ -437,6 +800,64 @@ impl Config {
         if let Ok(config_path) = env::var("WHYNOT_MSMTP_CONFIG") {
             config.mail.sending.config_path = Some(config_path);
         }
+        if let Ok(endpoint) = env::var("WHYNOT_SENDING_AGENT_ENDPOINT") {
+            config.mail.sending.agent_endpoint = Some(endpoint);
+            config.mail.sending.connection_type = Some("agent".to_string());
+        }
+        if let Ok(token) = env::var("WHYNOT_SENDING_AGENT_TOKEN") {
+            config.mail.sending.agent_token = Some(token);
+        }
+        if let Ok(host) = env::var("WHYNOT_SMTP_HOST") {
+            config.mail.sending.host = Some(host);
+            config.mail.sending.connection_type = Some("smtp".to_string());
+        }
+        if let Ok(port) = env::var("WHYNOT_SMTP_PORT") {
+            config.mail.sending.port = Some(port.parse().map_err(|e| {
+                NotmuchError::ConfigError(format!("Invalid WHYNOT_SMTP_PORT: {}", e))
+            })?);
+        }
+        if let Ok(user) = env::var("WHYNOT_SMTP_USER") {
+            config.mail.sending.user = Some(user);
+        }
+        if let Ok(tls) = env::var("WHYNOT_SMTP_TLS") {
+            config.mail.sending.tls = Some(tls);
+        }
+        if let Ok(command) = env::var("WHYNOT_SMTP_PASSWORD_COMMAND") {
+            config.mail.sending.password_command = Some(command);
+        }
+        if let Ok(var) = env::var("WHYNOT_SMTP_PASSWORD_ENV") {
+            config.mail.sending.password_env = Some(var);
+        }
+        if let Ok(path) = env::var("WHYNOT_SMTP_CA_CERT") {
+            config.mail.sending.ca_cert = Some(path);
+        }
+        if let Ok(allow) = env::var("WHYNOT_SMTP_ALLOW_INSECURE_AUTH") {
+            config.mail.sending.allow_insecure_auth = Some(allow.parse().map_err(|e| {
+                NotmuchError::ConfigError(format!("Invalid WHYNOT_SMTP_ALLOW_INSECURE_AUTH: {}", e))
+            })?);
+        }
+        if let Ok(folder) = env::var("WHYNOT_SENT_FOLDER") {
+            config.mail.sending.sent_folder = Some(folder);
+        }
+        if let Ok(folder) = env::var("WHYNOT_DRAFTS_FOLDER") {
+            config.mail.sending.drafts_folder = Some(folder);
+        }
+        if let Ok(dir) = env::var("WHYNOT_OUTBOX_DIR") {
+            config.mail.sending.outbox_dir = Some(dir);
+        }
+        if let Ok(seconds) = env::var("WHYNOT_UNDO_SEND_SECONDS") {
+            config.mail.sending.undo_send_seconds = Some(seconds.parse().map_err(|e| {
+                NotmuchError::ConfigError(format!("Invalid WHYNOT_UNDO_SEND_SECONDS: {}", e))
+            })?);
+        }
+        if let Ok(size) = env::var("WHYNOT_MAX_ATTACHMENT_MB") {
+            config.mail.sending.max_attachment_mb = Some(size.parse().map_err(|e| {
+                NotmuchError::ConfigError(format!("Invalid WHYNOT_MAX_ATTACHMENT_MB: {}", e))
+            })?);
+        }
+        if let Ok(path) = env::var("WHYNOT_GPG_PATH") {
+            config.mail.sending.gpg_path = Some(path);
+        }
 
         // User configuration
         if let Ok(name) = env::var("WHYNOT_USER_NAME") {
@@ -448,6 +869,23 @@ impl Config {
         if let Ok(signature) = env::var("WHYNOT_USER_SIGNATURE") {
             config.user.signature = Some(signature);
         }
+        if let Ok(domains) = env::var("WHYNOT_USER_TRUSTED_DOMAINS") {
+            config.user.trusted_domains = Some(
+                domains
+                    .split(',')
+                    .map(|d| d.trim().to_string())
+                    .filter(|d| !d.is_empty())
+                    .collect(),
+            );
+        }
+        if let Ok(ids) = env::var("WHYNOT_USER_TRUSTED_AUTHSERV_IDS") {
+            config.user.trusted_authserv_ids = Some(
+                ids.split(',')
+                    .map(|id| id.trim().to_string())
+                    .filter(|id| !id.is_empty())
+                    .collect(),
+            );
+        }
 
         // General configuration
         if let Ok(interval) = env::var("WHYNOT_AUTO_REFRESH_INTERVAL") {
@@ -522,9 +960,28 @@ impl Config {
         if let Some(path) = args.notmuch_path {
             config.mail.reading.notmuch_path = Some(path);
         }
+        if let Some(format) = args.notmuch_format {
+            config.mail.reading.format = Some(format);
+        }
         if let Some(db) = args.notmuch_database.or(args.database) {
             config.mail.reading.database_path = Some(db);
         }
+        if let Some(endpoint) = args.agent_endpoint {
+            config.mail.reading.agent_endpoint = Some(endpoint);
+            config.mail.reading.connection_type = Some("agent".to_string());
+        }
+        if let Some(token) = args.agent_token {
+            config.mail.reading.agent_token = Some(token);
+        }
+        if let Some(folder) = args.archive_folder {
+            config.mail.reading.archive_folder = Some(folder);
+        }
+        if let Some(tags) = args.expunge_protected_tags {
+            config.mail.reading.expunge_protected_tags = Some(tags);
+        }
+        if let Some(dir) = args.trash_dir {
+            config.mail.reading.trash_dir = Some(dir);
+        }
 
         // Mail sending configuration
         if let Some(host) = args.msmtp_host {
@@ -543,6 +1000,56 @@ impl Config {
         if let Some(config_path) = args.msmtp_config_path {
             config.mail.sending.config_path = Some(config_path);
         }
+        if let Some(endpoint) = args.sending_agent_endpoint {
+            config.mail.sending.agent_endpoint = Some(endpoint);
+            config.mail.sending.connection_type = Some("agent".to_string());
+        }
+        if let Some(token) = args.sending_agent_token {
+            config.mail.sending.agent_token = Some(token);
+        }
+        if let Some(host) = args.smtp_host {
+            config.mail.sending.host = Some(host);
+            config.mail.sending.connection_type = Some("smtp".to_string());
+        }
+        if let Some(port) = args.smtp_port {
+            config.mail.sending.port = Some(port);
+        }
+        if let Some(user) = args.smtp_user {
+            config.mail.sending.user = Some(user);
+        }
+        if let Some(tls) = args.smtp_tls {
+            config.mail.sending.tls = Some(tls);
+        }
+        if let Some(command) = args.smtp_password_command {
+            config.mail.sending.password_command = Some(command);
+        }
+        if let Some(var) = args.smtp_password_env {
+            config.mail.sending.password_env = Some(var);
+        }
+        if let Some(path) = args.smtp_ca_cert {
+            config.mail.sending.ca_cert = Some(path);
+        }
+        if let Some(allow) = args.smtp_allow_insecure_auth {
+            config.mail.sending.allow_insecure_auth = Some(allow);
+        }
+        if let Some(folder) = args.sent_folder {
+            config.mail.sending.sent_folder = Some(folder);
+        }
+        if let Some(folder) = args.drafts_folder {
+            config.mail.sending.drafts_folder = Some(folder);
+        }
+        if let Some(dir) = args.outbox_dir {
+            config.mail.sending.outbox_dir = Some(dir);
+        }
+        if let Some(seconds) = args.undo_send_seconds {
+            config.mail.sending.undo_send_seconds = Some(seconds);
+        }
+        if let Some(size) = args.max_attachment_mb {
+            config.mail.sending.max_attachment_mb = Some(size);
+        }
+        if let Some(path) = args.gpg_path {
+            config.mail.sending.gpg_path = Some(path);
+        }
 
         // User configuration
         if let Some(name) = args.user_name {
@@ -554,6 +1061,12 @@ impl Config {
         if let Some(signature) = args.user_signature {
             config.user.signature = Some(signature);
         }
+        if let Some(domains) = args.user_trusted_domains {
+            config.user.trusted_domains = Some(domains);
+        }
+        if let Some(ids) = args.user_trusted_authserv_ids {
+            config.user.trusted_authserv_ids = Some(ids);
+        }
 
         // General configuration
         if let Some(interval) = args.auto_refresh_interval {
@@ -583,9 +1096,27 @@ impl Config {
         if other.mail.reading.notmuch_path.is_some() {
             base.mail.reading.notmuch_path = other.mail.reading.notmuch_path;
         }
+        if other.mail.reading.format.is_some() {
+            base.mail.reading.format = other.mail.reading.format;
+        }
         if other.mail.reading.database_path.is_some() {
             base.mail.reading.database_path = other.mail.reading.database_path;
         }
+        if other.mail.reading.agent_endpoint.is_some() {
+            base.mail.reading.agent_endpoint = other.mail.reading.agent_endpoint;
+        }
+        if other.mail.reading.agent_token.is_some() {
+            base.mail.reading.agent_token = other.mail.reading.agent_token;
+        }
+        if other.mail.reading.archive_folder.is_some() {
+            base.mail.reading.archive_folder = other.mail.reading.archive_folder;
+        }
+        if other.mail.reading.expunge_protected_tags.is_some() {
+            base.mail.reading.expunge_protected_tags = other.mail.reading.expunge_protected_tags;
+        }
+        if other.mail.reading.trash_dir.is_some() {
+            base.mail.reading.trash_dir = other.mail.reading.trash_dir;
+        }
 
         // Merge mail sending config
         if other.mail.sending.connection_type.is_some() {
@@ -606,6 +1137,45 @@ impl Config {
         if other.mail.sending.config_path.is_some() {
             base.mail.sending.config_path = other.mail.sending.config_path;
         }
+        if other.mail.sending.agent_endpoint.is_some() {
+            base.mail.sending.agent_endpoint = other.mail.sending.agent_endpoint;
+        }
+        if other.mail.sending.agent_token.is_some() {
+            base.mail.sending.agent_token = other.mail.sending.agent_token;
+        }
+        if other.mail.sending.tls.is_some() {
+            base.mail.sending.tls = other.mail.sending.tls;
+        }
+        if other.mail.sending.password_command.is_some() {
+            base.mail.sending.password_command = other.mail.sending.password_command;
+        }
+        if other.mail.sending.password_env.is_some() {
+            base.mail.sending.password_env = other.mail.sending.password_env;
+        }
+        if other.mail.sending.ca_cert.is_some() {
+            base.mail.sending.ca_cert = other.mail.sending.ca_cert;
+        }
+        if other.mail.sending.allow_insecure_auth.is_some() {
+            base.mail.sending.allow_insecure_auth = other.mail.sending.allow_insecure_auth;
+        }
+        if other.mail.sending.sent_folder.is_some() {
+            base.mail.sending.sent_folder = other.mail.sending.sent_folder;
+        }
+        if other.mail.sending.drafts_folder.is_some() {
+            base.mail.sending.drafts_folder = other.mail.sending.drafts_folder;
+        }
+        if other.mail.sending.outbox_dir.is_some() {
+            base.mail.sending.outbox_dir = other.mail.sending.outbox_dir;
+        }
+        if other.mail.sending.undo_send_seconds.is_some() {
+            base.mail.sending.undo_send_seconds = other.mail.sending.undo_send_seconds;
+        }
+        if other.mail.sending.max_attachment_mb.is_some() {
+            base.mail.sending.max_attachment_mb = other.mail.sending.max_attachment_mb;
+        }
+        if other.mail.sending.gpg_path.is_some() {
+            base.mail.sending.gpg_path = other.mail.sending.gpg_path;
+        }
 
         // Merge web config
         if other.ui.web.bind.is_some() {
@@ -654,6 +1224,27 @@ impl Config {
         if other.user.signature.is_some() {
             base.user.signature = other.user.signature;
         }
+        if other.user.trusted_domains.is_some() {
+            base.user.trusted_domains = other.user.trusted_domains;
+        }
+        if other.user.trusted_authserv_ids.is_some() {
+            base.user.trusted_authserv_ids = other.user.trusted_authserv_ids;
+        }
+        if other.user.signing_key.is_some() {
+            base.user.signing_key = other.user.signing_key;
+        }
+        if other.user.reply_attribution.is_some() {
+            base.user.reply_attribution = other.user.reply_attribution;
+        }
+        if other.user.reply_position.is_some() {
+            base.user.reply_position = other.user.reply_position;
+        }
+        if other.user.trim_quoted_signature.is_some() {
+            base.user.trim_quoted_signature = other.user.trim_quoted_signature;
+        }
+        if !other.identities.is_empty() {
+            base.identities = other.identities;
+        }
 
         // Merge general config
         if other.general.auto_refresh_interval.is_some() {
@@ -693,8 +1284,102 @@ impl Config {
         self.ui.web.items_per_page.unwrap_or(50)
     }
 
+    /// Get the safeguards used when expunging messages
+    pub fn expunge_options(&self) -> crate::client::ExpungeOptions {
+        let defaults = crate::client::ExpungeOptions::default();
+        crate::client::ExpungeOptions {
+            protected_tags: self
+                .mail
+                .reading
+                .expunge_protected_tags
+                .clone()
+                .unwrap_or(defaults.protected_tags),
+            trash_dir: self.mail.reading.trash_dir.clone().map(Into::into),
+        }
+    }
+
+    /// Get the Maildir folder sent messages are filed in, if any
+    pub fn sent_folder(&self) -> Option<String> {
+        match self.mail.sending.sent_folder.as_deref() {
+            Some(folder) if folder.trim().is_empty() => None,
+            Some(folder) => Some(folder.trim().to_string()),
+            None => Some("Sent".to_string()),
+        }
+    }
+
+    /// Get the Maildir folder drafts are saved in
+    pub fn drafts_folder(&self) -> String {
+        match self.mail.sending.drafts_folder.as_deref() {
+            Some(folder) if !folder.trim().is_empty() => folder.trim().to_string(),
+            _ => "Drafts".to_string(),
+        }
+    }
+
+    /// Get the spool directory outgoing mail is queued in
+    pub fn outbox_dir(&self) -> PathBuf {
+        match self.mail.sending.outbox_dir.as_deref() {
+            Some(dir) if !dir.trim().is_empty() => PathBuf::from(dir.trim()),
+            _ => dirs::data_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("whynot")
+                .join("outbox"),
+        }
+    }
+
+    /// Get how long a sent message waits in the outbox, where it can still
+    /// be taken back, before it is handed to the mail sender
+    pub fn undo_send_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.mail.sending.undo_send_seconds.unwrap_or(10))
+    }
+
+    /// Get the limits on what can be attached to outgoing messages
+    pub fn attachment_limits(&self) -> crate::mail_sender::AttachmentLimits {
+        match self.mail.sending.max_attachment_mb {
+            Some(size) => crate::mail_sender::AttachmentLimits {
+                max_total_size: usize::try_from(size.saturating_mul(1024 * 1024))
+                    .unwrap_or(usize::MAX),
+            },
+            None => Default::default(),
+        }
+    }
+
+    /// Get the gpg used to sign and encrypt outgoing mail
+    pub fn gpg(&self) -> crate::mail_sender::Gpg {
+        crate::mail_sender::Gpg::new(
+            self.mail
+                .sending
+                .gpg_path
+                .as_deref()
+                .filter(|path| !path.trim().is_empty())
+                .map(PathBuf::from),
+        )
+    }
+
     /// Create a ClientConfig for notmuch from this configuration
     pub fn to_client_config(&self) -> Result<crate::client::ClientConfig> {
+        let reading = &self.mail.reading;
+        let is_agent = match reading.connection_type.as_deref() {
+            Some(connection_type) => connection_type == "agent",
+            None => reading.agent_endpoint.is_some(),
+        };
+
+        if is_agent {
+            let endpoint = reading.agent_endpoint.clone().ok_or_else(|| {
+                NotmuchError::ConfigError(
+                    "Agent endpoint not configured for mail reading".to_string(),
+                )
+            })?;
+            let token = reading.agent_token.clone().ok_or_else(|| {
+                NotmuchError::ConfigError("Agent token not configured for mail reading".to_string())
+            })?;
+            return Ok(crate::client::ClientConfig::Agent { endpoint, token });
+        }
+
+        let format = match &self.mail.reading.format {
+            Some(format) => format.parse()?,
+            None => crate::client::OutputFormat::default(),
+        };
+
         let is_remote = self.mail.reading.connection_type.as_deref() == Some("remote")
             || self.mail.reading.host.is_some();
 
@@ -717,25 +1402,159 @@ impl Config {
                 port: self.mail.reading.port,
                 identity_file: None,
                 notmuch_path: self.mail.reading.notmuch_path.clone().map(Into::into),
+                format,
             })
         } else {
             Ok(crate::client::ClientConfig::Local {
                 notmuch_path: self.mail.reading.notmuch_path.clone().map(Into::into),
                 database_path: self.mail.reading.database_path.clone().map(Into::into),
                 mail_root: None,
+                format,
             })
         }
     }
 
+    /// The user settings, with the sending identities from `[[identities]]`
+    pub fn user_config(&self) -> UserConfig {
+        UserConfig {
+            identities: self.identities.clone(),
+            ..self.user.clone()
+        }
+    }
+
     /// Create a MailSenderConfig from this configuration
     pub fn to_mail_sender_config(&self) -> Result<crate::mail_sender::MailSenderConfig> {
-        let is_remote = self.mail.sending.connection_type.as_deref() == Some("remote")
-            || self.mail.sending.host.is_some();
+        // Messages without a From header are sent as the default identity
+        let from = self
+            .user_config()
+            .sending_identities()
+            .first()
+            .map(IdentityConfig::mailbox);
+        self.sender_config(&self.mail.sending, from)
+    }
+
+    /// Wrap `sender` so that identities with their own msmtp account,
+    /// sender or Sent folder get their mail routed accordingly
+    pub fn route_identities(
+        &self,
+        sender: Box<dyn crate::mail_sender::MailSender>,
+    ) -> Result<Box<dyn crate::mail_sender::MailSender>> {
+        let routed: Vec<&IdentityConfig> = self
+            .identities
+            .iter()
+            .filter(|identity| identity.is_routed())
+            .collect();
+        if routed.is_empty() {
+            return Ok(sender);
+        }
+
+        let mut identity_sender = crate::mail_sender::IdentityMailSender::new(sender);
+        for identity in routed {
+            let own_sender = if identity.msmtp_account.is_some() || identity.sending.is_some() {
+                Some(crate::mail_sender::create_mail_sender(
+                    self.identity_sender_config(identity)?,
+                )?)
+            } else {
+                None
+            };
+            identity_sender = identity_sender.route(
+                identity.email.clone(),
+                own_sender,
+                identity.sent_folder.clone(),
+            );
+        }
+        Ok(Box::new(identity_sender))
+    }
+
+    /// The sender for an identity: its own `sending` table, or the main
+    /// one, switched to its msmtp account
+    fn identity_sender_config(
+        &self,
+        identity: &IdentityConfig,
+    ) -> Result<crate::mail_sender::MailSenderConfig> {
+        use crate::mail_sender::MailSenderConfig;
+
+        let mut config = match &identity.sending {
+            Some(sending) => self.sender_config(sending, Some(identity.mailbox()))?,
+            None => self.to_mail_sender_config()?,
+        };
+        if let Some(msmtp_account) = &identity.msmtp_account {
+            match &mut config {
+                MailSenderConfig::Local { account, .. }
+                | MailSenderConfig::Remote { account, .. } => {
+                    *account = Some(msmtp_account.clone());
+                }
+                _ => {
+                    return Err(NotmuchError::ConfigError(format!(
+                        "Identity {} sets msmtp_account but does not send through msmtp",
+                        identity.email
+                    )));
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Create a MailSenderConfig from a `[mail.sending]`-style table
+    fn sender_config(
+        &self,
+        sending: &MailSendingConfig,
+        from: Option<String>,
+    ) -> Result<crate::mail_sender::MailSenderConfig> {
+        let is_agent = match sending.connection_type.as_deref() {
+            Some(connection_type) => connection_type == "agent",
+            None => sending.agent_endpoint.is_some(),
+        };
+
+        if is_agent {
+            // Fall back to the reading agent, which is usually the same daemon
+            let endpoint = sending
+                .agent_endpoint
+                .clone()
+                .or_else(|| self.mail.reading.agent_endpoint.clone())
+                .ok_or_else(|| {
+                    NotmuchError::ConfigError(
+                        "Agent endpoint not configured for mail sending".to_string(),
+                    )
+                })?;
+            let token = sending
+                .agent_token
+                .clone()
+                .or_else(|| self.mail.reading.agent_token.clone())
+                .ok_or_else(|| {
+                    NotmuchError::ConfigError(
+                        "Agent token not configured for mail sending".to_string(),
+                    )
+                })?;
+            return Ok(crate::mail_sender::MailSenderConfig::Agent { endpoint, token });
+        }
+
+        if sending.connection_type.as_deref() == Some("smtp") {
+            let host = sending.host.clone().ok_or_else(|| {
+                NotmuchError::ConfigError("SMTP host not configured for mail sending".to_string())
+            })?;
+            let tls = match sending.tls.as_deref() {
+                Some(tls) => tls.parse()?,
+                None => crate::mail_sender::SmtpTls::default(),
+            };
+            return Ok(crate::mail_sender::MailSenderConfig::Smtp {
+                host,
+                port: sending.port,
+                tls,
+                user: sending.user.clone(),
+                password_command: sending.password_command.clone(),
+                password_env: sending.password_env.clone(),
+                from,
+                ca_cert: sending.ca_cert.clone().map(Into::into),
+                allow_insecure_auth: sending.allow_insecure_auth.unwrap_or(false),
+            });
+        }
+
+        let is_remote =
+            sending.connection_type.as_deref() == Some("remote") || sending.host.is_some();
 
         if is_remote {
-            let host = self
-                .mail
-                .sending
+            let host = sending
                 .host
                 .as_ref()
                 .ok_or_else(|| {
@@ -747,16 +1566,18 @@ impl Config {
 
             Ok(crate::mail_sender::MailSenderConfig::Remote {
                 host,
-                user: self.mail.sending.user.clone(),
-                port: self.mail.sending.port,
+                user: sending.user.clone(),
+                port: sending.port,
                 identity_file: None,
-                msmtp_path: self.mail.sending.msmtp_path.clone().map(Into::into),
-                config_path: self.mail.sending.config_path.clone().map(Into::into),
+                msmtp_path: sending.msmtp_path.clone().map(Into::into),
+                config_path: sending.config_path.clone().map(Into::into),
+                account: None,
             })
         } else {
             Ok(crate::mail_sender::MailSenderConfig::Local {
-                msmtp_path: self.mail.sending.msmtp_path.clone().map(Into::into),
-                config_path: self.mail.sending.config_path.clone().map(Into::into),
+                msmtp_path: sending.msmtp_path.clone().map(Into::into),
+                config_path: sending.config_path.clone().map(Into::into),
+                account: None,
             })
         }
     }
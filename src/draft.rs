@@ -0,0 +1,373 @@
+//! Drafts kept as messages in the notmuch database.
+//!
+//! A draft is saved with [`NotmuchClient::insert`] into a Drafts folder and
+//! tagged `draft`, so it survives closing the compose form and can be
+//! resumed from any client. Every save inserts a fresh copy and removes the
+//! previous one, and the draft is removed once the message has been sent.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use whynot::client::NotmuchClient;
+//! # use whynot::draft::Draft;
+//! # async fn example(client: &dyn NotmuchClient) -> Result<(), Box<dyn std::error::Error>> {
+//! let mut draft = Draft {
+//!     to: "bob@example.com".to_string(),
+//!     subject: "Plans".to_string(),
+//!     body: "Half-finished thought".to_string(),
+//!     ..Default::default()
+//! };
+//! draft.save(client, "Drafts", None).await?;
+//!
+//! // Later, possibly from another client
+//! let resumed = Draft::load(client, draft.id.as_deref().unwrap()).await?;
+//! assert_eq!(resumed.body, "Half-finished thought");
+//! # Ok(())
+//! # }
+//! ```
+
+use mail_parser::{MessageParser, MimeHeaders};
+
+use crate::client::{ExpungeOptions, NotmuchClient, TagOperation};
+use crate::common::decode_encoded_words;
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::{Attachment, ComposableMessage, MessageBuilder, PgpOptions};
+
+/// Tag carried by saved drafts.
+pub const DRAFT_TAG: &str = "draft";
+
+/// Query listing all saved drafts.
+pub const DRAFTS_QUERY: &str = "tag:draft";
+
+/// Tags for a newly saved copy; drafts are neither new mail nor unread.
+const DRAFT_TAGS: &[&str] = &[DRAFT_TAG, "-unread", "-inbox"];
+
+/// Header recording whether the draft was written in markdown.
+const MARKDOWN_HEADER: &str = "X-Whynot-Markdown";
+
+/// Header recording whether to sign and encrypt the message, e.g.
+/// `sign, encrypt` or `none`.
+const PGP_HEADER: &str = "X-Whynot-Pgp";
+
+/// The fields of a message being composed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Draft {
+    /// Message ID (without angle brackets) of the saved copy, if any.
+    pub id: Option<String>,
+    /// The identity the message is sent as; empty for the default one.
+    pub from: String,
+    pub to: String,
+    pub cc: String,
+    pub bcc: String,
+    pub subject: String,
+    pub body: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// Whether the body is markdown, to be sent with an HTML alternative.
+    pub markdown: bool,
+    pub attachments: Vec<Attachment>,
+    /// Whether to sign and encrypt the message; `None` for the identity's
+    /// default.
+    pub pgp: Option<PgpOptions>,
+}
+
+impl Draft {
+    /// Whether nothing worth saving has been typed or attached.
+    pub fn is_empty(&self) -> bool {
+        [&self.to, &self.cc, &self.bcc, &self.subject, &self.body]
+            .iter()
+            .all(|field| field.trim().is_empty())
+            && self.attachments.is_empty()
+    }
+
+    /// Render the draft as a message, which need not have recipients yet.
+    /// `from` is used when the draft does not name an identity.
+    pub fn to_message(&self, from: Option<&str>) -> ComposableMessage {
+        let mut builder = MessageBuilder::new()
+            .subject(self.subject.clone())
+            .body(self.body.clone());
+        let from = Some(self.from.trim())
+            .filter(|from| !from.is_empty())
+            .or(from);
+        if let Some(from) = from {
+            builder = builder.from(from.to_string());
+        }
+        if !self.to.trim().is_empty() {
+            builder = builder.to(self.to.trim().to_string());
+        }
+        if !self.cc.trim().is_empty() {
+            builder = builder.cc(self.cc.trim().to_string());
+        }
+        if !self.bcc.trim().is_empty() {
+            builder = builder.bcc(self.bcc.trim().to_string());
+        }
+        if let Some(in_reply_to) = &self.in_reply_to {
+            builder = builder.in_reply_to(in_reply_to.clone());
+        }
+        for reference in &self.references {
+            builder = builder.add_reference(reference.clone());
+        }
+        if self.markdown {
+            builder = builder.header(MARKDOWN_HEADER.to_string(), "yes".to_string());
+        }
+        if let Some(pgp) = self.pgp {
+            let value = match (pgp.sign, pgp.encrypt) {
+                (false, false) => "none",
+                (true, false) => "sign",
+                (false, true) => "encrypt",
+                (true, true) => "sign, encrypt",
+            };
+            builder = builder.header(PGP_HEADER.to_string(), value.to_string());
+        }
+        for attachment in &self.attachments {
+            builder = builder.attachment(attachment.clone());
+        }
+        builder.build_draft()
+    }
+
+    /// The fields of a message that was ready to be sent, e.g. to edit it
+    /// again before it goes out. The draft has not been saved yet.
+    pub fn from_message(message: &ComposableMessage) -> Draft {
+        Draft {
+            id: None,
+            from: message.from.clone().unwrap_or_default(),
+            to: message.to.join(", "),
+            cc: message.cc.join(", "),
+            bcc: message.bcc.join(", "),
+            subject: message.subject.clone(),
+            body: message.body.clone(),
+            in_reply_to: message.in_reply_to.clone(),
+            references: message.references.clone(),
+            markdown: message.html_body.is_some()
+                || message
+                    .headers
+                    .get(MARKDOWN_HEADER)
+                    .is_some_and(|value| value.eq_ignore_ascii_case("yes")),
+            attachments: message.attachments.clone(),
+            pgp: Some(message.pgp),
+        }
+    }
+
+    /// Read a draft back from the raw message saved by [`save`](Self::save).
+    pub fn from_rfc822(id: &str, raw: &[u8]) -> Result<Draft> {
+        let message = MessageParser::default()
+            .parse(raw)
+            .ok_or_else(|| NotmuchError::InvalidInput(format!("Draft {} is not a message", id)))?;
+        let header = |name: &str| {
+            message
+                .header_raw(name)
+                .map(|value| decode_encoded_words(&unfold(value)))
+                .unwrap_or_default()
+        };
+
+        let in_reply_to = header("In-Reply-To");
+        Ok(Draft {
+            id: Some(id.to_string()),
+            from: header("From"),
+            to: header("To"),
+            cc: header("Cc"),
+            bcc: header("Bcc"),
+            subject: message.subject().unwrap_or_default().to_string(),
+            body: body_text(&message),
+            in_reply_to: (!in_reply_to.is_empty()).then_some(in_reply_to),
+            references: header("References")
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            markdown: header(MARKDOWN_HEADER).eq_ignore_ascii_case("yes"),
+            attachments: message.attachments().map(attachment).collect(),
+            pgp: message.header_raw(PGP_HEADER).map(|value| {
+                let has = |option: &str| {
+                    value
+                        .split(',')
+                        .any(|word| word.trim().eq_ignore_ascii_case(option))
+                };
+                PgpOptions {
+                    sign: has("sign"),
+                    encrypt: has("encrypt"),
+                }
+            }),
+        })
+    }
+
+    /// Load a saved draft by its message ID.
+    pub async fn load(client: &dyn NotmuchClient, id: &str) -> Result<Draft> {
+        let raw = client.part(&format!("id:{}", id), 0).await?;
+        Draft::from_rfc822(id, &raw)
+    }
+
+    /// Save the draft into `folder`, replacing the previously saved copy.
+    ///
+    /// On success [`id`](Self::id) refers to the new copy.
+    pub async fn save(
+        &mut self,
+        client: &dyn NotmuchClient,
+        folder: &str,
+        from: Option<&str>,
+    ) -> Result<()> {
+        let message = self.to_message(from);
+        client
+            .insert(&message.to_rfc822()?, Some(folder), DRAFT_TAGS)
+            .await?;
+
+        let id = message
+            .message_id
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string();
+        if let Some(previous) = self.id.replace(id) {
+            discard_draft(client, &previous).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove a saved draft, e.g. after the message has been sent.
+///
+/// Only messages tagged `draft` are touched. Clients without access to the
+/// maildir cannot delete files, so there the draft is tagged `deleted`
+/// instead, which also hides it from the Drafts view.
+pub async fn discard_draft(client: &dyn NotmuchClient, id: &str) -> Result<()> {
+    let query = format!("id:{} AND tag:{}", id, DRAFT_TAG);
+    let options = ExpungeOptions {
+        protected_tags: Vec::new(),
+        trash_dir: None,
+    };
+    match client.expunge_report(&query, &options).await {
+        Ok(report) => {
+            if !report.messages.is_empty() {
+                client.expunge(&query, &options, &report.token).await?;
+            }
+            Ok(())
+        }
+        Err(NotmuchError::ConfigError(_)) => {
+            client
+                .tag(
+                    &query,
+                    &[
+                        TagOperation::Remove(DRAFT_TAG.to_string()),
+                        TagOperation::Add("deleted".to_string()),
+                    ],
+                )
+                .await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The text of a saved draft's body, reflowed as it was typed if it was
+/// saved as format=flowed.
+fn body_text(message: &mail_parser::Message) -> String {
+    let Some(text) = message.body_text(0) else {
+        return String::new();
+    };
+    let text = text.replace("\r\n", "\n");
+    let is_flowed = message
+        .text_part(0)
+        .and_then(|part| part.content_type())
+        .and_then(|content_type| content_type.attribute("format"))
+        .is_some_and(|format| format.eq_ignore_ascii_case("flowed"));
+    if !is_flowed {
+        return text;
+    }
+    // Every flowed line ends in a break, including the last one typed
+    let mut body = crate::flowed::decode(&text, false);
+    if body.ends_with('\n') {
+        body.pop();
+    }
+    body
+}
+
+/// An attachment of a saved draft, as it was attached.
+fn attachment(part: &mail_parser::MessagePart) -> Attachment {
+    let content_type = part
+        .content_type()
+        .map(|content_type| match content_type.subtype() {
+            Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+            None => content_type.ctype().to_string(),
+        })
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    Attachment {
+        filename: part.attachment_name().unwrap_or("attachment").to_string(),
+        content_type,
+        data: part.contents().to_vec(),
+    }
+}
+
+/// Join folded header lines.
+fn unfold(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draft_round_trip() {
+        let draft = Draft {
+            id: None,
+            from: "Alice Smith <alice@work.example.com>".to_string(),
+            to: "Bob <bob@example.com>, carol@example.com".to_string(),
+            cc: String::new(),
+            bcc: "dave@example.com".to_string(),
+            subject: "Re: Café plans".to_string(),
+            body: format!(
+                "First line\n\n* a markdown list\n\n{}",
+                "A paragraph long enough to be wrapped when saved. "
+                    .repeat(3)
+                    .trim_end()
+            ),
+            in_reply_to: Some("<a@example.com>".to_string()),
+            references: vec![
+                "<root@example.com>".to_string(),
+                "<a@example.com>".to_string(),
+            ],
+            markdown: true,
+            attachments: vec![
+                Attachment {
+                    filename: "plan.pdf".to_string(),
+                    content_type: "application/pdf".to_string(),
+                    data: vec![0x25, 0x50, 0x44, 0x46, 0x00, 0xff],
+                },
+                Attachment {
+                    filename: "notes.txt".to_string(),
+                    content_type: "text/plain".to_string(),
+                    data: b"Bring snacks".to_vec(),
+                },
+            ],
+            pgp: Some(PgpOptions {
+                sign: false,
+                encrypt: true,
+            }),
+        };
+        let message = draft.to_message(Some("me@example.com"));
+        let raw = message.to_rfc822().unwrap();
+
+        let resumed = Draft::from_rfc822("d1@whynot", &raw).unwrap();
+        assert_eq!(
+            resumed,
+            Draft {
+                id: Some("d1@whynot".to_string()),
+                ..draft
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_draft() {
+        let draft = Draft {
+            body: "  \n".to_string(),
+            ..Default::default()
+        };
+        assert!(draft.is_empty());
+        // Drafts without recipients can still be rendered
+        let raw = draft.to_message(None).to_rfc822().unwrap();
+        let resumed = Draft::from_rfc822("d2@whynot", &raw).unwrap();
+        assert_eq!(resumed.to, "");
+        assert!(!resumed.markdown);
+        assert_eq!(resumed.in_reply_to, None);
+        assert!(resumed.attachments.is_empty());
+        assert_eq!(resumed.pgp, None);
+    }
+}
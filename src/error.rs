@@ -23,8 +23,14 @@ pub enum NotmuchError {
     #[error("Mail sending failed: {0}")]
     MailSendError(String),
 
+    #[error("Mail sending failed: {message}")]
+    SmtpRejected { code: u16, message: String },
+
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+
+    #[error("Invalid address in {field}: {message}")]
+    InvalidAddress { field: String, message: String },
 }
 
 pub type Result<T> = std::result::Result<T, NotmuchError>;
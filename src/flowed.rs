@@ -0,0 +1,274 @@
+//! RFC 3676 `format=flowed` plain text.
+//!
+//! Flowed text is sent wrapped into short lines, with a trailing space
+//! marking each line break the reader's client may undo (a soft break).
+//! Receiving clients join those lines again to fit their own width, so
+//! long paragraphs neither get hard-broken in transit nor show up ragged.
+//! Quoted lines carry their depth as leading `>` characters, and lines that
+//! would otherwise start with a space, `>` or `From ` are space-stuffed.
+
+/// Width outgoing flowed text is wrapped to, as RFC 3676 recommends.
+pub const WIDTH: usize = 72;
+
+/// Encode `text`, with one line per paragraph, as flowed text.
+///
+/// Lines starting with `>` are quotes, whose depth is the number of `>`
+/// (spaces between them are allowed, as in `> > quoted`). Trailing spaces
+/// before hard breaks are dropped, except in the `-- ` signature separator.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::flowed;
+/// let text = format!("{}\n> quoted", "word ".repeat(20).trim_end());
+/// let encoded = flowed::encode(&text);
+/// assert!(encoded.lines().next().unwrap().ends_with("word "));
+/// assert_eq!(flowed::decode(&encoded, false), text + "\n");
+/// ```
+pub fn encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for line in text.lines() {
+        let (depth, content) = split_quote(line);
+        let content = if depth == 0 && content == "-- " {
+            content
+        } else {
+            content.trim_end()
+        };
+
+        let prefix = ">".repeat(depth);
+        if content.is_empty() {
+            encoded.push_str(&prefix);
+            encoded.push('\n');
+            continue;
+        }
+        // The space after the quote marks doubles as space-stuffing
+        let prefix = if depth > 0 { prefix + " " } else { prefix };
+        let width = WIDTH.saturating_sub(prefix.chars().count()).max(1);
+        for piece in wrap(content, width) {
+            encoded.push_str(&prefix);
+            if depth == 0 && needs_stuffing(piece) {
+                encoded.push(' ');
+            }
+            encoded.push_str(piece);
+            encoded.push('\n');
+        }
+    }
+    encoded
+}
+
+/// Reflow flowed `text` into one line per paragraph, the reverse of
+/// [`encode`].
+///
+/// With `delsp` (the `DelSp=yes` parameter) the space marking each soft
+/// break was added by the sender and is removed. Quoted paragraphs are
+/// prefixed with `> ` for each level.
+pub fn decode(text: &str, delsp: bool) -> String {
+    let mut decoded = String::new();
+    let mut paragraph: Option<(usize, String)> = None;
+
+    let flush = |decoded: &mut String, paragraph: &mut Option<(usize, String)>| {
+        if let Some((depth, text)) = paragraph.take() {
+            let line = format!("{}{}", "> ".repeat(depth), text);
+            decoded.push_str(line.trim_end_matches(|c| c == ' ' && text.is_empty()));
+            decoded.push('\n');
+        }
+    };
+
+    for line in text.lines() {
+        let depth = line.chars().take_while(|&c| c == '>').count();
+        let content = &line[depth..];
+        let content = content.strip_prefix(' ').unwrap_or(content);
+        let soft = content.ends_with(' ') && content != "-- ";
+
+        // Paragraphs only continue at the same depth
+        if paragraph.as_ref().is_some_and(|(d, _)| *d != depth) {
+            flush(&mut decoded, &mut paragraph);
+        }
+        let content = match content.strip_suffix(' ') {
+            Some(stripped) if soft && delsp => stripped,
+            _ => content,
+        };
+        paragraph
+            .get_or_insert_with(|| (depth, String::new()))
+            .1
+            .push_str(content);
+        if !soft {
+            flush(&mut decoded, &mut paragraph);
+        }
+    }
+    flush(&mut decoded, &mut paragraph);
+
+    if !text.ends_with('\n') {
+        decoded.pop();
+    }
+    decoded
+}
+
+/// Whether `text` reads as flowed text sent without saying so.
+///
+/// notmuch does not pass on the Content-Type parameters of a part, so
+/// `format=flowed` is recognized from the text itself: most lines that
+/// continue a paragraph end in a single space, and none of those is longer
+/// than a flowed line would be.
+pub fn looks_flowed(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut continued = 0;
+    let mut soft = 0;
+    for pair in lines.windows(2) {
+        let (line, next) = (pair[0], pair[1]);
+        if line.trim().is_empty() || next.trim().is_empty() {
+            continue;
+        }
+        continued += 1;
+        if line.ends_with(' ') && !line.ends_with("  ") && line != "-- " {
+            if line.chars().count() > 79 {
+                return false;
+            }
+            soft += 1;
+        }
+    }
+    soft >= 2 && soft * 2 >= continued
+}
+
+/// The quote depth of `line` and the text after its quote marks, without
+/// the space conventionally following them.
+fn split_quote(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+    while let Some(after) = rest.strip_prefix('>') {
+        depth += 1;
+        // `> > text` quotes twice, like `>> text`
+        rest = match after.strip_prefix(' ') {
+            Some(unspaced) if unspaced.starts_with('>') => unspaced,
+            _ => after,
+        };
+    }
+    if depth > 0 {
+        rest = rest.strip_prefix(' ').unwrap_or(rest);
+    }
+    (depth, rest)
+}
+
+/// Split `content` into lines of at most `width` characters, breaking after
+/// spaces so every line but the last ends in one. Words longer than `width`
+/// get a line of their own.
+fn wrap(content: &str, width: usize) -> Vec<&str> {
+    // Each chunk is a word with the spaces that follow it
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut in_spaces = false;
+    for (i, c) in content.char_indices() {
+        if c == ' ' {
+            in_spaces = true;
+        } else if in_spaces {
+            chunks.push(&content[start..i]);
+            start = i;
+            in_spaces = false;
+        }
+    }
+    chunks.push(&content[start..]);
+
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut line_width = 0;
+    let mut offset = 0;
+    for chunk in chunks {
+        let chunk_width = chunk.chars().count();
+        if line_width > 0 && line_width + chunk.trim_end().chars().count() > width {
+            lines.push(&content[line_start..offset]);
+            line_start = offset;
+            line_width = 0;
+        }
+        line_width += chunk_width;
+        offset += chunk.len();
+    }
+    lines.push(&content[line_start..]);
+    lines
+}
+
+/// Whether a line must be space-stuffed so it is not read as a quote, as
+/// space-stuffed, or mangled into `>From` by mail transports.
+fn needs_stuffing(line: &str) -> bool {
+    line.starts_with(' ') || line.starts_with('>') || line.starts_with("From ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_wraps_with_soft_breaks() {
+        let paragraph = "The quick brown fox jumps over the lazy dog. ".repeat(4);
+        let encoded = encode(paragraph.trim_end());
+        let lines: Vec<&str> = encoded.lines().collect();
+        assert!(lines.len() > 1);
+        for line in &lines[..lines.len() - 1] {
+            assert!(line.ends_with(' '));
+            assert!(line.chars().count() <= WIDTH);
+        }
+        assert!(!lines[lines.len() - 1].ends_with(' '));
+        assert_eq!(
+            decode(&encoded, false),
+            format!("{}\n", paragraph.trim_end())
+        );
+    }
+
+    #[test]
+    fn test_encode_trims_hard_breaks_and_keeps_signature() {
+        assert_eq!(encode("Hello   \n\n-- \nAlice  "), "Hello\n\n-- \nAlice\n");
+    }
+
+    #[test]
+    fn test_encode_space_stuffs() {
+        assert_eq!(
+            encode(" indented\nFrom here\n>> quoted\n> > also quoted\n>\nnot >quoted"),
+            "  indented\n From here\n>> quoted\n>> also quoted\n>\nnot >quoted\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_wraps_quotes_and_long_words() {
+        let quote = format!("> {}", "quoted text ".repeat(10).trim_end());
+        let encoded = encode(&quote);
+        assert!(encoded.lines().count() > 1);
+        assert!(encoded.lines().all(|line| line.starts_with("> ")));
+        assert_eq!(decode(&encoded, false), format!("{}\n", quote));
+        let url = format!("See https://example.com/{}", "x".repeat(100));
+        assert_eq!(
+            encode(&url),
+            format!("See \nhttps://example.com/{}\n", "x".repeat(100))
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let text =
+            "This is a \nflowed paragraph.\n\n> Quoted \n> text.\n>> Deeper \n>> quote\n-- \nSig";
+        assert_eq!(
+            decode(text, false),
+            "This is a flowed paragraph.\n\n> Quoted text.\n> > Deeper quote\n-- \nSig"
+        );
+        // DelSp removes the space marking each soft break
+        assert_eq!(
+            decode("Auf\u{30b9} \nwiedersehen\n", true),
+            "Auf\u{30b9}wiedersehen\n"
+        );
+        // Space-stuffing is undone
+        assert_eq!(
+            decode(" From me\n  indented\n", false),
+            "From me\n indented\n"
+        );
+        // A soft break before a change of depth ends the paragraph
+        assert_eq!(decode("> quoted \nreply\n", false), "> quoted \nreply\n");
+    }
+
+    #[test]
+    fn test_looks_flowed() {
+        let flowed = encode(&"A long paragraph of prose that wraps. ".repeat(8));
+        assert!(looks_flowed(&flowed));
+        assert!(!looks_flowed(
+            "Line one\nLine two \nLine three\nLine four\n"
+        ));
+        assert!(!looks_flowed("Short\nlines\nonly\n"));
+    }
+}
@@ -3,6 +3,7 @@
 //! This crate provides:
 //! - Comprehensive types for deserializing all notmuch JSON formats
 //! - A unified client interface for executing notmuch commands locally or remotely
+//! - A JSON-RPC agent daemon for serving a notmuch database over the network
 //! - Support for search results, email threads, messages, and attachments
 //! - Test utilities for integration testing with temporary notmuch databases
 //!
@@ -33,13 +34,22 @@
 //! }
 //! ```
 
+pub mod agent;
+pub mod auth;
 pub mod body;
+pub mod calendar;
 pub mod client;
 pub mod common;
 pub mod config;
+pub mod draft;
 pub mod error;
+pub mod flowed;
+pub mod list;
 pub mod mail_sender;
+pub mod outbox;
 pub mod search;
+pub mod sexp;
+pub mod summary;
 pub mod text_renderer;
 pub mod thread;
 pub mod tui;
@@ -0,0 +1,559 @@
+//! Mailing list headers.
+//!
+//! Mail delivered through a list manager carries a `List-Id` (RFC 2919)
+//! naming the list, and usually `List-Post` and `List-Unsubscribe`
+//! (RFC 2369) with the addresses to write to. [`ListInfo`] collects these
+//! from a message's headers so clients can label list mail, reply to the
+//! list rather than the author, and unsubscribe.
+//!
+//! Unsubscribing prefers the RFC 8058 one-click POST when the sender
+//! advertises it with `List-Unsubscribe-Post`, then a `mailto:` URI sent
+//! through a [`MailSender`], and otherwise leaves an `https:` URI for the
+//! user to open in a browser. See [`Unsubscriber`].
+//!
+//! notmuch only outputs the headers listed in its `show.extra_headers`
+//! setting, so these headers have to be added there first:
+//!
+//! ```text
+//! notmuch config set show.extra_headers 'List-Id;List-Post;List-Unsubscribe;List-Unsubscribe-Post'
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::client::NotmuchClient;
+use crate::common::{Headers, Mailbox};
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::{ComposableMessage, MailSender};
+use crate::search::SearchItem;
+
+/// The mailing list a message was delivered through.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ListInfo {
+    /// The list identifier from `List-Id`, e.g. `dev.lists.example.com`
+    pub id: Option<String>,
+    /// The descriptive name in front of the `List-Id` identifier
+    pub name: Option<String>,
+    /// URIs from `List-Post`
+    pub post: Vec<String>,
+    /// Whether `List-Post: NO` said that posting to the list is not allowed
+    pub posting_disabled: bool,
+    /// URIs from `List-Unsubscribe`, in order of preference
+    pub unsubscribe: Vec<String>,
+    /// Whether `List-Unsubscribe-Post` advertises RFC 8058 one-click
+    /// unsubscription
+    pub one_click: bool,
+}
+
+impl ListInfo {
+    /// Read the list headers of a message, or `None` if it was not sent
+    /// through a list.
+    pub fn from_headers(headers: &Headers) -> Option<ListInfo> {
+        let list_id = headers.get("List-Id");
+        let list_post = headers.get("List-Post");
+        let list_unsubscribe = headers.get("List-Unsubscribe");
+        if list_id.is_none() && list_post.is_none() && list_unsubscribe.is_none() {
+            return None;
+        }
+
+        let (name, id) = list_id
+            .map(|value| parse_list_id(value))
+            .unwrap_or_default();
+        let posting_disabled =
+            list_post.is_some_and(|value| strip_comments(value).trim().eq_ignore_ascii_case("NO"));
+        let one_click = headers.get("List-Unsubscribe-Post").is_some_and(|value| {
+            value
+                .trim()
+                .eq_ignore_ascii_case("List-Unsubscribe=One-Click")
+        });
+
+        Some(ListInfo {
+            id,
+            name,
+            post: list_post
+                .map(|value| parse_uri_list(value))
+                .unwrap_or_default(),
+            posting_disabled,
+            unsubscribe: list_unsubscribe
+                .map(|value| parse_uri_list(value))
+                .unwrap_or_default(),
+            one_click,
+        })
+    }
+
+    /// A short name for the list, for badges and status lines.
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        if let Some(id) = &self.id {
+            // dev.lists.example.com -> dev
+            return id.split('.').next().unwrap_or(id).to_string();
+        }
+        self.post_address()
+            .map(|mailbox| mailbox.address)
+            .unwrap_or_else(|| "list".to_string())
+    }
+
+    /// The address to send replies to the list to, if posting is allowed.
+    pub fn post_address(&self) -> Option<Mailbox> {
+        if self.posting_disabled {
+            return None;
+        }
+        self.post
+            .iter()
+            .filter_map(|uri| Mailto::parse(uri))
+            .find_map(|mailto| mailto.to.into_iter().next())
+            .and_then(|address| Mailbox::parse(&address))
+    }
+
+    /// The URI to POST to for one-click unsubscription.
+    ///
+    /// RFC 8058 requires an `https:` URI; plain `http:` is only accepted
+    /// when `allow_http` is set.
+    pub fn one_click_url(&self, allow_http: bool) -> Option<&str> {
+        if !self.one_click {
+            return None;
+        }
+        self.unsubscribe
+            .iter()
+            .map(String::as_str)
+            .find(|uri| is_https(uri) || (allow_http && is_http(uri)))
+    }
+
+    /// The first `mailto:` unsubscribe URI.
+    pub fn unsubscribe_mailto(&self) -> Option<Mailto> {
+        self.unsubscribe.iter().find_map(|uri| Mailto::parse(uri))
+    }
+
+    /// The first web unsubscribe URI, to open in a browser.
+    pub fn unsubscribe_url(&self) -> Option<&str> {
+        self.unsubscribe
+            .iter()
+            .map(String::as_str)
+            .find(|uri| is_https(uri) || is_http(uri))
+    }
+
+    /// Whether any unsubscribe method is known.
+    pub fn can_unsubscribe(&self) -> bool {
+        !self.unsubscribe.is_empty()
+    }
+}
+
+/// A parsed `mailto:` URI (RFC 6068).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Mailto {
+    pub to: Vec<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+impl Mailto {
+    /// Parse a `mailto:` URI, or `None` for other schemes.
+    pub fn parse(uri: &str) -> Option<Mailto> {
+        let rest = uri
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+            .map(|_| &uri[7..])?;
+        let (addresses, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut mailto = Mailto {
+            to: split_addresses(&percent_decode(addresses)),
+            ..Default::default()
+        };
+        for field in query.split('&').filter(|field| !field.is_empty()) {
+            let (name, value) = field.split_once('=').unwrap_or((field, ""));
+            let value = percent_decode(value);
+            match name.to_ascii_lowercase().as_str() {
+                "to" => mailto.to.extend(split_addresses(&value)),
+                "subject" => mailto.subject = Some(value),
+                "body" => mailto.body = Some(value),
+                _ => {}
+            }
+        }
+
+        if mailto.to.is_empty() {
+            None
+        } else {
+            Some(mailto)
+        }
+    }
+
+    /// A message to send for this URI.
+    pub fn to_message(&self, from: Option<String>) -> Result<ComposableMessage> {
+        let mut builder = ComposableMessage::builder()
+            .subject(
+                self.subject
+                    .clone()
+                    .unwrap_or_else(|| "unsubscribe".to_string()),
+            )
+            .body(self.body.clone().unwrap_or_default());
+        if let Some(from) = from {
+            builder = builder.from(from);
+        }
+        for address in &self.to {
+            builder = builder.to(address.clone());
+        }
+        builder.build()
+    }
+}
+
+/// What [`Unsubscriber::unsubscribe`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsubscribed {
+    /// The one-click POST to this URI was accepted
+    OneClick(String),
+    /// An unsubscribe request was mailed; holds its Message-ID
+    Mail(String),
+    /// Nothing could be done automatically; the user has to visit this URI
+    Browser(String),
+}
+
+/// Performs list unsubscriptions.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use whynot::list::{ListInfo, Unsubscribed, Unsubscriber};
+/// # use whynot::mail_sender::MailSender;
+/// # async fn example(list: &ListInfo, sender: &dyn MailSender) -> whynot::error::Result<()> {
+/// let http = reqwest::Client::builder()
+///     .timeout(std::time::Duration::from_secs(5))
+///     .build()
+///     .unwrap();
+/// match Unsubscriber::new(http).unsubscribe(list, Some(sender)).await? {
+///     Unsubscribed::OneClick(_) | Unsubscribed::Mail(_) => println!("Unsubscribed"),
+///     Unsubscribed::Browser(url) => println!("Visit {} to unsubscribe", url),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Unsubscriber {
+    http: reqwest::Client,
+    allow_http: bool,
+}
+
+impl Default for Unsubscriber {
+    fn default() -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent("whynot-email-client/1.0")
+            .build()
+            .expect("default HTTP client");
+        Unsubscriber::new(http)
+    }
+}
+
+impl Unsubscriber {
+    /// Use `http` for one-click requests, so proxies, timeouts and TLS
+    /// settings can be configured by the caller.
+    pub fn new(http: reqwest::Client) -> Self {
+        Unsubscriber {
+            http,
+            allow_http: false,
+        }
+    }
+
+    /// Also accept plain `http:` one-click URIs. RFC 8058 forbids them, so
+    /// this is only meant for testing.
+    pub fn allow_http(mut self, allow: bool) -> Self {
+        self.allow_http = allow;
+        self
+    }
+
+    /// Unsubscribe from `list`.
+    ///
+    /// Without a `sender`, `mailto:` URIs are skipped.
+    pub async fn unsubscribe(
+        &self,
+        list: &ListInfo,
+        sender: Option<&dyn MailSender>,
+    ) -> Result<Unsubscribed> {
+        if let Some(url) = list.one_click_url(self.allow_http) {
+            self.one_click(url).await?;
+            return Ok(Unsubscribed::OneClick(url.to_string()));
+        }
+
+        if let (Some(mailto), Some(sender)) = (list.unsubscribe_mailto(), sender) {
+            let from = sender.get_from_address().await.ok();
+            let message_id = sender.send(mailto.to_message(from)?).await?;
+            return Ok(Unsubscribed::Mail(message_id));
+        }
+
+        list.unsubscribe_url()
+            .map(|url| Unsubscribed::Browser(url.to_string()))
+            .ok_or_else(|| {
+                NotmuchError::InvalidInput("List offers no usable unsubscribe method".to_string())
+            })
+    }
+
+    /// Send the RFC 8058 POST request.
+    async fn one_click(&self, url: &str) -> Result<()> {
+        let response = self
+            .http
+            .post(url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .body("List-Unsubscribe=One-Click")
+            .send()
+            .await
+            .map_err(|e| {
+                NotmuchError::CommandFailed(format!("Unsubscribe request failed: {}", e))
+            })?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(NotmuchError::CommandFailed(format!(
+                "Unsubscribe request to {} returned {}",
+                url,
+                response.status()
+            )))
+        }
+    }
+}
+
+/// The list label of each search result sent through a mailing list,
+/// keyed by thread ID.
+///
+/// Headers are fetched with a single
+/// [`show_headers`](NotmuchClient::show_headers) call; the most recent
+/// message with list headers decides the label.
+pub async fn list_labels(
+    client: &dyn NotmuchClient,
+    items: &[SearchItem],
+) -> Result<HashMap<String, String>> {
+    if items.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let query = items
+        .iter()
+        .map(|item| format!("thread:{}", item.thread_id()))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    let thread = client.show_headers(&query).await?;
+
+    let mut thread_of_message = HashMap::new();
+    for item in items {
+        for id in item.message_ids() {
+            thread_of_message.insert(id, item.thread_id().to_string());
+        }
+    }
+
+    let mut labels: HashMap<String, (i64, String)> = HashMap::new();
+    for message in thread.get_messages() {
+        let (Some(thread_id), Some(list)) = (
+            thread_of_message.get(&message.id),
+            ListInfo::from_headers(&message.headers),
+        ) else {
+            continue;
+        };
+        let newer = labels
+            .get(thread_id)
+            .is_none_or(|(timestamp, _)| message.timestamp > *timestamp);
+        if newer {
+            labels.insert(thread_id.clone(), (message.timestamp, list.label()));
+        }
+    }
+
+    Ok(labels
+        .into_iter()
+        .map(|(thread_id, (_, label))| (thread_id, label))
+        .collect())
+}
+
+/// Split a `List-Id` value into its optional description and identifier.
+fn parse_list_id(value: &str) -> (Option<String>, Option<String>) {
+    let value = value.trim();
+    match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            let id = value[start + 1..end].trim();
+            let name = value[..start].trim().trim_matches('"').trim();
+            (
+                (!name.is_empty()).then(|| name.to_string()),
+                (!id.is_empty()).then(|| id.to_string()),
+            )
+        }
+        // Not in the standard form; treat the whole value as the identifier
+        _ => (None, (!value.is_empty()).then(|| value.to_string())),
+    }
+}
+
+/// The URIs of an RFC 2369 header: angle-bracketed and comma separated,
+/// possibly with comments and folding whitespace.
+fn parse_uri_list(value: &str) -> Vec<String> {
+    let value = strip_comments(value);
+    let mut uris = Vec::new();
+    let mut rest = value.as_str();
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let uri: String = rest[start + 1..start + len]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        if !uri.is_empty() {
+            uris.push(uri);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    uris
+}
+
+/// Remove parenthesized comments outside of angle brackets.
+fn strip_comments(value: &str) -> String {
+    let mut result = String::new();
+    let mut depth = 0usize;
+    let mut in_uri = false;
+    for c in value.chars() {
+        match c {
+            '<' if depth == 0 => in_uri = true,
+            '>' if depth == 0 => in_uri = false,
+            '(' if !in_uri => {
+                depth += 1;
+                continue;
+            }
+            ')' if !in_uri && depth > 0 => {
+                depth -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn split_addresses(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    urlencoding::decode(value)
+        .map(|decoded| decoded.into_owned())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+fn is_https(uri: &str) -> bool {
+    uri.get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+fn is_http(uri: &str) -> bool {
+    uri.get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(additional: &[(&str, &str)]) -> Headers {
+        Headers {
+            subject: Some("[dev] Release".to_string()),
+            from: "Alice <alice@example.com>".to_string(),
+            to: Some("dev@lists.example.com".to_string()),
+            reply_to: None,
+            date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+            additional: additional
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_from_headers() {
+        let list = ListInfo::from_headers(&headers(&[
+            ("List-Id", "\"Developer list\" <dev.lists.example.com>"),
+            ("List-Post", "<mailto:dev@lists.example.com>"),
+            (
+                "List-Unsubscribe",
+                "<https://lists.example.com/u/abc>,\r\n <mailto:dev-leave@lists.example.com?subject=unsubscribe>",
+            ),
+            ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+        ]))
+        .unwrap();
+
+        assert_eq!(list.id.as_deref(), Some("dev.lists.example.com"));
+        assert_eq!(list.label(), "Developer list");
+        assert_eq!(
+            list.post_address().unwrap().address,
+            "dev@lists.example.com"
+        );
+        assert_eq!(
+            list.one_click_url(false),
+            Some("https://lists.example.com/u/abc")
+        );
+        assert_eq!(
+            list.unsubscribe_mailto().unwrap().to,
+            vec!["dev-leave@lists.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_not_a_list() {
+        assert_eq!(ListInfo::from_headers(&headers(&[])), None);
+    }
+
+    #[test]
+    fn test_posting_disabled_and_comments() {
+        let list = ListInfo::from_headers(&headers(&[
+            ("list-id", "<announce.example.org>"),
+            ("list-post", "NO (posting not allowed on this list)"),
+            (
+                "list-unsubscribe",
+                "(Use this to leave) <http://example.org/leave>",
+            ),
+        ]))
+        .unwrap();
+
+        assert!(list.posting_disabled);
+        assert_eq!(list.post_address(), None);
+        assert_eq!(list.label(), "announce");
+        assert_eq!(list.unsubscribe, vec!["http://example.org/leave"]);
+        // Not advertised as one-click, and not https
+        assert_eq!(list.one_click_url(true), None);
+        assert_eq!(list.unsubscribe_url(), Some("http://example.org/leave"));
+    }
+
+    #[test]
+    fn test_mailto_parse() {
+        let mailto = Mailto::parse(
+            "MAILTO:leave@example.org,other@example.org?Subject=bye%20now&body=please",
+        )
+        .unwrap();
+        assert_eq!(mailto.to, vec!["leave@example.org", "other@example.org"]);
+        assert_eq!(mailto.subject.as_deref(), Some("bye now"));
+        assert_eq!(mailto.body.as_deref(), Some("please"));
+
+        let mailto = Mailto::parse("mailto:?to=list%2Bleave@example.org").unwrap();
+        assert_eq!(mailto.to, vec!["list+leave@example.org"]);
+
+        assert_eq!(Mailto::parse("https://example.org"), None);
+        assert_eq!(Mailto::parse("mailto:"), None);
+    }
+
+    #[test]
+    fn test_mailto_message() {
+        let message = Mailto::parse("mailto:leave@example.org")
+            .unwrap()
+            .to_message(Some("me@example.com".to_string()))
+            .unwrap();
+        assert_eq!(message.to, vec!["leave@example.org"]);
+        assert_eq!(message.subject, "unsubscribe");
+        assert_eq!(message.from.as_deref(), Some("me@example.com"));
+    }
+}
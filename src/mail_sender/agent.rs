@@ -0,0 +1,101 @@
+//! Mail sender that submits messages through a `whynot-agent` daemon.
+
+use async_trait::async_trait;
+
+use crate::agent::AgentConnection;
+use crate::agent::protocol::{ForwardParams, ReplyParams, SendParams, methods};
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::{ComposableMessage, MailSender, MailSenderConfig};
+use crate::thread::Message;
+
+/// A mail sender that forwards messages to a `whynot-agent` daemon.
+///
+/// The agent must have been started with mail sending enabled; it then
+/// hands the message to its own configured `MailSender` (usually msmtp).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use whynot::mail_sender::{AgentMailSender, MailSenderConfig};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let sender = AgentMailSender::new(MailSenderConfig::Agent {
+///     endpoint: "mail.example.com:7878".to_string(),
+///     token: "s3cret".to_string(),
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AgentMailSender {
+    connection: AgentConnection,
+}
+
+impl AgentMailSender {
+    pub fn new(config: MailSenderConfig) -> Result<Self> {
+        match config {
+            MailSenderConfig::Agent { endpoint, token } => Ok(AgentMailSender {
+                connection: AgentConnection::new(endpoint.parse()?, token),
+            }),
+            _ => Err(NotmuchError::ConfigError(
+                "Invalid config type for AgentMailSender".to_string(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl MailSender for AgentMailSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String> {
+        self.connection
+            .call(methods::MAIL_SEND, &SendParams { message })
+            .await
+    }
+
+    async fn reply(
+        &self,
+        original: &Message,
+        reply: ComposableMessage,
+        reply_all: bool,
+    ) -> Result<String> {
+        let params = ReplyParams {
+            original: original.clone(),
+            reply,
+            reply_all,
+        };
+        self.connection.call(methods::MAIL_REPLY, &params).await
+    }
+
+    async fn forward(&self, original: &Message, forward: ComposableMessage) -> Result<String> {
+        let params = ForwardParams {
+            original: original.clone(),
+            forward,
+        };
+        self.connection.call(methods::MAIL_FORWARD, &params).await
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        self.connection
+            .call(methods::MAIL_TEST_CONNECTION, &())
+            .await
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        self.connection
+            .call(methods::MAIL_GET_FROM_ADDRESS, &())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_mail_sender_invalid_config() {
+        let config = MailSenderConfig::Local {
+            msmtp_path: None,
+            config_path: None,
+            account: None,
+        };
+        assert!(AgentMailSender::new(config).is_err());
+    }
+}
@@ -1,11 +1,15 @@
 //! Configuration types for mail sender clients.
 
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::{NotmuchError, Result};
 
 /// Configuration for creating a mail sender client.
 ///
-/// This enum determines whether to use local msmtp execution or
-/// remote execution via SSH.
+/// This enum determines whether to use local msmtp execution, remote
+/// execution via SSH, direct SMTP submission, or submission through a
+/// `whynot-agent` daemon.
 #[derive(Debug, Clone)]
 pub enum MailSenderConfig {
     /// Configuration for local msmtp execution.
@@ -14,6 +18,8 @@ pub enum MailSenderConfig {
         msmtp_path: Option<PathBuf>,
         /// Path to msmtp configuration file (defaults to ~/.msmtprc).
         config_path: Option<PathBuf>,
+        /// The msmtp account to send through (`-a`), instead of the default.
+        account: Option<String>,
     },
     /// Configuration for remote msmtp execution via SSH.
     Remote {
@@ -29,7 +35,79 @@ pub enum MailSenderConfig {
         msmtp_path: Option<PathBuf>,
         /// Path to msmtp configuration file on the remote host.
         config_path: Option<PathBuf>,
+        /// The msmtp account to send through (`-a`), instead of the default.
+        account: Option<String>,
+    },
+    /// Configuration for sending through a `whynot-agent` daemon.
+    Agent {
+        /// Agent address: `host:port`, `tcp:host:port`, `unix:/path` or an
+        /// absolute socket path.
+        endpoint: String,
+        /// Shared token the agent was started with.
+        token: String,
     },
+    /// Configuration for direct SMTP submission.
+    Smtp {
+        /// The submission server to connect to.
+        host: String,
+        /// The server port (defaults to 587 for STARTTLS, 465 for implicit
+        /// TLS and 25 without TLS).
+        port: Option<u16>,
+        /// How the connection is secured.
+        tls: SmtpTls,
+        /// Username for SMTP AUTH; no authentication is attempted without it.
+        user: Option<String>,
+        /// Shell command whose first output line is the password.
+        password_command: Option<String>,
+        /// Environment variable holding the password.
+        password_env: Option<String>,
+        /// Default From address, used when a message does not set one.
+        from: Option<String>,
+        /// Extra PEM certificate to trust, e.g. for a self-signed server.
+        ca_cert: Option<PathBuf>,
+        /// Allow authenticating when `tls` is `None`, which sends the
+        /// password in clear text.
+        allow_insecure_auth: bool,
+    },
+}
+
+/// Transport security for SMTP submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SmtpTls {
+    /// Connect in plain text and upgrade with STARTTLS (required).
+    #[default]
+    Starttls,
+    /// Negotiate TLS immediately after connecting (SMTPS).
+    Tls,
+    /// Never use TLS. Only suitable for local relays.
+    None,
+}
+
+impl SmtpTls {
+    /// The default port for this mode.
+    pub fn default_port(self) -> u16 {
+        match self {
+            SmtpTls::Starttls => 587,
+            SmtpTls::Tls => 465,
+            SmtpTls::None => 25,
+        }
+    }
+}
+
+impl FromStr for SmtpTls {
+    type Err = NotmuchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "starttls" => Ok(SmtpTls::Starttls),
+            "tls" | "ssl" | "smtps" => Ok(SmtpTls::Tls),
+            "none" | "plain" => Ok(SmtpTls::None),
+            other => Err(NotmuchError::ConfigError(format!(
+                "Unknown SMTP TLS mode '{}' (expected starttls, tls or none)",
+                other
+            ))),
+        }
+    }
 }
 
 impl MailSenderConfig {
@@ -38,10 +116,12 @@ impl MailSenderConfig {
     /// Recognizes:
     /// - `MSMTP_PATH` - Path to msmtp binary
     /// - `MSMTP_CONFIG` - Path to msmtp config file
+    /// - `MSMTP_ACCOUNT` - msmtp account to send through
     pub fn from_env_local() -> Self {
         Self::Local {
             msmtp_path: std::env::var("MSMTP_PATH").ok().map(PathBuf::from),
             config_path: std::env::var("MSMTP_CONFIG").ok().map(PathBuf::from),
+            account: std::env::var("MSMTP_ACCOUNT").ok(),
         }
     }
 
@@ -54,6 +134,7 @@ impl MailSenderConfig {
     /// - `MSMTP_IDENTITY_FILE` - SSH identity file
     /// - `MSMTP_PATH` - Path to msmtp on remote
     /// - `MSMTP_CONFIG` - Path to config on remote
+    /// - `MSMTP_ACCOUNT` - msmtp account to send through
     ///
     /// Returns None if MSMTP_HOST is not set.
     pub fn from_env_remote() -> Option<Self> {
@@ -67,6 +148,7 @@ impl MailSenderConfig {
             identity_file: std::env::var("MSMTP_IDENTITY_FILE").ok().map(PathBuf::from),
             msmtp_path: std::env::var("MSMTP_PATH").ok().map(PathBuf::from),
             config_path: std::env::var("MSMTP_CONFIG").ok().map(PathBuf::from),
+            account: std::env::var("MSMTP_ACCOUNT").ok(),
         })
     }
 
@@ -0,0 +1,129 @@
+//! Mail sender that routes messages by the identity they are sent as.
+
+use async_trait::async_trait;
+
+use crate::common::Mailbox;
+use crate::error::Result;
+use crate::mail_sender::{ComposableMessage, MailSender};
+use crate::thread::Message;
+
+/// Where mail from one sending identity goes.
+struct Route {
+    address: String,
+    sender: Option<Box<dyn MailSender>>,
+    sent_folder: Option<String>,
+}
+
+/// A mail sender that picks the sender and Sent folder for each message
+/// from its From address.
+///
+/// Identities can send through their own msmtp account or backend and file
+/// their sent mail in their own folder. Messages from any other address go
+/// through the default sender.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use whynot::mail_sender::{create_mail_sender, IdentityMailSender, MailSenderConfig};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let default = create_mail_sender(MailSenderConfig::Local {
+///     msmtp_path: None,
+///     config_path: None,
+///     account: None,
+/// })?;
+/// let work = create_mail_sender(MailSenderConfig::Local {
+///     msmtp_path: None,
+///     config_path: None,
+///     account: Some("work".to_string()),
+/// })?;
+/// let sender = IdentityMailSender::new(default).route(
+///     "alice@work.example.com",
+///     Some(work),
+///     Some("Work/Sent".to_string()),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct IdentityMailSender {
+    default: Box<dyn MailSender>,
+    routes: Vec<Route>,
+}
+
+impl IdentityMailSender {
+    pub fn new(default: Box<dyn MailSender>) -> Self {
+        IdentityMailSender {
+            default,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Send mail from `address` through `sender`, or the default sender
+    /// without one, and file it in `sent_folder` when given.
+    pub fn route(
+        mut self,
+        address: impl Into<String>,
+        sender: Option<Box<dyn MailSender>>,
+        sent_folder: Option<String>,
+    ) -> Self {
+        self.routes.push(Route {
+            address: address.into(),
+            sender,
+            sent_folder,
+        });
+        self
+    }
+
+    fn route_for(&self, from: Option<&str>) -> Option<&Route> {
+        let from = Mailbox::parse(from?)?;
+        self.routes
+            .iter()
+            .find(|route| from.has_address(&route.address))
+    }
+
+    fn sender_for(&self, from: Option<&str>) -> &dyn MailSender {
+        self.route_for(from)
+            .and_then(|route| route.sender.as_deref())
+            .unwrap_or(self.default.as_ref())
+    }
+}
+
+#[async_trait]
+impl MailSender for IdentityMailSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String> {
+        self.sender_for(message.from.as_deref()).send(message).await
+    }
+
+    async fn reply(
+        &self,
+        original: &Message,
+        reply: ComposableMessage,
+        reply_all: bool,
+    ) -> Result<String> {
+        self.sender_for(reply.from.as_deref())
+            .reply(original, reply, reply_all)
+            .await
+    }
+
+    async fn forward(&self, original: &Message, forward: ComposableMessage) -> Result<String> {
+        self.sender_for(forward.from.as_deref())
+            .forward(original, forward)
+            .await
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        self.default.test_connection().await?;
+        for sender in self.routes.iter().filter_map(|route| route.sender.as_ref()) {
+            sender.test_connection().await?;
+        }
+        Ok(())
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        self.default.get_from_address().await
+    }
+
+    fn sent_folder_for(&self, message: &ComposableMessage) -> Option<String> {
+        self.route_for(message.from.as_deref())
+            .and_then(|route| route.sent_folder.clone())
+    }
+}
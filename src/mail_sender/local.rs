@@ -6,7 +6,7 @@ use std::process::Stdio;
 use tokio::process::Command;
 
 use crate::error::{NotmuchError, Result};
-use crate::mail_sender::{ComposableMessage, MailSender, MailSenderConfig};
+use crate::mail_sender::{ComposableMessage, MailSender, MailSenderConfig, smtp_reply_code};
 use crate::thread::Message;
 
 /// A mail sender that executes msmtp commands locally.
@@ -25,6 +25,7 @@ use crate::thread::Message;
 /// let config = MailSenderConfig::Local {
 ///     msmtp_path: None,
 ///     config_path: None,
+///     account: None,
 /// };
 /// let client = LocalMsmtpClient::new(config)?;
 ///
@@ -32,6 +33,7 @@ use crate::thread::Message;
 /// let config = MailSenderConfig::Local {
 ///     msmtp_path: Some(PathBuf::from("/usr/local/bin/msmtp")),
 ///     config_path: Some(PathBuf::from("/home/user/.msmtprc")),
+///     account: Some("work".to_string()),
 /// };
 /// let client = LocalMsmtpClient::new(config)?;
 /// # Ok(())
@@ -40,6 +42,7 @@ use crate::thread::Message;
 pub struct LocalMsmtpClient {
     msmtp_path: PathBuf,
     config_path: Option<PathBuf>,
+    account: Option<String>,
 }
 
 impl LocalMsmtpClient {
@@ -48,9 +51,11 @@ impl LocalMsmtpClient {
             MailSenderConfig::Local {
                 msmtp_path,
                 config_path,
+                account,
             } => Ok(LocalMsmtpClient {
                 msmtp_path: msmtp_path.unwrap_or_else(|| PathBuf::from("msmtp")),
                 config_path,
+                account,
             }),
             _ => Err(NotmuchError::ConfigError(
                 "Invalid config type for LocalMsmtpClient".to_string(),
@@ -58,13 +63,22 @@ impl LocalMsmtpClient {
         }
     }
 
-    async fn execute_msmtp_command(&self, args: &[&str], message_data: &[u8]) -> Result<String> {
+    /// An msmtp command with the configured config file and account.
+    fn msmtp_command(&self) -> Command {
         let mut cmd = Command::new(&self.msmtp_path);
 
         // Add config file if specified
         if let Some(config_path) = &self.config_path {
             cmd.arg("--file").arg(config_path);
         }
+        if let Some(account) = &self.account {
+            cmd.arg("-a").arg(account);
+        }
+        cmd
+    }
+
+    async fn execute_msmtp_command(&self, args: &[&str], message_data: &[u8]) -> Result<String> {
+        let mut cmd = self.msmtp_command();
 
         let mut child = cmd
             .args(args)
@@ -83,11 +97,11 @@ impl LocalMsmtpClient {
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(NotmuchError::CommandFailed(format!(
-                "msmtp {} failed: {}",
-                args.join(" "),
-                stderr
-            )));
+            let message = format!("msmtp {} failed: {}", args.join(" "), stderr);
+            return Err(match smtp_reply_code(&stderr) {
+                Some(code) => NotmuchError::SmtpRejected { code, message },
+                None => NotmuchError::CommandFailed(message),
+            });
         }
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
@@ -150,13 +164,8 @@ impl MailSender for LocalMsmtpClient {
 
     async fn test_connection(&self) -> Result<()> {
         // Test msmtp configuration with --serverinfo flag
-        let mut cmd = Command::new(&self.msmtp_path);
-
-        if let Some(config_path) = &self.config_path {
-            cmd.arg("--file").arg(config_path);
-        }
-
-        let output = cmd
+        let output = self
+            .msmtp_command()
             .arg("--serverinfo")
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -176,13 +185,8 @@ impl MailSender for LocalMsmtpClient {
 
     async fn get_from_address(&self) -> Result<String> {
         // Get the default from address from msmtp config
-        let mut cmd = Command::new(&self.msmtp_path);
-
-        if let Some(config_path) = &self.config_path {
-            cmd.arg("--file").arg(config_path);
-        }
-
-        let output = cmd
+        let output = self
+            .msmtp_command()
             .arg("--print-config")
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -223,6 +227,7 @@ mod tests {
         let config = MailSenderConfig::Local {
             msmtp_path: Some(PathBuf::from("/usr/bin/msmtp")),
             config_path: Some(PathBuf::from("/home/user/.msmtprc")),
+            account: Some("work".to_string()),
         };
 
         let client = LocalMsmtpClient::new(config);
@@ -234,6 +239,7 @@ mod tests {
             client.config_path,
             Some(PathBuf::from("/home/user/.msmtprc"))
         );
+        assert_eq!(client.account.as_deref(), Some("work"));
     }
 
     #[test]
@@ -241,6 +247,7 @@ mod tests {
         let config = MailSenderConfig::Local {
             msmtp_path: None,
             config_path: None,
+            account: None,
         };
 
         let client = LocalMsmtpClient::new(config);
@@ -251,6 +258,42 @@ mod tests {
         assert_eq!(client.config_path, None);
     }
 
+    #[tokio::test]
+    async fn test_rejection_keeps_smtp_reply_code() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let msmtp = dir.path().join("msmtp");
+        std::fs::write(
+            &msmtp,
+            "#!/bin/sh\ncat > /dev/null\n\
+             echo 'msmtp: recipient address bob@example.org not accepted by the server' >&2\n\
+             echo 'msmtp: server message: 550 5.1.1 User unknown' >&2\n\
+             exit 69\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&msmtp, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let client = LocalMsmtpClient::new(MailSenderConfig::Local {
+            msmtp_path: Some(msmtp),
+            config_path: None,
+            account: None,
+        })
+        .unwrap();
+        let message = ComposableMessage::builder()
+            .from("alice@example.com".to_string())
+            .to("bob@example.org".to_string())
+            .subject("Hello".to_string())
+            .body("Hi".to_string())
+            .build()
+            .unwrap();
+
+        match client.send(message).await {
+            Err(NotmuchError::SmtpRejected { code, .. }) => assert_eq!(code, 550),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_local_msmtp_client_invalid_config() {
         let config = MailSenderConfig::Remote {
@@ -260,6 +303,7 @@ mod tests {
             identity_file: None,
             msmtp_path: None,
             config_path: None,
+            account: None,
         };
 
         let result = LocalMsmtpClient::new(config);
@@ -1,8 +1,12 @@
 //! Message composition types for sending email.
 
 use crate::body::BodyPart;
+use crate::client::NotmuchClient;
+use crate::common::{Mailbox, format_parameter};
 use crate::error::Result;
+use crate::mail_sender::pgp::PgpOptions;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
@@ -11,7 +15,7 @@ use uuid::Uuid;
 /// This struct represents an email message that can be sent. It includes
 /// all necessary headers and body content. Use `MessageBuilder` to construct
 /// instances of this type.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComposableMessage {
     /// The Message-ID header (generated if not provided).
     pub message_id: String,
@@ -39,19 +43,441 @@ pub struct ComposableMessage {
     pub html_body: Option<String>,
     /// Attachments to include.
     pub attachments: Vec<Attachment>,
+    /// iCalendar alternative, for meeting invitations and replies.
+    #[serde(default)]
+    pub calendar: Option<CalendarPart>,
+    /// OpenPGP signed or encrypted entity sent in place of the body,
+    /// alternatives and attachments; see [`super::pgp::protect`].
+    #[serde(default)]
+    pub pgp_body: Option<String>,
+    /// How `pgp_body` protects the message, to edit it again as it was.
+    #[serde(default)]
+    pub pgp: PgpOptions,
+}
+
+/// An iCalendar object sent as a `text/calendar` alternative to the body,
+/// as iMIP (RFC 6047) expects.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalendarPart {
+    /// The iTIP method, e.g. `REQUEST` or `REPLY`; must match the `METHOD`
+    /// property of the calendar.
+    pub method: String,
+    /// The iCalendar text.
+    pub data: String,
 }
 
 /// An attachment to be included in an email message.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Attachment {
     /// The filename for the attachment.
     pub filename: String,
     /// The MIME content type.
     pub content_type: String,
-    /// The attachment data.
+    /// The attachment data (base64 encoded when serialized).
+    #[serde(with = "base64_data")]
     pub data: Vec<u8>,
 }
 
+impl Attachment {
+    /// Attach a whole message, such as one being forwarded, given its raw
+    /// RFC 822 source.
+    ///
+    /// The attachment is named after the message subject.
+    pub fn message(raw: Vec<u8>, subject: Option<&str>) -> Self {
+        let name: String = subject
+            .unwrap_or("")
+            .chars()
+            .filter(|c| !c.is_control() && !matches!(c, '/' | '\\'))
+            .collect();
+        let name = name.trim();
+        Attachment {
+            filename: format!("{}.eml", if name.is_empty() { "message" } else { name }),
+            content_type: "message/rfc822".to_string(),
+            data: raw,
+        }
+    }
+
+    /// Attach the file at `path`, named after it, with a content type
+    /// guessed by [`guess_content_type`].
+    pub async fn from_file(path: &std::path::Path) -> Result<Self> {
+        let data = tokio::fs::read(path).await?;
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "attachment".to_string());
+        Ok(Attachment {
+            content_type: guess_content_type(&filename, &data),
+            filename,
+            data,
+        })
+    }
+
+    /// Write this attachment as a part of a multipart body.
+    fn push_part(&self, message: &mut String, boundary: &str) {
+        message.push_str(&format!("--{}\r\n", boundary));
+        message.push_str(&format!("Content-Type: {}\r\n", self.content_type));
+        message.push_str(&format!(
+            "Content-Disposition: attachment; {}\r\n",
+            format_parameter("filename", &self.filename)
+        ));
+
+        // Encoded message/rfc822 parts are not allowed (RFC 2046 5.2.1), so
+        // messages are included as they are unless they are not text at all
+        let is_message = self.content_type.eq_ignore_ascii_case("message/rfc822");
+        match std::str::from_utf8(&self.data) {
+            Ok(text) if is_message => {
+                message.push_str("Content-Transfer-Encoding: 8bit\r\n");
+                message.push_str("\r\n");
+                for line in text.lines() {
+                    message.push_str(line);
+                    message.push_str("\r\n");
+                }
+            }
+            _ => {
+                message.push_str("Content-Transfer-Encoding: base64\r\n");
+                message.push_str("\r\n");
+                push_base64(message, &self.data);
+            }
+        }
+    }
+}
+
+/// Write `data` base64 encoded, in lines of 76 characters as MIME requires.
+fn push_base64(message: &mut String, data: &[u8]) {
+    let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data);
+    for line in encoded.as_bytes().chunks(76) {
+        message.push_str(std::str::from_utf8(line).unwrap_or_default());
+        message.push_str("\r\n");
+    }
+}
+
+/// Write a text part's Content-Type, Content-Transfer-Encoding and body.
+///
+/// Plain text is sent as format=flowed (RFC 3676). ASCII in short lines
+/// without trailing spaces goes as it is; anything else is quoted-printable,
+/// or base64 where that is shorter, as it is for most non-Latin scripts.
+/// Either way trailing spaces, which mark flowed soft breaks, survive
+/// transport and signatures over the part keep verifying.
+fn push_text(message: &mut String, subtype: &str, text: &str) {
+    let flowed;
+    let text = if subtype == "plain" {
+        message.push_str("Content-Type: text/plain; charset=utf-8; format=flowed\r\n");
+        flowed = crate::flowed::encode(text);
+        flowed.as_str()
+    } else {
+        message.push_str(&format!(
+            "Content-Type: text/{}; charset=utf-8\r\n",
+            subtype
+        ));
+        text
+    };
+
+    let plain = text
+        .lines()
+        .all(|line| line.is_ascii() && line.len() <= 78 && !line.ends_with([' ', '\t']));
+    if plain {
+        message.push_str("Content-Transfer-Encoding: 7bit\r\n");
+        message.push_str("\r\n");
+        for line in text.lines() {
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        return;
+    }
+
+    let mut quoted = String::new();
+    push_quoted_printable(&mut quoted, text);
+    let base64_len = text.len().div_ceil(3) * 4 / 76 * 78 + 78;
+    if quoted.len() <= base64_len {
+        message.push_str("Content-Transfer-Encoding: quoted-printable\r\n");
+        message.push_str("\r\n");
+        message.push_str(&quoted);
+    } else {
+        // Base64 carries line breaks as they are, so they are made CRLF first
+        let crlf: String = text.lines().flat_map(|line| [line, "\r\n"]).collect();
+        message.push_str("Content-Transfer-Encoding: base64\r\n");
+        message.push_str("\r\n");
+        push_base64(message, crlf.as_bytes());
+    }
+}
+
+/// Write `text` quoted-printable encoded (RFC 2045 6.7), keeping its line
+/// breaks and soft-breaking lines longer than 76 characters.
+fn push_quoted_printable(message: &mut String, text: &str) {
+    for line in text.lines() {
+        let bytes = line.as_bytes();
+        let mut width = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            // Spaces before a line break would be lost, so they are encoded
+            let literal = match byte {
+                b' ' | b'\t' => i + 1 < bytes.len(),
+                b'=' => false,
+                _ => byte.is_ascii_graphic(),
+            };
+            let encoded_width = if literal { 1 } else { 3 };
+            if width + encoded_width > 75 {
+                message.push_str("=\r\n");
+                width = 0;
+            }
+            if literal {
+                message.push(byte as char);
+            } else {
+                message.push_str(&format!("={:02X}", byte));
+            }
+            width += encoded_width;
+        }
+        message.push_str("\r\n");
+    }
+}
+
+/// Default limit on the total size of a message's attachments, which most
+/// mail providers accept.
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: usize = 25 * 1024 * 1024;
+
+/// Limits on what can be attached to an outgoing message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttachmentLimits {
+    /// Largest total size of all attachments, in bytes
+    pub max_total_size: usize,
+}
+
+impl Default for AttachmentLimits {
+    fn default() -> Self {
+        AttachmentLimits {
+            max_total_size: DEFAULT_MAX_ATTACHMENT_SIZE,
+        }
+    }
+}
+
+impl AttachmentLimits {
+    /// Check that `attachments` fit within the limits.
+    pub fn check(&self, attachments: &[Attachment]) -> Result<()> {
+        let total: usize = attachments.iter().map(|a| a.data.len()).sum();
+        if total > self.max_total_size {
+            return Err(crate::error::Error::InvalidInput(format!(
+                "Attachments are {}, more than the limit of {}",
+                format_size(total),
+                format_size(self.max_total_size)
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A byte count for people, e.g. `1.5 MB`.
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: &[&str] = &["bytes", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Content types of common file extensions.
+const EXTENSION_TYPES: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("text", "text/plain"),
+    ("log", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("ics", "text/calendar"),
+    ("vcf", "text/vcard"),
+    ("patch", "text/x-diff"),
+    ("diff", "text/x-diff"),
+    ("xml", "application/xml"),
+    ("json", "application/json"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("7z", "application/x-7z-compressed"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    ("eml", "message/rfc822"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+/// Content types recognized by the bytes a file starts with.
+const MAGIC_TYPES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", "application/msword"),
+    (b"BEGIN:VCALENDAR", "text/calendar"),
+    (b"BEGIN:VCARD", "text/vcard"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+];
+
+/// Guess the content type of a file to attach.
+///
+/// The extension decides when it is a known one, since several formats
+/// share their leading bytes (DOCX and ODT files are ZIP archives).
+/// Otherwise the leading bytes are recognized, and anything left is
+/// `text/plain` if it is UTF-8 without NUL bytes.
+pub fn guess_content_type(filename: &str, data: &[u8]) -> String {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    if let Some(content_type) = extension.and_then(|extension| {
+        EXTENSION_TYPES
+            .iter()
+            .find(|(known, _)| *known == extension)
+            .map(|(_, content_type)| *content_type)
+    }) {
+        return content_type.to_string();
+    }
+
+    if let Some((_, content_type)) = MAGIC_TYPES
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+    {
+        return content_type.to_string();
+    }
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        return "image/webp".to_string();
+    }
+    if !data.contains(&0) && std::str::from_utf8(data).is_ok() {
+        return "text/plain".to_string();
+    }
+    "application/octet-stream".to_string()
+}
+
+/// How the original message is included when forwarding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForwardMode {
+    /// The text is quoted in the body and the original attachments are
+    /// attached again
+    #[default]
+    Inline,
+    /// The whole original message is attached as `message/rfc822`
+    Attachment,
+}
+
+impl ForwardMode {
+    /// Parse a mode as used in forms: `inline` or `attachment`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "inline" => Some(ForwardMode::Inline),
+            "attachment" => Some(ForwardMode::Attachment),
+            _ => None,
+        }
+    }
+
+    /// The form value of this mode.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForwardMode::Inline => "inline",
+            ForwardMode::Attachment => "attachment",
+        }
+    }
+}
+
+/// Fetch what a forward of `original` carries along: its attachments,
+/// re-read through [`NotmuchClient::part`] since `show` leaves out their
+/// content, or the whole message as an attachment.
+pub async fn forwarded_attachments(
+    client: &dyn NotmuchClient,
+    original: &crate::thread::Message,
+    mode: ForwardMode,
+) -> Result<Vec<Attachment>> {
+    let message_spec = format!("id:{}", original.id);
+    match mode {
+        ForwardMode::Inline => {
+            let mut attachments = Vec::new();
+            for part in original.body.iter().flat_map(BodyPart::attachments) {
+                attachments.push(original_attachment(client, &original.id, part).await?);
+            }
+            Ok(attachments)
+        }
+        ForwardMode::Attachment => {
+            // Part 0 is the whole message
+            let raw = client.part(&message_spec, 0).await?;
+            Ok(vec![Attachment::message(
+                raw,
+                original.headers.subject.as_deref(),
+            )])
+        }
+    }
+}
+
+/// Fetch one attachment `part` of the message with ID `message_id` to
+/// attach it again, e.g. to a reply.
+pub async fn original_attachment(
+    client: &dyn NotmuchClient,
+    message_id: &str,
+    part: &BodyPart,
+) -> Result<Attachment> {
+    let data = client.part(&format!("id:{}", message_id), part.id).await?;
+    Ok(Attachment {
+        filename: part
+            .filename
+            .clone()
+            .unwrap_or_else(|| format!("attachment_{}", part.id)),
+        content_type: part.content_type.clone(),
+        data,
+    })
+}
+
+mod base64_data {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
 impl ComposableMessage {
     /// Create a new message builder.
     pub fn builder() -> MessageBuilder {
@@ -100,21 +526,42 @@ impl ComposableMessage {
             message.push_str(&format!("{}: {}\r\n", key, value));
         }
 
+        match &self.pgp_body {
+            Some(entity) => {
+                message.push_str("MIME-Version: 1.0\r\n");
+                message.push_str(entity);
+            }
+            None => {
+                if self.html_body.is_some()
+                    || self.calendar.is_some()
+                    || !self.attachments.is_empty()
+                {
+                    message.push_str("MIME-Version: 1.0\r\n");
+                }
+                message.push_str(&self.body_entity());
+            }
+        }
+
+        Ok(message.into_bytes())
+    }
+
+    /// The body, alternatives and attachments as one MIME entity, starting
+    /// with its Content-Type header; what OpenPGP signs or encrypts.
+    pub(super) fn body_entity(&self) -> String {
+        let mut message = String::new();
+
         // Determine MIME structure based on content
-        let has_html = self.html_body.is_some();
+        let has_alternatives = self.html_body.is_some() || self.calendar.is_some();
         let has_attachments = !self.attachments.is_empty();
 
-        match (has_html, has_attachments) {
+        match (has_alternatives, has_attachments) {
             (false, false) => {
                 // Case 1: Plain text only - no multipart needed
-                message.push_str("Content-Type: text/plain; charset=utf-8\r\n");
-                message.push_str("\r\n");
-                message.push_str(&self.body);
+                push_text(&mut message, "plain", &self.body);
             }
             (true, false) => {
                 // Case 2: Text + HTML - use multipart/alternative
-                let boundary = format!("boundary_{}", Uuid::new_v4());
-                message.push_str("MIME-Version: 1.0\r\n");
+                let boundary = self.boundary("alternative");
                 message.push_str(&format!(
                     "Content-Type: multipart/alternative; boundary=\"{}\"\r\n",
                     boundary
@@ -123,28 +570,15 @@ impl ComposableMessage {
 
                 // Plain text part
                 message.push_str(&format!("--{}\r\n", boundary));
-                message.push_str("Content-Type: text/plain; charset=utf-8\r\n");
-                message.push_str("Content-Transfer-Encoding: quoted-printable\r\n");
-                message.push_str("\r\n");
-                message.push_str(&self.body);
-                message.push_str("\r\n");
+                push_text(&mut message, "plain", &self.body);
 
-                // HTML part
-                if let Some(html) = &self.html_body {
-                    message.push_str(&format!("--{}\r\n", boundary));
-                    message.push_str("Content-Type: text/html; charset=utf-8\r\n");
-                    message.push_str("Content-Transfer-Encoding: quoted-printable\r\n");
-                    message.push_str("\r\n");
-                    message.push_str(html);
-                    message.push_str("\r\n");
-                }
+                self.push_alternative_parts(&mut message, &boundary);
 
                 message.push_str(&format!("--{}--\r\n", boundary));
             }
             (false, true) => {
                 // Case 3: Text + attachments - use multipart/mixed
-                let boundary = format!("boundary_{}", Uuid::new_v4());
-                message.push_str("MIME-Version: 1.0\r\n");
+                let boundary = self.boundary("mixed");
                 message.push_str(&format!(
                     "Content-Type: multipart/mixed; boundary=\"{}\"\r\n",
                     boundary
@@ -153,37 +587,20 @@ impl ComposableMessage {
 
                 // Plain text part
                 message.push_str(&format!("--{}\r\n", boundary));
-                message.push_str("Content-Type: text/plain; charset=utf-8\r\n");
-                message.push_str("Content-Transfer-Encoding: quoted-printable\r\n");
-                message.push_str("\r\n");
-                message.push_str(&self.body);
-                message.push_str("\r\n");
+                push_text(&mut message, "plain", &self.body);
 
                 // Attachments
                 for attachment in &self.attachments {
-                    message.push_str(&format!("--{}\r\n", boundary));
-                    message.push_str(&format!("Content-Type: {}\r\n", attachment.content_type));
-                    message.push_str(&format!(
-                        "Content-Disposition: attachment; filename=\"{}\"\r\n",
-                        attachment.filename
-                    ));
-                    message.push_str("Content-Transfer-Encoding: base64\r\n");
-                    message.push_str("\r\n");
-                    message.push_str(&base64::Engine::encode(
-                        &base64::engine::general_purpose::STANDARD,
-                        &attachment.data,
-                    ));
-                    message.push_str("\r\n");
+                    attachment.push_part(&mut message, &boundary);
                 }
 
                 message.push_str(&format!("--{}--\r\n", boundary));
             }
             (true, true) => {
                 // Case 4: Text + HTML + attachments - nested multipart structure
-                let outer_boundary = format!("boundary_{}", Uuid::new_v4());
-                let inner_boundary = format!("boundary_{}", Uuid::new_v4());
-                
-                message.push_str("MIME-Version: 1.0\r\n");
+                let outer_boundary = self.boundary("mixed");
+                let inner_boundary = self.boundary("alternative");
+
                 message.push_str(&format!(
                     "Content-Type: multipart/mixed; boundary=\"{}\"\r\n",
                     outer_boundary
@@ -200,40 +617,16 @@ impl ComposableMessage {
 
                 // Plain text part
                 message.push_str(&format!("--{}\r\n", inner_boundary));
-                message.push_str("Content-Type: text/plain; charset=utf-8\r\n");
-                message.push_str("Content-Transfer-Encoding: quoted-printable\r\n");
-                message.push_str("\r\n");
-                message.push_str(&self.body);
-                message.push_str("\r\n");
+                push_text(&mut message, "plain", &self.body);
 
-                // HTML part
-                if let Some(html) = &self.html_body {
-                    message.push_str(&format!("--{}\r\n", inner_boundary));
-                    message.push_str("Content-Type: text/html; charset=utf-8\r\n");
-                    message.push_str("Content-Transfer-Encoding: quoted-printable\r\n");
-                    message.push_str("\r\n");
-                    message.push_str(html);
-                    message.push_str("\r\n");
-                }
+                self.push_alternative_parts(&mut message, &inner_boundary);
 
                 // Close inner multipart/alternative
                 message.push_str(&format!("--{}--\r\n", inner_boundary));
 
                 // Attachments at outer level
                 for attachment in &self.attachments {
-                    message.push_str(&format!("--{}\r\n", outer_boundary));
-                    message.push_str(&format!("Content-Type: {}\r\n", attachment.content_type));
-                    message.push_str(&format!(
-                        "Content-Disposition: attachment; filename=\"{}\"\r\n",
-                        attachment.filename
-                    ));
-                    message.push_str("Content-Transfer-Encoding: base64\r\n");
-                    message.push_str("\r\n");
-                    message.push_str(&base64::Engine::encode(
-                        &base64::engine::general_purpose::STANDARD,
-                        &attachment.data,
-                    ));
-                    message.push_str("\r\n");
+                    attachment.push_part(&mut message, &outer_boundary);
                 }
 
                 // Close outer multipart/mixed
@@ -241,73 +634,106 @@ impl ComposableMessage {
             }
         }
 
-        Ok(message.into_bytes())
+        message
     }
 
-    /// Create a reply builder from an original message.
-    ///
-    /// This sets up proper headers for replying including In-Reply-To
-    /// and References headers, and quotes the original message body.
-    pub fn reply_builder(original: &crate::thread::Message, reply_all: bool) -> MessageBuilder {
-        let mut builder = MessageBuilder::new();
-
-        // Set In-Reply-To to the original message ID
-        builder = builder.in_reply_to(original.id.clone());
+    /// A MIME boundary derived from the Message-ID, so that rendering the
+    /// same message twice gives identical bytes (e.g. for the Sent copy).
+    pub(super) fn boundary(&self, kind: &str) -> String {
+        use std::hash::{DefaultHasher, Hash, Hasher};
 
-        // Build References header
-        let mut references = vec![];
+        let half = |n: u8| {
+            let mut hasher = DefaultHasher::new();
+            (&self.message_id, kind, n).hash(&mut hasher);
+            hasher.finish()
+        };
+        format!("boundary_{}", Uuid::from_u64_pair(half(0), half(1)))
+    }
 
-        // Check if original has References header
-        if let Some(orig_refs) = original.headers.get("references") {
-            // Parse space-separated message IDs
-            references.extend(orig_refs.split_whitespace().map(|s| s.to_string()));
+    /// Write the HTML and calendar alternatives to the plain text body.
+    fn push_alternative_parts(&self, message: &mut String, boundary: &str) {
+        // HTML part
+        if let Some(html) = &self.html_body {
+            message.push_str(&format!("--{}\r\n", boundary));
+            push_text(message, "html", html);
         }
 
-        // Add the original message ID to references
-        references.push(original.id.clone());
-
-        for reference in references {
-            builder = builder.add_reference(reference);
+        // Calendar part, base64 encoded so its CRLF line endings and any
+        // non-ASCII text survive transport unchanged
+        if let Some(calendar) = &self.calendar {
+            message.push_str(&format!("--{}\r\n", boundary));
+            message.push_str(&format!(
+                "Content-Type: text/calendar; charset=utf-8; method={}\r\n",
+                calendar.method
+            ));
+            message.push_str("Content-Transfer-Encoding: base64\r\n");
+            message.push_str("\r\n");
+            push_base64(message, calendar.data.as_bytes());
         }
+    }
 
-        // Set subject with Re: prefix if not already present
-        let subject = match &original.headers.subject {
-            Some(subj) if subj.starts_with("Re: ") => subj.clone(),
-            Some(subj) => format!("Re: {}", subj),
-            None => "Re: (No subject)".to_string(),
-        };
-        builder = builder.subject(subject);
+    /// Create a reply builder from an original message.
+    ///
+    /// This sets up proper headers for replying including In-Reply-To
+    /// and References headers, and quotes the original message body.
+    pub fn reply_builder(original: &crate::thread::Message, reply_all: bool) -> MessageBuilder {
+        let mut builder = reply_base(original);
 
-        // Set To field
-        if reply_all {
-            // Reply to sender
-            builder = builder.to(original.headers.from.clone());
+        // Reply to the sender, or to where they asked replies to go
+        let mut recipients = original.headers.reply_recipients();
+        let mut cc = Vec::new();
 
-            // Add original To recipients (except ourselves if we can determine that)
-            if let Some(to) = &original.headers.to {
-                for addr in to.split(',').map(|s| s.trim()) {
-                    builder = builder.to(addr.to_string());
+        if reply_all {
+            // Add original To and Cc recipients, each address only once
+            for mailbox in original.headers.to_mailboxes() {
+                if !recipients.iter().any(|r| r.has_address(&mailbox.address)) {
+                    recipients.push(mailbox);
                 }
             }
-
-            // Add original Cc recipients
-            if let Some(cc_header) = original.headers.get("cc") {
-                for addr in cc_header.split(',').map(|s| s.trim()) {
-                    builder = builder.cc(addr.to_string());
+            for mailbox in original.headers.cc_mailboxes() {
+                let seen = recipients
+                    .iter()
+                    .chain(cc.iter())
+                    .any(|r: &Mailbox| r.has_address(&mailbox.address));
+                if !seen {
+                    cc.push(mailbox);
                 }
             }
-        } else {
-            // Just reply to sender
-            builder = builder.to(original.headers.from.clone());
         }
 
-        // Quote original message
-        let quoted_body = quote_message_body(original);
-        builder = builder.body(quoted_body);
+        if recipients.is_empty() {
+            // Unparseable From header; fall back to the raw value
+            builder = builder.to(original.headers.from.clone());
+        }
+        for mailbox in recipients {
+            builder = builder.to(mailbox.to_string());
+        }
+        for mailbox in cc {
+            builder = builder.cc(mailbox.to_string());
+        }
 
         builder
     }
 
+    /// Create a builder for replying to the mailing list a message was sent
+    /// through, rather than to its author.
+    ///
+    /// The reply goes to the `List-Post` address. Returns an error if the
+    /// message has no list headers or the list does not accept posts.
+    pub fn list_reply_builder(original: &crate::thread::Message) -> Result<MessageBuilder> {
+        let list = crate::list::ListInfo::from_headers(&original.headers).ok_or_else(|| {
+            crate::error::Error::InvalidInput("Message was not sent to a mailing list".to_string())
+        })?;
+        let address = list.post_address().ok_or_else(|| {
+            crate::error::Error::InvalidInput(format!(
+                "Mailing list {} does not accept posts",
+                list.label()
+            ))
+        })?;
+
+        Ok(reply_base(original).to(address.to_string()))
+    }
+
     /// Create a forward builder from an original message.
     ///
     /// This sets up the message for forwarding, including the original
@@ -323,12 +749,11 @@ impl ComposableMessage {
         };
         builder = builder.subject(subject);
 
-        // Include original message info in body
+        // Include original message info in body; the attachments are
+        // fetched separately, see `forwarded_attachments`
         let forward_body = format_forward_body(original);
         builder = builder.body(forward_body);
 
-        // TODO: Handle attachments from original message
-
         builder
     }
 }
@@ -349,6 +774,7 @@ pub struct MessageBuilder {
     body: Option<String>,
     html_body: Option<String>,
     attachments: Vec<Attachment>,
+    calendar: Option<CalendarPart>,
 }
 
 impl MessageBuilder {
@@ -435,27 +861,44 @@ impl MessageBuilder {
         self
     }
 
+    /// Set the iCalendar alternative, sent with the given iTIP method.
+    pub fn calendar(mut self, method: String, data: String) -> Self {
+        self.calendar = Some(CalendarPart { method, data });
+        self
+    }
+
     /// Build the ComposableMessage.
     ///
-    /// Returns an error if required fields are missing.
-    pub fn build(self) -> Result<ComposableMessage> {
-        // Generate Message-ID if not provided
-        let message_id = self
-            .message_id
-            .unwrap_or_else(|| format!("<{}@whynot>", Uuid::new_v4()));
-
-        // Ensure we have required fields
+    /// Returns an error if there are no recipients or one of them is not a
+    /// valid address; see [`parse_address_list`].
+    pub fn build(mut self) -> Result<ComposableMessage> {
+        // Check the recipients, leaving them one mailbox each
+        self.to = recipients("To", &self.to)?;
+        self.cc = recipients("Cc", &self.cc)?;
+        self.bcc = recipients("Bcc", &self.bcc)?;
         if self.to.is_empty() && self.cc.is_empty() && self.bcc.is_empty() {
             return Err(crate::error::Error::InvalidInput(
                 "Message must have at least one recipient".to_string(),
             ));
         }
 
+        Ok(self.build_draft())
+    }
+
+    /// Build the ComposableMessage without checking for recipients.
+    ///
+    /// Used for drafts, which may be saved before they are addressed.
+    pub fn build_draft(self) -> ComposableMessage {
+        // Generate Message-ID if not provided
+        let message_id = self
+            .message_id
+            .unwrap_or_else(|| format!("<{}@whynot>", Uuid::new_v4()));
+
         let subject = self.subject.unwrap_or_default();
         let body = self.body.unwrap_or_default();
         let date = self.date.unwrap_or_else(Utc::now);
 
-        Ok(ComposableMessage {
+        ComposableMessage {
             message_id,
             from: self.from,
             to: self.to,
@@ -469,31 +912,425 @@ impl MessageBuilder {
             body,
             html_body: self.html_body,
             attachments: self.attachments,
-        })
+            calendar: self.calendar,
+            pgp_body: None,
+            pgp: PgpOptions::default(),
+        }
     }
 }
 
-/// Quote the body of a message for replying.
-fn quote_message_body(message: &crate::thread::Message) -> String {
-    let mut quoted = String::new();
+/// Parse and check an address list as typed into a To, Cc or Bcc field.
+///
+/// Follows RFC 5322: display names may be atoms or quoted strings, groups
+/// such as `Team: a@example.com, b@example.com;` are flattened into their
+/// members, and comments are skipped, or taken as the name of an address
+/// without one (`bob@example.com (Bob)`). Internationalized domains are
+/// converted to their ASCII form. Empty entries between commas are ignored.
+///
+/// The error describes the first problem found.
+///
+/// # Examples
+///
+/// ```
+/// # use whynot::mail_sender::message::parse_address_list;
+/// let list = parse_address_list("\"Doe, John\" <john@example.com>, jane@bücher.example").unwrap();
+/// assert_eq!(list[0].name.as_deref(), Some("Doe, John"));
+/// assert_eq!(list[1].address, "jane@xn--bcher-kva.example");
+/// assert!(parse_address_list("john@").is_err());
+/// ```
+pub fn parse_address_list(input: &str) -> std::result::Result<Vec<Mailbox>, String> {
+    AddressParser::new(input).address_list()
+}
 
-    // Add attribution line
-    quoted.push_str(&format!(
-        "On {}, {} wrote:\n",
-        message.date_relative, message.headers.from
-    ));
+/// A word of a display name or local part: an atom, which may contain
+/// dots, or a quoted string.
+struct Word {
+    text: String,
+    quoted: bool,
+}
 
-    // Extract plain text body
-    let body_text = extract_plain_text_body(message);
+/// Recursive descent over the RFC 5322 address grammar.
+struct AddressParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// The last comment skipped, which may name the address before it
+    comment: Option<String>,
+}
+
+impl AddressParser {
+    fn new(input: &str) -> Self {
+        AddressParser {
+            chars: input.chars().collect(),
+            pos: 0,
+            comment: None,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn address_list(&mut self) -> std::result::Result<Vec<Mailbox>, String> {
+        let mut mailboxes = Vec::new();
+        loop {
+            self.skip_cfws()?;
+            match self.peek() {
+                None => return Ok(mailboxes),
+                Some(',') => self.pos += 1,
+                Some(_) => {
+                    self.address(&mut mailboxes, false)?;
+                    self.skip_cfws()?;
+                    match self.peek() {
+                        None => return Ok(mailboxes),
+                        Some(',') => self.pos += 1,
+                        Some(c) => return Err(self.missing_comma(&mailboxes, c)),
+                    }
+                }
+            }
+        }
+    }
+
+    fn missing_comma(&self, mailboxes: &[Mailbox], found: char) -> String {
+        match mailboxes.last() {
+            Some(last) => format!("missing comma after {}", last.address),
+            None => format!("unexpected \"{}\"", found),
+        }
+    }
+
+    /// A mailbox, or within an address list a group, adding its mailboxes
+    /// to `mailboxes`.
+    fn address(
+        &mut self,
+        mailboxes: &mut Vec<Mailbox>,
+        in_group: bool,
+    ) -> std::result::Result<(), String> {
+        let words = self.words()?;
+        match self.peek() {
+            Some('<') => {
+                self.pos += 1;
+                self.skip_cfws()?;
+                if self.peek() == Some('>') {
+                    return Err("empty address <>".to_string());
+                }
+                let local = self.words()?;
+                let address = self.addr_spec(local)?;
+                self.skip_cfws()?;
+                if self.peek() != Some('>') {
+                    return Err(format!("missing > after {}", address));
+                }
+                self.pos += 1;
+                mailboxes.push(Mailbox::new(display_name(&words), address));
+                Ok(())
+            }
+            Some(':') if !in_group => {
+                let name = display_name(&words)
+                    .ok_or_else(|| "group without a name before :".to_string())?;
+                self.pos += 1;
+                loop {
+                    self.skip_cfws()?;
+                    match self.peek() {
+                        None => return Err(format!("group {} is missing its closing ;", name)),
+                        Some(';') => {
+                            self.pos += 1;
+                            return Ok(());
+                        }
+                        Some(',') => self.pos += 1,
+                        Some(_) => {
+                            self.address(mailboxes, true)?;
+                            self.skip_cfws()?;
+                            match self.peek() {
+                                Some(',' | ';') => {}
+                                None => {
+                                    return Err(format!("group {} is missing its closing ;", name));
+                                }
+                                Some(c) => return Err(self.missing_comma(mailboxes, c)),
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                self.comment = None;
+                let address = self.addr_spec(words)?;
+                self.skip_cfws()?;
+                let name = self.comment.take();
+                mailboxes.push(Mailbox::new(name, address));
+                Ok(())
+            }
+        }
+    }
+
+    /// An address `local@domain` whose local part was read as `words`,
+    /// with the domain in its ASCII form.
+    fn addr_spec(&mut self, words: Vec<Word>) -> std::result::Result<String, String> {
+        let typed = words
+            .iter()
+            .map(|word| word.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.peek() != Some('@') {
+            return Err(match (words.is_empty(), self.peek()) {
+                (true, None) => "missing address".to_string(),
+                (true, Some(c)) => format!("unexpected \"{}\"", c),
+                (false, Some(c)) if !matches!(c, ',' | ';' | '>') => {
+                    format!("unexpected \"{}\" after {}", c, typed)
+                }
+                (false, _) => format!("{} is not an email address", typed),
+            });
+        }
+        self.pos += 1;
+        let local = match words.as_slice() {
+            [] => return Err("nothing before the @".to_string()),
+            [word] if word.quoted && is_dot_atom(&word.text) => word.text.clone(),
+            [word] if word.quoted => format!("\"{}\"", escape_quoted(&word.text)),
+            [word] if is_dot_atom(&word.text) => word.text.clone(),
+            _ => return Err(format!("{}@ is not a valid address", typed)),
+        };
 
-    // Quote each line
-    for line in body_text.lines() {
-        quoted.push_str("> ");
-        quoted.push_str(line);
-        quoted.push('\n');
+        self.skip_cfws()?;
+        let domain = if self.peek() == Some('[') {
+            self.domain_literal()?
+        } else {
+            let start = self.pos;
+            while self.peek().is_some_and(|c| is_atext(c) || c == '.') {
+                self.pos += 1;
+            }
+            let domain: String = self.chars[start..self.pos].iter().collect();
+            if domain.is_empty() {
+                return Err(format!("{}@ has no domain", local));
+            }
+            ascii_domain(&domain)
+                .ok_or_else(|| format!("{}@{} has an invalid domain", local, domain))?
+        };
+
+        let address = format!("{}@{}", local, domain);
+        if local.len() > 64 || address.len() > 254 {
+            return Err(format!("{} is too long", address));
+        }
+        Ok(address)
+    }
+
+    /// A domain literal such as `[192.0.2.1]`.
+    fn domain_literal(&mut self) -> std::result::Result<String, String> {
+        let start = self.pos;
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None => return Err("unclosed [ in domain".to_string()),
+                Some(']') => break,
+                Some('[' | '\\') => return Err("invalid domain literal".to_string()),
+                Some(_) => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        Ok(self.chars[start..self.pos]
+            .iter()
+            .filter(|c| !c.is_whitespace())
+            .collect())
+    }
+
+    /// The words up to the next special character, with the comments and
+    /// folding whitespace between them skipped.
+    fn words(&mut self) -> std::result::Result<Vec<Word>, String> {
+        let mut words = Vec::new();
+        loop {
+            self.skip_cfws()?;
+            match self.peek() {
+                Some('"') => words.push(Word {
+                    text: self.quoted_string()?,
+                    quoted: true,
+                }),
+                Some(c) if is_atext(c) || c == '.' => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| is_atext(c) || c == '.') {
+                        self.pos += 1;
+                    }
+                    words.push(Word {
+                        text: self.chars[start..self.pos].iter().collect(),
+                        quoted: false,
+                    });
+                }
+                _ => return Ok(words),
+            }
+        }
+    }
+
+    /// The contents of a quoted string, unescaped.
+    fn quoted_string(&mut self) -> std::result::Result<String, String> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(format!("unclosed quote in \"{}", text)),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    if let Some(c) = self.peek() {
+                        text.push(c);
+                        self.pos += 1;
+                    }
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Skip whitespace and comments, remembering the last comment.
+    fn skip_cfws(&mut self) -> std::result::Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('(') => {
+                    let comment = self.comment_text()?;
+                    let comment = comment.trim();
+                    if !comment.is_empty() {
+                        self.comment = Some(comment.to_string());
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// The text of a comment, which may nest.
+    fn comment_text(&mut self) -> std::result::Result<String, String> {
+        self.pos += 1;
+        let mut depth = 1;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err("unclosed ( in comment".to_string());
+            };
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(c) = self.peek() {
+                        text.push(c);
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(text);
+                    }
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+    }
+}
+
+/// Characters allowed in an atom; RFC 6532 adds any non-ASCII character.
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c) || !c.is_ascii()
+}
+
+/// Whether `text` is a dot-atom: atoms joined by single dots.
+fn is_dot_atom(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+fn escape_quoted(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A display name from its words, with encoded-words decoded.
+fn display_name(words: &[Word]) -> Option<String> {
+    let name = words
+        .iter()
+        .map(|word| word.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!name.trim().is_empty()).then(|| crate::common::decode_encoded_words(&name))
+}
+
+/// `domain` with internationalized labels in their ASCII form, if it is a
+/// valid host name.
+fn ascii_domain(domain: &str) -> Option<String> {
+    let ascii = idna::domain_to_ascii(domain).ok()?;
+    let valid = ascii.len() <= 253
+        && ascii.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    valid.then_some(ascii)
+}
+
+/// Parse and normalize the address lists given for a recipient field.
+fn recipients(field: &str, values: &[String]) -> Result<Vec<String>> {
+    let mut recipients = Vec::new();
+    for value in values {
+        let mailboxes =
+            parse_address_list(value).map_err(|message| crate::error::Error::InvalidAddress {
+                field: field.to_string(),
+                message,
+            })?;
+        recipients.extend(mailboxes.iter().map(Mailbox::to_string));
+    }
+    Ok(recipients)
+}
+
+/// Threading headers, subject and quoted body shared by all replies.
+fn reply_base(original: &crate::thread::Message) -> MessageBuilder {
+    let mut builder = MessageBuilder::new();
+
+    // Set In-Reply-To to the original message ID
+    builder = builder.in_reply_to(original.id.clone());
+
+    // Build References header
+    let mut references = vec![];
+
+    // Check if original has References header
+    if let Some(orig_refs) = original.headers.get("references") {
+        // Parse space-separated message IDs
+        references.extend(orig_refs.split_whitespace().map(|s| s.to_string()));
     }
 
-    quoted
+    // Add the original message ID to references
+    references.push(original.id.clone());
+
+    for reference in references {
+        builder = builder.add_reference(reference);
+    }
+
+    // Set subject with Re: prefix if not already present
+    let subject = match &original.headers.subject {
+        Some(subj) if subj.starts_with("Re: ") => subj.clone(),
+        Some(subj) => format!("Re: {}", subj),
+        None => "Re: (No subject)".to_string(),
+    };
+    builder = builder.subject(subject);
+
+    // Quote original message
+    let quoted_body = quote_message_body(original);
+    builder.body(quoted_body)
+}
+
+/// Quote the body of a message for replying, converting HTML-only
+/// messages to text.
+fn quote_message_body(message: &crate::thread::Message) -> String {
+    let body_text = match crate::body::find_body(&message.body, crate::body::PREFER_PLAIN) {
+        Some(part) if part.media_type().essence() == "text/html" => {
+            super::quote::html_to_text(part.text().unwrap_or_default())
+        }
+        _ => extract_plain_text_body(message),
+    };
+    super::quote::ReplyQuoting::default().quote(message, &body_text)
 }
 
 /// Format a message for forwarding.
@@ -501,7 +1338,7 @@ fn format_forward_body(message: &crate::thread::Message) -> String {
     let mut forward = String::new();
 
     forward.push_str("---------- Forwarded message ----------\n");
-    forward.push_str(&format!("From: {}\n", message.headers.from));
+    forward.push_str(&format!("From: {}\n", message.headers.formatted_from()));
     forward.push_str(&format!("Date: {}\n", message.date_relative));
     forward.push_str(&format!(
         "Subject: {}\n",
@@ -523,28 +1360,8 @@ fn format_forward_body(message: &crate::thread::Message) -> String {
 
 /// Extract plain text body from a message.
 fn extract_plain_text_body(message: &crate::thread::Message) -> String {
-    for part in &message.body {
-        if let Some(text) = extract_text_from_part(part) {
-            return text;
-        }
-    }
-    String::new()
-}
-
-/// Recursively extract text from a body part.
-fn extract_text_from_part(part: &BodyPart) -> Option<String> {
-    use crate::body::BodyContent;
-
-    match &part.content {
-        BodyContent::Text(text) if part.content_type == "text/plain" => Some(text.clone()),
-        BodyContent::Multipart(parts) => {
-            for subpart in parts {
-                if let Some(text) = extract_text_from_part(subpart) {
-                    return Some(text);
-                }
-            }
-            None
-        }
-        _ => None,
-    }
+    crate::body::find_body(&message.body, &["text/plain"])
+        .and_then(BodyPart::display_text)
+        .unwrap_or_default()
+        .into_owned()
 }
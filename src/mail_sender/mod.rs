@@ -1,8 +1,8 @@
-//! Mail sender implementations for sending email via msmtp.
+//! Mail sender implementations for sending email via msmtp or SMTP.
 //!
 //! This module provides a unified interface for sending email messages
 //! using msmtp, supporting both local command execution and remote execution
-//! via SSH.
+//! via SSH, or by speaking SMTP submission directly.
 //!
 //! # Overview
 //!
@@ -10,6 +10,9 @@
 //! - `MailSender` trait - The main interface for all mail sender implementations
 //! - `LocalMsmtpClient` - Executes msmtp commands locally
 //! - `RemoteMsmtpClient` - Executes msmtp commands on a remote host via SSH
+//! - `SmtpMailSender` - Submits messages directly to an SMTP server
+//! - `AgentMailSender` - Submits messages through a `whynot-agent` daemon
+//! - `IdentityMailSender` - Routes each sending identity's mail to its own sender
 //! - `MailSenderConfig` - Configuration for creating mail senders
 //! - `ComposableMessage` - Structure for composing email messages
 //!
@@ -23,6 +26,7 @@
 //! let config = MailSenderConfig::Local {
 //!     msmtp_path: None,
 //!     config_path: None,
+//!     account: None,
 //! };
 //! let sender = create_mail_sender(config)?;
 //!
@@ -38,19 +42,32 @@
 //! # }
 //! ```
 
+use crate::client::NotmuchClient;
 use crate::error::Result;
 use crate::thread::Message;
 use async_trait::async_trait;
 
+pub mod agent;
 pub mod config;
+pub mod identity;
 pub mod local;
 pub mod message;
+pub mod pgp;
+pub mod quote;
 pub mod remote;
+pub mod smtp;
 
-pub use config::MailSenderConfig;
+pub use agent::AgentMailSender;
+pub use config::{MailSenderConfig, SmtpTls};
+pub use identity::IdentityMailSender;
 pub use local::LocalMsmtpClient;
-pub use message::{ComposableMessage, MessageBuilder};
+pub use message::{
+    Attachment, AttachmentLimits, CalendarPart, ComposableMessage, ForwardMode, MessageBuilder,
+};
+pub use pgp::{Gpg, PgpOptions};
+pub use quote::ReplyQuoting;
 pub use remote::RemoteMsmtpClient;
+pub use smtp::SmtpMailSender;
 
 /// A client for sending email messages.
 ///
@@ -213,12 +230,20 @@ pub trait MailSender: Send + Sync {
     /// # }
     /// ```
     async fn get_from_address(&self) -> Result<String>;
+
+    /// The Sent folder for `message` when it differs from the configured
+    /// one, e.g. because the identity it is sent as has its own. An empty
+    /// name means no copy is filed.
+    fn sent_folder_for(&self, _message: &ComposableMessage) -> Option<String> {
+        None
+    }
 }
 
 /// Create a new mail sender based on the provided configuration.
 ///
-/// This factory function creates either a `LocalMsmtpClient` or `RemoteMsmtpClient`
-/// based on the configuration variant, returning it as a trait object.
+/// This factory function creates a `LocalMsmtpClient`, `RemoteMsmtpClient`,
+/// `SmtpMailSender` or `AgentMailSender` based on the configuration variant,
+/// returning it as a trait object.
 ///
 /// # Arguments
 ///
@@ -238,6 +263,7 @@ pub trait MailSender: Send + Sync {
 /// let local_config = MailSenderConfig::Local {
 ///     msmtp_path: None,
 ///     config_path: None,
+///     account: None,
 /// };
 /// let local_sender = create_mail_sender(local_config)?;
 ///
@@ -249,6 +275,7 @@ pub trait MailSender: Send + Sync {
 ///     identity_file: Some(PathBuf::from("/home/alice/.ssh/id_rsa")),
 ///     msmtp_path: None,
 ///     config_path: None,
+///     account: None,
 /// };
 /// let remote_sender = create_mail_sender(remote_config)?;
 /// # Ok(())
@@ -264,5 +291,67 @@ pub fn create_mail_sender(config: MailSenderConfig) -> Result<Box<dyn MailSender
             let client = RemoteMsmtpClient::new(config)?;
             Ok(Box::new(client))
         }
+        MailSenderConfig::Smtp { .. } => {
+            let client = SmtpMailSender::new(config)?;
+            Ok(Box::new(client))
+        }
+        MailSenderConfig::Agent { .. } => {
+            let client = AgentMailSender::new(config)?;
+            Ok(Box::new(client))
+        }
+    }
+}
+
+/// The code of the last SMTP reply msmtp passes on in `stderr`, as in
+/// `msmtp: server message: 550 5.1.1 User unknown`.
+pub(crate) fn smtp_reply_code(stderr: &str) -> Option<u16> {
+    stderr
+        .split("server message: ")
+        .skip(1)
+        .filter_map(|reply| reply.get(..3)?.parse().ok())
+        .last()
+}
+
+/// Tags applied to the copy of a sent message filed by [`send_and_file`].
+pub const SENT_TAGS: &[&str] = &["sent", "-unread"];
+
+/// Send `message` and file a copy of it in the `sent_folder` Maildir folder.
+///
+/// The copy is the exact output of [`ComposableMessage::to_rfc822`], inserted
+/// through `client` with [`SENT_TAGS`], so a reply shows up in its thread
+/// straight away. The sender can pick another folder for the message with
+/// [`MailSender::sent_folder_for`]. Without a folder this is just
+/// [`MailSender::send`]. A failure to file the copy is logged rather than
+/// returned, since the message has already gone out.
+pub async fn send_and_file(
+    sender: &dyn MailSender,
+    client: &dyn NotmuchClient,
+    message: ComposableMessage,
+    sent_folder: Option<&str>,
+) -> Result<String> {
+    let own_folder = sender.sent_folder_for(&message);
+    let sent_folder = match own_folder.as_deref() {
+        Some("") => None,
+        Some(folder) => Some(folder),
+        None => sent_folder,
+    };
+    let copy = match sent_folder {
+        Some(_) => Some(message.to_rfc822()?),
+        None => None,
+    };
+
+    let message_id = sender.send(message).await?;
+
+    if let (Some(folder), Some(copy)) = (sent_folder, copy)
+        && let Err(e) = client.insert(&copy, Some(folder), SENT_TAGS).await
+    {
+        tracing::warn!(
+            "Sent {} but could not file it in {}: {}",
+            message_id,
+            folder,
+            e
+        );
     }
+
+    Ok(message_id)
 }
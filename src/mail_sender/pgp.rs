@@ -0,0 +1,326 @@
+//! OpenPGP signing and encryption of outgoing mail through the local `gpg`
+//! binary, producing RFC 3156 `multipart/signed` and `multipart/encrypted`
+//! messages.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::common::Mailbox;
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::ComposableMessage;
+
+/// How a message is protected with OpenPGP.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PgpOptions {
+    /// Sign the message with the sender's key
+    pub sign: bool,
+    /// Encrypt the message to every recipient's key
+    pub encrypt: bool,
+}
+
+impl PgpOptions {
+    /// Whether the message is protected at all.
+    pub fn is_enabled(&self) -> bool {
+        self.sign || self.encrypt
+    }
+
+    /// A short description for status lines, e.g. `signed and encrypted`.
+    pub fn label(&self) -> &'static str {
+        match (self.sign, self.encrypt) {
+            (false, false) => "not signed or encrypted",
+            (true, false) => "signed",
+            (false, true) => "encrypted",
+            (true, true) => "signed and encrypted",
+        }
+    }
+}
+
+/// The `gpg` binary and keyring used to sign and encrypt mail.
+#[derive(Debug, Clone)]
+pub struct Gpg {
+    path: PathBuf,
+    homedir: Option<PathBuf>,
+}
+
+impl Default for Gpg {
+    fn default() -> Self {
+        Gpg {
+            path: PathBuf::from("gpg"),
+            homedir: None,
+        }
+    }
+}
+
+impl Gpg {
+    /// Run the `gpg` at `path`, or the one on the `PATH`.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Gpg {
+            path: path.unwrap_or_else(|| PathBuf::from("gpg")),
+            homedir: None,
+        }
+    }
+
+    /// Use the keyring in `homedir` rather than `GNUPGHOME` or `~/.gnupg`.
+    pub fn with_homedir(mut self, homedir: impl Into<PathBuf>) -> Self {
+        self.homedir = Some(homedir.into());
+        self
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.path);
+        cmd.args(["--batch", "--no-tty", "--yes"]);
+        if let Some(homedir) = &self.homedir {
+            cmd.arg("--homedir").arg(homedir);
+        }
+        cmd
+    }
+
+    /// Run gpg with `args`, feeding it `input`, and return what it prints.
+    async fn run(&self, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+        let mut child = self
+            .command()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| NotmuchError::CommandFailed(format!("Could not run gpg: {}", e)))?;
+
+        // gpg writes while it reads, so feed it alongside collecting output
+        let mut stdin = child.stdin.take();
+        let write = async move {
+            if let Some(stdin) = stdin.as_mut() {
+                stdin.write_all(input).await?;
+            }
+            drop(stdin);
+            Ok::<_, std::io::Error>(())
+        };
+        let (_, output) = tokio::try_join!(write, child.wait_with_output())?;
+
+        if !output.status.success() {
+            return Err(NotmuchError::CommandFailed(format!(
+                "gpg {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+
+    /// The fingerprint of a valid key for encrypting to `address`, if the
+    /// keyring has one.
+    pub async fn find_key(&self, address: &str) -> Result<Option<String>> {
+        let pattern = format!("<{}>", address);
+        // gpg fails when nothing matches
+        let Ok(listing) = self
+            .run(&["--with-colons", "--list-keys", "--", &pattern], &[])
+            .await
+        else {
+            return Ok(None);
+        };
+        Ok(usable_key(&String::from_utf8_lossy(&listing)))
+    }
+
+    /// An ASCII-armored detached signature of `data`, made with the key
+    /// `signer` or gpg's default key.
+    pub async fn sign(&self, data: &[u8], signer: Option<&str>) -> Result<String> {
+        let mut args = vec!["--armor", "--detach-sign", "--digest-algo", DIGEST_ALGO];
+        if let Some(signer) = signer {
+            args.extend(["--local-user", signer]);
+        }
+        let signature = self.run(&args, data).await?;
+        Ok(String::from_utf8_lossy(&signature).into_owned())
+    }
+
+    /// `data` encrypted to the keys `recipients` and, without their key IDs
+    /// showing, `hidden_recipients`; with `sign`, also signed with the key
+    /// `signer` or gpg's default key.
+    pub async fn encrypt(
+        &self,
+        data: &[u8],
+        recipients: &[String],
+        hidden_recipients: &[String],
+        sign: bool,
+        signer: Option<&str>,
+    ) -> Result<String> {
+        // The keys were picked by address from the user's own keyring, as
+        // most mail clients do, rather than by the web of trust
+        let mut args = vec!["--armor", "--encrypt", "--trust-model", "always"];
+        if sign {
+            args.extend(["--sign", "--digest-algo", DIGEST_ALGO]);
+            if let Some(signer) = signer {
+                args.extend(["--local-user", signer]);
+            }
+        }
+        for recipient in recipients {
+            args.extend(["--recipient", recipient]);
+        }
+        for recipient in hidden_recipients {
+            args.extend(["--hidden-recipient", recipient]);
+        }
+        let encrypted = self.run(&args, data).await?;
+        Ok(String::from_utf8_lossy(&encrypted).into_owned())
+    }
+}
+
+/// Hash used for signatures, named in the `micalg` parameter.
+const DIGEST_ALGO: &str = "SHA256";
+
+/// The fingerprint of the first key in a `--with-colons` listing that can
+/// encrypt and is not revoked, expired, invalid or disabled.
+fn usable_key(listing: &str) -> Option<String> {
+    let mut usable = false;
+    for line in listing.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.first() {
+            Some(&"pub") => {
+                let validity = fields.get(1).copied().unwrap_or("");
+                let capabilities = fields.get(11).copied().unwrap_or("");
+                usable = !matches!(validity, "r" | "e" | "i" | "d")
+                    && capabilities.contains('E')
+                    && !capabilities.contains('D');
+            }
+            Some(&"fpr") if usable => {
+                return fields.get(9).map(|fpr| fpr.to_string());
+            }
+            // Only the primary key's fingerprint is wanted
+            Some(&"sub") => usable = false,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Sign and/or encrypt `message` as `options` say, replacing its body with
+/// an RFC 3156 `multipart/signed` or `multipart/encrypted` entity.
+///
+/// Messages are signed with `signing_key`, or else the key of the From
+/// address. Encrypted messages are also encrypted to the sender's key, so
+/// the copy in the Sent folder stays readable, and fail when a recipient
+/// has no key. Bcc recipients are hidden in the encrypted message.
+pub async fn protect(
+    gpg: &Gpg,
+    mut message: ComposableMessage,
+    options: PgpOptions,
+    signing_key: Option<&str>,
+) -> Result<ComposableMessage> {
+    if !options.is_enabled() {
+        return Ok(message);
+    }
+
+    let from = message.from.as_deref().and_then(Mailbox::parse);
+    let signer = signing_key.or(from.as_ref().map(|from| from.address.as_str()));
+    let entity = canonical(&message.body_entity());
+
+    let protected = if options.encrypt {
+        let mut recipients = Vec::new();
+        let mut hidden_recipients = Vec::new();
+        let mut missing = Vec::new();
+        let addresses = |list: &[String]| -> Vec<String> {
+            list.iter()
+                .flat_map(|value| Mailbox::parse_list(value))
+                .map(|mailbox| mailbox.address)
+                .collect()
+        };
+        let visible = addresses(&[message.to.clone(), message.cc.clone()].concat());
+        for (address, hidden) in visible
+            .into_iter()
+            .map(|address| (address, false))
+            .chain(addresses(&message.bcc).into_iter().map(|a| (a, true)))
+        {
+            match gpg.find_key(&address).await? {
+                Some(key) if hidden => hidden_recipients.push(key),
+                Some(key) => recipients.push(key),
+                None => missing.push(address),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(NotmuchError::InvalidInput(format!(
+                "No OpenPGP key for {}",
+                missing.join(", ")
+            )));
+        }
+
+        // Encrypt to self
+        let own_key = match (signing_key, &from) {
+            (Some(key), _) => Some(key.to_string()),
+            (None, Some(from)) => gpg.find_key(&from.address).await?,
+            (None, None) => None,
+        };
+        recipients.extend(own_key);
+
+        let encrypted = gpg
+            .encrypt(
+                entity.as_bytes(),
+                &recipients,
+                &hidden_recipients,
+                options.sign,
+                signer,
+            )
+            .await?;
+        encrypted_entity(&message.boundary("encrypted"), &encrypted)
+    } else {
+        let signature = gpg.sign(entity.as_bytes(), signer).await?;
+        signed_entity(&message.boundary("signed"), &entity, &signature)
+    };
+
+    message.pgp_body = Some(protected);
+    message.pgp = options;
+    Ok(message)
+}
+
+/// `entity` with CRLF line endings throughout, as it is signed and sent.
+fn canonical(entity: &str) -> String {
+    entity.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+/// A `multipart/signed` entity of `entity` and its detached `signature`.
+fn signed_entity(boundary: &str, entity: &str, signature: &str) -> String {
+    // The CRLF before a boundary belongs to the boundary, so the signed
+    // part is exactly `entity`
+    format!(
+        "Content-Type: multipart/signed; micalg=pgp-{}; protocol=\"application/pgp-signature\"; boundary=\"{b}\"\r\n\
+         \r\n\
+         This is an OpenPGP/MIME signed message (RFC 4880 and 3156)\r\n\
+         --{b}\r\n\
+         {entity}\r\n\
+         --{b}\r\n\
+         Content-Type: application/pgp-signature; name=\"signature.asc\"\r\n\
+         Content-Description: OpenPGP digital signature\r\n\
+         Content-Disposition: attachment; filename=\"signature.asc\"\r\n\
+         \r\n\
+         {signature}\r\n\
+         --{b}--\r\n",
+        DIGEST_ALGO.to_ascii_lowercase(),
+        b = boundary,
+        entity = entity,
+        signature = canonical(signature.trim_end()),
+    )
+}
+
+/// A `multipart/encrypted` entity carrying the armored `encrypted` data.
+fn encrypted_entity(boundary: &str, encrypted: &str) -> String {
+    format!(
+        "Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=\"{b}\"\r\n\
+         \r\n\
+         This is an OpenPGP/MIME encrypted message (RFC 4880 and 3156)\r\n\
+         --{b}\r\n\
+         Content-Type: application/pgp-encrypted\r\n\
+         Content-Description: PGP/MIME version identification\r\n\
+         \r\n\
+         Version: 1\r\n\
+         \r\n\
+         --{b}\r\n\
+         Content-Type: application/octet-stream; name=\"encrypted.asc\"\r\n\
+         Content-Description: OpenPGP encrypted message\r\n\
+         Content-Disposition: inline; filename=\"encrypted.asc\"\r\n\
+         \r\n\
+         {encrypted}\r\n\
+         --{b}--\r\n",
+        b = boundary,
+        encrypted = canonical(encrypted.trim_end()),
+    )
+}
@@ -0,0 +1,174 @@
+//! Quoting the message a reply answers: the attribution line, the original
+//! text quoted with `>` and where the reply goes relative to it.
+
+use crate::body::{self, PREFER_PLAIN};
+use crate::text_renderer::builtin::BuiltinConverter;
+use crate::text_renderer::{HtmlToTextConverter, TextRendererConfig};
+use crate::thread::Message;
+
+/// Attribution line used unless `reply_attribution` is configured.
+pub const DEFAULT_ATTRIBUTION: &str = "On {date}, {from} wrote:";
+
+/// How replies quote the message they answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyQuoting {
+    /// Line introducing the quote, see [`ReplyQuoting::attribution`]
+    pub attribution: String,
+    /// Put the reply above the quote instead of below it
+    pub top_posting: bool,
+    /// Leave the original's signature out of the quote
+    pub trim_signature: bool,
+}
+
+impl Default for ReplyQuoting {
+    fn default() -> Self {
+        ReplyQuoting {
+            attribution: DEFAULT_ATTRIBUTION.to_string(),
+            top_posting: false,
+            trim_signature: true,
+        }
+    }
+}
+
+impl ReplyQuoting {
+    /// The attribution line for `message`: `{date}` is replaced with when
+    /// it was sent, in the sender's time zone, `{from}` with its From
+    /// header, `{name}` with the sender's name (or address, if they gave
+    /// none) and `{email}` with their address.
+    pub fn attribution(&self, message: &Message) -> String {
+        let sender = message.headers.sender();
+        let name = sender
+            .as_ref()
+            .map(|mailbox| mailbox.display_name().to_string())
+            .unwrap_or_else(|| message.headers.from.clone());
+        let email = sender
+            .map(|mailbox| mailbox.address)
+            .unwrap_or_else(|| message.headers.from.clone());
+        let date = message
+            .headers
+            .parsed_date()
+            .map(|date| date.format("%a, %d %b %Y %H:%M").to_string())
+            .unwrap_or_else(|| message.date_relative.clone());
+        self.attribution
+            .replace("{date}", &date)
+            .replace("{from}", &message.headers.formatted_from())
+            .replace("{name}", &name)
+            .replace("{email}", &email)
+    }
+
+    /// Quote `text`, the body of `message`, under its attribution line.
+    ///
+    /// Quoted lines keep their depth, so `> earlier` becomes `>> earlier`.
+    pub fn quote(&self, message: &Message, text: &str) -> String {
+        let text = if self.trim_signature {
+            without_signature(text)
+        } else {
+            text
+        };
+
+        let mut quoted = self.attribution(message);
+        quoted.push('\n');
+        for line in text.trim_end().lines() {
+            quoted.push('>');
+            if !line.is_empty() && !line.starts_with('>') {
+                quoted.push(' ');
+            }
+            quoted.push_str(line);
+            quoted.push('\n');
+        }
+        quoted
+    }
+
+    /// The body a reply starts out with: the `quote`, blank lines for the
+    /// reply above or below it and the sender's `signature`, if any.
+    pub fn reply_body(&self, quote: &str, signature: Option<&str>) -> String {
+        let signature = signature
+            .filter(|signature| !signature.trim().is_empty())
+            .map(|signature| format!("-- \n{}", signature));
+        if self.top_posting {
+            let mut body = String::from("\n\n");
+            if let Some(signature) = signature {
+                body.push_str(&signature);
+                body.push_str("\n\n");
+            }
+            body.push_str(quote);
+            body
+        } else {
+            let mut body = format!("{}\n", quote);
+            if let Some(signature) = signature {
+                body.push_str("\n\n");
+                body.push_str(&signature);
+            }
+            body
+        }
+    }
+
+    /// Put the `reply`, written apart from the `quote`, above or below it.
+    pub fn join(&self, reply: &str, quote: &str) -> String {
+        let reply = reply.trim_end();
+        if reply.is_empty() {
+            quote.to_string()
+        } else if self.top_posting {
+            format!("{}\n\n{}", reply, quote)
+        } else {
+            // The blank line keeps markdown from continuing the quote
+            format!("{}\n{}\n", quote, reply)
+        }
+    }
+}
+
+/// The text of `message` to quote: its plain text, reflowed if it was sent
+/// as format=flowed, or its HTML converted to text by `converter`.
+pub async fn reply_text(message: &Message, converter: &dyn HtmlToTextConverter) -> String {
+    let Some(part) = body::find_body(&message.body, PREFER_PLAIN) else {
+        return String::new();
+    };
+    let Some(html) = part
+        .text()
+        .filter(|_| part.media_type().essence() == "text/html")
+    else {
+        return part.display_text().unwrap_or_default().into_owned();
+    };
+    match converter.convert(html).await {
+        Ok(text) => text,
+        Err(e) => {
+            tracing::warn!("Quoting HTML with {} failed: {}", converter.name(), e);
+            html_to_text(html)
+        }
+    }
+}
+
+/// Convert `html` to text with the built-in converter.
+pub(super) fn html_to_text(html: &str) -> String {
+    BuiltinConverter::new(TextRendererConfig::default())
+        .convert_html_to_text(html)
+        .unwrap_or_default()
+}
+
+/// `text` up to the line that starts its signature, `-- ` (or `--`, as
+/// some clients strip the trailing space).
+fn without_signature(text: &str) -> &str {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end() == "--" {
+            return &text[..offset];
+        }
+        offset += line.len();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_signature() {
+        assert_eq!(
+            without_signature("Hello\n\n-- \nBob\n-- \nmore"),
+            "Hello\n\n"
+        );
+        assert_eq!(without_signature("Hi\n--\nBob"), "Hi\n");
+        assert_eq!(without_signature("a -- b\n---\n"), "a -- b\n---\n");
+    }
+}
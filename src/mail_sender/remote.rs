@@ -6,7 +6,7 @@ use std::process::Stdio;
 use tokio::process::Command;
 
 use crate::error::{NotmuchError, Result};
-use crate::mail_sender::{ComposableMessage, MailSender, MailSenderConfig};
+use crate::mail_sender::{ComposableMessage, MailSender, MailSenderConfig, smtp_reply_code};
 use crate::thread::Message;
 
 /// A mail sender that executes msmtp commands on a remote host via SSH.
@@ -37,6 +37,7 @@ use crate::thread::Message;
 ///     identity_file: None,
 ///     msmtp_path: None,
 ///     config_path: None,
+///     account: None,
 /// };
 /// let client = RemoteMsmtpClient::new(config)?;
 ///
@@ -48,6 +49,7 @@ use crate::thread::Message;
 ///     identity_file: Some(PathBuf::from("/home/alice/.ssh/mail_key")),
 ///     msmtp_path: Some(PathBuf::from("/usr/local/bin/msmtp")),
 ///     config_path: Some(PathBuf::from("/home/alice/.msmtprc")),
+///     account: Some("work".to_string()),
 /// };
 /// let client = RemoteMsmtpClient::new(config)?;
 /// # Ok(())
@@ -60,6 +62,7 @@ pub struct RemoteMsmtpClient {
     identity_file: Option<PathBuf>,
     msmtp_path: PathBuf,
     config_path: Option<PathBuf>,
+    account: Option<String>,
 }
 
 impl RemoteMsmtpClient {
@@ -72,6 +75,7 @@ impl RemoteMsmtpClient {
                 identity_file,
                 msmtp_path,
                 config_path,
+                account,
             } => Ok(RemoteMsmtpClient {
                 host,
                 user,
@@ -79,6 +83,7 @@ impl RemoteMsmtpClient {
                 identity_file,
                 msmtp_path: msmtp_path.unwrap_or_else(|| PathBuf::from("msmtp")),
                 config_path,
+                account,
             }),
             _ => Err(NotmuchError::ConfigError(
                 "Invalid config type for RemoteMsmtpClient".to_string(),
@@ -132,6 +137,10 @@ impl RemoteMsmtpClient {
             msmtp_cmd_parts.push("--file".to_string());
             msmtp_cmd_parts.push(config_path.to_string_lossy().to_string());
         }
+        if let Some(account) = &self.account {
+            msmtp_cmd_parts.push("-a".to_string());
+            msmtp_cmd_parts.push(account.clone());
+        }
 
         // Add msmtp arguments
         msmtp_cmd_parts.extend(msmtp_args.iter().map(|s| s.to_string()));
@@ -173,10 +182,11 @@ impl RemoteMsmtpClient {
             tracing::error!("SSH msmtp command failed with status: {:?}", output.status);
             tracing::error!("SSH stderr: {}", stderr);
             tracing::error!("SSH stdout: {}", stdout);
-            return Err(NotmuchError::SshError(format!(
-                "SSH msmtp command failed: {}",
-                stderr
-            )));
+            let message = format!("SSH msmtp command failed: {}", stderr);
+            return Err(match smtp_reply_code(&stderr) {
+                Some(code) => NotmuchError::SmtpRejected { code, message },
+                None => NotmuchError::SshError(message),
+            });
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -230,6 +240,10 @@ impl RemoteMsmtpClient {
             msmtp_cmd_parts.push("--file".to_string());
             msmtp_cmd_parts.push(config_path.to_string_lossy().to_string());
         }
+        if let Some(account) = &self.account {
+            msmtp_cmd_parts.push("-a".to_string());
+            msmtp_cmd_parts.push(account.clone());
+        }
 
         // Add msmtp arguments
         msmtp_cmd_parts.extend(msmtp_args.iter().map(|s| s.to_string()));
@@ -372,6 +386,7 @@ mod tests {
             identity_file: Some(PathBuf::from("/home/user/.ssh/id_rsa")),
             msmtp_path: Some(PathBuf::from("/usr/local/bin/msmtp")),
             config_path: Some(PathBuf::from("/home/user/.msmtprc")),
+            account: None,
         };
 
         let client = RemoteMsmtpClient::new(config);
@@ -397,6 +412,7 @@ mod tests {
             identity_file: None,
             msmtp_path: None,
             config_path: None,
+            account: None,
         };
 
         let client = RemoteMsmtpClient::new(config);
@@ -415,6 +431,7 @@ mod tests {
         let config = MailSenderConfig::Local {
             msmtp_path: None,
             config_path: None,
+            account: None,
         };
 
         let result = RemoteMsmtpClient::new(config);
@@ -0,0 +1,669 @@
+//! SMTP submission client implementation for sending email without msmtp.
+
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+use crate::common::Mailbox;
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::{ComposableMessage, MailSender, MailSenderConfig, SmtpTls};
+use crate::thread::Message;
+
+/// How long to wait for the server before giving up on a single step.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A mail sender that submits messages directly to an SMTP server.
+///
+/// `SmtpMailSender` opens a new connection for every message. It secures
+/// the connection with STARTTLS or implicit TLS, authenticates with
+/// `AUTH PLAIN` or `AUTH LOGIN` when a user is configured, and pipelines
+/// the envelope commands when the server advertises `PIPELINING`. It will
+/// not authenticate over an unencrypted connection unless
+/// `allow_insecure_auth` is set.
+///
+/// Replies in the 4xx range are reported as temporary failures and 5xx
+/// replies as permanent ones, so callers can decide whether to retry.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use whynot::mail_sender::{MailSenderConfig, SmtpMailSender, SmtpTls};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let sender = SmtpMailSender::new(MailSenderConfig::Smtp {
+///     host: "smtp.example.com".to_string(),
+///     port: None,
+///     tls: SmtpTls::Starttls,
+///     user: Some("alice@example.com".to_string()),
+///     password_command: Some("pass show mail/example.com".to_string()),
+///     password_env: None,
+///     from: Some("Alice <alice@example.com>".to_string()),
+///     ca_cert: None,
+///     allow_insecure_auth: false,
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SmtpMailSender {
+    host: String,
+    port: u16,
+    tls: SmtpTls,
+    user: Option<String>,
+    password_command: Option<String>,
+    password_env: Option<String>,
+    from: Option<String>,
+    ca_cert: Option<PathBuf>,
+}
+
+impl SmtpMailSender {
+    pub fn new(config: MailSenderConfig) -> Result<Self> {
+        match config {
+            MailSenderConfig::Smtp {
+                host,
+                port,
+                tls,
+                user,
+                password_command,
+                password_env,
+                from,
+                ca_cert,
+                allow_insecure_auth,
+            } => {
+                if tls == SmtpTls::None && user.is_some() && !allow_insecure_auth {
+                    return Err(NotmuchError::ConfigError(
+                        "Refusing to send the SMTP password without TLS; use tls = \"starttls\" \
+                         or \"tls\", or set allow_insecure_auth for a trusted relay"
+                            .to_string(),
+                    ));
+                }
+                Ok(SmtpMailSender {
+                    host,
+                    port: port.unwrap_or_else(|| tls.default_port()),
+                    tls,
+                    user,
+                    password_command,
+                    password_env,
+                    from,
+                    ca_cert,
+                })
+            }
+            _ => Err(NotmuchError::ConfigError(
+                "Invalid config type for SmtpMailSender".to_string(),
+            )),
+        }
+    }
+
+    /// Name sent with EHLO: the domain of the From address when known.
+    fn hello_name(&self) -> String {
+        self.from
+            .as_deref()
+            .and_then(Mailbox::parse)
+            .and_then(|m| m.address.rsplit_once('@').map(|(_, d)| d.to_string()))
+            .unwrap_or_else(|| "localhost".to_string())
+    }
+
+    fn tls_connector(&self) -> Result<tokio_native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path)?;
+            let cert = native_tls::Certificate::from_pem(&pem).map_err(|e| {
+                NotmuchError::ConfigError(format!(
+                    "Invalid CA certificate {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            builder.add_root_certificate(cert);
+        }
+        let connector = builder
+            .build()
+            .map_err(|e| NotmuchError::MailSendError(format!("TLS setup failed: {}", e)))?;
+        Ok(connector.into())
+    }
+
+    async fn start_tls(&self, stream: Box<dyn Stream>) -> Result<Box<dyn Stream>> {
+        let stream = self
+            .tls_connector()?
+            .connect(&self.host, stream)
+            .await
+            .map_err(|e| {
+                NotmuchError::MailSendError(format!(
+                    "TLS handshake with {} failed: {}",
+                    self.host, e
+                ))
+            })?;
+        Ok(Box::new(stream))
+    }
+
+    /// Connect, secure and authenticate a session ready for a transaction.
+    async fn open(&self) -> Result<Connection> {
+        let tcp = timed(TcpStream::connect((self.host.as_str(), self.port)))
+            .await
+            .map_err(|e| {
+                NotmuchError::MailSendError(format!(
+                    "Could not connect to {}:{}: {}",
+                    self.host, self.port, e
+                ))
+            })?;
+        let mut stream: Box<dyn Stream> = Box::new(tcp);
+        if self.tls == SmtpTls::Tls {
+            stream = self.start_tls(stream).await?;
+        }
+
+        let mut conn = Connection::new(stream);
+        check(conn.read_reply().await?, 2, "connection greeting")?;
+        conn.hello(&self.hello_name()).await?;
+
+        if self.tls == SmtpTls::Starttls {
+            if !conn.supports("STARTTLS") {
+                return Err(NotmuchError::MailSendError(format!(
+                    "{} does not offer STARTTLS",
+                    self.host
+                )));
+            }
+            check(conn.command("STARTTLS").await?, 2, "STARTTLS")?;
+            // Anything already buffered was sent before the handshake and
+            // could have been injected by an attacker
+            if !conn.stream.buffer().is_empty() {
+                return Err(NotmuchError::MailSendError(
+                    "SMTP server sent unexpected data before STARTTLS".to_string(),
+                ));
+            }
+            let stream = self.start_tls(conn.stream.into_inner()).await?;
+            conn = Connection::new(stream);
+            conn.hello(&self.hello_name()).await?;
+        }
+
+        if let Some(user) = &self.user {
+            self.authenticate(&mut conn, user).await?;
+        }
+
+        Ok(conn)
+    }
+
+    async fn authenticate(&self, conn: &mut Connection, user: &str) -> Result<()> {
+        let password = self.password().await?;
+        let mechanisms = conn.auth_mechanisms();
+
+        let reply = if mechanisms.iter().any(|m| m == "PLAIN") {
+            let token = STANDARD.encode(format!("\0{}\0{}", user, password));
+            conn.command(&format!("AUTH PLAIN {}", token)).await?
+        } else if mechanisms.iter().any(|m| m == "LOGIN") {
+            check(conn.command("AUTH LOGIN").await?, 3, "AUTH LOGIN")?;
+            check(
+                conn.command(&STANDARD.encode(user)).await?,
+                3,
+                "AUTH LOGIN username",
+            )?;
+            conn.command(&STANDARD.encode(&password)).await?
+        } else {
+            return Err(NotmuchError::MailSendError(format!(
+                "{} offers no supported authentication mechanism (PLAIN or LOGIN)",
+                self.host
+            )));
+        };
+
+        check(reply, 2, "authentication").map(|_| ())
+    }
+
+    async fn password(&self) -> Result<String> {
+        if let Some(command) = &self.password_command {
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+                .await?;
+            if !output.status.success() {
+                return Err(NotmuchError::CommandFailed(format!(
+                    "SMTP password command failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            return Ok(stdout.lines().next().unwrap_or("").to_string());
+        }
+
+        if let Some(var) = &self.password_env {
+            return std::env::var(var).map_err(|_| {
+                NotmuchError::ConfigError(format!("SMTP password variable {} is not set", var))
+            });
+        }
+
+        Err(NotmuchError::ConfigError(
+            "SMTP user is set but neither password_command nor password_env is configured"
+                .to_string(),
+        ))
+    }
+
+    /// Run one mail transaction on an open session.
+    async fn transaction(
+        &self,
+        conn: &mut Connection,
+        sender: &str,
+        recipients: &[String],
+        data: &[u8],
+    ) -> Result<()> {
+        let mut mail_from = format!("MAIL FROM:<{}>", sender);
+        if conn.supports("8BITMIME") {
+            mail_from.push_str(" BODY=8BITMIME");
+        }
+        let rcpt_to: Vec<String> = recipients
+            .iter()
+            .map(|r| format!("RCPT TO:<{}>", r))
+            .collect();
+
+        if conn.supports("PIPELINING") {
+            let mut batch = format!("{}\r\n", mail_from);
+            for command in &rcpt_to {
+                batch.push_str(command);
+                batch.push_str("\r\n");
+            }
+            batch.push_str("DATA\r\n");
+            conn.write(batch.as_bytes()).await?;
+
+            // Replies arrive in command order; on failure the connection is
+            // dropped, which aborts the transaction before any data is sent
+            check(conn.read_reply().await?, 2, &mail_from)?;
+            for command in &rcpt_to {
+                check(conn.read_reply().await?, 2, command)?;
+            }
+            check(conn.read_reply().await?, 3, "DATA")?;
+        } else {
+            check(conn.command(&mail_from).await?, 2, &mail_from)?;
+            for command in &rcpt_to {
+                check(conn.command(command).await?, 2, command)?;
+            }
+            check(conn.command("DATA").await?, 3, "DATA")?;
+        }
+
+        conn.write(&encode_data(data)).await?;
+        check(conn.read_reply().await?, 2, "message data")?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MailSender for SmtpMailSender {
+    async fn send(&self, mut message: ComposableMessage) -> Result<String> {
+        if message.from.is_none() {
+            message.from = self.from.clone();
+        }
+        let sender = message
+            .from
+            .as_deref()
+            .and_then(Mailbox::parse)
+            .map(|m| m.address)
+            .ok_or_else(|| {
+                NotmuchError::ConfigError("No From address set for SMTP sending".to_string())
+            })?;
+
+        let recipients: Vec<String> = message
+            .to
+            .iter()
+            .chain(&message.cc)
+            .chain(&message.bcc)
+            .flat_map(|value| Mailbox::parse_list(value))
+            .map(|m| m.address)
+            .collect();
+        if recipients.is_empty() {
+            return Err(NotmuchError::InvalidInput(
+                "Message has no recipients".to_string(),
+            ));
+        }
+
+        let rfc822_data = message.to_rfc822()?;
+        let data = without_bcc(&rfc822_data);
+
+        let mut conn = self.open().await?;
+        self.transaction(&mut conn, &sender, &recipients, &data)
+            .await?;
+        // The message is accepted; a failed QUIT does not change that
+        let _ = conn.command("QUIT").await;
+
+        Ok(message.message_id)
+    }
+
+    async fn reply(
+        &self,
+        original: &Message,
+        reply: ComposableMessage,
+        reply_all: bool,
+    ) -> Result<String> {
+        let mut full_reply = ComposableMessage::reply_builder(original, reply_all)
+            .body(reply.body.clone())
+            .build()?;
+
+        // Merge in any additional fields from the reply
+        if let Some(from) = reply.from {
+            full_reply.from = Some(from);
+        }
+
+        self.send(full_reply).await
+    }
+
+    async fn forward(&self, original: &Message, forward: ComposableMessage) -> Result<String> {
+        let mut full_forward = ComposableMessage::forward_builder(original)
+            .body(forward.body.clone())
+            .build()?;
+
+        // Merge in recipients from forward
+        full_forward.to = forward.to;
+        full_forward.cc = forward.cc;
+        full_forward.bcc = forward.bcc;
+
+        if let Some(from) = forward.from {
+            full_forward.from = Some(from);
+        }
+
+        self.send(full_forward).await
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        let mut conn = self.open().await?;
+        check(conn.command("QUIT").await?, 2, "QUIT").map(|_| ())
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        self.from
+            .clone()
+            .or_else(|| self.user.clone().filter(|user| user.contains('@')))
+            .ok_or_else(|| {
+                NotmuchError::ConfigError("SMTP from address not configured".to_string())
+            })
+    }
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// A server reply: the status code and the text of each line.
+#[derive(Debug)]
+struct Reply {
+    code: u16,
+    lines: Vec<String>,
+}
+
+impl Reply {
+    fn text(&self) -> String {
+        format!("{} {}", self.code, self.lines.join(" "))
+    }
+}
+
+/// Accept `reply` if its code is in the `class` hundred, or turn it into
+/// an error naming the `command` it answered.
+fn check(reply: Reply, class: u16, command: &str) -> Result<Reply> {
+    if reply.code / 100 == class {
+        return Ok(reply);
+    }
+    let kind = match reply.code / 100 {
+        4 => "Temporary SMTP failure",
+        5 => "Permanent SMTP failure",
+        _ => {
+            return Err(NotmuchError::MailSendError(format!(
+                "Unexpected SMTP reply after {}: {}",
+                command,
+                reply.text()
+            )));
+        }
+    };
+    Err(NotmuchError::SmtpRejected {
+        code: reply.code,
+        message: format!("{} after {}: {}", kind, command, reply.text()),
+    })
+}
+
+async fn timed<T>(future: impl Future<Output = std::io::Result<T>>) -> Result<T> {
+    match tokio::time::timeout(SMTP_TIMEOUT, future).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(NotmuchError::MailSendError(
+            "Timed out waiting for the SMTP server".to_string(),
+        )),
+    }
+}
+
+struct Connection {
+    stream: BufReader<Box<dyn Stream>>,
+    /// EHLO keywords with their parameters, upper-cased.
+    extensions: Vec<String>,
+}
+
+impl Connection {
+    fn new(stream: Box<dyn Stream>) -> Self {
+        Connection {
+            stream: BufReader::new(stream),
+            extensions: Vec::new(),
+        }
+    }
+
+    async fn hello(&mut self, name: &str) -> Result<()> {
+        let reply = self.command(&format!("EHLO {}", name)).await?;
+        if reply.code / 100 == 2 {
+            self.extensions = reply
+                .lines
+                .iter()
+                .skip(1)
+                .map(|line| line.to_ascii_uppercase())
+                .collect();
+            return Ok(());
+        }
+        // Servers without ESMTP support still understand HELO
+        self.extensions.clear();
+        check(self.command(&format!("HELO {}", name)).await?, 2, "HELO").map(|_| ())
+    }
+
+    fn supports(&self, keyword: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|ext| ext.split_whitespace().next() == Some(keyword))
+    }
+
+    fn auth_mechanisms(&self) -> Vec<String> {
+        self.extensions
+            .iter()
+            .filter_map(|ext| {
+                ext.strip_prefix("AUTH")
+                    .filter(|rest| rest.starts_with([' ', '=']))
+            })
+            .flat_map(|rest| rest[1..].split_whitespace().map(str::to_string))
+            .collect()
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        let stream = self.stream.get_mut();
+        timed(async {
+            stream.write_all(data).await?;
+            stream.flush().await
+        })
+        .await
+    }
+
+    async fn command(&mut self, line: &str) -> Result<Reply> {
+        self.write(format!("{}\r\n", line).as_bytes()).await?;
+        self.read_reply().await
+    }
+
+    async fn read_reply(&mut self) -> Result<Reply> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if timed(self.stream.read_line(&mut line)).await? == 0 {
+                return Err(NotmuchError::MailSendError(
+                    "SMTP server closed the connection".to_string(),
+                ));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse::<u16>().ok())
+                .filter(|code| (200..600).contains(code))
+                .ok_or_else(|| malformed(line))?;
+            lines.push(line.get(4..).unwrap_or("").to_string());
+            match line.as_bytes().get(3) {
+                Some(b'-') => continue,
+                None | Some(b' ') => return Ok(Reply { code, lines }),
+                Some(_) => return Err(malformed(line)),
+            }
+        }
+    }
+}
+
+fn malformed(line: &str) -> NotmuchError {
+    NotmuchError::MailSendError(format!("Malformed SMTP reply: {}", line))
+}
+
+/// Remove Bcc headers so blind recipients stay hidden from the others.
+///
+/// Works on the raw bytes so an 8bit body goes out exactly as composed.
+fn without_bcc(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(message.len());
+    let mut in_headers = true;
+    let mut skipping = false;
+    for line in message.split_inclusive(|&b| b == b'\n') {
+        if in_headers {
+            let end = line
+                .iter()
+                .rposition(|&b| b != b'\r' && b != b'\n')
+                .map_or(0, |i| i + 1);
+            let content = &line[..end];
+            if content.is_empty() {
+                in_headers = false;
+            } else if line.starts_with(b" ") || line.starts_with(b"\t") {
+                if skipping {
+                    continue;
+                }
+            } else {
+                skipping = content
+                    .get(..4)
+                    .is_some_and(|name| name.eq_ignore_ascii_case(b"bcc:"));
+                if skipping {
+                    continue;
+                }
+            }
+        }
+        out.extend_from_slice(line);
+    }
+    out
+}
+
+/// Prepare message data for the DATA command: normalise line endings to
+/// CRLF, dot-stuff lines starting with `.` and append the terminator.
+fn encode_data(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(message.len() + message.len() / 64 + 5);
+    let mut at_line_start = true;
+    let mut bytes = message.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\r' | b'\n' => {
+                if byte == b'\r' && bytes.peek() == Some(&b'\n') {
+                    bytes.next();
+                }
+                out.extend_from_slice(b"\r\n");
+                at_line_start = true;
+                continue;
+            }
+            b'.' if at_line_start => out.push(b'.'),
+            _ => {}
+        }
+        out.push(byte);
+        at_line_start = false;
+    }
+    if !out.is_empty() && !out.ends_with(b"\r\n") {
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b".\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smtp_sender_default_ports() {
+        for (tls, port) in [
+            (SmtpTls::Starttls, 587),
+            (SmtpTls::Tls, 465),
+            (SmtpTls::None, 25),
+        ] {
+            let sender = SmtpMailSender::new(MailSenderConfig::Smtp {
+                host: "smtp.example.com".to_string(),
+                port: None,
+                tls,
+                user: None,
+                password_command: None,
+                password_env: None,
+                from: Some("Alice <alice@example.com>".to_string()),
+                ca_cert: None,
+                allow_insecure_auth: false,
+            })
+            .unwrap();
+            assert_eq!(sender.port, port);
+            assert_eq!(sender.hello_name(), "example.com");
+        }
+    }
+
+    #[test]
+    fn test_smtp_sender_invalid_config() {
+        let config = MailSenderConfig::Local {
+            msmtp_path: None,
+            config_path: None,
+            account: None,
+        };
+        assert!(SmtpMailSender::new(config).is_err());
+    }
+
+    #[test]
+    fn test_encode_data_dot_stuffing_and_line_endings() {
+        let data = encode_data(b"Subject: x\n\n.hidden\r\n..two\rlast");
+        assert_eq!(
+            data,
+            b"Subject: x\r\n\r\n..hidden\r\n...two\r\nlast\r\n.\r\n".to_vec()
+        );
+        assert_eq!(encode_data(b"body\r\n"), b"body\r\n.\r\n".to_vec());
+    }
+
+    #[test]
+    fn test_without_bcc_strips_folded_header() {
+        let message = b"From: a@example.com\r\nBcc: b@example.com,\r\n\x20c@example.com\r\nTo: d@example.com\r\n\r\nBcc: kept in body\r\n";
+        assert_eq!(
+            without_bcc(message),
+            b"From: a@example.com\r\nTo: d@example.com\r\n\r\nBcc: kept in body\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_without_bcc_keeps_8bit_body_intact() {
+        let message =
+            b"BCC: b@example.com\r\nContent-Transfer-Encoding: 8bit\r\n\r\nGr\xfc\xdfe \xff\r\n";
+        assert_eq!(
+            without_bcc(message),
+            b"Content-Transfer-Encoding: 8bit\r\n\r\nGr\xfc\xdfe \xff\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_check_classifies_failures() {
+        let reply = |code| Reply {
+            code,
+            lines: vec!["4.2.1 busy".to_string()],
+        };
+        assert!(check(reply(250), 2, "RCPT TO:<a@b>").is_ok());
+        let err = check(reply(451), 2, "RCPT TO:<a@b>").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Temporary SMTP failure after RCPT TO:<a@b>: 451")
+        );
+        let err = check(reply(550), 2, "DATA").unwrap_err();
+        assert!(err.to_string().contains("Permanent SMTP failure"));
+    }
+}
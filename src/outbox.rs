@@ -0,0 +1,825 @@
+//! A persistent outbox for outgoing mail.
+//!
+//! Every message is written to a spool directory before it is handed to a
+//! [`MailSender`], so a message that cannot be sent right away (say, the SSH
+//! host running msmtp is unreachable) is not lost. Failed deliveries are
+//! classified as transient or permanent: transient ones are retried by a
+//! background worker with exponential backoff, permanent ones stay in the
+//! outbox marked failed until they are retried or cancelled by hand.
+//!
+//! Sending can also be held back for a short grace period, during which
+//! the message can still be taken back ("undo send"). Messages can also be
+//! scheduled to go out later; they wait in the outbox
+//! until their time comes and can be taken out again to be edited until
+//! then. As the outbox is on disk, both queued and scheduled messages
+//! survive restarts.
+//!
+//! Each entry is one JSON file. While a message is being delivered its file
+//! is renamed, which keeps several processes sharing the spool (such as the
+//! web server and the TUI) from sending the same message twice.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use whynot::client::NotmuchClient;
+//! # use whynot::mail_sender::{ComposableMessage, MailSender};
+//! # use whynot::outbox::{Delivery, Outbox};
+//! # async fn example(
+//! #     sender: &dyn MailSender,
+//! #     client: &dyn NotmuchClient,
+//! # ) -> Result<(), Box<dyn std::error::Error>> {
+//! let outbox = Outbox::new("/home/me/.local/share/whynot/outbox");
+//! let message = ComposableMessage::builder()
+//!     .to("bob@example.com".to_string())
+//!     .subject("Hello".to_string())
+//!     .body("Hi Bob".to_string())
+//!     .build()?;
+//!
+//! match outbox.submit(sender, client, Some("Sent"), message, None).await? {
+//!     Delivery::Sent(id) => println!("Sent {}", id),
+//!     Delivery::Queued { retry_at, error } => {
+//!         println!("Will retry at {}: {}", retry_at, error)
+//!     }
+//!     Delivery::Failed(error) => println!("Gave up: {}", error),
+//!     Delivery::Scheduled(at) | Delivery::Held { until: at, .. } => {
+//!         println!("Will send at {}", at)
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::client::NotmuchClient;
+use crate::draft::{Draft, discard_draft};
+use crate::error::{NotmuchError, Result};
+use crate::mail_sender::{ComposableMessage, MailSender, send_and_file};
+
+/// How often the background worker looks for messages that are due.
+pub const WORKER_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Delay before the first retry; it doubles with every failed attempt.
+const INITIAL_BACKOFF_SECS: i64 = 30;
+
+/// Longest delay between two retries.
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+
+/// Claims older than this are assumed to belong to a process that died
+/// while sending, and are queued again.
+const STALE_CLAIM: Duration = Duration::from_secs(10 * 60);
+
+/// Extension of queued and failed entries.
+const QUEUED_EXT: &str = "json";
+
+/// Extension of entries claimed by a process that is sending them.
+const SENDING_EXT: &str = "sending";
+
+/// Where an outbox entry is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Waiting to be sent at `next_attempt`
+    Queued,
+    /// Scheduled by the user for `next_attempt`, not tried yet
+    Scheduled,
+    /// Being handed to the mail sender right now
+    Sending,
+    /// Rejected permanently; only sent again when retried by hand
+    Failed,
+}
+
+impl OutboxStatus {
+    /// Short label for queue views.
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutboxStatus::Queued => "queued",
+            OutboxStatus::Scheduled => "scheduled",
+            OutboxStatus::Sending => "sending",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+}
+
+/// A message waiting in the outbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub message: ComposableMessage,
+    /// Saved draft to remove once the message has been sent
+    #[serde(default)]
+    pub draft_id: Option<String>,
+    pub queued_at: DateTime<Utc>,
+    pub status: OutboxStatus,
+    /// Failed delivery attempts so far
+    pub attempts: u32,
+    pub next_attempt: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+impl OutboxEntry {
+    /// Recipients for queue views.
+    pub fn recipients(&self) -> String {
+        self.message.to.join(", ")
+    }
+
+    /// Whether the worker should try to send this entry at `now`.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.status, OutboxStatus::Queued | OutboxStatus::Scheduled)
+            && self.next_attempt <= now
+    }
+}
+
+/// What happened to a delivery attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    /// Sent, with the Message-ID; the entry is gone from the outbox
+    Sent(String),
+    /// Failed for now and stays queued until `retry_at`
+    Queued {
+        retry_at: DateTime<Utc>,
+        error: String,
+    },
+    /// Rejected permanently; the entry stays in the outbox marked failed
+    Failed(String),
+    /// Not tried yet, as it was scheduled for later
+    Scheduled(DateTime<Utc>),
+    /// Held in outbox entry `id` until `until`, and can still be taken back
+    Held { id: String, until: DateTime<Utc> },
+}
+
+/// Whether a failed delivery is worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The server or connection may recover, e.g. an unreachable host or a
+    /// 4xx reply
+    Transient,
+    /// Retrying will not help, e.g. a 5xx reply or a broken configuration
+    Permanent,
+}
+
+/// Classify a mail sender error.
+///
+/// The SMTP reply code decides where the sender reports one: 5xx replies
+/// are permanent. Configuration problems are permanent too; anything else,
+/// such as a connection that could not be opened, is assumed to be
+/// transient.
+pub fn classify(error: &NotmuchError) -> Failure {
+    match error {
+        NotmuchError::ConfigError(_)
+        | NotmuchError::InvalidInput(_)
+        | NotmuchError::InvalidAddress { .. } => Failure::Permanent,
+        NotmuchError::SmtpRejected { code, .. } if *code >= 500 => Failure::Permanent,
+        _ => Failure::Transient,
+    }
+}
+
+/// Delay before the next attempt after `attempts` failures.
+pub fn backoff(attempts: u32) -> chrono::Duration {
+    let secs = INITIAL_BACKOFF_SECS
+        .saturating_mul(1i64 << attempts.saturating_sub(1).min(20))
+        .min(MAX_BACKOFF_SECS);
+    chrono::Duration::seconds(secs)
+}
+
+/// Read a "send at" time as typed by the user, in local time.
+///
+/// Accepts a date and time (`2026-10-20 08:00`, or `2026-10-20T08:00` as
+/// sent by a browser's date-time input), `tomorrow 08:00`, a bare `08:00`
+/// (its next occurrence) and offsets such as `+30m`, `+2h` or `in 1 day`.
+/// Times that are not in the future are rejected.
+pub fn parse_send_at(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    let invalid = || {
+        NotmuchError::InvalidInput(format!(
+            "Cannot read send time \"{}\"; try 2026-10-20 08:00, tomorrow 08:00, 08:00 or +2h",
+            input
+        ))
+    };
+    let parse_time = |time: &str| {
+        ["%H:%M", "%H:%M:%S"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(time.trim(), format).ok())
+    };
+    let at_local = |date: NaiveDate, time: NaiveTime| {
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or_else(invalid)
+    };
+
+    let at = if let Some(offset) = input
+        .strip_prefix('+')
+        .or_else(|| input.strip_prefix("in "))
+    {
+        let offset = offset.trim();
+        let split = offset
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (amount, unit) = offset.split_at(split);
+        let amount: i64 = amount.parse().map_err(|_| invalid())?;
+        let duration = match unit.trim() {
+            "m" | "min" | "mins" | "minute" | "minutes" => chrono::Duration::try_minutes(amount),
+            "h" | "hour" | "hours" => chrono::Duration::try_hours(amount),
+            "d" | "day" | "days" => chrono::Duration::try_days(amount),
+            _ => None,
+        };
+        now.checked_add_signed(duration.ok_or_else(invalid)?)
+            .ok_or_else(invalid)?
+    } else if let Some(time) = input.strip_prefix("tomorrow") {
+        let date = now.date_naive().succ_opt().ok_or_else(invalid)?;
+        at_local(date, parse_time(time).ok_or_else(invalid)?)?
+    } else if let Some(time) = parse_time(input) {
+        let today = at_local(now.date_naive(), time)?;
+        if today > now {
+            today
+        } else {
+            let date = now.date_naive().succ_opt().ok_or_else(invalid)?;
+            at_local(date, time)?
+        }
+    } else {
+        let naive = ["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+            .ok_or_else(invalid)?;
+        at_local(naive.date(), naive.time())?
+    };
+
+    if at <= now {
+        return Err(NotmuchError::InvalidInput(format!(
+            "Send time {} is in the past",
+            at.format("%Y-%m-%d %H:%M")
+        )));
+    }
+    Ok(at.with_timezone(&Utc))
+}
+
+/// A spool directory of outgoing messages.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    dir: PathBuf,
+}
+
+impl Outbox {
+    /// Use `dir` as the spool; it is created when the first message is
+    /// queued.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The spool directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, id: &str, ext: &str) -> Result<PathBuf> {
+        // Entry IDs come from URLs and key presses; keep them in the spool
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(NotmuchError::InvalidInput(format!(
+                "Invalid outbox entry: {}",
+                id
+            )));
+        }
+        Ok(self.dir.join(format!("{}.{}", id, ext)))
+    }
+
+    /// Write `entry` atomically under the queued name.
+    async fn write(&self, entry: &OutboxEntry) -> Result<()> {
+        self.write_to(entry, &self.path(&entry.id, QUEUED_EXT)?)
+            .await
+    }
+
+    /// Write `entry` to `path` in one step, through a temporary file.
+    async fn write_to(&self, entry: &OutboxEntry, path: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let tmp = self.dir.join(format!(".{}.tmp", entry.id));
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(entry)?).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    /// Claim a queued entry, so no other process sends or changes it, and
+    /// return the path of the claimed file.
+    async fn claim(&self, id: &str) -> Result<PathBuf> {
+        let queued = self.path(id, QUEUED_EXT)?;
+        let claimed = self.path(id, SENDING_EXT)?;
+        // A claim's age is that of its file, which renaming keeps, so the
+        // file is touched first; otherwise `recover` in another process
+        // could take an entry written long ago back while it is sent
+        let claim = async {
+            touch(&queued).await?;
+            tokio::fs::rename(&queued, &claimed).await
+        };
+        match claim.await {
+            Ok(()) => Ok(claimed),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(self.not_queued(id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Queue a claimed entry again as `entry`.
+    ///
+    /// The claimed file is replaced and then renamed back, so the entry is
+    /// never queued while the claim is still held.
+    async fn release(&self, entry: &OutboxEntry, claimed: &Path) -> Result<()> {
+        self.write_to(entry, claimed).await?;
+        tokio::fs::rename(claimed, self.path(&entry.id, QUEUED_EXT)?).await?;
+        Ok(())
+    }
+
+    async fn read(path: &Path) -> Result<OutboxEntry> {
+        let data = tokio::fs::read(path).await?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Put `message` in the outbox, due straight away.
+    pub async fn enqueue(
+        &self,
+        message: ComposableMessage,
+        draft_id: Option<String>,
+    ) -> Result<OutboxEntry> {
+        let now = Utc::now();
+        let entry = OutboxEntry {
+            id: Uuid::new_v4().to_string(),
+            message,
+            draft_id,
+            queued_at: now,
+            status: OutboxStatus::Queued,
+            attempts: 0,
+            next_attempt: now,
+            last_error: None,
+        };
+        self.write(&entry).await?;
+        Ok(entry)
+    }
+
+    /// Put `message` in the outbox to be sent at `send_at`.
+    ///
+    /// The message is dated `send_at`. Its draft is removed straight away,
+    /// as the outbox now holds the message; [`reopen`](Self::reopen) saves
+    /// it as a draft again when it is taken out to be edited.
+    pub async fn schedule(
+        &self,
+        client: &dyn NotmuchClient,
+        mut message: ComposableMessage,
+        draft_id: Option<String>,
+        send_at: DateTime<Utc>,
+    ) -> Result<OutboxEntry> {
+        message.date = send_at;
+        let entry = OutboxEntry {
+            id: Uuid::new_v4().to_string(),
+            message,
+            draft_id: None,
+            queued_at: Utc::now(),
+            status: OutboxStatus::Scheduled,
+            attempts: 0,
+            next_attempt: send_at,
+            last_error: None,
+        };
+        self.write(&entry).await?;
+
+        if let Some(draft_id) = &draft_id
+            && let Err(e) = discard_draft(client, draft_id).await
+        {
+            tracing::warn!("Failed to remove draft {}: {}", draft_id, e);
+        }
+        Ok(entry)
+    }
+
+    /// Put `message` in the outbox to be sent once `delay` has passed.
+    ///
+    /// Until then it can be taken back with [`cancel`](Self::cancel) or
+    /// [`reopen`](Self::reopen); [`spawn_delivery`] sends it when the time
+    /// is up, or the worker does if this process stops first.
+    pub async fn hold(
+        &self,
+        client: &dyn NotmuchClient,
+        message: ComposableMessage,
+        draft_id: Option<String>,
+        delay: Duration,
+    ) -> Result<OutboxEntry> {
+        let delay = chrono::Duration::from_std(delay)
+            .map_err(|e| NotmuchError::InvalidInput(format!("Invalid delay: {}", e)))?;
+        self.schedule(client, message, draft_id, Utc::now() + delay)
+            .await
+    }
+
+    /// All entries, oldest first. Unreadable files are skipped.
+    pub async fn list(&self) -> Result<Vec<OutboxEntry>> {
+        let mut dir = match tokio::fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            let sending = match path.extension().and_then(|ext| ext.to_str()) {
+                Some(QUEUED_EXT) => false,
+                Some(SENDING_EXT) => true,
+                _ => continue,
+            };
+            match Self::read(&path).await {
+                Ok(mut entry) => {
+                    if sending {
+                        entry.status = OutboxStatus::Sending;
+                    }
+                    entries.push(entry);
+                }
+                // Possibly renamed by a worker in the meantime
+                Err(NotmuchError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Skipping outbox file {}: {}", path.display(), e),
+            }
+        }
+        entries.sort_by_key(|entry| entry.queued_at);
+        Ok(entries)
+    }
+
+    /// Look up a queued or failed entry.
+    pub async fn get(&self, id: &str) -> Result<OutboxEntry> {
+        match Self::read(&self.path(id, QUEUED_EXT)?).await {
+            Err(NotmuchError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(self.not_queued(id))
+            }
+            result => result,
+        }
+    }
+
+    fn not_queued(&self, id: &str) -> NotmuchError {
+        let sending = self
+            .path(id, SENDING_EXT)
+            .map(|path| path.exists())
+            .unwrap_or(false);
+        if sending {
+            NotmuchError::InvalidInput(format!("Outbox entry {} is being sent", id))
+        } else {
+            NotmuchError::InvalidInput(format!("No outbox entry {}", id))
+        }
+    }
+
+    /// Remove an entry that is not being sent, returning its message.
+    pub async fn cancel(&self, id: &str) -> Result<OutboxEntry> {
+        let entry = self.get(id).await?;
+        match tokio::fs::remove_file(self.path(id, QUEUED_EXT)?).await {
+            Ok(()) => Ok(entry),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(self.not_queued(id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Take an entry out of the outbox to edit it.
+    ///
+    /// Its message is saved as a draft in `drafts_folder` (replacing the
+    /// draft it was sent from, if that is still around), so nothing is lost
+    /// if editing is abandoned.
+    pub async fn reopen(
+        &self,
+        id: &str,
+        client: &dyn NotmuchClient,
+        drafts_folder: &str,
+    ) -> Result<(OutboxEntry, Draft)> {
+        let entry = self.cancel(id).await?;
+        let mut draft = Draft::from_message(&entry.message);
+        draft.id = entry.draft_id.clone();
+        let from = entry.message.from.as_deref();
+        if let Err(e) = draft.save(client, drafts_folder, from).await {
+            // Keep the message queued rather than losing it
+            self.write(&entry).await?;
+            return Err(e);
+        }
+        Ok((entry, draft))
+    }
+
+    /// Queue an entry to be sent straight away, also after a permanent
+    /// failure or before the time it was scheduled for.
+    pub async fn retry(&self, id: &str) -> Result<OutboxEntry> {
+        let claimed = self.claim(id).await?;
+        let mut entry = match Self::read(&claimed).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                tokio::fs::rename(&claimed, self.path(id, QUEUED_EXT)?).await?;
+                return Err(e);
+            }
+        };
+        entry.status = OutboxStatus::Queued;
+        entry.next_attempt = Utc::now();
+        self.release(&entry, &claimed).await?;
+        Ok(entry)
+    }
+
+    /// Try to send one entry now, whether it is due or not.
+    ///
+    /// On success the sent copy is filed in `sent_folder` and the entry and
+    /// its draft are removed; otherwise the failure is recorded and the
+    /// entry rescheduled or marked failed. An error means the entry could
+    /// not be claimed or the spool could not be written.
+    pub async fn deliver(
+        &self,
+        id: &str,
+        sender: &dyn MailSender,
+        client: &dyn NotmuchClient,
+        sent_folder: Option<&str>,
+    ) -> Result<Delivery> {
+        let claimed = self.claim(id).await?;
+        let mut entry = match Self::read(&claimed).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                // Leave the file where the queue view can show it
+                tokio::fs::rename(&claimed, self.path(id, QUEUED_EXT)?).await?;
+                return Err(e);
+            }
+        };
+
+        let result = send_and_file(sender, client, entry.message.clone(), sent_folder).await;
+        let delivery = match result {
+            Ok(message_id) => {
+                // The message is out; a leftover claim must not make it look
+                // as if it was not
+                if let Err(e) = tokio::fs::remove_file(&claimed).await {
+                    tracing::warn!("Failed to remove sent outbox entry {}: {}", id, e);
+                }
+                if let Some(draft_id) = &entry.draft_id
+                    && let Err(e) = discard_draft(client, draft_id).await
+                {
+                    tracing::warn!("Failed to remove draft {}: {}", draft_id, e);
+                }
+                return Ok(Delivery::Sent(message_id));
+            }
+            Err(e) => {
+                entry.attempts += 1;
+                entry.last_error = Some(e.to_string());
+                match classify(&e) {
+                    Failure::Permanent => {
+                        entry.status = OutboxStatus::Failed;
+                        Delivery::Failed(e.to_string())
+                    }
+                    Failure::Transient => {
+                        entry.status = OutboxStatus::Queued;
+                        entry.next_attempt = Utc::now() + backoff(entry.attempts);
+                        Delivery::Queued {
+                            retry_at: entry.next_attempt,
+                            error: e.to_string(),
+                        }
+                    }
+                }
+            }
+        };
+
+        self.release(&entry, &claimed).await?;
+        Ok(delivery)
+    }
+
+    /// Spool `message` and make the first delivery attempt straight away.
+    ///
+    /// A message rejected permanently on this first attempt is taken out of
+    /// the outbox again, since the caller still has it and can show the
+    /// error where it was composed; the result is then an error.
+    pub async fn submit(
+        &self,
+        sender: &dyn MailSender,
+        client: &dyn NotmuchClient,
+        sent_folder: Option<&str>,
+        message: ComposableMessage,
+        draft_id: Option<String>,
+    ) -> Result<Delivery> {
+        let entry = self.enqueue(message, draft_id).await?;
+        match self.deliver(&entry.id, sender, client, sent_folder).await? {
+            Delivery::Failed(error) => {
+                self.cancel(&entry.id).await?;
+                Err(NotmuchError::MailSendError(error))
+            }
+            delivery => Ok(delivery),
+        }
+    }
+
+    /// Send every entry that is due, returning what happened to each.
+    pub async fn deliver_due(
+        &self,
+        sender: &dyn MailSender,
+        client: &dyn NotmuchClient,
+        sent_folder: Option<&str>,
+    ) -> Result<Vec<(OutboxEntry, Result<Delivery>)>> {
+        let now = Utc::now();
+        let mut results = Vec::new();
+        for entry in self.list().await? {
+            if !entry.is_due(now) {
+                continue;
+            }
+            let delivery = self.deliver(&entry.id, sender, client, sent_folder).await;
+            results.push((entry, delivery));
+        }
+        Ok(results)
+    }
+
+    /// Queue again entries left claimed by a process that stopped while
+    /// sending them.
+    ///
+    /// The message may or may not have gone out, so it could be sent twice;
+    /// losing it would be worse.
+    pub async fn recover(&self) -> Result<usize> {
+        let mut dir = match tokio::fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut recovered = 0;
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SENDING_EXT) {
+                continue;
+            }
+            let age = file
+                .metadata()
+                .await?
+                .modified()?
+                .elapsed()
+                .unwrap_or_default();
+            if age >= STALE_CLAIM {
+                tokio::fs::rename(&path, path.with_extension(QUEUED_EXT)).await?;
+                recovered += 1;
+            }
+        }
+        Ok(recovered)
+    }
+}
+
+/// Set the modification time of the file at `path` to now.
+async fn touch(path: &Path) -> std::io::Result<()> {
+    let file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.into_std().await.set_modified(SystemTime::now())
+}
+
+/// Deliver due outbox entries in the background every [`WORKER_INTERVAL`]
+/// for as long as the process runs.
+pub fn spawn_worker(
+    outbox: Outbox,
+    sender: Arc<dyn MailSender>,
+    client: Arc<dyn NotmuchClient>,
+    sent_folder: Option<String>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WORKER_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = outbox.recover().await {
+                tracing::warn!("Failed to recover outbox entries: {}", e);
+            }
+            let results = outbox
+                .deliver_due(sender.as_ref(), client.as_ref(), sent_folder.as_deref())
+                .await;
+            match results {
+                Ok(results) => {
+                    for (entry, delivery) in results {
+                        match delivery {
+                            Ok(Delivery::Sent(id)) => tracing::info!("Sent queued message {}", id),
+                            Ok(Delivery::Queued { retry_at, error }) => tracing::warn!(
+                                "Sending {} failed, retrying at {}: {}",
+                                entry.id,
+                                retry_at,
+                                error
+                            ),
+                            Ok(Delivery::Failed(error)) => {
+                                tracing::error!("Sending {} failed: {}", entry.id, error)
+                            }
+                            Err(e) => tracing::warn!("Could not deliver {}: {}", entry.id, e),
+                            Ok(Delivery::Scheduled(_) | Delivery::Held { .. }) => {}
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to read the outbox: {}", e),
+            }
+        }
+    })
+}
+
+/// Deliver one entry once `delay` has passed, unless it has been taken out
+/// of the outbox by then.
+pub fn spawn_delivery(
+    outbox: Outbox,
+    id: String,
+    delay: Duration,
+    sender: Arc<dyn MailSender>,
+    client: Arc<dyn NotmuchClient>,
+    sent_folder: Option<String>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let delivery = outbox
+            .deliver(
+                &id,
+                sender.as_ref(),
+                client.as_ref(),
+                sent_folder.as_deref(),
+            )
+            .await;
+        match delivery {
+            Ok(Delivery::Sent(message_id)) => tracing::info!("Sent message {}", message_id),
+            Ok(delivery) => tracing::warn!("Sending {} did not succeed: {:?}", id, delivery),
+            // Undone, or picked up by the worker
+            Err(e) => tracing::debug!("Not delivering {}: {}", id, e),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let permanent = NotmuchError::SmtpRejected {
+            code: 550,
+            message: "Permanent SMTP failure after RCPT TO:<a@b>: 550 5.1.1 No such user"
+                .to_string(),
+        };
+        assert_eq!(classify(&permanent), Failure::Permanent);
+        let temporary = NotmuchError::SmtpRejected {
+            code: 451,
+            message: "Temporary SMTP failure after DATA: 451 4.3.0 Try later".to_string(),
+        };
+        assert_eq!(classify(&temporary), Failure::Transient);
+
+        // The wording of an error does not count
+        let text = NotmuchError::CommandFailed("Permanent SMTP failure".to_string());
+        assert_eq!(classify(&text), Failure::Transient);
+        let ssh = NotmuchError::SshError(
+            "SSH msmtp command failed: ssh: connect to host mail port 22: No route to host"
+                .to_string(),
+        );
+        assert_eq!(classify(&ssh), Failure::Transient);
+        assert_eq!(
+            classify(&NotmuchError::ConfigError("no recipients".to_string())),
+            Failure::Permanent
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), chrono::Duration::seconds(30));
+        assert_eq!(backoff(2), chrono::Duration::seconds(60));
+        assert_eq!(backoff(4), chrono::Duration::seconds(240));
+        assert_eq!(backoff(40), chrono::Duration::hours(1));
+    }
+
+    #[test]
+    fn test_parse_send_at() {
+        let now = Local.with_ymd_and_hms(2026, 10, 19, 21, 30, 0).unwrap();
+        let local = |y, m, d, h, min| {
+            Local
+                .with_ymd_and_hms(y, m, d, h, min, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        assert_eq!(
+            parse_send_at("2026-10-20T08:00", now).unwrap(),
+            local(2026, 10, 20, 8, 0)
+        );
+        assert_eq!(
+            parse_send_at("2026-10-20 08:15", now).unwrap(),
+            local(2026, 10, 20, 8, 15)
+        );
+        assert_eq!(
+            parse_send_at("tomorrow 07:45", now).unwrap(),
+            local(2026, 10, 20, 7, 45)
+        );
+        // Bare times are the next time the clock shows them
+        assert_eq!(
+            parse_send_at("08:00", now).unwrap(),
+            local(2026, 10, 20, 8, 0)
+        );
+        assert_eq!(
+            parse_send_at("23:00", now).unwrap(),
+            local(2026, 10, 19, 23, 0)
+        );
+        assert_eq!(
+            parse_send_at("+2h", now).unwrap(),
+            (now + chrono::Duration::hours(2)).with_timezone(&Utc)
+        );
+        assert_eq!(
+            parse_send_at("in 30 minutes", now).unwrap(),
+            (now + chrono::Duration::minutes(30)).with_timezone(&Utc)
+        );
+
+        assert!(parse_send_at("2026-10-19 08:00", now).is_err());
+        assert!(parse_send_at("next week", now).is_err());
+        assert!(parse_send_at("+2 fortnights", now).is_err());
+        assert!(parse_send_at("+100000000d", now).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_ids_outside_the_spool() {
+        let outbox = Outbox::new("/tmp/whynot-outbox-test");
+        assert!(outbox.get("../etc/passwd").await.is_err());
+        assert!(outbox.cancel("").await.is_err());
+    }
+}
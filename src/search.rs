@@ -13,6 +13,7 @@ pub struct SearchItem {
     pub matched: u32,
     pub total: u32,
     pub authors: String,
+    #[serde(deserialize_with = "deserialize_subject")]
     pub subject: String,
     pub query: Vec<Option<String>>,
     pub tags: Vec<String>,
@@ -23,6 +24,58 @@ impl SearchItem {
     pub fn thread_id(&self) -> &str {
         &self.thread
     }
+
+    /// Message IDs of the thread, taken from the `id:` terms of `query`:
+    /// matched messages first, then the rest.
+    pub fn message_ids(&self) -> Vec<String> {
+        self.query
+            .iter()
+            .flatten()
+            .flat_map(|query| parse_id_terms(query))
+            .collect()
+    }
+}
+
+/// Extract the IDs from a notmuch query of the form `id:a id:"b c"`.
+///
+/// notmuch quotes IDs containing spaces or quotes, doubling embedded quotes.
+fn parse_id_terms(query: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = query.trim_start();
+    while let Some(term) = rest.strip_prefix("id:") {
+        if let Some(quoted) = term.strip_prefix('"') {
+            let mut id = String::new();
+            let mut chars = quoted.char_indices().peekable();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                if c == '"' {
+                    if chars.peek().is_some_and(|&(_, next)| next == '"') {
+                        chars.next();
+                    } else {
+                        end = i + 1;
+                        break;
+                    }
+                }
+                id.push(c);
+            }
+            ids.push(id);
+            rest = quoted[end..].trim_start();
+        } else {
+            let end = term.find(char::is_whitespace).unwrap_or(term.len());
+            ids.push(term[..end].to_string());
+            rest = term[end..].trim_start();
+        }
+    }
+    ids
+}
+
+fn deserialize_subject<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(crate::common::decode_encoded_words(&String::deserialize(
+        deserializer,
+    )?))
 }
 
 #[cfg(test)]
@@ -133,4 +186,32 @@ mod tests {
 
         assert_eq!(item.thread_id(), "00000000000276db");
     }
+
+    #[test]
+    fn test_message_ids() {
+        let item = SearchItem {
+            thread: "00000000000276db".to_string(),
+            timestamp: 1748767608,
+            date_relative: "46 mins. ago".to_string(),
+            matched: 1,
+            total: 3,
+            authors: "Test Author".to_string(),
+            subject: "Test Subject".to_string(),
+            query: vec![
+                Some("id:a@example.com".to_string()),
+                Some(r#"id:b@example.com id:"odd ""id"" here" id:c@example.com"#.to_string()),
+            ],
+            tags: vec![],
+        };
+
+        assert_eq!(
+            item.message_ids(),
+            vec![
+                "a@example.com",
+                "b@example.com",
+                "odd \"id\" here",
+                "c@example.com"
+            ]
+        );
+    }
 }
@@ -0,0 +1,422 @@
+//! Deserializer for notmuch's `--format=sexp` output.
+//!
+//! notmuch prints the same structures as `--format=json`, written as Emacs
+//! S-expressions: objects become property lists (`(:id "x" :match t)`),
+//! arrays become lists and strings only escape `"` and `\`. The encoding is
+//! lossy in two places, which this deserializer resolves from the type
+//! being deserialized:
+//!
+//! - `nil` is false, null and the empty list
+//! - `()` is both the empty list and the empty object
+//!
+//! Errors are reported as [`serde_json::Error`] so they surface as
+//! [`NotmuchError::ParseError`](crate::error::NotmuchError::ParseError) just
+//! like JSON output.
+//!
+//! # Examples
+//!
+//! ```
+//! use whynot::search::SearchResult;
+//!
+//! let output = r#"((:thread "0000000000000001" :timestamp 1700000000
+//!   :date_relative "today" :matched 1 :total 1 :authors "Alice"
+//!   :subject "Hello" :query ("id:a@example.com" nil) :tags ("inbox")))"#;
+//! let result: SearchResult = whynot::sexp::from_str(output).unwrap();
+//! assert_eq!(result.0[0].query, vec![Some("id:a@example.com".to_string()), None]);
+//! ```
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Error as _, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+
+/// Errors produced while reading sexp output.
+pub type Error = serde_json::Error;
+
+/// A parsed S-expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexp {
+    /// `nil`
+    Nil,
+    /// `t`
+    True,
+    Integer(i64),
+    String(String),
+    /// A `:keyword`, stored without the colon
+    Keyword(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    /// Whether this is a property list, i.e. a list starting with a keyword.
+    fn is_plist(&self) -> bool {
+        matches!(self, Sexp::List(items) if matches!(items.first(), Some(Sexp::Keyword(_))))
+    }
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sexp::Nil => write!(f, "nil"),
+            Sexp::True => write!(f, "t"),
+            Sexp::Integer(value) => write!(f, "{}", value),
+            Sexp::String(value) => {
+                write!(f, "\"")?;
+                for c in value.chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\"")
+            }
+            Sexp::Keyword(name) => write!(f, ":{}", name),
+            Sexp::List(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Deserialize a value from notmuch sexp output.
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, Error> {
+    T::deserialize(parse(input)?)
+}
+
+/// Parse a single S-expression, ignoring surrounding whitespace.
+pub fn parse(input: &str) -> Result<Sexp, Error> {
+    let mut parser = Parser {
+        input,
+        chars: input.char_indices().peekable(),
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some((offset, _)) => Err(Error::custom(format!(
+            "trailing characters at offset {}",
+            offset
+        ))),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn value(&mut self) -> Result<Sexp, Error> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            None => Err(Error::custom("unexpected end of input")),
+            Some((_, '(')) => {
+                self.chars.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.chars.peek() {
+                        Some((_, ')')) => {
+                            self.chars.next();
+                            return Ok(Sexp::List(items));
+                        }
+                        None => return Err(Error::custom("unterminated list")),
+                        Some(_) => items.push(self.value()?),
+                    }
+                }
+            }
+            Some((offset, ')')) => Err(Error::custom(format!(
+                "unexpected ')' at offset {}",
+                offset
+            ))),
+            Some((_, '"')) => {
+                self.chars.next();
+                self.string()
+            }
+            Some((start, _)) => {
+                let mut end = self.input.len();
+                while let Some(&(offset, c)) = self.chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        end = offset;
+                        break;
+                    }
+                    self.chars.next();
+                }
+                atom(&self.input[start..end], start)
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<Sexp, Error> {
+        let mut value = String::new();
+        while let Some((_, c)) = self.chars.next() {
+            match c {
+                '"' => return Ok(Sexp::String(value)),
+                '\\' => match self.chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, escaped)) => value.push(escaped),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        Err(Error::custom("unterminated string"))
+    }
+}
+
+fn atom(token: &str, offset: usize) -> Result<Sexp, Error> {
+    match token {
+        "nil" => Ok(Sexp::Nil),
+        "t" => Ok(Sexp::True),
+        _ => {
+            if let Some(name) = token.strip_prefix(':') {
+                Ok(Sexp::Keyword(name.to_string()))
+            } else if let Ok(value) = token.parse() {
+                Ok(Sexp::Integer(value))
+            } else {
+                Err(Error::custom(format!(
+                    "unexpected symbol `{}` at offset {}",
+                    token, offset
+                )))
+            }
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Sexp {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Nil => visitor.visit_unit(),
+            Sexp::True => visitor.visit_bool(true),
+            Sexp::Integer(value) => visitor.visit_i64(value),
+            Sexp::String(value) | Sexp::Keyword(value) => visitor.visit_string(value),
+            list if list.is_plist() => list.deserialize_map(visitor),
+            Sexp::List(items) => visitor.visit_seq(SeqDeserializer(items.into_iter())),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Nil => visitor.visit_bool(false),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Nil => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Nil => visitor.visit_seq(SeqDeserializer(Vec::new().into_iter())),
+            Sexp::List(items) => visitor.visit_seq(SeqDeserializer(items.into_iter())),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Nil => visitor.visit_map(MapDeserializer::default()),
+            Sexp::List(items) if items.is_empty() => visitor.visit_map(MapDeserializer::default()),
+            Sexp::List(items) if items.len() % 2 == 0 => visitor.visit_map(MapDeserializer {
+                items: items.into_iter(),
+                value: None,
+            }),
+            list if list.is_plist() => Err(Error::custom("property list with odd length")),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct enum identifier
+        ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, Error> for Sexp {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct SeqDeserializer(std::vec::IntoIter<Sexp>);
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0.next().map(|item| seed.deserialize(item)).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+#[derive(Default)]
+struct MapDeserializer {
+    items: std::vec::IntoIter<Sexp>,
+    value: Option<Sexp>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some(key) = self.items.next() else {
+            return Ok(None);
+        };
+        let Sexp::Keyword(name) = key else {
+            return Err(Error::custom(format!("expected a :keyword, found {}", key)));
+        };
+        self.value = self.items.next();
+        seed.deserialize(name.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("property list key without a value"))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len() / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thread::Thread;
+
+    #[test]
+    fn test_parse_atoms_and_lists() {
+        assert_eq!(
+            parse(r#"(:id "a\"b\\c" :n -3 :ok t :no nil :list ())"#).unwrap(),
+            Sexp::List(vec![
+                Sexp::Keyword("id".to_string()),
+                Sexp::String("a\"b\\c".to_string()),
+                Sexp::Keyword("n".to_string()),
+                Sexp::Integer(-3),
+                Sexp::Keyword("ok".to_string()),
+                Sexp::True,
+                Sexp::Keyword("no".to_string()),
+                Sexp::Nil,
+                Sexp::Keyword("list".to_string()),
+                Sexp::List(vec![]),
+            ])
+        );
+        assert!(parse("(unterminated").is_err());
+        assert!(parse("\"open").is_err());
+        assert!(parse("() ()").is_err());
+        assert!(parse("(symbol)").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let input = r#"((:id "x" :content "line\nquote \" back \\") nil t 42)"#;
+        let value = parse(input).unwrap();
+        assert_eq!(parse(&value.to_string()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_nil_depends_on_target_type() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Target {
+            flag: bool,
+            maybe: Option<String>,
+            list: Vec<String>,
+            map: std::collections::HashMap<String, String>,
+        }
+
+        let target: Target = from_str("(:flag nil :maybe nil :list nil :map ())").unwrap();
+        assert_eq!(
+            target,
+            Target {
+                flag: false,
+                maybe: None,
+                list: vec![],
+                map: Default::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_thread_with_replies() {
+        let output = r#"((((:id "a@example.com" :match t :excluded nil
+            :filename ("/mail/cur/a") :timestamp 1700000000 :date_relative "today"
+            :tags ("inbox" "unread") :duplicate 1
+            :body ((:id 1 :content-type "text/plain" :content "Hello\n"))
+            :crypto ()
+            :headers (:Subject "Hi" :From "Alice <alice@example.com>"
+                      :To "bob@example.com" :Date "Tue, 14 Nov 2023 22:13:20 +0000"))
+           (((:id "b@example.com" :match nil :excluded nil :filename ("/mail/cur/b")
+              :timestamp 1700000300 :date_relative "today" :tags ("inbox")
+              :body ((:id 1 :content-type "text/plain" :content "Hi back"))
+              :crypto ()
+              :headers (:Subject "Re: Hi" :From "bob@example.com"
+                        :Date "Tue, 14 Nov 2023 22:18:20 +0000"
+                        :In-Reply-To "<a@example.com>"))
+             ())))))"#;
+
+        let thread: Thread = from_str(output).unwrap();
+        let messages = thread.get_messages();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].is_match);
+        assert!(!messages[1].is_match);
+        assert_eq!(messages[0].get_text_content(), Some("Hello\n"));
+        assert_eq!(
+            messages[1].headers.get("in-reply-to"),
+            Some(&"<a@example.com>".to_string())
+        );
+        assert_eq!(thread.parent("b@example.com").unwrap().id, "a@example.com");
+    }
+}
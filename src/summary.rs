@@ -0,0 +1,330 @@
+//! Statistics about a whole thread.
+//!
+//! notmuch search results only carry an `authors` string and message counts.
+//! [`ThreadSummary`] is computed from a full [`Thread`] instead and answers
+//! the questions a reader has before opening a conversation: who took part,
+//! how much is unread, and who spoke last.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::body::{BodyPart, PartRole};
+use crate::common::Mailbox;
+use crate::thread::{Message, Thread};
+
+/// Someone who sent at least one message in a thread.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Participant {
+    pub mailbox: Mailbox,
+    /// Number of messages this participant sent
+    pub messages: usize,
+}
+
+/// Aggregate information about a thread.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use whynot::client::{create_client, ClientConfig, NotmuchClient};
+/// # use whynot::summary::ThreadSummary;
+/// # async fn example() -> whynot::error::Result<()> {
+/// let client = create_client(ClientConfig::local())?;
+/// let thread = client.show("thread:0000000000000001").await?;
+/// let summary = ThreadSummary::new(&thread, &["me@example.com"]);
+/// if summary.sent_by_user {
+///     println!("Waiting for a reply from {}", summary.participant_names());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    /// Senders in order of their first message
+    pub participants: Vec<Participant>,
+    pub messages: usize,
+    pub unread: usize,
+    /// Number of attachment parts, not counting inline images
+    pub attachments: usize,
+    /// Total size in bytes of all message parts, as reported by notmuch
+    pub total_size: u64,
+    pub first_activity: Option<DateTime<Utc>>,
+    pub last_activity: Option<DateTime<Utc>>,
+    /// Whether the most recent message was sent by one of the user's
+    /// addresses
+    pub sent_by_user: bool,
+    /// Tags on the most recent message
+    pub latest_tags: Vec<String>,
+}
+
+impl ThreadSummary {
+    /// Summarize `thread`, treating messages from any of `user_addresses`
+    /// as sent by the user.
+    pub fn new(thread: &Thread, user_addresses: &[&str]) -> Self {
+        let messages = thread.get_messages();
+
+        let mut participants: Vec<Participant> = Vec::new();
+        for message in &messages {
+            let Some(sender) = message.headers.sender() else {
+                continue;
+            };
+            match participants
+                .iter_mut()
+                .find(|p| p.mailbox.has_address(&sender.address))
+            {
+                Some(participant) => participant.messages += 1,
+                None => participants.push(Participant {
+                    mailbox: sender,
+                    messages: 1,
+                }),
+            }
+        }
+
+        // Depth-first order is reply order, not time order, so pick the
+        // latest message by timestamp
+        let latest = messages.iter().max_by_key(|message| message.timestamp);
+        let sent_by_user = latest
+            .and_then(|message| message.headers.sender())
+            .is_some_and(|sender| {
+                user_addresses
+                    .iter()
+                    .any(|address| sender.has_address(address))
+            });
+
+        ThreadSummary {
+            participants,
+            messages: messages.len(),
+            unread: messages.iter().filter(|m| m.is_unread()).count(),
+            attachments: messages.iter().map(|m| attachment_count(m)).sum(),
+            total_size: messages.iter().map(|m| message_size(m)).sum(),
+            first_activity: messages
+                .iter()
+                .map(|m| m.timestamp)
+                .min()
+                .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+            last_activity: latest.and_then(|m| DateTime::from_timestamp(m.timestamp, 0)),
+            sent_by_user,
+            latest_tags: latest.map(|m| m.tags.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// Participant names with message counts, e.g. `Alice (2), Bob`.
+    pub fn participant_names(&self) -> String {
+        self.participants
+            .iter()
+            .map(|p| {
+                if p.messages > 1 {
+                    format!("{} ({})", p.mailbox.display_name(), p.messages)
+                } else {
+                    p.mailbox.display_name().to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// One-line description such as
+    /// `4 messages, 1 unread, 2 attachments (1.2 MB)`.
+    pub fn counts(&self) -> String {
+        let mut parts = vec![plural(self.messages, "message")];
+        if self.unread > 0 {
+            parts.push(format!("{} unread", self.unread));
+        }
+        if self.attachments > 0 {
+            parts.push(plural(self.attachments, "attachment"));
+        }
+        format!("{} ({})", parts.join(", "), format_size(self.total_size))
+    }
+
+    /// Multi-line text for a tooltip.
+    pub fn tooltip(&self) -> String {
+        let mut lines = vec![self.participant_names(), self.counts()];
+        if let (Some(first), Some(last)) = (self.first_activity, self.last_activity) {
+            lines.push(format!(
+                "{} – {}",
+                first.format("%Y-%m-%d %H:%M"),
+                last.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        if self.sent_by_user {
+            lines.push("Last message sent by you".to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+fn attachment_count(message: &Message) -> usize {
+    message
+        .body
+        .iter()
+        .flat_map(BodyPart::walk)
+        .filter(|part| part.role() == PartRole::Attachment)
+        .count()
+}
+
+/// Size of the leaf parts of a message: the decoded length notmuch reports
+/// for attachments, or the length of included text.
+fn message_size(message: &Message) -> u64 {
+    message
+        .body
+        .iter()
+        .flat_map(BodyPart::walk)
+        .filter(|part| part.role() != PartRole::Container)
+        .map(|part| {
+            part.content_length
+                .or_else(|| part.text().map(|text| text.len() as u64))
+                .unwrap_or(0)
+        })
+        .sum()
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Format a byte count for display, e.g. `1.2 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::BodyContent;
+    use crate::common::{CryptoInfo, Headers};
+    use crate::thread::{MessageNode, ThreadLevel};
+
+    fn message(from: &str, timestamp: i64, tags: &[&str], body: Vec<BodyPart>) -> Message {
+        Message {
+            id: format!("{}@example.com", timestamp),
+            is_match: true,
+            excluded: false,
+            filename: vec![],
+            timestamp,
+            date_relative: "Today".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            duplicate: None,
+            body,
+            crypto: CryptoInfo::default(),
+            headers: Headers {
+                subject: Some("Plans".to_string()),
+                from: from.to_string(),
+                to: None,
+                reply_to: None,
+                date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+                additional: std::collections::HashMap::new(),
+            },
+        }
+    }
+
+    fn part(content_type: &str, content: BodyContent) -> BodyPart {
+        BodyPart {
+            id: 1,
+            content_type: content_type.to_string(),
+            content,
+            content_disposition: None,
+            content_id: None,
+            content_charset: None,
+            filename: None,
+            content_transfer_encoding: None,
+            content_length: None,
+        }
+    }
+
+    fn thread() -> Thread {
+        let mut pdf = part("application/pdf", BodyContent::Empty);
+        pdf.content_disposition = Some("attachment".to_string());
+        pdf.filename = Some("plan.pdf".to_string());
+        pdf.content_length = Some(2048);
+
+        let first = message(
+            "Alice <alice@example.com>",
+            1_700_000_000,
+            &["inbox"],
+            vec![part(
+                "multipart/mixed",
+                BodyContent::Multipart(vec![
+                    part("text/plain", BodyContent::Text("Hello".to_string())),
+                    pdf,
+                ]),
+            )],
+        );
+        let reply = message(
+            "Me <me@example.com>",
+            1_700_000_300,
+            &["inbox", "replied"],
+            vec![],
+        );
+        let late_sibling = message(
+            "ALICE@example.com",
+            1_700_000_200,
+            &["inbox", "unread"],
+            vec![],
+        );
+
+        // The reply is walked before the sibling but is the latest message
+        Thread(vec![ThreadLevel(vec![MessageNode(
+            first,
+            vec![ThreadLevel(vec![
+                MessageNode(reply, vec![]),
+                MessageNode(late_sibling, vec![]),
+            ])],
+        )])])
+    }
+
+    #[test]
+    fn test_summary_counts() {
+        let summary = ThreadSummary::new(&thread(), &["me@example.com"]);
+
+        assert_eq!(summary.messages, 3);
+        assert_eq!(summary.unread, 1);
+        assert_eq!(summary.attachments, 1);
+        assert_eq!(summary.total_size, 2048 + 5);
+        assert_eq!(summary.participants.len(), 2);
+        assert_eq!(summary.participants[0].messages, 2);
+        assert_eq!(summary.participant_names(), "Alice (2), Me");
+        assert_eq!(
+            summary.counts(),
+            "3 messages, 1 unread, 1 attachment (2.0 KB)"
+        );
+    }
+
+    #[test]
+    fn test_summary_latest_message() {
+        let summary = ThreadSummary::new(&thread(), &["me@example.com"]);
+        assert!(summary.sent_by_user);
+        assert_eq!(summary.latest_tags, vec!["inbox", "replied"]);
+        assert_eq!(
+            summary.first_activity.map(|t| t.timestamp()),
+            Some(1_700_000_000)
+        );
+        assert_eq!(
+            summary.last_activity.map(|t| t.timestamp()),
+            Some(1_700_000_300)
+        );
+        assert!(summary.tooltip().contains("Last message sent by you"));
+
+        let summary = ThreadSummary::new(&thread(), &[]);
+        assert!(!summary.sent_by_user);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+use crate::error::Result;
+use crate::mail_sender::{ComposableMessage, MailSender};
+use crate::thread::Message;
+
+/// Records the messages it is asked to send instead of delivering them.
+#[derive(Default)]
+pub struct RecordingSender {
+    pub sent: Mutex<Vec<ComposableMessage>>,
+}
+
+#[async_trait]
+impl MailSender for RecordingSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String> {
+        let message_id = message.message_id.clone();
+        self.sent.lock().unwrap().push(message);
+        Ok(message_id)
+    }
+
+    async fn reply(&self, _: &Message, reply: ComposableMessage, _: bool) -> Result<String> {
+        self.send(reply).await
+    }
+
+    async fn forward(&self, _: &Message, forward: ComposableMessage) -> Result<String> {
+        self.send(forward).await
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        Ok("alice@example.com".to_string())
+    }
+}
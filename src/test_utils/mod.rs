@@ -1,10 +1,13 @@
+pub mod mail_sender;
 pub mod mbox;
 pub mod notmuch;
 
+#[cfg(feature = "test-utils")]
+pub use mail_sender::RecordingSender;
 #[cfg(feature = "test-utils")]
 pub use mbox::{Attachment, EmailMessage, MboxBuilder};
 #[cfg(feature = "test-utils")]
-pub use notmuch::TestNotmuch;
+pub use notmuch::{TestNotmuch, write_fake_notmuch};
 
 #[cfg(feature = "test-utils")]
 pub fn create_test_message_with_attachment(
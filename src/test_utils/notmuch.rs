@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 use tokio::process::Command;
 
@@ -158,6 +159,7 @@ impl TestNotmuch {
             notmuch_path: None,
             database_path: Some(self.database_path.clone()),
             mail_root: Some(self.mail_root.clone()),
+            format: Default::default(),
         };
 
         LocalClient::new(config).expect("Failed to create client")
@@ -194,6 +196,35 @@ fn parse_mbox_simple(mbox_content: &[u8]) -> Vec<Vec<u8>> {
     messages
 }
 
+/// Write a fake `notmuch` into `dir`, so clients can be tested without a
+/// database, and return its path.
+///
+/// Every invocation is appended to `calls.log`. `show` prints `show.json`,
+/// or `show.sexp` with `--format=sexp`; raw part requests get a fixed body,
+/// except part 0 of the last inserted message. `insert` saves its arguments
+/// to `insert.args` and the message to `insert.eml`.
+pub fn write_fake_notmuch(dir: &Path) -> PathBuf {
+    let script = dir.join("notmuch");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n\
+             echo \"$@\" >> {dir}/calls.log\n\
+             case \"$1 $2\" in\n\
+             \"show --format=raw\") if [ \"$3\" = --part=0 ] && [ -f {dir}/insert.eml ]; then cat {dir}/insert.eml; else printf 'part'; fi ;;\n\
+             \"show --format=sexp\") cat {dir}/show.sexp ;;\n\
+             show*) cat {dir}/show.json ;;\n\
+             insert*) echo \"$@\" > {dir}/insert.args; cat > {dir}/insert.eml ;;\n\
+             esac\n",
+            dir = dir.display(),
+        ),
+    )
+    .expect("Failed to write fake notmuch");
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to make fake notmuch executable");
+    script
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
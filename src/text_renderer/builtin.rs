@@ -19,7 +19,8 @@ impl BuiltinConverter {
 
     /// Convert HTML to plain text using improved text extraction
     /// This implementation handles CSS removal, better formatting, and structure preservation
-    fn convert_html_to_text(&self, html: &str) -> TextRendererResult<String> {
+    /// Unlike `convert`, this can be called where there is no runtime to await on
+    pub fn convert_html_to_text(&self, html: &str) -> TextRendererResult<String> {
         // Remove CSS styles and script content first
         let cleaned_html = self.remove_css_and_scripts(html);
 
@@ -1,6 +1,10 @@
 use crate::body::{BodyContent, BodyPart};
 use crate::common::{CryptoInfo, Headers};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
 
 /// Represents an email thread containing nested message levels
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -8,21 +12,152 @@ pub struct Thread(pub Vec<ThreadLevel>);
 
 impl Thread {
     /// Flatten the thread structure into a list of messages
+    ///
+    /// Messages are in depth-first order, the same order as [`Thread::iter`].
     pub fn get_messages(&self) -> Vec<&Message> {
-        let mut messages = Vec::new();
-        for level in &self.0 {
-            Self::collect_messages_from_level(level, &mut messages);
-        }
-        messages
+        self.iter().map(|entry| entry.message()).collect()
     }
 
-    fn collect_messages_from_level<'a>(level: &'a ThreadLevel, messages: &mut Vec<&'a Message>) {
-        for node in &level.0 {
-            messages.push(&node.0);
-            for child_level in &node.1 {
-                Self::collect_messages_from_level(child_level, messages);
-            }
-        }
+    /// The messages that do not reply to anything else in the thread.
+    pub fn roots(&self) -> impl Iterator<Item = &MessageNode> {
+        self.0.iter().flat_map(|level| level.0.iter())
+    }
+
+    /// Walk the thread depth-first, yielding each message with its depth
+    /// and parent.
+    pub fn iter(&self) -> DepthFirst<'_> {
+        DepthFirst::new(self.roots(), 0, None)
+    }
+
+    /// Find the subtree rooted at the message with `id`.
+    ///
+    /// The id may be given with or without angle brackets.
+    pub fn find(&self, id: &str) -> Option<&MessageNode> {
+        self.entry(id).map(|entry| entry.node)
+    }
+
+    /// Find the walk entry for the message with `id`.
+    pub fn entry(&self, id: &str) -> Option<ThreadEntry<'_>> {
+        self.iter().find(|entry| same_id(&entry.message().id, id))
+    }
+
+    /// Position of the message with `id` in depth-first order.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.iter()
+            .position(|entry| same_id(&entry.message().id, id))
+    }
+
+    /// The message that the message with `id` replies to.
+    pub fn parent(&self, id: &str) -> Option<&Message> {
+        self.entry(id)?.parent.map(MessageNode::message)
+    }
+
+    /// Direct replies to the message with `id`.
+    pub fn children(&self, id: &str) -> Vec<&Message> {
+        self.find(id)
+            .map(|node| node.children().map(MessageNode::message).collect())
+            .unwrap_or_default()
+    }
+
+    /// Other replies to the same parent as the message with `id`.
+    ///
+    /// Root messages are siblings of each other.
+    pub fn siblings(&self, id: &str) -> Vec<&Message> {
+        let Some(entry) = self.entry(id) else {
+            return Vec::new();
+        };
+        let nodes: Vec<&MessageNode> = match entry.parent {
+            Some(parent) => parent.children().collect(),
+            None => self.roots().collect(),
+        };
+        nodes
+            .into_iter()
+            .map(MessageNode::message)
+            .filter(|message| !same_id(&message.id, id))
+            .collect()
+    }
+
+    /// The next unread message in depth-first order.
+    ///
+    /// With `after` set, only messages following that one are considered;
+    /// otherwise the search starts at the beginning of the thread.
+    pub fn next_unread(&self, after: Option<&str>) -> Option<ThreadEntry<'_>> {
+        let skip = match after {
+            Some(id) => self.position(id)? + 1,
+            None => 0,
+        };
+        self.iter()
+            .skip(skip)
+            .find(|entry| entry.message().is_unread())
+    }
+}
+
+/// Compare message ids, ignoring optional angle brackets.
+fn same_id(a: &str, b: &str) -> bool {
+    let trim = |id: &str| {
+        id.trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string()
+    };
+    trim(a) == trim(b)
+}
+
+/// A message visited while walking a thread.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadEntry<'a> {
+    pub node: &'a MessageNode,
+    /// Number of ancestors; root messages have depth 0.
+    pub depth: usize,
+    pub parent: Option<&'a MessageNode>,
+}
+
+impl<'a> ThreadEntry<'a> {
+    pub fn message(&self) -> &'a Message {
+        &self.node.0
+    }
+}
+
+/// Depth-first iterator over a thread or subtree, see [`Thread::iter`].
+pub struct DepthFirst<'a> {
+    stack: Vec<ThreadEntry<'a>>,
+}
+
+impl<'a> DepthFirst<'a> {
+    fn new(
+        nodes: impl Iterator<Item = &'a MessageNode>,
+        depth: usize,
+        parent: Option<&'a MessageNode>,
+    ) -> Self {
+        let mut iter = DepthFirst { stack: Vec::new() };
+        iter.push(nodes, depth, parent);
+        iter
+    }
+
+    fn push(
+        &mut self,
+        nodes: impl Iterator<Item = &'a MessageNode>,
+        depth: usize,
+        parent: Option<&'a MessageNode>,
+    ) {
+        let nodes: Vec<_> = nodes.collect();
+        // Reversed so the first node is popped first
+        self.stack
+            .extend(nodes.into_iter().rev().map(|node| ThreadEntry {
+                node,
+                depth,
+                parent,
+            }));
+    }
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = ThreadEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.stack.pop()?;
+        self.push(entry.node.children(), entry.depth + 1, Some(entry.node));
+        Some(entry)
     }
 }
 
@@ -31,38 +166,88 @@ impl Thread {
 pub struct ThreadLevel(pub Vec<MessageNode>);
 
 /// A node in the thread tree containing a message and its replies
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MessageNode(pub Message, pub Vec<ThreadLevel>);
 
+impl MessageNode {
+    pub fn message(&self) -> &Message {
+        &self.0
+    }
+
+    /// Direct replies to this message.
+    pub fn children(&self) -> impl Iterator<Item = &MessageNode> {
+        self.1.iter().flat_map(|level| level.0.iter())
+    }
+
+    /// Walk this subtree depth-first, starting with this message at depth 0.
+    pub fn iter(&self) -> DepthFirst<'_> {
+        DepthFirst::new(std::iter::once(self), 0, None)
+    }
+}
+
+impl Serialize for MessageNode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Use notmuch's `[message, [replies]]` shape so the output can be
+        // deserialized again; a node without reply levels is a bare message.
+        if self.1.is_empty() {
+            return self.0.serialize(serializer);
+        }
+        let mut seq = serializer.serialize_seq(Some(self.1.len() + 1))?;
+        seq.serialize_element(&self.0)?;
+        for level in &self.1 {
+            seq.serialize_element(level)?;
+        }
+        seq.end()
+    }
+}
+
 impl<'de> Deserialize<'de> for MessageNode {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let value = serde_json::Value::deserialize(deserializer)?;
-        match value {
-            serde_json::Value::Array(mut arr) => {
-                if arr.len() >= 2 {
-                    let message: Message =
-                        serde_json::from_value(arr.remove(0)).map_err(serde::de::Error::custom)?;
-                    let children: Vec<ThreadLevel> =
-                        serde_json::from_value(serde_json::Value::Array(arr))
-                            .map_err(serde::de::Error::custom)?;
-                    Ok(MessageNode(message, children))
-                } else {
-                    Err(serde::de::Error::custom(
-                        "Expected array with at least 2 elements",
-                    ))
+        // A visitor rather than a detour through serde_json::Value, so the
+        // same impl works for the sexp deserializer
+        struct NodeVisitor;
+
+        impl<'de> Visitor<'de> for NodeVisitor {
+            type Value = MessageNode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("array or object")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<MessageNode, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let message: Message = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::custom("Expected array with at least 2 elements"))?;
+                let mut children = Vec::new();
+                while let Some(level) = seq.next_element()? {
+                    children.push(level);
+                }
+                if children.is_empty() {
+                    return Err(de::Error::custom("Expected array with at least 2 elements"));
                 }
+                Ok(MessageNode(message, children))
             }
-            serde_json::Value::Object(_) => {
+
+            fn visit_map<A>(self, map: A) -> Result<MessageNode, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
                 // Single message without children
-                let message: Message =
-                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                let message = Message::deserialize(de::value::MapAccessDeserializer::new(map))?;
                 Ok(MessageNode(message, vec![]))
             }
-            _ => Err(serde::de::Error::custom("Expected array or object")),
         }
+
+        deserializer.deserialize_any(NodeVisitor)
     }
 }
 
@@ -78,12 +263,20 @@ pub struct Message {
     pub date_relative: String,
     pub tags: Vec<String>,
     pub duplicate: Option<u32>,
+    /// Empty when shown with `--body=false`
+    #[serde(default)]
     pub body: Vec<BodyPart>,
+    #[serde(default)]
     pub crypto: CryptoInfo,
     pub headers: Headers,
 }
 
 impl Message {
+    /// Check if this message is tagged `unread`
+    pub fn is_unread(&self) -> bool {
+        self.tags.iter().any(|tag| tag == "unread")
+    }
+
     /// Check if this message contains any attachments
     pub fn has_attachments(&self) -> bool {
         self.body.iter().any(|part| {
@@ -114,40 +307,15 @@ impl Message {
     }
 
     /// Get the primary text content of the message
-    /// Returns the first text/plain or text/html content found
+    /// Returns the text/plain body, or the text/html body if there is none
     pub fn get_text_content(&self) -> Option<&str> {
-        Self::find_text_content(&self.body)
+        crate::body::find_body(&self.body, crate::body::PREFER_PLAIN)?.text()
     }
 
-    fn find_text_content(parts: &[BodyPart]) -> Option<&str> {
-        // First look for text/plain
-        for part in parts {
-            if part.content_type.starts_with("text/plain") {
-                if let BodyContent::Text(ref content) = part.content {
-                    return Some(content);
-                }
-            }
-        }
-
-        // Then look for text/html if no plain text found
-        for part in parts {
-            if part.content_type.starts_with("text/html") {
-                if let BodyContent::Text(ref content) = part.content {
-                    return Some(content);
-                }
-            }
-        }
-
-        // Recursively search in multipart content
-        for part in parts {
-            if let BodyContent::Multipart(ref subparts) = part.content {
-                if let Some(content) = Self::find_text_content(subparts) {
-                    return Some(content);
-                }
-            }
-        }
-
-        None
+    /// The primary text content as it reads, with `format=flowed` text
+    /// reflowed; see [`BodyPart::display_text`]
+    pub fn display_text(&self) -> Option<Cow<'_, str>> {
+        crate::body::find_body(&self.body, crate::body::PREFER_PLAIN)?.display_text()
     }
 }
 
@@ -297,6 +465,7 @@ mod tests {
                         content: BodyContent::Text("Hello".to_string()),
                         content_disposition: None,
                         content_id: None,
+                        content_charset: None,
                         filename: None,
                         content_transfer_encoding: None,
                         content_length: None,
@@ -307,6 +476,7 @@ mod tests {
                         content: BodyContent::Empty,
                         content_disposition: Some("attachment".to_string()),
                         content_id: None,
+                        content_charset: None,
                         filename: Some("image.png".to_string()),
                         content_transfer_encoding: Some("base64".to_string()),
                         content_length: Some(1000),
@@ -314,6 +484,7 @@ mod tests {
                 ]),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
@@ -353,6 +524,7 @@ mod tests {
                 content: BodyContent::Text("Hello".to_string()),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
@@ -453,4 +625,130 @@ mod tests {
         assert_eq!(messages[1].id, "msg2@example.com");
         assert_eq!(messages[2].id, "msg3@example.com");
     }
+    #[test]
+    fn test_thread_serialize_roundtrip() {
+        let json_data = r#"[[[{"id": "a@example.com", "match": true, "excluded": false,
+            "filename": ["/mail/a"], "timestamp": 1, "date_relative": "Today",
+            "tags": ["inbox"], "duplicate": 1,
+            "body": [{"id": 1, "content-type": "text/plain", "content": "Hi"}],
+            "crypto": {}, "headers": {"Subject": "Hi", "From": "a@example.com", "Date": "Mon, 1 Jan 2024 12:00:00 +0000"}},
+            [[{"id": "b@example.com", "match": true, "excluded": false,
+            "filename": ["/mail/b"], "timestamp": 2, "date_relative": "Today",
+            "tags": [], "duplicate": 1, "body": [], "crypto": {},
+            "headers": {"Subject": "Re: Hi", "From": "b@example.com", "Date": "Mon, 1 Jan 2024 13:00:00 +0000",
+            "In-Reply-To": "<a@example.com>"}}, []]]]]]"#;
+
+        let thread: Thread = serde_json::from_str(json_data).unwrap();
+        let serialized = serde_json::to_string(&thread).unwrap();
+        let reparsed: Thread = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(thread, reparsed);
+        assert_eq!(reparsed.get_messages().len(), 2);
+    }
+
+    fn tree_message(id: &str, tags: &[&str]) -> Message {
+        Message {
+            id: id.to_string(),
+            is_match: true,
+            excluded: false,
+            filename: vec![],
+            timestamp: 0,
+            date_relative: "Today".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            duplicate: None,
+            body: vec![],
+            crypto: CryptoInfo::default(),
+            headers: Headers {
+                subject: None,
+                from: "sender@example.com".to_string(),
+                to: None,
+                reply_to: None,
+                date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+                additional: std::collections::HashMap::new(),
+            },
+        }
+    }
+
+    fn node(id: &str, tags: &[&str], children: Vec<MessageNode>) -> MessageNode {
+        MessageNode(tree_message(id, tags), vec![ThreadLevel(children)])
+    }
+
+    /// a
+    /// ├── b
+    /// │   └── d
+    /// └── c
+    /// e
+    fn tree() -> Thread {
+        Thread(vec![ThreadLevel(vec![
+            node(
+                "a",
+                &[],
+                vec![
+                    node("b", &["unread"], vec![node("d", &[], vec![])]),
+                    node("c", &["unread"], vec![]),
+                ],
+            ),
+            node("e", &[], vec![]),
+        ])])
+    }
+
+    #[test]
+    fn test_thread_depth_first_walk() {
+        let thread = tree();
+        let walk: Vec<(&str, usize, Option<&str>)> = thread
+            .iter()
+            .map(|entry| {
+                (
+                    entry.message().id.as_str(),
+                    entry.depth,
+                    entry.parent.map(|p| p.message().id.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            walk,
+            vec![
+                ("a", 0, None),
+                ("b", 1, Some("a")),
+                ("d", 2, Some("b")),
+                ("c", 1, Some("a")),
+                ("e", 0, None),
+            ]
+        );
+        assert_eq!(thread.get_messages().len(), 5);
+    }
+
+    #[test]
+    fn test_thread_relations() {
+        let thread = tree();
+        let ids = |messages: Vec<&Message>| -> Vec<String> {
+            messages.iter().map(|m| m.id.clone()).collect()
+        };
+
+        assert_eq!(thread.parent("d").map(|m| m.id.as_str()), Some("b"));
+        assert!(thread.parent("a").is_none());
+        assert_eq!(ids(thread.children("a")), vec!["b", "c"]);
+        assert_eq!(ids(thread.siblings("b")), vec!["c"]);
+        assert_eq!(ids(thread.siblings("a")), vec!["e"]);
+        assert_eq!(thread.position("<c>"), Some(3));
+
+        let subtree: Vec<_> = thread
+            .find("b")
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.message().id.clone(), entry.depth))
+            .collect();
+        assert_eq!(subtree, vec![("b".to_string(), 0), ("d".to_string(), 1)]);
+        assert!(thread.find("missing").is_none());
+    }
+
+    #[test]
+    fn test_thread_next_unread() {
+        let thread = tree();
+        let id = |entry: Option<ThreadEntry>| entry.map(|e| e.message().id.clone());
+
+        assert_eq!(id(thread.next_unread(None)), Some("b".to_string()));
+        assert_eq!(id(thread.next_unread(Some("b"))), Some("c".to_string()));
+        assert_eq!(id(thread.next_unread(Some("c"))), None);
+    }
 }
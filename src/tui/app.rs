@@ -1,15 +1,33 @@
-use crate::client::NotmuchClient;
-use crate::config::Config;
+use crate::auth::{Authentication, PhishingWarning, phishing_warnings};
+use crate::body::BodyPart;
+use crate::calendar::{Calendar, PartStat, load_invite, send_rsvp};
+use crate::client::{NotmuchClient, TagOperation};
+use crate::common::{Headers, Mailbox};
+use crate::config::{Config, UserConfig};
+use crate::draft::{DRAFT_TAG, DRAFTS_QUERY, Draft};
 use crate::error::NotmuchError;
-use crate::mail_sender::{ComposableMessage, MailSender};
+use crate::list::{ListInfo, Unsubscribed, Unsubscriber, list_labels};
+use crate::mail_sender::message::{format_size, original_attachment};
+use crate::mail_sender::{
+    Attachment, AttachmentLimits, ComposableMessage, Gpg, MailSender, PgpOptions, ReplyQuoting,
+    pgp, quote,
+};
+use crate::outbox::{Delivery, Outbox, OutboxEntry, OutboxStatus, parse_send_at};
 use crate::search::SearchItem;
+use crate::summary::ThreadSummary;
 use crate::text_renderer::{
     HtmlToTextConverter, TextRendererConfig, TextRendererFactory, styled::StyledTextConverter,
 };
 use crate::thread::{Message, Thread};
 use crate::tui::markdown::markdown_to_html;
 use ratatui::text::Text;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often an edited compose form is saved as a draft
+const DRAFT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Default, PartialEq)]
 pub enum AppState {
@@ -18,6 +36,7 @@ pub enum AppState {
     EmailView,
     Search,
     Compose,
+    Outbox,
     Help,
 }
 
@@ -25,12 +44,26 @@ pub enum AppState {
 pub enum ComposeMode {
     #[default]
     New,
-    Reply(String),    // Thread ID
-    ReplyAll(String), // Thread ID
-    Forward(String),  // Thread ID
+    Reply(String),     // Thread ID
+    ReplyAll(String),  // Thread ID
+    ReplyList(String), // Thread ID
+    Forward(String),   // Thread ID
 }
 
-#[derive(Debug, Default)]
+impl ComposeMode {
+    /// ID of the message being replied to or forwarded
+    pub fn original_id(&self) -> Option<&str> {
+        match self {
+            ComposeMode::New => None,
+            ComposeMode::Reply(id)
+            | ComposeMode::ReplyAll(id)
+            | ComposeMode::ReplyList(id)
+            | ComposeMode::Forward(id) => Some(id),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum ComposeField {
     #[default]
     To,
@@ -38,11 +71,19 @@ pub enum ComposeField {
     Bcc,
     Subject,
     Body,
+    /// When to send the message; only shown once asked for
+    SendAt,
+    /// Path of a file to attach, above the list of attachments; only
+    /// shown once asked for
+    Attach,
 }
 
 #[derive(Debug, Default)]
 pub struct ComposeForm {
     pub mode: ComposeMode,
+    /// The identity the message is sent as, as a From mailbox; empty for
+    /// the default one
+    pub from: String,
     pub to: String,
     pub cc: String,
     pub bcc: String,
@@ -50,6 +91,159 @@ pub struct ComposeForm {
     pub body: String,
     pub current_field: ComposeField,
     pub markdown_mode: bool,
+    /// Threading headers of the message being replied to
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// The form as last saved to the Drafts folder
+    pub saved_draft: Option<Draft>,
+    /// When to send the message, as typed; empty sends it straight away
+    pub send_at: String,
+    /// Files attached to the message
+    pub attachments: Vec<ComposeAttachment>,
+    /// Path of the next file to attach, as typed
+    pub attach_path: String,
+    /// Attachments of the message being replied to or forwarded, which can
+    /// be attached in turn
+    pub original_parts: Vec<BodyPart>,
+    /// Selected entry in the attachment list: the attachments, followed by
+    /// the original's parts not attached yet
+    pub selected_attachment: usize,
+    /// Whether to sign and encrypt the message with OpenPGP
+    pub pgp: PgpOptions,
+    /// A recipient field that is not a valid address list, and why
+    pub address_error: Option<(ComposeField, String)>,
+    /// The message replied to, quoted; typing goes to the body, which is
+    /// put above or below the quote as `quoting` says
+    pub quote: String,
+    pub quoting: ReplyQuoting,
+}
+
+/// A file attached to the message being composed
+#[derive(Debug, Clone)]
+pub struct ComposeAttachment {
+    pub attachment: Attachment,
+    /// The part of the original message it was taken from
+    pub original_part: Option<u32>,
+}
+
+/// An entry of the attachment list in the compose form
+#[derive(Debug, Clone, Copy)]
+pub enum AttachmentEntry<'a> {
+    Attached(&'a ComposeAttachment),
+    /// A part of the original message that is not attached
+    Original(&'a BodyPart),
+}
+
+impl AttachmentEntry<'_> {
+    /// Name, content type and size, as listed in the compose form
+    pub fn label(&self) -> String {
+        match self {
+            AttachmentEntry::Attached(attached) => format!(
+                "{} ({}, {})",
+                attached.attachment.filename,
+                attached.attachment.content_type,
+                format_size(attached.attachment.data.len())
+            ),
+            AttachmentEntry::Original(part) => {
+                let name = part
+                    .filename
+                    .clone()
+                    .unwrap_or_else(|| format!("attachment_{}", part.id));
+                match part.content_length {
+                    Some(length) => format!(
+                        "{} ({}, {})",
+                        name,
+                        part.content_type,
+                        format_size(length as usize)
+                    ),
+                    None => format!("{} ({})", name, part.content_type),
+                }
+            }
+        }
+    }
+}
+
+impl ComposeForm {
+    /// The form's contents as a draft, replacing the last saved copy
+    pub fn draft(&self) -> Draft {
+        Draft {
+            id: self.saved_draft.as_ref().and_then(|draft| draft.id.clone()),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            cc: self.cc.clone(),
+            bcc: self.bcc.clone(),
+            subject: self.subject.clone(),
+            body: self.full_body(),
+            in_reply_to: self.in_reply_to.clone(),
+            references: self.references.clone(),
+            markdown: self.markdown_mode,
+            attachments: self
+                .attachments
+                .iter()
+                .map(|attached| attached.attachment.clone())
+                .collect(),
+            pgp: Some(self.pgp),
+        }
+    }
+
+    /// The body as sent, with the quote of the message replied to
+    pub fn full_body(&self) -> String {
+        if self.quote.is_empty() {
+            self.body.clone()
+        } else {
+            self.quoting.join(&self.body, &self.quote)
+        }
+    }
+
+    /// The attachment list: what is attached, then the parts of the
+    /// original message that could be
+    pub fn attachment_entries(&self) -> Vec<AttachmentEntry<'_>> {
+        let attached = self.attachments.iter().map(AttachmentEntry::Attached);
+        let offered = self
+            .original_parts
+            .iter()
+            .filter(|part| {
+                !self
+                    .attachments
+                    .iter()
+                    .any(|attached| attached.original_part == Some(part.id))
+            })
+            .map(AttachmentEntry::Original);
+        attached.chain(offered).collect()
+    }
+}
+
+/// A sent message still waiting out the undo grace period
+#[derive(Debug)]
+pub struct PendingSend {
+    /// Outbox entry holding the message
+    pub id: String,
+    /// What was sent, e.g. "reply"
+    pub kind: &'static str,
+    /// Status shown once it has gone out
+    pub sent_status: &'static str,
+    pub deadline: Instant,
+    /// The form it was sent from, restored if sending is undone
+    pub form: ComposeForm,
+}
+
+impl PendingSend {
+    /// Whole seconds left to undo, rounded up
+    pub fn seconds_left(&self) -> u64 {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        left.as_millis().div_ceil(1000) as u64
+    }
+}
+
+/// One message in the thread outline shown above the email body
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadOutlineEntry {
+    /// Nesting level in the reply tree
+    pub depth: usize,
+    pub sender: String,
+    pub unread: bool,
+    /// Whether this is the message being viewed
+    pub current: bool,
 }
 
 pub struct App {
@@ -62,6 +256,9 @@ pub struct App {
     /// Current search results/email list
     pub search_results: Vec<SearchItem>,
 
+    /// Mailing list labels of the search results, by thread ID
+    pub list_labels: HashMap<String, String>,
+
     /// Currently selected email index
     pub selected_email: usize,
 
@@ -77,6 +274,9 @@ pub struct App {
     /// Processed email body text (after HTML conversion)
     pub current_email_body: Option<Text<'static>>,
 
+    /// Calendar invitation carried by the current email
+    pub current_invite: Option<Calendar>,
+
     /// Current search query
     pub search_query: String,
 
@@ -105,16 +305,55 @@ pub struct App {
     styled_converter: Option<StyledTextConverter>,
 
     /// Mail sender for sending emails (optional if not configured)
-    mail_sender: Option<Box<dyn MailSender>>,
+    mail_sender: Option<Arc<dyn MailSender>>,
+
+    /// Spool outgoing mail is queued in until it has been sent
+    outbox: Outbox,
+
+    /// Entries shown in the outbox view
+    pub outbox_entries: Vec<OutboxEntry>,
+
+    /// Selected entry in the outbox view
+    pub selected_outbox_entry: usize,
+
+    /// How long a sent message can still be undone
+    undo_send_delay: Duration,
+
+    /// The last message sent, while it can still be undone
+    pub pending_send: Option<PendingSend>,
 
     /// Default markdown compose mode setting
     markdown_compose_default: bool,
+
+    /// Maildir folder the archive action moves threads to
+    archive_folder: Option<String>,
+
+    /// Maildir folder copies of sent messages are filed in
+    sent_folder: Option<String>,
+
+    /// Maildir folder drafts are saved in
+    drafts_folder: String,
+
+    /// How much can be attached to a message
+    attachment_limits: AttachmentLimits,
+
+    /// The gpg used to sign and encrypt messages
+    gpg: Gpg,
+
+    /// When the compose form was last checked for unsaved changes
+    draft_checked_at: Instant,
+
+    /// Summary of the current thread, computed when it is opened
+    pub current_summary: Option<ThreadSummary>,
+
+    /// The user's identity, to recognize their own messages
+    user_config: UserConfig,
 }
 
 impl App {
     pub async fn new(
         client: Arc<dyn NotmuchClient>,
-        mail_sender: Option<Box<dyn MailSender>>,
+        mail_sender: Option<Arc<dyn MailSender>>,
         config: &Config,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // Check if styled text is enabled
@@ -138,10 +377,12 @@ impl App {
             state: AppState::EmailList,
             should_quit: false,
             search_results: Vec::new(),
+            list_labels: HashMap::new(),
             selected_email: 0,
             current_thread: None,
             current_message_index: 0,
             current_email: None,
+            current_invite: None,
             current_email_body: None,
             search_query: String::new(),
             search_input: String::new(),
@@ -153,7 +394,20 @@ impl App {
             styled_text_enabled,
             styled_converter,
             mail_sender,
+            outbox: Outbox::new(config.outbox_dir()),
+            outbox_entries: Vec::new(),
+            selected_outbox_entry: 0,
+            undo_send_delay: config.undo_send_delay(),
+            pending_send: None,
             markdown_compose_default,
+            archive_folder: config.mail.reading.archive_folder.clone(),
+            sent_folder: config.sent_folder(),
+            drafts_folder: config.drafts_folder(),
+            attachment_limits: config.attachment_limits(),
+            gpg: config.gpg(),
+            draft_checked_at: Instant::now(),
+            current_summary: None,
+            user_config: config.user_config(),
         })
     }
 
@@ -168,9 +422,19 @@ impl App {
         self.load_search_results().await
     }
 
+    /// Load the saved drafts
+    pub async fn load_drafts(&mut self) -> Result<(), NotmuchError> {
+        self.search_query = DRAFTS_QUERY.to_string();
+        self.load_search_results().await
+    }
+
     /// Load search results based on current query
     pub async fn load_search_results(&mut self) -> Result<(), NotmuchError> {
         let search_results = self.client.search(&self.search_query).await?;
+        // List badges are decoration; don't fail the search over them
+        self.list_labels = list_labels(self.client.as_ref(), &search_results)
+            .await
+            .unwrap_or_default();
         self.search_results = search_results;
         self.selected_email = 0;
         self.scroll_position = 0;
@@ -227,12 +491,27 @@ impl App {
     }
 
     /// Open the currently selected email
+    ///
+    /// Drafts are reopened in the compose form instead.
     pub async fn open_selected_email(&mut self) -> Result<(), NotmuchError> {
         if let Some(search_item) = self.search_results.get(self.selected_email) {
             // Load the full thread to get all messages
             let thread = self.client.show(&search_item.thread).await?;
 
+            if let Some(draft) = thread
+                .get_messages()
+                .into_iter()
+                .find(|message| message.tags.iter().any(|tag| tag == DRAFT_TAG))
+            {
+                let id = draft.id.clone();
+                return self.resume_draft(&id).await;
+            }
+
             // Store the thread and start with the first message
+            self.current_summary = Some(ThreadSummary::new(
+                &thread,
+                &self.user_config.addresses(),
+            ));
             self.current_thread = Some(thread);
             self.current_message_index = 0;
 
@@ -252,6 +531,11 @@ impl App {
             if let Some(&message) = messages.get(self.current_message_index) {
                 // Process the email body content
                 self.current_email_body = self.process_email_body_styled(message).await;
+                // An unreadable invitation is still shown as an attachment
+                self.current_invite = load_invite(self.client.as_ref(), message)
+                    .await
+                    .ok()
+                    .flatten();
                 self.current_email = Some(message.clone());
                 self.scroll_position = 0; // Reset scroll when switching messages
             }
@@ -316,22 +600,135 @@ impl App {
         Ok(())
     }
 
+    /// Jump to the next unread message after the current one
+    pub async fn next_unread_in_thread(&mut self) -> Result<(), NotmuchError> {
+        let next = self.current_thread.as_ref().and_then(|thread| {
+            let current = thread
+                .get_messages()
+                .get(self.current_message_index)?
+                .id
+                .clone();
+            let next = thread.next_unread(Some(&current))?;
+            thread.position(&next.message().id)
+        });
+
+        match next {
+            Some(index) => {
+                self.current_message_index = index;
+                self.load_current_message().await?;
+                if let Some(info) = self.get_thread_info() {
+                    self.set_status(info);
+                }
+            }
+            None if self.current_thread.is_some() => {
+                self.set_status("No more unread messages in thread".to_string());
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Authentication results of the current email
+    pub fn sender_authentication(&self) -> Option<Authentication> {
+        self.current_email.as_ref().map(|email| {
+            Authentication::from_headers(&email.headers, self.user_config.trusted_authserv_ids())
+        })
+    }
+
+    /// Phishing warnings for the sender of the current email
+    pub fn sender_warnings(&self) -> Vec<PhishingWarning> {
+        self.current_email
+            .as_ref()
+            .map(|email| phishing_warnings(&email.headers, &self.user_config.known_domains()))
+            .unwrap_or_default()
+    }
+
     /// Get thread info for display
     pub fn get_thread_info(&self) -> Option<String> {
-        if let Some(ref thread) = self.current_thread {
-            let messages = thread.get_messages();
-            if messages.len() > 1 {
-                Some(format!(
-                    "Thread: Message {}/{}",
-                    self.current_message_index + 1,
-                    messages.len()
-                ))
-            } else {
-                None
+        let thread = self.current_thread.as_ref()?;
+        let messages = thread.get_messages();
+        if messages.len() <= 1 {
+            return None;
+        }
+
+        let mut info = format!(
+            "Thread: Message {}/{}",
+            self.current_message_index + 1,
+            messages.len()
+        );
+        let parent = messages
+            .get(self.current_message_index)
+            .and_then(|message| thread.parent(&message.id));
+        if let Some(parent) = parent {
+            info.push_str(&format!(", reply to {}", sender_name(&parent.headers)));
+        }
+        if let Some(summary) = &self.current_summary {
+            info.push_str(&format!(" · {}", summary.participant_names()));
+            if summary.unread > 0 {
+                info.push_str(&format!(" · {} unread", summary.unread));
+            }
+            if summary.attachments > 0 {
+                info.push_str(&format!(" · {} attached", summary.attachments));
+            }
+            if summary.sent_by_user {
+                info.push_str(" · you sent the last message");
             }
-        } else {
-            None
         }
+        Some(info)
+    }
+
+    /// The reply tree of the current thread, in reading order
+    pub fn thread_outline(&self) -> Vec<ThreadOutlineEntry> {
+        let Some(thread) = &self.current_thread else {
+            return Vec::new();
+        };
+        thread
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| ThreadOutlineEntry {
+                depth: entry.depth,
+                sender: sender_name(&entry.message().headers),
+                unread: entry.message().is_unread(),
+                current: index == self.current_message_index,
+            })
+            .collect()
+    }
+
+    /// Archive the selected thread by moving it to the archive folder
+    ///
+    /// The thread also loses its `inbox` tag. Works from both the email
+    /// list and the email view, and returns to a refreshed email list.
+    pub async fn archive_selected_thread(&mut self) -> Result<(), NotmuchError> {
+        let Some(folder) = self.archive_folder.clone() else {
+            self.set_status(
+                "Archiving is not configured (set archive_folder in [mail.reading])".to_string(),
+            );
+            return Ok(());
+        };
+        let Some(search_item) = self.search_results.get(self.selected_email) else {
+            return Ok(());
+        };
+
+        // Untag only once the files have moved, so a failed move leaves the
+        // thread in the inbox
+        let query = format!("thread:{}", search_item.thread_id());
+        let moved = self.client.move_to_folder(&query, &folder).await?;
+        self.client
+            .tag(&query, &[TagOperation::Remove("inbox".to_string())])
+            .await?;
+
+        let selected = self.selected_email;
+        self.load_search_results().await?;
+        self.selected_email = selected.min(self.search_results.len().saturating_sub(1));
+        self.current_thread = None;
+        self.current_summary = None;
+        self.current_email = None;
+        self.current_invite = None;
+        self.set_status(format!(
+            "Archived thread ({} files moved to {})",
+            moved, folder
+        ));
+        Ok(())
     }
 
     /// Go back to the previous view
@@ -340,6 +737,7 @@ impl App {
             AppState::EmailView => self.state = AppState::EmailList,
             AppState::Search => self.state = AppState::EmailList,
             AppState::Compose => self.state = AppState::EmailList,
+            AppState::Outbox => self.state = AppState::EmailList,
             AppState::Help => self.state = AppState::EmailList,
             AppState::EmailList => {} // Already at top level
         }
@@ -435,131 +833,66 @@ impl App {
         Some(Text::from("[No readable content]"))
     }
 
-    /// Recursively search through body parts to find text content
-    /// Prefers plain text over HTML, and recursively searches multipart containers
+    /// Find the text to show for a message body
+    /// Prefers plain text over HTML, converting HTML to text when needed
     async fn find_text_content(&self, parts: &[crate::body::BodyPart]) -> Option<String> {
-        // Use iterative approach with a stack to avoid async recursion issues
-        let mut stack: Vec<&[crate::body::BodyPart]> = vec![parts];
-
-        // First pass: look for plain text parts (depth-first search)
-        while let Some(current_parts) = stack.pop() {
-            for part in current_parts {
-                if part.content_type.starts_with("text/plain") {
-                    if let crate::body::BodyContent::Text(text) = &part.content {
-                        return Some(text.clone());
-                    }
-                }
-                // Add nested multipart containers to stack for processing
-                if let crate::body::BodyContent::Multipart(nested_parts) = &part.content {
-                    stack.push(nested_parts);
-                }
-            }
+        let part = crate::body::find_body(parts, crate::body::PREFER_PLAIN)?;
+        if part.media_type().essence() != "text/html" {
+            return part.display_text().map(|text| text.into_owned());
         }
+        let text = part.text()?;
 
-        // Second pass: look for HTML parts if no plain text found
-        let mut stack: Vec<&[crate::body::BodyPart]> = vec![parts];
-        while let Some(current_parts) = stack.pop() {
-            for part in current_parts {
-                if part.content_type.starts_with("text/html") {
-                    if let crate::body::BodyContent::Text(html) = &part.content {
-                        // Convert HTML to text using our text renderer
-                        match self.html_converter.convert(html).await {
-                            Ok(converted_text) => return Some(converted_text),
-                            Err(e) => {
-                                // Fallback to showing raw HTML if conversion fails
-                                return Some(format!(
-                                    "[HTML conversion failed: {}]\n\n{}",
-                                    e, html
-                                ));
-                            }
-                        }
-                    }
-                }
-                // Add nested multipart containers to stack for processing
-                if let crate::body::BodyContent::Multipart(nested_parts) = &part.content {
-                    stack.push(nested_parts);
-                }
-            }
+        // Convert HTML to text using our text renderer
+        match self.html_converter.convert(text).await {
+            Ok(converted_text) => Some(converted_text),
+            // Fallback to showing raw HTML if conversion fails
+            Err(e) => Some(format!("[HTML conversion failed: {}]\n\n{}", e, text)),
         }
-
-        None
     }
 
-    /// Recursively search through body parts to find text content and return styled Text
-    /// Prefers plain text over HTML, and recursively searches multipart containers
+    /// Find the text to show for a message body and return styled Text
+    /// Prefers plain text over HTML, converting HTML to styled text when enabled
     async fn find_text_content_styled(
         &self,
         parts: &[crate::body::BodyPart],
     ) -> Option<Text<'static>> {
-        // Use iterative approach with a stack to avoid async recursion issues
-        let mut stack: Vec<&[crate::body::BodyPart]> = vec![parts];
-
-        // First pass: look for plain text parts (depth-first search)
-        while let Some(current_parts) = stack.pop() {
-            for part in current_parts {
-                if part.content_type.starts_with("text/plain") {
-                    if let crate::body::BodyContent::Text(text) = &part.content {
-                        // Plain text always returns as Text::from (no styling)
-                        return Some(Text::from(text.clone()));
-                    }
-                }
-                // Add nested multipart containers to stack for processing
-                if let crate::body::BodyContent::Multipart(nested_parts) = &part.content {
-                    stack.push(nested_parts);
-                }
-            }
+        let part = crate::body::find_body(parts, crate::body::PREFER_PLAIN)?;
+        if part.media_type().essence() != "text/html" {
+            // Plain text always returns as Text::from (no styling)
+            return part
+                .display_text()
+                .map(|text| Text::from(text.into_owned()));
         }
-
-        // Second pass: look for HTML parts if no plain text found
-        let mut stack: Vec<&[crate::body::BodyPart]> = vec![parts];
-        while let Some(current_parts) = stack.pop() {
-            for part in current_parts {
-                if part.content_type.starts_with("text/html") {
-                    if let crate::body::BodyContent::Text(html) = &part.content {
-                        // Use styled converter if enabled, otherwise fall back to plain
-                        if self.styled_text_enabled {
-                            if let Some(ref styled_converter) = self.styled_converter {
-                                match styled_converter.convert_to_styled_text(html) {
-                                    Ok(styled_text) => return Some(styled_text),
-                                    Err(e) => {
-                                        // Fallback to plain text conversion if styled fails
-                                        match self.html_converter.convert(html).await {
-                                            Ok(converted_text) => {
-                                                return Some(Text::from(converted_text));
-                                            }
-                                            Err(_) => {
-                                                return Some(Text::from(format!(
-                                                    "[HTML conversion failed: {}]\n\n{}",
-                                                    e, html
-                                                )));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        } else {
-                            // Convert HTML to plain text using regular converter
-                            match self.html_converter.convert(html).await {
-                                Ok(converted_text) => return Some(Text::from(converted_text)),
-                                Err(e) => {
-                                    // Fallback to showing raw HTML if conversion fails
-                                    return Some(Text::from(format!(
-                                        "[HTML conversion failed: {}]\n\n{}",
-                                        e, html
-                                    )));
-                                }
-                            }
-                        }
+        let html = part.text()?;
+
+        // Use styled converter if enabled, otherwise fall back to plain
+        if self.styled_text_enabled {
+            if let Some(ref styled_converter) = self.styled_converter {
+                match styled_converter.convert_to_styled_text(html) {
+                    Ok(styled_text) => return Some(styled_text),
+                    Err(e) => {
+                        // Fallback to plain text conversion if styled fails
+                        return match self.html_converter.convert(html).await {
+                            Ok(converted_text) => Some(Text::from(converted_text)),
+                            Err(_) => Some(Text::from(format!(
+                                "[HTML conversion failed: {}]\n\n{}",
+                                e, html
+                            ))),
+                        };
                     }
                 }
-                // Add nested multipart containers to stack for processing
-                if let crate::body::BodyContent::Multipart(nested_parts) = &part.content {
-                    stack.push(nested_parts);
-                }
             }
         }
 
-        None
+        // Convert HTML to plain text using regular converter
+        match self.html_converter.convert(html).await {
+            Ok(converted_text) => Some(Text::from(converted_text)),
+            // Fallback to showing raw HTML if conversion fails
+            Err(e) => Some(Text::from(format!(
+                "[HTML conversion failed: {}]\n\n{}",
+                e, html
+            ))),
+        }
     }
 
     /// Start composing a new email
@@ -569,16 +902,29 @@ impl App {
             return;
         }
 
+        let from = self.default_from();
         self.compose_form = ComposeForm {
             mode: ComposeMode::New,
+            pgp: self.default_pgp(&from),
+            from,
             markdown_mode: self.markdown_compose_default,
             ..Default::default()
         };
         self.state = AppState::Compose;
     }
 
+    /// The current email quoted for a reply, with HTML-only messages
+    /// converted to text
+    async fn quote_current_email(&self) -> String {
+        let Some(email) = &self.current_email else {
+            return String::new();
+        };
+        let text = quote::reply_text(email, self.html_converter.as_ref()).await;
+        self.user_config.reply_quoting().quote(email, &text)
+    }
+
     /// Start composing a reply to the current email
-    pub fn start_compose_reply(&mut self, reply_all: bool) {
+    pub async fn start_compose_reply(&mut self, reply_all: bool) {
         if self.mail_sender.is_none() {
             self.set_status("Mail sending not configured".to_string());
             return;
@@ -616,10 +962,19 @@ impl App {
                 )
             };
 
+            let (in_reply_to, references) = reply_threading(current_email);
+            let from = self.reply_from(&current_email.headers);
             self.compose_form = ComposeForm {
                 mode,
-                to: current_email.headers.from.clone(),
+                pgp: self.default_pgp(&from),
+                from,
+                to: reply_to_field(&current_email.headers),
                 subject,
+                in_reply_to,
+                references,
+                original_parts: original_parts(current_email),
+                quote: self.quote_current_email().await,
+                quoting: self.user_config.reply_quoting(),
                 ..Default::default()
             };
             self.state = AppState::Compose;
@@ -628,6 +983,103 @@ impl App {
         }
     }
 
+    /// Start composing a reply to the mailing list the current email was
+    /// sent through
+    pub async fn start_compose_reply_list(&mut self) {
+        if self.mail_sender.is_none() {
+            self.set_status("Mail sending not configured".to_string());
+            return;
+        }
+        let Some(current_email) = &self.current_email else {
+            self.set_status("No email selected for reply".to_string());
+            return;
+        };
+        let Some(list) = ListInfo::from_headers(&current_email.headers) else {
+            self.set_status("Not a mailing list message".to_string());
+            return;
+        };
+        let Some(address) = list.post_address() else {
+            self.set_status(format!("Mailing list {} does not accept posts", list.label()));
+            return;
+        };
+
+        let subject = match current_email.headers.subject.as_deref() {
+            Some(subject) if subject.starts_with("Re: ") => subject.to_string(),
+            Some(subject) => format!("Re: {}", subject),
+            None => "Re: (No subject)".to_string(),
+        };
+        let (in_reply_to, references) = reply_threading(current_email);
+        let from = self.reply_from(&current_email.headers);
+        self.compose_form = ComposeForm {
+            mode: ComposeMode::ReplyList(current_email.id.clone()),
+            pgp: self.default_pgp(&from),
+            from,
+            to: address.to_string(),
+            subject,
+            markdown_mode: self.markdown_compose_default,
+            in_reply_to,
+            references,
+            original_parts: original_parts(current_email),
+            quote: self.quote_current_email().await,
+            quoting: self.user_config.reply_quoting(),
+            ..Default::default()
+        };
+        self.state = AppState::Compose;
+    }
+
+    /// Unsubscribe from the mailing list the current email was sent through
+    ///
+    /// Uses one-click unsubscription when the list offers it, otherwise
+    /// mails the list's unsubscribe address. Lists that only have a web
+    /// page get its address shown in the status bar.
+    pub async fn unsubscribe_current_list(&mut self) -> Result<(), NotmuchError> {
+        let Some(list) = self
+            .current_email
+            .as_ref()
+            .and_then(|email| ListInfo::from_headers(&email.headers))
+        else {
+            self.set_status("Not a mailing list message".to_string());
+            return Ok(());
+        };
+
+        let outcome = Unsubscriber::default()
+            .unsubscribe(&list, self.mail_sender.as_deref())
+            .await?;
+        self.set_status(match outcome {
+            Unsubscribed::OneClick(_) => format!("Unsubscribed from {}", list.label()),
+            Unsubscribed::Mail(_) => format!("Sent unsubscribe request to {}", list.label()),
+            Unsubscribed::Browser(url) => {
+                format!("To unsubscribe from {}, visit {}", list.label(), url)
+            }
+        });
+        Ok(())
+    }
+
+    /// Respond to the calendar invitation in the current email
+    pub async fn respond_to_invite(&mut self, partstat: PartStat) -> Result<(), NotmuchError> {
+        let (Some(email), Some(calendar)) = (&self.current_email, &self.current_invite) else {
+            self.set_status("No calendar invitation in this message".to_string());
+            return Ok(());
+        };
+        let Some(mail_sender) = &self.mail_sender else {
+            self.set_status("Mail sending not configured".to_string());
+            return Ok(());
+        };
+
+        // Respond as the identity that was invited
+        let identity = self.user_config.reply_identity(&email.headers);
+        send_rsvp(
+            mail_sender.as_ref(),
+            email,
+            calendar,
+            partstat,
+            identity.as_ref().map(|identity| identity.email.as_str()),
+        )
+        .await?;
+        self.set_status(format!("Response sent: {}", partstat.label()));
+        Ok(())
+    }
+
     /// Start composing a forward of the current email
     pub fn start_compose_forward(&mut self) {
         if self.mail_sender.is_none() {
@@ -660,9 +1112,13 @@ impl App {
                 )
             };
 
+            let from = self.default_from();
             self.compose_form = ComposeForm {
                 mode: ComposeMode::Forward(thread_id),
+                pgp: self.default_pgp(&from),
+                from,
                 subject,
+                original_parts: original_parts(current_email),
                 ..Default::default()
             };
             self.state = AppState::Compose;
@@ -671,6 +1127,71 @@ impl App {
         }
     }
 
+    /// The From mailbox of the default identity
+    fn default_from(&self) -> String {
+        self.user_config
+            .default_identity()
+            .map(|identity| identity.mailbox())
+            .unwrap_or_default()
+    }
+
+    /// The From mailbox to reply to a message with these headers as
+    fn reply_from(&self, headers: &Headers) -> String {
+        self.user_config
+            .reply_identity(headers)
+            .map(|identity| identity.mailbox())
+            .unwrap_or_default()
+    }
+
+    /// Messages from identities with a signing key are signed unless the
+    /// user says otherwise
+    fn default_pgp(&self, from: &str) -> PgpOptions {
+        let signing_key = self
+            .user_config
+            .identity(from)
+            .ok()
+            .flatten()
+            .and_then(|identity| identity.signing_key);
+        PgpOptions {
+            sign: signing_key.is_some(),
+            encrypt: false,
+        }
+    }
+
+    /// Cycle the message through unprotected, signed, encrypted, and signed
+    /// and encrypted
+    pub fn compose_cycle_pgp(&mut self) {
+        let PgpOptions { sign, encrypt } = self.compose_form.pgp;
+        self.compose_form.pgp = PgpOptions {
+            sign: !sign,
+            encrypt: encrypt != sign,
+        };
+        self.set_status(format!("Message will be {}", self.compose_form.pgp.label()));
+    }
+
+    /// Send the message being composed as the next configured identity
+    pub fn compose_next_identity(&mut self) {
+        let identities = self.user_config.sending_identities();
+        if identities.len() < 2 {
+            self.set_status("No other identities configured".to_string());
+            return;
+        }
+        let current = self
+            .user_config
+            .identity(&self.compose_form.from)
+            .ok()
+            .flatten()
+            .and_then(|current| {
+                identities
+                    .iter()
+                    .position(|identity| identity.email == current.email)
+            });
+        let next = &identities[current.map_or(0, |i| (i + 1) % identities.len())];
+        self.compose_form.from = next.mailbox();
+        self.compose_form.pgp.sign = next.signing_key.is_some();
+        self.set_status(format!("Sending as {}", self.compose_form.from));
+    }
+
     /// Navigate between compose form fields
     pub fn compose_next_field(&mut self) {
         self.compose_form.current_field = match self.compose_form.current_field {
@@ -678,7 +1199,7 @@ impl App {
             ComposeField::Cc => ComposeField::Bcc,
             ComposeField::Bcc => ComposeField::Subject,
             ComposeField::Subject => ComposeField::Body,
-            ComposeField::Body => ComposeField::To,
+            ComposeField::Body | ComposeField::SendAt | ComposeField::Attach => ComposeField::To,
         };
     }
 
@@ -690,17 +1211,158 @@ impl App {
             ComposeField::Bcc => ComposeField::Cc,
             ComposeField::Subject => ComposeField::Bcc,
             ComposeField::Body => ComposeField::Subject,
+            ComposeField::SendAt | ComposeField::Attach => ComposeField::Body,
+        };
+    }
+
+    /// Move to the field asking when to send the message, or back to the
+    /// body from there
+    pub fn compose_toggle_send_at(&mut self) {
+        self.compose_form.current_field = match self.compose_form.current_field {
+            ComposeField::SendAt => ComposeField::Body,
+            _ => ComposeField::SendAt,
+        };
+    }
+
+    /// Move to the attachments, where a file path can be typed, or back to
+    /// the body from there
+    pub fn compose_toggle_attach(&mut self) {
+        self.compose_form.current_field = match self.compose_form.current_field {
+            ComposeField::Attach => ComposeField::Body,
+            _ => ComposeField::Attach,
+        };
+    }
+
+    /// Complete the path being typed in the attachments as far as the
+    /// files it could name agree
+    pub fn compose_complete_path(&mut self) {
+        let candidates = path_completions(&self.compose_form.attach_path);
+        match candidates.as_slice() {
+            [] => self.set_status("No matching files".to_string()),
+            [only] => self.compose_form.attach_path = only.clone(),
+            _ => {
+                let common = common_prefix(&candidates);
+                if common.len() > self.compose_form.attach_path.len() {
+                    self.compose_form.attach_path = common;
+                } else {
+                    let names: Vec<&str> = candidates
+                        .iter()
+                        .map(|candidate| {
+                            let candidate = candidate.trim_end_matches('/');
+                            candidate.rsplit('/').next().unwrap_or(candidate)
+                        })
+                        .collect();
+                    self.set_status(names.join("  "));
+                }
+            }
+        }
+    }
+
+    /// Select the previous entry in the attachment list
+    pub fn compose_attachment_up(&mut self) {
+        self.compose_form.selected_attachment =
+            self.compose_form.selected_attachment.saturating_sub(1);
+    }
+
+    /// Select the next entry in the attachment list
+    pub fn compose_attachment_down(&mut self) {
+        if self.compose_form.selected_attachment + 1 < self.compose_form.attachment_entries().len()
+        {
+            self.compose_form.selected_attachment += 1;
+        }
+    }
+
+    /// Attach the file whose path was typed or, without one, the selected
+    /// part of the original message
+    pub async fn compose_attach(&mut self) -> Result<(), NotmuchError> {
+        let path = self.compose_form.attach_path.trim();
+        if !path.is_empty() {
+            let attachment = Attachment::from_file(&expand_home(path)).await?;
+            self.add_attachment(attachment, None)?;
+            self.compose_form.attach_path.clear();
+            return Ok(());
+        }
+
+        let entries = self.compose_form.attachment_entries();
+        let Some(AttachmentEntry::Original(part)) =
+            entries.get(self.compose_form.selected_attachment)
+        else {
+            self.set_status("Type the path of a file to attach".to_string());
+            return Ok(());
         };
+        let part = (*part).clone();
+        let Some(message_id) = self.compose_form.mode.original_id() else {
+            return Err(NotmuchError::ConfigError(
+                "Original message not found".to_string(),
+            ));
+        };
+        let attachment = original_attachment(self.client.as_ref(), message_id, &part).await?;
+        self.add_attachment(attachment, Some(part.id))
+    }
+
+    /// Add an attachment if it stays within the size limit
+    fn add_attachment(
+        &mut self,
+        attachment: Attachment,
+        original_part: Option<u32>,
+    ) -> Result<(), NotmuchError> {
+        let mut all: Vec<Attachment> = self
+            .compose_form
+            .attachments
+            .iter()
+            .map(|attached| attached.attachment.clone())
+            .collect();
+        all.push(attachment.clone());
+        self.attachment_limits.check(&all)?;
+
+        self.set_status(format!(
+            "Attached {} ({})",
+            attachment.filename,
+            format_size(attachment.data.len())
+        ));
+        self.compose_form.attachments.push(ComposeAttachment {
+            attachment,
+            original_part,
+        });
+        Ok(())
+    }
+
+    /// Remove the selected attachment from the message
+    pub fn compose_remove_attachment(&mut self) {
+        let form = &mut self.compose_form;
+        if form.selected_attachment >= form.attachments.len() {
+            return;
+        }
+        let removed = form.attachments.remove(form.selected_attachment);
+        form.selected_attachment = form
+            .selected_attachment
+            .min(form.attachment_entries().len().saturating_sub(1));
+        self.set_status(format!("Removed {}", removed.attachment.filename));
     }
 
     /// Handle character input in compose mode
     pub fn compose_handle_char(&mut self, c: char) {
+        self.compose_field_edited();
         match self.compose_form.current_field {
             ComposeField::To => self.compose_form.to.push(c),
             ComposeField::Cc => self.compose_form.cc.push(c),
             ComposeField::Bcc => self.compose_form.bcc.push(c),
             ComposeField::Subject => self.compose_form.subject.push(c),
             ComposeField::Body => self.compose_form.body.push(c),
+            ComposeField::SendAt => self.compose_form.send_at.push(c),
+            ComposeField::Attach => self.compose_form.attach_path.push(c),
+        }
+    }
+
+    /// Forget the error about the field being edited
+    fn compose_field_edited(&mut self) {
+        let form = &mut self.compose_form;
+        if form
+            .address_error
+            .as_ref()
+            .is_some_and(|(field, _)| *field == form.current_field)
+        {
+            form.address_error = None;
         }
     }
 
@@ -720,6 +1382,7 @@ impl App {
 
     /// Handle backspace in compose mode
     pub fn compose_handle_backspace(&mut self) {
+        self.compose_field_edited();
         match self.compose_form.current_field {
             ComposeField::To => {
                 self.compose_form.to.pop();
@@ -736,153 +1399,468 @@ impl App {
             ComposeField::Body => {
                 self.compose_form.body.pop();
             }
+            ComposeField::SendAt => {
+                self.compose_form.send_at.pop();
+            }
+            ComposeField::Attach => {
+                self.compose_form.attach_path.pop();
+            }
         }
     }
 
     /// Send the composed email
+    ///
+    /// Replies and forwards are built here rather than by the mail sender,
+    /// so the exact message sent can be filed in the Sent folder.
     pub async fn send_composed_email(&mut self) -> Result<(), NotmuchError> {
-        if let Some(ref mail_sender) = self.mail_sender {
-            // Validate required fields
-            if self.compose_form.to.trim().is_empty() {
-                return Err(NotmuchError::ConfigError(
-                    "To field is required".to_string(),
-                ));
-            }
-
-            match &self.compose_form.mode {
-                ComposeMode::New => {
-                    let mut builder = ComposableMessage::builder()
-                        .to(self.compose_form.to.clone())
-                        .subject(self.compose_form.subject.clone())
-                        .body(self.compose_form.body.clone());
-
-                    // Add HTML body if markdown mode is enabled
-                    if self.compose_form.markdown_mode {
-                        let html_body = markdown_to_html(&self.compose_form.body);
-                        builder = builder.html_body(html_body);
-                    }
+        let Some(mail_sender) = &self.mail_sender else {
+            return Err(NotmuchError::ConfigError(
+                "Mail sender not configured".to_string(),
+            ));
+        };
 
-                    // Add CC recipients if any
-                    for cc_email in self.parse_email_list(&self.compose_form.cc) {
-                        builder = builder.cc(cc_email);
-                    }
+        // Validate required fields
+        if self.compose_form.to.trim().is_empty() {
+            return Err(NotmuchError::ConfigError(
+                "To field is required".to_string(),
+            ));
+        }
+        let send_at = match self.compose_form.send_at.trim() {
+            "" => None,
+            input => Some(parse_send_at(input, chrono::Local::now())?),
+        };
 
-                    // Add BCC recipients if any
-                    for bcc_email in self.parse_email_list(&self.compose_form.bcc) {
-                        builder = builder.bcc(bcc_email);
-                    }
+        let (builder, kind, status) = match &self.compose_form.mode {
+            ComposeMode::New => {
+                let mut builder = ComposableMessage::builder()
+                    .to(self.compose_form.to.clone())
+                    .subject(self.compose_form.subject.clone());
+                // Resumed drafts of replies keep their threading
+                if let Some(in_reply_to) = &self.compose_form.in_reply_to {
+                    builder = builder.in_reply_to(in_reply_to.clone());
+                }
+                for reference in &self.compose_form.references {
+                    builder = builder.add_reference(reference.clone());
+                }
+                (builder, "message", "Email sent successfully")
+            }
+            ComposeMode::Reply(thread_id) | ComposeMode::ReplyAll(thread_id) => {
+                // Get the original message for reply
+                let thread = self.client.show(thread_id).await?;
+                let Some(original_message) = thread.get_messages().into_iter().next() else {
+                    return Err(NotmuchError::ConfigError(
+                        "Original message not found".to_string(),
+                    ));
+                };
+                let is_reply_all = matches!(self.compose_form.mode, ComposeMode::ReplyAll(_));
+                let builder = ComposableMessage::reply_builder(original_message, is_reply_all);
+                (builder, "reply", "Reply sent successfully")
+            }
+            ComposeMode::ReplyList(message_id) => {
+                let thread = self.client.show(&format!("id:{}", message_id)).await?;
+                let Some(original_message) = thread.get_messages().into_iter().next() else {
+                    return Err(NotmuchError::ConfigError(
+                        "Original message not found".to_string(),
+                    ));
+                };
+                let builder = ComposableMessage::list_reply_builder(original_message)?;
+                (builder, "reply", "Reply sent to list")
+            }
+            ComposeMode::Forward(thread_id) => {
+                // Get the original message for forward
+                let thread = self.client.show(thread_id).await?;
+                let Some(original_message) = thread.get_messages().into_iter().next() else {
+                    return Err(NotmuchError::ConfigError(
+                        "Original message not found".to_string(),
+                    ));
+                };
+                let builder = ComposableMessage::forward_builder(original_message)
+                    .to(self.compose_form.to.clone());
+                (builder, "forward", "Email forwarded successfully")
+            }
+        };
 
-                    let message = builder.build().map_err(|e| {
-                        NotmuchError::ConfigError(format!("Failed to build message: {}", e))
-                    })?;
+        let body = self.compose_form.full_body();
+        let mut builder = builder.body(body.clone());
 
-                    let _message_id = mail_sender.send(message).await.map_err(|e| {
-                        NotmuchError::MailSendError(format!("Failed to send message: {}", e))
-                    })?;
+        // Add HTML body if markdown mode is enabled
+        if self.compose_form.markdown_mode {
+            builder = builder.html_body(markdown_to_html(&body));
+        }
 
-                    self.set_status("Email sent successfully".to_string());
-                }
-                ComposeMode::Reply(thread_id) | ComposeMode::ReplyAll(thread_id) => {
-                    // Get the original message for reply
-                    let thread = self.client.show(thread_id).await?;
-                    if let Some(original_message) = thread.get_messages().into_iter().next() {
-                        let is_reply_all =
-                            matches!(self.compose_form.mode, ComposeMode::ReplyAll(_));
-
-                        let mut reply_builder = ComposableMessage::builder()
-                            .to("dummy@example.com".to_string()) // Will be replaced by reply builder
-                            .body(self.compose_form.body.clone());
-
-                        // Add HTML body if markdown mode is enabled
-                        if self.compose_form.markdown_mode {
-                            let html_body = markdown_to_html(&self.compose_form.body);
-                            reply_builder = reply_builder.html_body(html_body);
-                        }
-
-                        let reply = reply_builder.build().map_err(|e| {
-                            NotmuchError::ConfigError(format!("Failed to build reply: {}", e))
-                        })?;
-
-                        let _message_id = mail_sender
-                            .reply(original_message, reply, is_reply_all)
-                            .await
-                            .map_err(|e| {
-                                NotmuchError::MailSendError(format!("Failed to send reply: {}", e))
-                            })?;
-
-                        self.set_status("Reply sent successfully".to_string());
-                    } else {
-                        return Err(NotmuchError::ConfigError(
-                            "Original message not found".to_string(),
-                        ));
-                    }
-                }
-                ComposeMode::Forward(thread_id) => {
-                    // Get the original message for forward
-                    let thread = self.client.show(thread_id).await?;
-                    if let Some(original_message) = thread.get_messages().into_iter().next() {
-                        let mut builder = ComposableMessage::builder()
-                            .to(self.compose_form.to.clone())
-                            .body(self.compose_form.body.clone());
-
-                        // Add HTML body if markdown mode is enabled
-                        if self.compose_form.markdown_mode {
-                            let html_body = markdown_to_html(&self.compose_form.body);
-                            builder = builder.html_body(html_body);
-                        }
-
-                        // Add CC recipients if any
-                        for cc_email in self.parse_email_list(&self.compose_form.cc) {
-                            builder = builder.cc(cc_email);
-                        }
-
-                        // Add BCC recipients if any
-                        for bcc_email in self.parse_email_list(&self.compose_form.bcc) {
-                            builder = builder.bcc(bcc_email);
-                        }
-
-                        let forward = builder.build().map_err(|e| {
-                            NotmuchError::ConfigError(format!("Failed to build forward: {}", e))
-                        })?;
-
-                        let _message_id = mail_sender
-                            .forward(original_message, forward)
-                            .await
-                            .map_err(|e| {
-                                NotmuchError::MailSendError(format!(
-                                    "Failed to send forward: {}",
-                                    e
-                                ))
-                            })?;
-
-                        self.set_status("Email forwarded successfully".to_string());
-                    } else {
-                        return Err(NotmuchError::ConfigError(
-                            "Original message not found".to_string(),
-                        ));
-                    }
-                }
+        for attached in &self.compose_form.attachments {
+            builder = builder.attachment(attached.attachment.clone());
+        }
+
+        // Replies get their recipients from the original message
+        if !matches!(
+            self.compose_form.mode,
+            ComposeMode::Reply(_) | ComposeMode::ReplyAll(_)
+        ) {
+            if !self.compose_form.cc.trim().is_empty() {
+                builder = builder.cc(self.compose_form.cc.clone());
             }
+            if !self.compose_form.bcc.trim().is_empty() {
+                builder = builder.bcc(self.compose_form.bcc.clone());
+            }
+        }
 
-            // Return to email list after sending
+        let identity = self.user_config.identity(&self.compose_form.from)?;
+        if let Some(identity) = &identity {
+            builder = builder.from(identity.mailbox());
+        }
+
+        let message = match builder.build() {
+            Ok(message) => message,
+            Err(NotmuchError::InvalidAddress { field, message }) => {
+                // Take the user to the field to fix
+                let compose_field = match field.as_str() {
+                    "Cc" => ComposeField::Cc,
+                    "Bcc" => ComposeField::Bcc,
+                    _ => ComposeField::To,
+                };
+                self.compose_form.current_field = compose_field;
+                self.compose_form.address_error = Some((compose_field, message.clone()));
+                return Err(NotmuchError::InvalidAddress { field, message });
+            }
+            Err(e) => {
+                return Err(NotmuchError::ConfigError(format!(
+                    "Failed to build {}: {}",
+                    kind, e
+                )));
+            }
+        };
+        let signing_key = identity.and_then(|identity| identity.signing_key);
+        let message = pgp::protect(
+            &self.gpg,
+            message,
+            self.compose_form.pgp,
+            signing_key.as_deref(),
+        )
+        .await?;
+
+        if let Some(send_at) = send_at {
+            self.outbox
+                .schedule(
+                    self.client.as_ref(),
+                    message,
+                    self.compose_form.draft().id,
+                    send_at,
+                )
+                .await?;
+            self.set_status(format!(
+                "Scheduled {} for {} (o: outbox)",
+                kind,
+                send_at
+                    .with_timezone(&chrono::Local)
+                    .format("%a %Y-%m-%d %H:%M")
+            ));
             self.state = AppState::EmailList;
             self.compose_form = ComposeForm::default();
-        } else {
-            return Err(NotmuchError::ConfigError(
-                "Mail sender not configured".to_string(),
-            ));
+            return Ok(());
         }
 
+        if !self.undo_send_delay.is_zero() {
+            // Only the last message can be undone; send any earlier one now
+            self.flush_pending_send().await;
+            let entry = self
+                .outbox
+                .hold(
+                    self.client.as_ref(),
+                    message,
+                    self.compose_form.draft().id,
+                    self.undo_send_delay,
+                )
+                .await?;
+            let mut form = std::mem::take(&mut self.compose_form);
+            // The draft was removed when the message was queued
+            form.saved_draft = None;
+            self.pending_send = Some(PendingSend {
+                id: entry.id,
+                kind,
+                sent_status: status,
+                deadline: Instant::now() + self.undo_send_delay,
+                form,
+            });
+            self.state = AppState::EmailList;
+            return Ok(());
+        }
+
+        // The outbox removes the draft once the message has gone out
+        let delivery = self
+            .outbox
+            .submit(
+                mail_sender.as_ref(),
+                self.client.as_ref(),
+                self.sent_folder.as_deref(),
+                message,
+                self.compose_form.draft().id,
+            )
+            .await
+            .map_err(|e| NotmuchError::MailSendError(format!("Failed to send {}: {}", kind, e)))?;
+
+        self.report_delivery(kind, status, delivery);
+
+        // Return to email list after sending
+        self.state = AppState::EmailList;
+        self.compose_form = ComposeForm::default();
+
         Ok(())
     }
 
-    /// Helper method to parse comma-separated email list
-    fn parse_email_list(&self, input: &str) -> Vec<String> {
-        input
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect()
+    /// Show what happened to a message handed to the outbox
+    fn report_delivery(&mut self, kind: &str, sent_status: &str, delivery: Delivery) {
+        match delivery {
+            Delivery::Queued { retry_at, error } => self.set_status(format!(
+                "Could not send {} yet, retrying at {} (o: outbox): {}",
+                kind,
+                retry_at.with_timezone(&chrono::Local).format("%H:%M:%S"),
+                error
+            )),
+            Delivery::Failed(error) => {
+                self.set_status(format!("Failed to send {} (o: outbox): {}", kind, error))
+            }
+            _ => self.set_status(sent_status.to_string()),
+        }
+    }
+
+    /// Take back the last message sent while it can still be undone, and
+    /// reopen it in the compose form
+    pub async fn undo_send(&mut self) {
+        let Some(pending) = self.pending_send.take() else {
+            return;
+        };
+        match self.outbox.cancel(&pending.id).await {
+            Ok(_) => {
+                self.compose_form = pending.form;
+                self.state = AppState::Compose;
+                self.draft_checked_at = Instant::now();
+                self.set_status(format!("Sending {} undone", pending.kind));
+            }
+            Err(e) => self.set_status(format!("Too late to undo: {}", e)),
+        }
+    }
+
+    /// Send the last message once its undo grace period is over
+    pub async fn send_pending_when_due(&mut self) {
+        if self
+            .pending_send
+            .as_ref()
+            .is_some_and(|pending| Instant::now() >= pending.deadline)
+        {
+            self.flush_pending_send().await;
+        }
+    }
+
+    /// Send the message waiting out its undo grace period straight away,
+    /// e.g. before quitting
+    pub async fn flush_pending_send(&mut self) {
+        let Some(pending) = self.pending_send.take() else {
+            return;
+        };
+        let Some(mail_sender) = &self.mail_sender else {
+            return;
+        };
+        let delivery = self
+            .outbox
+            .deliver(
+                &pending.id,
+                mail_sender.as_ref(),
+                self.client.as_ref(),
+                self.sent_folder.as_deref(),
+            )
+            .await;
+        match delivery {
+            Ok(delivery) => self.report_delivery(pending.kind, pending.sent_status, delivery),
+            // Already picked up by the background worker
+            Err(e) => self.set_status(format!("Sending {}: {}", pending.kind, e)),
+        }
+    }
+
+    /// Save the compose form to the Drafts folder if it changed since the
+    /// last save
+    pub async fn save_draft(&mut self) -> Result<(), NotmuchError> {
+        let mut draft = self.compose_form.draft();
+        if self.compose_form.saved_draft.as_ref() == Some(&draft)
+            || (draft.is_empty() && draft.id.is_none())
+        {
+            return Ok(());
+        }
+
+        draft
+            .save(
+                self.client.as_ref(),
+                &self.drafts_folder,
+                self.user_config.email.as_deref(),
+            )
+            .await?;
+        self.compose_form.saved_draft = Some(draft);
+        Ok(())
+    }
+
+    /// Save the compose form as a draft every so often while editing
+    pub async fn autosave_draft(&mut self) -> Result<(), NotmuchError> {
+        if self.state != AppState::Compose
+            || self.draft_checked_at.elapsed() < DRAFT_AUTOSAVE_INTERVAL
+        {
+            return Ok(());
+        }
+        self.draft_checked_at = Instant::now();
+        self.save_draft().await
+    }
+
+    /// Leave the compose form, keeping what was typed as a draft
+    pub async fn close_compose(&mut self) -> Result<(), NotmuchError> {
+        let saved = self.save_draft().await;
+        self.go_back();
+        if saved.is_ok() && self.compose_form.saved_draft.is_some() {
+            self.set_status("Draft saved".to_string());
+        }
+        saved
+    }
+
+    /// Reopen a saved draft in the compose form
+    pub async fn resume_draft(&mut self, id: &str) -> Result<(), NotmuchError> {
+        if self.mail_sender.is_none() {
+            self.set_status("Mail sending not configured".to_string());
+            return Ok(());
+        }
+
+        let draft = Draft::load(self.client.as_ref(), id).await?;
+        self.open_draft(draft);
+        Ok(())
+    }
+
+    /// Fill the compose form from a saved draft
+    fn open_draft(&mut self, draft: Draft) {
+        self.compose_form = ComposeForm {
+            mode: ComposeMode::New,
+            pgp: draft
+                .pgp
+                .unwrap_or_else(|| self.default_pgp(&draft.from)),
+            from: draft.from.clone(),
+            to: draft.to.clone(),
+            cc: draft.cc.clone(),
+            bcc: draft.bcc.clone(),
+            subject: draft.subject.clone(),
+            body: draft.body.clone(),
+            markdown_mode: draft.markdown,
+            in_reply_to: draft.in_reply_to.clone(),
+            references: draft.references.clone(),
+            attachments: draft
+                .attachments
+                .iter()
+                .map(|attachment| ComposeAttachment {
+                    attachment: attachment.clone(),
+                    original_part: None,
+                })
+                .collect(),
+            saved_draft: Some(draft),
+            ..Default::default()
+        };
+        self.state = AppState::Compose;
+        self.draft_checked_at = Instant::now();
+    }
+
+    /// Show the messages waiting in the outbox
+    pub async fn show_outbox(&mut self) -> Result<(), NotmuchError> {
+        self.refresh_outbox().await?;
+        self.selected_outbox_entry = 0;
+        self.state = AppState::Outbox;
+        Ok(())
+    }
+
+    /// Reload the outbox view, e.g. after the background worker sent
+    /// something
+    pub async fn refresh_outbox(&mut self) -> Result<(), NotmuchError> {
+        self.outbox_entries = self.outbox.list().await?;
+        self.selected_outbox_entry = self
+            .selected_outbox_entry
+            .min(self.outbox_entries.len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Select the previous entry in the outbox view
+    pub fn outbox_up(&mut self) {
+        self.selected_outbox_entry = self.selected_outbox_entry.saturating_sub(1);
+    }
+
+    /// Select the next entry in the outbox view
+    pub fn outbox_down(&mut self) {
+        if self.selected_outbox_entry + 1 < self.outbox_entries.len() {
+            self.selected_outbox_entry += 1;
+        }
+    }
+
+    /// Remove the selected message from the outbox without sending it
+    pub async fn cancel_selected_outbox_entry(&mut self) -> Result<(), NotmuchError> {
+        let Some(entry) = self.outbox_entries.get(self.selected_outbox_entry) else {
+            return Ok(());
+        };
+        let entry = self.outbox.cancel(&entry.id).await?;
+        self.set_status(format!("Cancelled \"{}\"", entry.message.subject));
+        self.refresh_outbox().await
+    }
+
+    /// Take the selected message out of the outbox and reopen it in the
+    /// compose form, keeping the time it was scheduled for
+    pub async fn edit_selected_outbox_entry(&mut self) -> Result<(), NotmuchError> {
+        if self.mail_sender.is_none() {
+            self.set_status("Mail sending not configured".to_string());
+            return Ok(());
+        }
+        let Some(entry) = self.outbox_entries.get(self.selected_outbox_entry) else {
+            return Ok(());
+        };
+
+        let (entry, draft) = self
+            .outbox
+            .reopen(&entry.id, self.client.as_ref(), &self.drafts_folder)
+            .await?;
+        self.open_draft(draft);
+        if entry.status == OutboxStatus::Scheduled {
+            self.compose_form.send_at = entry
+                .next_attempt
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string();
+            self.compose_form.current_field = ComposeField::SendAt;
+        }
+        self.set_status("Taken out of the outbox; Ctrl+S puts it back".to_string());
+        Ok(())
+    }
+
+    /// Send the selected message again straight away
+    pub async fn retry_selected_outbox_entry(&mut self) -> Result<(), NotmuchError> {
+        let Some(mail_sender) = &self.mail_sender else {
+            self.set_status("Mail sending not configured".to_string());
+            return Ok(());
+        };
+        let Some(entry) = self.outbox_entries.get(self.selected_outbox_entry) else {
+            return Ok(());
+        };
+
+        self.outbox.retry(&entry.id).await?;
+        let delivery = self
+            .outbox
+            .deliver(
+                &entry.id,
+                mail_sender.as_ref(),
+                self.client.as_ref(),
+                self.sent_folder.as_deref(),
+            )
+            .await?;
+        self.set_status(match delivery {
+            Delivery::Sent(_) => "Message sent".to_string(),
+            Delivery::Queued { error, .. } | Delivery::Failed(error) => {
+                format!("Sending failed: {}", error)
+            }
+            Delivery::Scheduled(at) | Delivery::Held { until: at, .. } => format!(
+                "Scheduled for {}",
+                at.with_timezone(&chrono::Local).format("%a %Y-%m-%d %H:%M")
+            ),
+        });
+        self.refresh_outbox().await
     }
 
     /// Toggle markdown mode in compose form
@@ -900,3 +1878,106 @@ impl App {
         }
     }
 }
+
+/// The attachments of `message` offered in a reply to or forward of it
+fn original_parts(message: &Message) -> Vec<BodyPart> {
+    message
+        .body
+        .iter()
+        .flat_map(BodyPart::attachments)
+        .cloned()
+        .collect()
+}
+
+/// Expand a leading `~/` in a typed path to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The paths a partly typed path could be completed to, as typed, with a
+/// `/` after directories. Hidden files are left out unless their name has
+/// been started.
+fn path_completions(input: &str) -> Vec<String> {
+    let (dir_part, name_part) = match input.rfind('/') {
+        Some(slash) => input.split_at(slash + 1),
+        None => ("", input),
+    };
+    let dir = if dir_part.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_home(dir_part)
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(name_part)
+                || (name.starts_with('.') && !name_part.starts_with('.'))
+            {
+                return None;
+            }
+            let is_dir = Path::new(&dir).join(&name).is_dir();
+            Some(format!(
+                "{}{}{}",
+                dir_part,
+                name,
+                if is_dir { "/" } else { "" }
+            ))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// The longest prefix shared by all of `candidates`
+fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for candidate in &candidates[1..] {
+        let shared = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.len()), |((i, _), _)| i);
+        prefix = &prefix[..shared];
+    }
+    prefix.to_string()
+}
+
+/// In-Reply-To and References for a reply to `message`.
+fn reply_threading(message: &Message) -> (Option<String>, Vec<String>) {
+    let mut references: Vec<String> = message
+        .headers
+        .get("references")
+        .map(|refs| refs.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+    references.push(message.id.clone());
+    (Some(message.id.clone()), references)
+}
+
+/// Short sender name for status lines and the thread outline.
+fn sender_name(headers: &Headers) -> String {
+    headers
+        .sender()
+        .map(|sender| sender.display_name().to_string())
+        .unwrap_or_else(|| headers.from.clone())
+}
+
+/// The To field for a reply, honouring Reply-To.
+fn reply_to_field(headers: &Headers) -> String {
+    let recipients = headers.reply_recipients();
+    if recipients.is_empty() {
+        headers.from.clone()
+    } else {
+        Mailbox::format_list(&recipients)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::calendar::PartStat;
 use crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers};
 use std::time::Duration;
 
@@ -137,6 +138,54 @@ impl Event {
         )
     }
 
+    /// Check if this is a drafts key (d)
+    pub fn is_drafts(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is an outbox key (o)
+    pub fn is_outbox(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is a cancel key (x), e.g. for an outbox entry
+    pub fn is_cancel(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is an edit key (e), e.g. for an outbox entry
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        )
+    }
+
     /// Check if this is a reply key (r)
     pub fn is_reply(&self) -> bool {
         matches!(
@@ -165,6 +214,38 @@ impl Event {
         )
     }
 
+    /// Check if this is a reply-to-list key (L or Shift+l)
+    pub fn is_reply_list(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('L'),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                ..
+            }) | Event::Key(KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is an unsubscribe key (U or Shift+u)
+    pub fn is_unsubscribe(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('U'),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                ..
+            }) | Event::Key(KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            })
+        )
+    }
+
     /// Check if this is a forward key (f)
     pub fn is_forward(&self) -> bool {
         matches!(
@@ -177,6 +258,18 @@ impl Event {
         )
     }
 
+    /// Check if this is an archive key (a)
+    pub fn is_archive(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        )
+    }
+
     /// Check if this is a tab key (for field navigation)
     pub fn is_tab(&self) -> bool {
         matches!(
@@ -285,6 +378,97 @@ impl Event {
         )
     }
 
+    /// Check if this is a next unread message key (u)
+    pub fn is_next_unread(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        )
+    }
+
+    /// The invitation response chosen with Y (accept), T (tentative) or
+    /// D (decline)
+    pub fn rsvp_response(&self) -> Option<PartStat> {
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+            ..
+        }) = self
+        else {
+            return None;
+        };
+        match c {
+            'Y' => Some(PartStat::Accepted),
+            'T' => Some(PartStat::Tentative),
+            'D' => Some(PartStat::Declined),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a send later key (Ctrl+L)
+    pub fn is_send_later(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is an attach key (Ctrl+A)
+    pub fn is_attach(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is a sign/encrypt key (Ctrl+P)
+    pub fn is_pgp(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is a switch identity key (Ctrl+F)
+    pub fn is_next_identity(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            })
+        )
+    }
+
+    /// Check if this is an undo send key (Ctrl+Z)
+    pub fn is_undo(&self) -> bool {
+        matches!(
+            self,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            })
+        )
+    }
+
     /// Check if this is a markdown toggle key (Ctrl+M)
     pub fn is_markdown_toggle(&self) -> bool {
         matches!(
@@ -1,6 +1,6 @@
 //! Markdown to HTML conversion for TUI email composition.
 
-use pulldown_cmark::{html, Options, Parser};
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
 
 /// Converts markdown text to HTML for email composition.
 ///
@@ -8,6 +8,9 @@ use pulldown_cmark::{html, Options, Parser};
 /// sending as the text/html part of a multipart email message. The conversion
 /// includes proper handling of common markdown features like headers, lists,
 /// links, code blocks, and basic formatting.
+///
+/// Quoted text (`> ` lines, as in a reply) becomes a `<blockquote>` that
+/// keeps its line breaks and shows any HTML in it as text.
 pub fn markdown_to_html(markdown: &str) -> String {
     // Enable GitHub-flavored markdown extensions
     let mut options = Options::empty();
@@ -16,7 +19,20 @@ pub fn markdown_to_html(markdown: &str) -> String {
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TASKLISTS);
 
-    let parser = Parser::new_ext(markdown, options);
+    let mut quote_depth = 0;
+    let parser = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Start(Tag::BlockQuote(_)) => {
+            quote_depth += 1;
+            event
+        }
+        Event::End(TagEnd::BlockQuote(_)) => {
+            quote_depth -= 1;
+            event
+        }
+        Event::SoftBreak if quote_depth > 0 => Event::HardBreak,
+        Event::Html(html) | Event::InlineHtml(html) if quote_depth > 0 => Event::Text(html),
+        event => event,
+    });
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
 
@@ -99,6 +115,16 @@ mod tests {
         assert!(html.contains("</blockquote>"));
     }
 
+    #[test]
+    fn test_markdown_to_html_reply_quote() {
+        let markdown =
+            "Sounds good.\n\nOn Monday, Bob wrote:\n> Lunch at\n> <b>noon</b>?\n>\n>> Earlier";
+        let html = markdown_to_html(markdown);
+        assert!(html.contains("<p>On Monday, Bob wrote:</p>\n<blockquote>"));
+        assert!(html.contains("Lunch at<br />\n&lt;b&gt;noon&lt;/b&gt;?"));
+        assert!(html.contains("<blockquote>\n<p>Earlier</p>\n</blockquote>\n</blockquote>"));
+    }
+
     #[test]
     fn test_markdown_to_html_tables() {
         let markdown = "| Header 1 | Header 2 |\n|----------|----------|\n| Cell 1   | Cell 2   |";
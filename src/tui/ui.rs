@@ -1,9 +1,15 @@
-use crate::tui::app::{App, AppState};
+use crate::auth::AuthStatus;
+use crate::calendar::Calendar;
+use crate::common::Mailbox;
+use crate::list::ListInfo;
+use crate::mail_sender::message::format_size;
+use crate::outbox::OutboxStatus;
+use crate::tui::app::{App, AppState, AttachmentEntry, ThreadOutlineEntry};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Line, Span},
+    text::{Line, Span, Text},
     widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
 
@@ -27,9 +33,61 @@ fn draw_main_content(f: &mut Frame, app: &mut App, area: Rect) {
         AppState::Search => draw_search(f, app, area),
         AppState::Help => draw_help(f, app, area),
         AppState::Compose => draw_compose(f, app, area),
+        AppState::Outbox => draw_outbox(f, app, area),
     }
 }
 
+fn draw_outbox(f: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .outbox_entries
+        .iter()
+        .map(|entry| {
+            let mut state = entry.status.label().to_string();
+            let next_attempt = entry.next_attempt.with_timezone(&chrono::Local);
+            match entry.status {
+                OutboxStatus::Queued => state.push_str(&format!(
+                    ", next attempt {}",
+                    next_attempt.format("%Y-%m-%d %H:%M:%S")
+                )),
+                OutboxStatus::Scheduled => state.push_str(&format!(
+                    " for {}",
+                    next_attempt.format("%a %Y-%m-%d %H:%M")
+                )),
+                OutboxStatus::Sending | OutboxStatus::Failed => {}
+            }
+            if entry.attempts > 0 {
+                state.push_str(&format!(", {} failed", entry.attempts));
+            }
+
+            let mut lines = vec![Line::from(format!(
+                "{}: {} [{}]",
+                entry.recipients(),
+                entry.message.subject,
+                state
+            ))];
+            if let Some(error) = &entry.last_error {
+                lines.push(Line::styled(
+                    format!("  {}", error.lines().next().unwrap_or_default()),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    state.select(Some(app.selected_outbox_entry));
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Outbox ({} waiting) - r: send now, e: edit, x: cancel, Esc: back",
+            app.outbox_entries.len()
+        )))
+        .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
 fn draw_email_list(f: &mut Frame, app: &mut App, area: Rect) {
     let items: Vec<ListItem> = app
         .search_results
@@ -50,8 +108,11 @@ fn draw_email_list(f: &mut Frame, app: &mut App, area: Rect) {
                 .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
                 .unwrap_or_else(|| "Unknown".to_string());
 
-            // Format: "Authors: Subject [Date]"
-            let content = format!("{}: {} [{}]", authors, subject, date);
+            // Format: "Authors: [list] Subject [Date]"
+            let content = match app.list_labels.get(search_item.thread_id()) {
+                Some(list) => format!("{}: [{}] {} [{}]", authors, list, subject, date),
+                None => format!("{}: {} [{}]", authors, subject, date),
+            };
 
             ListItem::new(content).style(style)
         })
@@ -78,17 +139,43 @@ fn draw_email_list(f: &mut Frame, app: &mut App, area: Rect) {
 }
 
 fn draw_email_view(f: &mut Frame, app: &mut App, area: Rect) {
-    if app.current_email.is_some() {
+    if let Some(email) = &app.current_email {
+        // One more header line each for Cc recipients, a mailing list,
+        // authentication results and every phishing warning
+        let header_height = 7
+            + u16::from(!email.headers.cc_mailboxes().is_empty())
+            + u16::from(ListInfo::from_headers(&email.headers).is_some())
+            + u16::from(
+                app.sender_authentication()
+                    .is_some_and(|auth| !auth.is_empty()),
+            )
+            + app.sender_warnings().len() as u16;
+        // Show the reply tree when there is more than one message
+        let outline = app.thread_outline();
+        let outline_height = if outline.len() > 1 {
+            outline.len().min(MAX_OUTLINE_LINES) as u16 + 2
+        } else {
+            0
+        };
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(7), Constraint::Min(1)])
+            .constraints([
+                Constraint::Length(header_height),
+                Constraint::Length(outline_height),
+                Constraint::Min(1),
+            ])
             .split(area);
 
         // Email headers
         draw_email_headers(f, app, chunks[0]);
 
+        // Thread outline
+        if outline_height > 0 {
+            draw_thread_outline(f, &outline, chunks[1]);
+        }
+
         // Email body
-        draw_email_body(f, app, chunks[1]);
+        draw_email_body(f, app, chunks[2]);
     } else {
         let paragraph = Paragraph::new("No email selected")
             .block(Block::default().borders(Borders::ALL).title("Email View"));
@@ -96,20 +183,75 @@ fn draw_email_view(f: &mut Frame, app: &mut App, area: Rect) {
     }
 }
 
+/// Most messages listed in the thread outline at once
+const MAX_OUTLINE_LINES: usize = 6;
+
+fn draw_thread_outline(f: &mut Frame, outline: &[ThreadOutlineEntry], area: Rect) {
+    // Keep the current message visible in long threads
+    let current = outline.iter().position(|entry| entry.current).unwrap_or(0);
+    let start = current
+        .saturating_sub(MAX_OUTLINE_LINES / 2)
+        .min(outline.len().saturating_sub(MAX_OUTLINE_LINES));
+
+    let lines: Vec<Line> = outline
+        .iter()
+        .skip(start)
+        .take(MAX_OUTLINE_LINES)
+        .map(|entry| {
+            let marker = if entry.current { "> " } else { "  " };
+            let branch = if entry.depth > 0 { "└ " } else { "" };
+            let text = format!(
+                "{}{}{}{}",
+                marker,
+                "  ".repeat(entry.depth.saturating_sub(1)),
+                branch,
+                entry.sender
+            );
+            let mut style = Style::default();
+            if entry.current {
+                style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+            } else if entry.unread {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            Line::from(Span::styled(text, style))
+        })
+        .collect();
+
+    let paragraph =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Thread"));
+    f.render_widget(paragraph, area);
+}
+
+/// Format parsed mailboxes for display, falling back to the raw header.
+fn format_mailboxes(mailboxes: &[Mailbox], raw: &str) -> String {
+    if mailboxes.is_empty() {
+        raw.to_string()
+    } else {
+        Mailbox::format_list(mailboxes)
+    }
+}
+
 fn draw_email_headers(f: &mut Frame, app: &App, area: Rect) {
     let message = match &app.current_email {
         Some(msg) => msg,
         None => return,
     };
-    let from = &message.headers.from;
-    let to = message
-        .headers
-        .to
-        .as_deref()
-        .unwrap_or("Undisclosed recipients");
+    let from = message.headers.formatted_from();
+    let to = match &message.headers.to {
+        Some(to) => format_mailboxes(&message.headers.to_mailboxes(), to),
+        None => "Undisclosed recipients".to_string(),
+    };
+    let cc = message.headers.cc_mailboxes();
     let subject = message.headers.subject.as_deref().unwrap_or("(No subject)");
-    let date = chrono::DateTime::from_timestamp(message.timestamp, 0)
-        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+    // Show the date as the sender wrote it, in their timezone
+    let date = message
+        .headers
+        .parsed_date()
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S %z").to_string())
+        .or_else(|| {
+            chrono::DateTime::from_timestamp(message.timestamp, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        })
         .unwrap_or_else(|| "Unknown".to_string());
 
     let mut headers_text = vec![
@@ -121,15 +263,63 @@ fn draw_email_headers(f: &mut Frame, app: &App, area: Rect) {
             Span::styled("To: ", Style::default().add_modifier(Modifier::BOLD)),
             Span::raw(to),
         ]),
-        Line::from(vec![
-            Span::styled("Subject: ", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(subject),
-        ]),
-        Line::from(vec![
-            Span::styled("Date: ", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(date.to_string()),
-        ]),
     ];
+    if !cc.is_empty() {
+        headers_text.push(Line::from(vec![
+            Span::styled("Cc: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(Mailbox::format_list(&cc)),
+        ]));
+    }
+    headers_text.push(Line::from(vec![
+        Span::styled("Subject: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(subject),
+    ]));
+    headers_text.push(Line::from(vec![
+        Span::styled("Date: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(date),
+    ]));
+    if let Some(list) = ListInfo::from_headers(&message.headers) {
+        let mut actions = Vec::new();
+        if list.post_address().is_some() {
+            actions.push("L: reply to list");
+        }
+        if list.can_unsubscribe() {
+            actions.push("U: unsubscribe");
+        }
+        let mut spans = vec![
+            Span::styled("List: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(list.label(), Style::default().fg(Color::Cyan)),
+        ];
+        if let Some(id) = &list.id {
+            spans.push(Span::raw(format!(" <{}>", id)));
+        }
+        if !actions.is_empty() {
+            spans.push(Span::styled(
+                format!(" ({})", actions.join(", ")),
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        headers_text.push(Line::from(spans));
+    }
+
+    if let Some(auth) = app.sender_authentication().filter(|auth| !auth.is_empty()) {
+        let color = match auth.status() {
+            AuthStatus::Verified => Color::Green,
+            AuthStatus::Failed => Color::Red,
+            AuthStatus::Unverified => Color::Gray,
+        };
+        headers_text.push(Line::from(vec![
+            Span::styled("Auth: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(auth.status().label(), Style::default().fg(color)),
+            Span::raw(format!(" ({})", auth.details())),
+        ]));
+    }
+    for warning in app.sender_warnings() {
+        headers_text.push(Line::from(Span::styled(
+            format!("⚠ {}", warning),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )));
+    }
 
     // Add thread info if this is part of a multi-message thread
     if let Some(thread_info) = app.get_thread_info() {
@@ -148,11 +338,18 @@ fn draw_email_headers(f: &mut Frame, app: &App, area: Rect) {
 
 fn draw_email_body(f: &mut Frame, app: &App, area: Rect) {
     // Use the processed email body text if available
-    let body_text = app
+    let mut body_text = app
         .current_email_body
         .clone()
         .unwrap_or_else(|| ratatui::text::Text::from("[No body content]"));
 
+    // Show an invitation above the message text
+    if let Some(calendar) = &app.current_invite {
+        let mut lines = invite_lines(calendar);
+        lines.append(&mut body_text.lines);
+        body_text.lines = lines;
+    }
+
     // Count total lines for scroll indicators
     let total_lines = body_text.lines.len();
 
@@ -175,6 +372,61 @@ fn draw_email_body(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+/// The invitation block shown at the top of the message text.
+fn invite_lines(calendar: &Calendar) -> Vec<Line<'static>> {
+    let Some(event) = calendar.event() else {
+        return Vec::new();
+    };
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let field = |label: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("  {}: ", label), bold),
+            Span::raw(value),
+        ])
+    };
+
+    let summary = event
+        .summary
+        .clone()
+        .unwrap_or_else(|| "(No title)".to_string());
+    let title = if calendar.is_cancelled() {
+        format!("Cancelled: {}", summary)
+    } else {
+        summary
+    };
+    let mut lines = vec![Line::from(Span::styled(
+        format!("📅 {}", title),
+        bold.fg(Color::Cyan),
+    ))];
+    lines.push(field("When", event.when()));
+    if let Some(recurrence) = &event.recurrence {
+        lines.push(field("Repeats", recurrence.summary()));
+    }
+    if let Some(location) = &event.location {
+        lines.push(field("Where", location.clone()));
+    }
+    if let Some(organizer) = &event.organizer {
+        lines.push(field("Organizer", organizer.to_string()));
+    }
+    if !event.attendees.is_empty() {
+        let attendees = event
+            .attendees
+            .iter()
+            .map(|a| format!("{} ({})", a.mailbox.display_name(), a.partstat.label()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(field("Attendees", attendees));
+    }
+    if calendar.expects_reply() {
+        lines.push(Line::from(Span::styled(
+            "  Y: accept, T: tentative, D: decline",
+            Style::default().fg(Color::Gray),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines
+}
+
 fn draw_search(f: &mut Frame, app: &mut App, area: Rect) {
     // Create a centered modal for search input
     let modal_area = centered_rect(60, 20, area);
@@ -211,10 +463,17 @@ fn draw_help(f: &mut Frame, _app: &mut App, area: Rect) {
         Line::from("Actions:"),
         Line::from("  /       - Search"),
         Line::from("  c       - Compose (from email list)"),
+        Line::from("  d       - Drafts (from email list, Enter resumes one)"),
+        Line::from("  o       - Outbox (r: send now, e: edit, x: cancel)"),
         Line::from("  r       - Reply (from email view)"),
         Line::from("  R       - Reply all (from email view)"),
+        Line::from("  L       - Reply to mailing list (from email view)"),
+        Line::from("  U       - Unsubscribe from mailing list (from email view)"),
+        Line::from("  Y/T/D   - Accept/tentatively accept/decline an invitation"),
         Line::from("  f       - Forward (from email view)"),
+        Line::from("  a       - Archive thread to the configured folder"),
         Line::from("  n/p     - Next/previous message in thread (email view)"),
+        Line::from("  u       - Next unread message in thread (email view)"),
         Line::from("  ?       - Show this help"),
         Line::from("  q       - Quit"),
         Line::from(""),
@@ -222,7 +481,13 @@ fn draw_help(f: &mut Frame, _app: &mut App, area: Rect) {
         Line::from("  Tab/Shift+Tab - Navigate fields"),
         Line::from("  Enter    - New line in body field"),
         Line::from("  Ctrl+S   - Send email"),
-        Line::from("  Esc      - Cancel compose"),
+        Line::from("  Ctrl+L   - Send later: type a time such as 08:00 or +2h"),
+        Line::from("  Ctrl+F   - Send as the next configured identity"),
+        Line::from("  Ctrl+A   - Attach a file (Tab completes the path) or a part of"),
+        Line::from("             the original; Del removes the selected attachment"),
+        Line::from("  Ctrl+P   - Cycle OpenPGP: sign, encrypt, sign and encrypt, off"),
+        Line::from("  Ctrl+Z   - Undo sending while the countdown runs"),
+        Line::from("  Esc      - Close, keeping a draft (also saved every 30s)"),
         Line::from(""),
         Line::from("Press any key to continue..."),
     ];
@@ -238,15 +503,40 @@ fn draw_help(f: &mut Frame, _app: &mut App, area: Rect) {
 }
 
 fn draw_compose(f: &mut Frame, app: &mut App, area: Rect) {
+    // The send-at field only takes room once it has been asked for
+    let show_send_at = matches!(
+        app.compose_form.current_field,
+        crate::tui::app::ComposeField::SendAt
+    ) || !app.compose_form.send_at.is_empty();
+    let send_at_height = if show_send_at { 3 } else { 0 };
+
+    // So do the attachments, listing the original's parts and the path
+    // being typed only while the field is selected
+    let attaching = matches!(
+        app.compose_form.current_field,
+        crate::tui::app::ComposeField::Attach
+    );
+    let attachment_lines = if attaching {
+        app.compose_form.attachment_entries().len() + 1
+    } else {
+        app.compose_form.attachments.len()
+    };
+    let attachments_height = match attachment_lines {
+        0 => 0,
+        lines => lines.min(6) as u16 + 2,
+    };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3), // To field
-            Constraint::Length(3), // Cc field
-            Constraint::Length(3), // Bcc field
-            Constraint::Length(3), // Subject field
-            Constraint::Min(1),    // Body field
-            Constraint::Length(1), // Instructions
+            Constraint::Length(3),                  // To field
+            Constraint::Length(3),                  // Cc field
+            Constraint::Length(3),                  // Bcc field
+            Constraint::Length(3),                  // Subject field
+            Constraint::Length(send_at_height),     // Send at field
+            Constraint::Length(attachments_height), // Attachments
+            Constraint::Min(1),                     // Body field
+            Constraint::Length(1),                  // Instructions
         ])
         .split(area);
 
@@ -262,36 +552,44 @@ fn draw_compose(f: &mut Frame, app: &mut App, area: Rect) {
         }
     };
 
-    // To field
+    // Recipient fields that are not valid address lists are outlined in
+    // red, with the reason in their title
+    let recipient_block = |field: crate::tui::app::ComposeField, title: String| {
+        let block = Block::default().borders(Borders::ALL);
+        match &app.compose_form.address_error {
+            Some((error_field, message)) if *error_field == field => block
+                .title(format!("{} - {}", title, message))
+                .border_style(Style::default().fg(Color::Red)),
+            _ => block.title(title).border_style(field_style(&field)),
+        }
+    };
+
+    // To field, titled with the identity the message is sent as
+    let to_title = if app.compose_form.from.is_empty() {
+        "To".to_string()
+    } else {
+        format!("To (from {}, Ctrl+F: switch)", app.compose_form.from)
+    };
     let to_paragraph = Paragraph::new(format!("To: {}_", app.compose_form.to))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("To")
-                .border_style(field_style(&crate::tui::app::ComposeField::To)),
-        )
+        .block(recipient_block(crate::tui::app::ComposeField::To, to_title))
         .wrap(Wrap { trim: false });
     f.render_widget(to_paragraph, chunks[0]);
 
     // Cc field
     let cc_paragraph = Paragraph::new(format!("Cc: {}_", app.compose_form.cc))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Cc")
-                .border_style(field_style(&crate::tui::app::ComposeField::Cc)),
-        )
+        .block(recipient_block(
+            crate::tui::app::ComposeField::Cc,
+            "Cc".to_string(),
+        ))
         .wrap(Wrap { trim: false });
     f.render_widget(cc_paragraph, chunks[1]);
 
     // Bcc field
     let bcc_paragraph = Paragraph::new(format!("Bcc: {}_", app.compose_form.bcc))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Bcc")
-                .border_style(field_style(&crate::tui::app::ComposeField::Bcc)),
-        )
+        .block(recipient_block(
+            crate::tui::app::ComposeField::Bcc,
+            "Bcc".to_string(),
+        ))
         .wrap(Wrap { trim: false });
     f.render_widget(bcc_paragraph, chunks[2]);
 
@@ -306,6 +604,67 @@ fn draw_compose(f: &mut Frame, app: &mut App, area: Rect) {
         .wrap(Wrap { trim: false });
     f.render_widget(subject_paragraph, chunks[3]);
 
+    // Send at field
+    if show_send_at {
+        let send_at_paragraph = Paragraph::new(format!("Send at: {}_", app.compose_form.send_at))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Send at (e.g. 2026-10-20 08:00, tomorrow 08:00, 17:30, +2h)")
+                    .border_style(field_style(&crate::tui::app::ComposeField::SendAt)),
+            );
+        f.render_widget(send_at_paragraph, chunks[4]);
+    }
+
+    // Attachments
+    if attachments_height > 0 {
+        let mut lines = Vec::new();
+        if attaching {
+            lines.push(Line::from(format!(
+                "Path: {}_",
+                app.compose_form.attach_path
+            )));
+        }
+        for (i, entry) in app.compose_form.attachment_entries().iter().enumerate() {
+            let (marker, suffix) = match entry {
+                AttachmentEntry::Attached(_) => ("  ", ""),
+                // Parts of the original are only offered while attaching
+                AttachmentEntry::Original(_) if !attaching => continue,
+                AttachmentEntry::Original(_) => ("+ ", " from the original"),
+            };
+            let style = if attaching && i == app.compose_form.selected_attachment {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(Span::styled(
+                format!("{}{}{}", marker, entry.label(), suffix),
+                style,
+            )));
+        }
+        let total: usize = app
+            .compose_form
+            .attachments
+            .iter()
+            .map(|attached| attached.attachment.data.len())
+            .sum();
+        let attachments_title = if attaching {
+            format!(
+                "Attachments, {} (Tab: complete, Enter: attach, ↑/↓: select, Del: remove, Ctrl+A: back)",
+                format_size(total)
+            )
+        } else {
+            format!("Attachments, {} (Ctrl+A: edit)", format_size(total))
+        };
+        let attachments_paragraph = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(attachments_title)
+                .border_style(field_style(&crate::tui::app::ComposeField::Attach)),
+        );
+        f.render_widget(attachments_paragraph, chunks[5]);
+    }
+
     // Body field
     let body_text = if matches!(
         app.compose_form.current_field,
@@ -323,7 +682,26 @@ fn draw_compose(f: &mut Frame, app: &mut App, area: Rect) {
         "Body [Plain]"
     };
 
-    let body_paragraph = Paragraph::new(body_text)
+    // A reply's quote is shown dimmed on the side the body goes
+    let mut body = Text::from(body_text);
+    if !app.compose_form.quote.is_empty() {
+        let quote = Text::styled(
+            app.compose_form.quote.clone(),
+            Style::default().fg(Color::DarkGray),
+        );
+        body = if app.compose_form.quoting.top_posting {
+            body.lines.push(Line::default());
+            body.lines.extend(quote.lines);
+            body
+        } else {
+            let mut quoted = quote;
+            quoted.lines.push(Line::default());
+            quoted.lines.extend(body.lines);
+            quoted
+        };
+    }
+
+    let body_paragraph = Paragraph::new(body)
         .block(
             Block::default()
                 .borders(Borders::ALL)
@@ -331,32 +709,63 @@ fn draw_compose(f: &mut Frame, app: &mut App, area: Rect) {
                 .border_style(field_style(&crate::tui::app::ComposeField::Body)),
         )
         .wrap(Wrap { trim: true });
-    f.render_widget(body_paragraph, chunks[4]);
+    f.render_widget(body_paragraph, chunks[6]);
 
     // Instructions with mode indicator and Ctrl+M toggle
-    let mode_text = if app.compose_form.markdown_mode { "Markdown" } else { "Plain" };
+    let mut mode_text = if app.compose_form.markdown_mode {
+        "Markdown".to_string()
+    } else {
+        "Plain".to_string()
+    };
+    if app.compose_form.pgp.is_enabled() {
+        mode_text = format!("{}, {}", mode_text, app.compose_form.pgp.label());
+    }
     let instructions = match app.compose_form.mode {
         crate::tui::app::ComposeMode::New => {
-            format!("New Email [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Esc: cancel", mode_text)
+            format!(
+                "New Email [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Ctrl+L: send later, Ctrl+A: attach, Ctrl+P: sign/encrypt, Esc: save draft & close",
+                mode_text
+            )
         }
         crate::tui::app::ComposeMode::Reply(_) => {
-            format!("Reply [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Esc: cancel", mode_text)
+            format!(
+                "Reply [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Ctrl+L: send later, Ctrl+A: attach, Ctrl+P: sign/encrypt, Esc: save draft & close",
+                mode_text
+            )
         }
         crate::tui::app::ComposeMode::ReplyAll(_) => {
-            format!("Reply All [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Esc: cancel", mode_text)
+            format!(
+                "Reply All [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Ctrl+L: send later, Ctrl+A: attach, Ctrl+P: sign/encrypt, Esc: save draft & close",
+                mode_text
+            )
+        }
+        crate::tui::app::ComposeMode::ReplyList(_) => {
+            format!(
+                "Reply to List [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Ctrl+L: send later, Ctrl+A: attach, Ctrl+P: sign/encrypt, Esc: save draft & close",
+                mode_text
+            )
         }
         crate::tui::app::ComposeMode::Forward(_) => {
-            format!("Forward [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Esc: cancel", mode_text)
+            format!(
+                "Forward [{}] - Tab/Shift+Tab: switch fields, Enter: newline, Ctrl+M: toggle mode, Ctrl+S: send, Ctrl+L: send later, Ctrl+A: attach, Ctrl+P: sign/encrypt, Esc: save draft & close",
+                mode_text
+            )
         }
     };
 
     let instructions_paragraph =
         Paragraph::new(instructions).style(Style::default().fg(Color::Gray));
-    f.render_widget(instructions_paragraph, chunks[5]);
+    f.render_widget(instructions_paragraph, chunks[7]);
 }
 
 fn draw_status_bar(f: &mut Frame, app: &mut App, area: Rect) {
-    let status_text = if let Some(ref message) = app.status_message {
+    let status_text = if let Some(pending) = &app.pending_send {
+        format!(
+            "Sending {} in {}s | Ctrl+Z: undo",
+            pending.kind,
+            pending.seconds_left()
+        )
+    } else if let Some(ref message) = app.status_message {
         message.clone()
     } else {
         match app.state {
@@ -369,6 +778,10 @@ fn draw_status_bar(f: &mut Frame, app: &mut App, area: Rect) {
             AppState::Search => "Type your search query and press Enter".to_string(),
             AppState::Help => "Press any key to close help".to_string(),
             AppState::Compose => "Compose mode".to_string(),
+            AppState::Outbox => {
+                "Scheduled messages are sent and failed ones retried automatically | Press ? for help"
+                    .to_string()
+            }
         }
     };
 
@@ -1,4 +1,4 @@
-use crate::body::{BodyContent, BodyPart};
+use crate::body::{self, BodyPart};
 use crate::thread::Message;
 use ammonia::Builder;
 use maplit::hashset;
@@ -37,43 +37,45 @@ impl RenderedContent {
 }
 
 pub fn render_message_content(message: &Message) -> RenderedContent {
-    render_message_content_with_options(message, false, false)
+    render_message_content_with_options(message, false, false, None)
 }
 
 pub fn render_message_content_with_url_rewriting(message: &Message) -> RenderedContent {
-    render_message_content_with_options(message, true, false)
+    render_message_content_with_options(message, true, false, None)
 }
 
+/// Render a message for display in the email frame.
+///
+/// `cid:` references to inline parts are rewritten to
+/// `{part_url_prefix}/{part_id}` so embedded images load from the message.
 pub fn render_message_content_with_image_control(
     message: &Message,
     show_images: bool,
+    part_url_prefix: &str,
 ) -> RenderedContent {
-    render_message_content_with_options(message, true, !show_images)
+    render_message_content_with_options(message, true, !show_images, Some(part_url_prefix))
 }
 
 fn render_message_content_with_options(
     message: &Message,
     rewrite_urls: bool,
     block_images: bool,
+    part_url_prefix: Option<&str>,
 ) -> RenderedContent {
-    let mut html_content = None;
-    let mut plain_content = None;
-
     tracing::debug!(
         "Rendering message {} with {} body parts",
         message.id,
         message.body.len()
     );
 
-    // Extract content from all body parts
-    for body_part in &message.body {
-        tracing::debug!(
-            "Processing body part id={}, type={}",
-            body_part.id,
-            body_part.content_type
-        );
-        extract_content_from_body_part(body_part, &mut html_content, &mut plain_content);
-    }
+    // Pick the best HTML and plain text parts, skipping attachments
+    let text_of = |media_type: &str| {
+        body::find_body(&message.body, &[media_type])
+            .and_then(BodyPart::display_text)
+            .map(|text| text.into_owned())
+    };
+    let mut html_content = text_of("text/html");
+    let plain_content = text_of("text/plain");
 
     tracing::debug!(
         "After extraction - has_html: {}, has_plain: {}",
@@ -83,8 +85,12 @@ fn render_message_content_with_options(
 
     // Sanitize HTML content if present
     if let Some(html) = &html_content {
+        let html = match part_url_prefix {
+            Some(prefix) => rewrite_cid_references(html, &message.body, prefix),
+            None => html.clone(),
+        };
         html_content = Some(sanitize_html_with_image_control(
-            html,
+            &html,
             rewrite_urls,
             block_images,
         ));
@@ -112,60 +118,20 @@ fn render_message_content_with_options(
     }
 }
 
-fn extract_content_from_body_part(
-    body_part: &BodyPart,
-    html: &mut Option<String>,
-    plain: &mut Option<String>,
-) {
-    tracing::debug!(
-        "Extracting from part: id={}, type={}, is_attachment={}",
-        body_part.id,
-        body_part.content_type,
-        body_part.is_attachment()
-    );
-
-    // Skip attachments
-    if body_part.is_attachment() {
-        tracing::debug!("Skipping attachment part {}", body_part.id);
-        return;
-    }
-
-    match body_part.content_type.as_str() {
-        "text/html" => {
-            tracing::debug!("Found HTML content in part {}", body_part.id);
-            if html.is_none() {
-                if let BodyContent::Text(content) = &body_part.content {
-                    tracing::debug!("Setting HTML content (length: {})", content.len());
-                    *html = Some(content.clone());
-                }
+/// Point `cid:` URLs in `html` at the parts they reference.
+///
+/// References that do not resolve are left alone and get dropped by the
+/// sanitizer, which does not allow the `cid` scheme.
+fn rewrite_cid_references(html: &str, parts: &[BodyPart], prefix: &str) -> String {
+    let cid_pattern = Regex::new(r#"(?i)\bcid:([^"'\s>)]+)"#).unwrap();
+    cid_pattern
+        .replace_all(html, |captures: &regex::Captures| {
+            match body::resolve_cid(parts, &captures[1]) {
+                Some(part) => format!("{}/{}", prefix, part.id),
+                None => captures[0].to_string(),
             }
-        }
-        "text/plain" => {
-            tracing::debug!("Found plain text content in part {}", body_part.id);
-            if plain.is_none() {
-                if let BodyContent::Text(content) = &body_part.content {
-                    tracing::debug!("Setting plain text content (length: {})", content.len());
-                    *plain = Some(content.clone());
-                }
-            }
-        }
-        ct if ct.starts_with("multipart/") => {
-            tracing::debug!("Processing multipart {} in part {}", ct, body_part.id);
-            // Recursively extract from nested parts
-            if let BodyContent::Multipart(parts) = &body_part.content {
-                tracing::debug!("Found {} nested parts", parts.len());
-                for part in parts {
-                    extract_content_from_body_part(part, html, plain);
-                }
-            }
-        }
-        _ => {
-            tracing::debug!(
-                "Skipping unsupported content type: {}",
-                body_part.content_type
-            );
-        }
-    }
+        })
+        .into_owned()
 }
 
 fn sanitize_html_with_image_control(html: &str, rewrite_urls: bool, block_images: bool) -> String {
@@ -495,7 +461,7 @@ pub fn plain_text_to_html(text: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::body::BodyPart;
+    use crate::body::{BodyContent, BodyPart};
     use crate::common::Headers;
     use crate::thread::Message;
     use std::collections::HashMap;
@@ -593,6 +559,7 @@ mod tests {
                 content: BodyContent::Text("Hello, this is plain text".to_string()),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
@@ -641,6 +608,7 @@ mod tests {
                 content: BodyContent::Text("<p>Hello <b>world</b></p>".to_string()),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
@@ -689,6 +657,7 @@ mod tests {
                         content: BodyContent::Text("Plain text version".to_string()),
                         content_disposition: None,
                         content_id: None,
+                        content_charset: None,
                         filename: None,
                         content_transfer_encoding: None,
                         content_length: None,
@@ -699,6 +668,7 @@ mod tests {
                         content: BodyContent::Text("<p>HTML version</p>".to_string()),
                         content_disposition: None,
                         content_id: None,
+                        content_charset: None,
                         filename: None,
                         content_transfer_encoding: None,
                         content_length: None,
@@ -706,6 +676,7 @@ mod tests {
                 ]),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
@@ -733,4 +704,62 @@ mod tests {
                 .contains("<p>HTML version</p>")
         );
     }
+
+    #[test]
+    fn test_render_rewrites_cid_references() {
+        let leaf = |id: u32, content_type: &str, content: BodyContent| BodyPart {
+            id,
+            content_type: content_type.to_string(),
+            content,
+            content_disposition: None,
+            content_id: None,
+            content_charset: None,
+            filename: None,
+            content_transfer_encoding: None,
+            content_length: None,
+        };
+        let mut logo = leaf(3, "image/png", BodyContent::Empty);
+        logo.content_id = Some("<logo@example.com>".to_string());
+
+        let message = Message {
+            id: "related@example.com".to_string(),
+            is_match: true,
+            excluded: false,
+            filename: vec![],
+            timestamp: 0,
+            date_relative: "now".to_string(),
+            tags: vec![],
+            duplicate: None,
+            body: vec![leaf(
+                1,
+                "multipart/related",
+                BodyContent::Multipart(vec![
+                    leaf(
+                        2,
+                        "text/html",
+                        BodyContent::Text(
+                            r#"<img src="cid:logo@example.com"><img src="cid:missing">"#
+                                .to_string(),
+                        ),
+                    ),
+                    logo,
+                ]),
+            )],
+            crypto: crate::common::CryptoInfo::default(),
+            headers: Headers {
+                subject: None,
+                from: "test@example.com".to_string(),
+                to: None,
+                date: "2024-01-01".to_string(),
+                reply_to: None,
+                additional: HashMap::new(),
+            },
+        };
+
+        let rendered =
+            render_message_content_with_image_control(&message, false, "/attachment/t/0");
+        let html = rendered.html.unwrap();
+        assert!(html.contains(r#"src="/attachment/t/0/3""#));
+        assert!(!html.contains("cid:"));
+    }
 }
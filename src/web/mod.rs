@@ -1,19 +1,36 @@
+use crate::auth::{Authentication, PhishingWarning, phishing_warnings};
 use crate::body::BodyContent;
-use crate::client::NotmuchClient;
+use crate::calendar::{Calendar, PartStat, load_invite, send_rsvp};
+use crate::client::{ExpungeOptions, ExpungeReport, NotmuchClient, TagOperation};
+use crate::common::{Mailbox, encode_extended_value};
 use crate::config::UserConfig;
-use crate::mail_sender::{MailSender, MessageBuilder};
+use crate::draft::{DRAFT_TAG, DRAFTS_QUERY, Draft, discard_draft};
+use crate::list::{ListInfo, Unsubscribed, Unsubscriber, list_labels};
+use crate::mail_sender::message::{format_size, forwarded_attachments};
+use crate::mail_sender::{
+    Attachment, AttachmentLimits, ComposableMessage, ForwardMode, Gpg, MailSender, MessageBuilder,
+    PgpOptions, pgp, quote, send_and_file,
+};
+use crate::outbox::{self, Delivery, Outbox, OutboxEntry, OutboxStatus, parse_send_at};
 use crate::search::SearchItem;
+use crate::summary::ThreadSummary;
+use crate::text_renderer::TextRendererConfig;
+use crate::text_renderer::builtin::BuiltinConverter;
+use crate::tui::markdown::markdown_to_html;
 use askama_axum::{IntoResponse, Template};
+use async_trait::async_trait;
 use axum::{
     Form, Json, Router,
-    extract::{Path, Query, State},
+    extract::{DefaultBodyLimit, FromRequest, Multipart, Path, Query, Request, State},
     http::{HeaderMap, StatusCode, header},
     response::Redirect,
     routing::{get, post},
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tower_http::services::ServeDir;
 
@@ -37,6 +54,45 @@ pub struct WebConfig {
     pub initial_page_size: usize,
     pub pagination_size: usize,
     pub infinite_scroll_enabled: bool,
+    /// Maildir folder the archive action moves threads to (local databases)
+    pub archive_folder: Option<String>,
+    /// Maildir folder copies of sent messages are filed in
+    pub sent_folder: Option<String>,
+    /// Maildir folder drafts are saved in
+    pub drafts_folder: String,
+    /// Spool directory outgoing mail is queued in; without one messages
+    /// are sent directly
+    pub outbox_dir: Option<PathBuf>,
+    /// How long a sent message can still be taken back; needs the outbox
+    pub undo_send_delay: std::time::Duration,
+    /// Safeguards for permanently deleting messages from the settings page
+    pub expunge: ExpungeOptions,
+    /// Limits on the files attached in the compose form
+    pub attachment_limits: AttachmentLimits,
+    /// gpg used to sign and encrypt mail from the compose form
+    pub gpg: Gpg,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig {
+            bind_address: ([127, 0, 0, 1], 8080).into(),
+            base_url: "http://localhost:8080".to_string(),
+            items_per_page: 50,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            archive_folder: None,
+            sent_folder: None,
+            drafts_folder: "Drafts".to_string(),
+            outbox_dir: None,
+            undo_send_delay: std::time::Duration::ZERO,
+            expunge: ExpungeOptions::default(),
+            attachment_limits: AttachmentLimits::default(),
+            gpg: Gpg::default(),
+        }
+    }
 }
 
 #[derive(Template)]
@@ -52,14 +108,31 @@ struct InboxTemplate {
     pagination_size: usize,
     infinite_scroll_enabled: bool,
     has_more_messages: bool,
+    /// Mailing list labels by thread ID
+    list_labels: HashMap<String, String>,
 }
 
+/// Room for the text fields of the compose form on top of its attachments.
+const FORM_SIZE_ALLOWANCE: usize = 1024 * 1024;
+
 pub fn create_app(state: AppState) -> Router {
+    // Compose forms carry uploads, so they may be larger than other requests
+    let upload_limit = DefaultBodyLimit::max(
+        state
+            .config
+            .attachment_limits
+            .max_total_size
+            .saturating_add(FORM_SIZE_ALLOWANCE),
+    );
+
     Router::new()
         .route("/", get(index_handler))
         .route("/inbox", get(inbox_handler))
         .route("/search", get(search_handler))
         .route("/thread/:id", get(thread_handler))
+        .route("/thread/:id/archive", post(archive_handler))
+        .route("/thread/:id/unsubscribe", post(unsubscribe_handler))
+        .route("/thread/:id/rsvp", post(rsvp_handler))
         .route(
             "/attachment/:thread_id/:message_index/:part_id",
             get(attachment_handler),
@@ -73,9 +146,11 @@ pub fn create_app(state: AppState) -> Router {
         .route("/tags", get(tags_handler))
         .route("/settings", get(settings_handler))
         .route("/settings/theme", post(toggle_theme_handler))
+        .route("/settings/expunge", post(expunge_handler))
         .route("/api/log-redirect", post(log_redirect_handler))
         .route("/api/refresh-query", get(refresh_query_handler))
         .route("/api/load-more", get(load_more_handler))
+        .route("/api/thread-summary/:id", get(thread_summary_handler))
         .route("/test/email-gallery", get(test_email_gallery_handler))
         .route(
             "/test/email-gallery/:email_name",
@@ -83,15 +158,30 @@ pub fn create_app(state: AppState) -> Router {
         )
         .route(
             "/compose",
-            get(compose_get_handler).post(compose_post_handler),
+            get(compose_get_handler)
+                .post(compose_post_handler)
+                .layer(upload_limit),
         )
+        .route("/drafts", get(drafts_handler))
+        .route("/drafts/save", post(draft_save_handler))
+        .route("/drafts/:id", get(draft_resume_handler))
+        .route("/drafts/:id/delete", post(draft_delete_handler))
+        .route("/outbox", get(outbox_handler))
+        .route("/outbox/:id/cancel", post(outbox_cancel_handler))
+        .route("/outbox/:id/retry", post(outbox_retry_handler))
+        .route("/outbox/:id/edit", post(outbox_edit_handler))
+        .route("/outbox/:id/undo", post(outbox_undo_handler))
         .route(
             "/thread/:id/reply",
-            get(reply_get_handler).post(reply_post_handler),
+            get(reply_get_handler)
+                .post(reply_post_handler)
+                .layer(upload_limit),
         )
         .route(
             "/thread/:id/forward",
-            get(forward_get_handler).post(forward_post_handler),
+            get(forward_get_handler)
+                .post(forward_post_handler)
+                .layer(upload_limit),
         )
         .nest_service("/static", ServeDir::new("src/web/static"))
         .with_state(state)
@@ -115,6 +205,19 @@ fn get_theme_from_headers(headers: &HeaderMap) -> String {
         .to_string()
 }
 
+/// Mailing list labels for a page of search results.
+///
+/// Labels are decoration, so a failure is logged and yields no labels.
+async fn search_list_labels(
+    client: &dyn NotmuchClient,
+    items: &[SearchItem],
+) -> HashMap<String, String> {
+    list_labels(client, items).await.unwrap_or_else(|e| {
+        tracing::warn!("Failed to load mailing list headers: {}", e);
+        HashMap::new()
+    })
+}
+
 async fn inbox_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
     // Search for messages tagged with "inbox" with pagination
     let (messages, total_count) = match state
@@ -138,6 +241,7 @@ async fn inbox_handler(State(state): State<AppState>, headers: HeaderMap) -> imp
 
     let theme = get_theme_from_headers(&headers);
     let messages_count = messages.len();
+    let list_labels = search_list_labels(state.client.as_ref(), &messages).await;
 
     // Calculate if there are more messages available
     let has_more_messages = if let Some(total) = total_count {
@@ -158,18 +262,104 @@ async fn inbox_handler(State(state): State<AppState>, headers: HeaderMap) -> imp
         pagination_size: state.config.pagination_size,
         infinite_scroll_enabled: state.config.infinite_scroll_enabled,
         has_more_messages,
+        list_labels,
     }
 }
 
+/// Query suggested on the settings page for cleaning up deleted mail
+const DEFAULT_EXPUNGE_QUERY: &str = "tag:deleted AND date:..30d";
+
 #[derive(Template)]
 #[template(path = "settings.html")]
 struct SettingsTemplate {
     theme: String,
+    expunge_query: String,
+    protected_tags: String,
+    trash_dir: Option<String>,
+    expunge_report: Option<ExpungeReport>,
+    expunge_message: Option<String>,
+    expunge_error: Option<String>,
 }
 
-async fn settings_handler(headers: HeaderMap) -> impl IntoResponse {
+impl SettingsTemplate {
+    fn new(theme: String, options: &ExpungeOptions, expunge_query: String) -> Self {
+        SettingsTemplate {
+            theme,
+            expunge_query,
+            protected_tags: options.protected_tags.join(", "),
+            trash_dir: options
+                .trash_dir
+                .as_ref()
+                .map(|dir| dir.display().to_string()),
+            expunge_report: None,
+            expunge_message: None,
+            expunge_error: None,
+        }
+    }
+}
+
+async fn settings_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+    SettingsTemplate::new(
+        theme,
+        &state.config.expunge,
+        DEFAULT_EXPUNGE_QUERY.to_string(),
+    )
+}
+
+#[derive(Deserialize)]
+struct ExpungeForm {
+    query: String,
+    /// Confirmation token; absent for a dry run
+    token: Option<String>,
+}
+
+async fn expunge_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<ExpungeForm>,
+) -> impl IntoResponse {
     let theme = get_theme_from_headers(&headers);
-    SettingsTemplate { theme }
+    let options = &state.config.expunge;
+    let mut template = SettingsTemplate::new(theme, options, form.query.clone());
+
+    match form.token.as_deref().filter(|token| !token.is_empty()) {
+        None => match state.client.expunge_report(&form.query, options).await {
+            Ok(report) => template.expunge_report = Some(report),
+            Err(e) => {
+                tracing::error!("Expunge dry run for '{}' failed: {}", form.query, e);
+                template.expunge_error = Some(format!("Dry run failed: {}", e));
+            }
+        },
+        Some(token) => match state.client.expunge(&form.query, options, token).await {
+            Ok(outcome) => {
+                tracing::info!(
+                    "Expunged {} messages ({} files) for '{}'",
+                    outcome.messages,
+                    outcome.files,
+                    form.query
+                );
+                template.expunge_message = Some(match &outcome.trash_dir {
+                    Some(dir) => format!(
+                        "Moved {} files of {} messages to {}.",
+                        outcome.files,
+                        outcome.messages,
+                        dir.display()
+                    ),
+                    None => format!(
+                        "Deleted {} files of {} messages.",
+                        outcome.files, outcome.messages
+                    ),
+                });
+            }
+            Err(e) => {
+                tracing::error!("Expunge for '{}' failed: {}", form.query, e);
+                template.expunge_error = Some(format!("Expunge failed: {}", e));
+            }
+        },
+    }
+
+    template
 }
 
 async fn toggle_theme_handler(headers: HeaderMap) -> impl IntoResponse {
@@ -255,6 +445,7 @@ async fn search_handler(
 
     let theme = get_theme_from_headers(&headers);
     let messages_count = messages.len();
+    let list_labels = search_list_labels(state.client.as_ref(), &messages).await;
 
     // Calculate if there are more messages available
     let has_more_messages = if let Some(total) = total_count {
@@ -299,6 +490,7 @@ async fn search_handler(
         pagination_size: state.config.pagination_size,
         infinite_scroll_enabled: state.config.infinite_scroll_enabled,
         has_more_messages,
+        list_labels,
     }
 }
 
@@ -310,36 +502,165 @@ struct MessageWithContent {
     rendered_content: RenderedContent,
     thread_id: String,
     message_index: usize,
+    /// Nesting level in the reply tree, capped for display
+    indent: usize,
+    /// Display name of the sender of the message this one replies to
+    in_reply_to: Option<String>,
+    /// Mailing list the message was sent through
+    list: Option<ListInfo>,
+    /// Calendar invitation carried by the message
+    invite: Option<InviteCard>,
+    /// SPF, DKIM and DMARC results for the sender
+    auth: Authentication,
+    /// Reasons to distrust the sender
+    warnings: Vec<PhishingWarning>,
+}
+
+/// A calendar invitation prepared for the thread view.
+#[derive(Clone)]
+struct InviteCard {
+    summary: String,
+    when: String,
+    recurrence: Option<String>,
+    location: Option<String>,
+    organizer: Option<String>,
+    /// Display names with participation status labels
+    attendees: Vec<(String, &'static str)>,
+    cancelled: bool,
+    /// Whether RSVP buttons are offered: the organizer asked for a
+    /// response and mail sending is configured
+    can_respond: bool,
+    /// The user's own current response
+    response: Option<&'static str>,
+}
+
+impl InviteCard {
+    fn new(calendar: &Calendar, addresses: &[&str], can_send: bool) -> Option<InviteCard> {
+        let event = calendar.event()?;
+        let response = event
+            .attendees
+            .iter()
+            .find(|a| {
+                addresses
+                    .iter()
+                    .any(|address| a.mailbox.has_address(address))
+            })
+            .map(|a| a.partstat)
+            .filter(|partstat| *partstat != PartStat::NeedsAction)
+            .map(|partstat| partstat.label());
+        Some(InviteCard {
+            summary: event
+                .summary
+                .clone()
+                .unwrap_or_else(|| "(No title)".to_string()),
+            when: event.when(),
+            recurrence: event.recurrence.as_ref().map(|r| r.summary()),
+            location: event.location.clone(),
+            organizer: event.organizer.as_ref().map(|o| o.to_string()),
+            attendees: event
+                .attendees
+                .iter()
+                .map(|a| (a.mailbox.display_name().to_string(), a.partstat.label()))
+                .collect(),
+            cancelled: calendar.is_cancelled(),
+            can_respond: can_send && calendar.expects_reply(),
+            response,
+        })
+    }
+}
+
+/// Deepest reply nesting shown in the thread view; deeper replies line up
+/// with this level so long conversations stay readable.
+const MAX_THREAD_INDENT: usize = 6;
+
+#[derive(Deserialize)]
+struct ThreadParams {
+    /// Label of a list that was just unsubscribed from
+    unsubscribed: Option<String>,
+    /// Response just sent to an invitation: accept, tentative or decline
+    rsvp: Option<String>,
 }
 
 async fn thread_handler(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    Query(params): Query<ThreadParams>,
     headers: HeaderMap,
 ) -> impl IntoResponse {
     match state.client.show(&format!("thread:{}", id)).await {
         Ok(thread) => {
-            let messages = thread.get_messages();
-            tracing::info!("Loaded thread {} with {} messages", id, messages.len());
             let theme = get_theme_from_headers(&headers);
 
-            // Process messages to include rendered content
-            let messages_with_content: Vec<MessageWithContent> = messages
-                .into_iter()
+            let known_domains = state.user_config.known_domains();
+
+            // Process messages in reply order to include rendered content;
+            // indexes match `get_messages`, which the reply links rely on
+            let mut messages_with_content: Vec<MessageWithContent> = thread
+                .iter()
                 .enumerate()
-                .map(|(idx, msg)| {
+                .map(|(idx, entry)| {
+                    let msg = entry.message();
                     let rendered_content = render_message_content(msg);
+                    let in_reply_to = entry.parent.map(|parent| {
+                        let headers = &parent.message().headers;
+                        headers
+                            .sender()
+                            .map(|sender| sender.display_name().to_string())
+                            .unwrap_or_else(|| headers.from.clone())
+                    });
                     MessageWithContent {
                         message: msg.clone(),
                         rendered_content,
                         thread_id: id.clone(),
                         message_index: idx,
+                        indent: entry.depth.min(MAX_THREAD_INDENT),
+                        in_reply_to,
+                        list: ListInfo::from_headers(&msg.headers),
+                        invite: None,
+                        auth: Authentication::from_headers(
+                            &msg.headers,
+                            state.user_config.trusted_authserv_ids(),
+                        ),
+                        warnings: phishing_warnings(&msg.headers, &known_domains),
                     }
                 })
                 .collect();
 
+            let addresses = state.user_config.addresses();
+            for entry in &mut messages_with_content {
+                match load_invite(state.client.as_ref(), &entry.message).await {
+                    Ok(Some(calendar)) => {
+                        entry.invite =
+                            InviteCard::new(&calendar, &addresses, state.mail_sender.is_some())
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Failed to read invitation in {}: {}", entry.message.id, e)
+                    }
+                }
+            }
+            tracing::info!(
+                "Loaded thread {} with {} messages",
+                id,
+                messages_with_content.len()
+            );
+
             ThreadView {
+                thread_id: id,
+                summary: ThreadSummary::new(&thread, &state.user_config.addresses()),
                 messages: messages_with_content,
+                archive_enabled: state.config.archive_folder.is_some()
+                    && state.client.can_move_files(),
+                notice: params
+                    .unsubscribed
+                    .map(|list| format!("Unsubscribed from {}", list))
+                    .or_else(|| {
+                        params
+                            .rsvp
+                            .as_deref()
+                            .and_then(PartStat::from_response)
+                            .map(|partstat| format!("Response sent: {}", partstat.label()))
+                    }),
                 theme,
             }
             .into_response()
@@ -358,10 +679,231 @@ async fn thread_handler(
 #[derive(Template)]
 #[template(path = "thread_simple.html")]
 struct ThreadView {
+    thread_id: String,
+    summary: ThreadSummary,
     messages: Vec<MessageWithContent>,
+    archive_enabled: bool,
+    notice: Option<String>,
     theme: String,
 }
 
+async fn archive_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+
+    let Some(folder) = state.config.archive_folder.as_deref() else {
+        return (
+            StatusCode::NOT_FOUND,
+            ThreadErrorTemplate {
+                message: "Archiving is not configured.".to_string(),
+                theme,
+            },
+        )
+            .into_response();
+    };
+
+    // Untag only once the files have moved, so a failed move leaves the
+    // thread in the inbox
+    let query = format!("thread:{}", id);
+    let result = async {
+        let moved = state.client.move_to_folder(&query, folder).await?;
+        state
+            .client
+            .tag(&query, &[TagOperation::Remove("inbox".to_string())])
+            .await?;
+        Ok::<_, crate::error::NotmuchError>(moved)
+    }
+    .await;
+
+    match result {
+        Ok(moved) => {
+            tracing::info!(
+                "Archived thread {} ({} files moved to {})",
+                id,
+                moved,
+                folder
+            );
+            Redirect::to("/inbox").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to archive thread {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ThreadErrorTemplate {
+                    message: format!("Failed to archive thread: {}", e),
+                    theme,
+                },
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeParams {
+    message: usize,
+}
+
+async fn unsubscribe_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<UnsubscribeParams>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            ThreadErrorTemplate {
+                message,
+                theme: theme.clone(),
+            },
+        )
+            .into_response()
+    };
+
+    let thread = match state.client.show(&format!("thread:{}", id)).await {
+        Ok(thread) => thread,
+        Err(e) => {
+            tracing::error!("Failed to load thread {}: {}", id, e);
+            return error(StatusCode::NOT_FOUND, "Thread not found".to_string());
+        }
+    };
+    let Some(list) = thread
+        .get_messages()
+        .get(params.message)
+        .and_then(|message| ListInfo::from_headers(&message.headers))
+    else {
+        return error(
+            StatusCode::NOT_FOUND,
+            "This message was not sent through a mailing list".to_string(),
+        );
+    };
+
+    match Unsubscriber::default()
+        .unsubscribe(&list, state.mail_sender.as_deref())
+        .await
+    {
+        Ok(Unsubscribed::Browser(url)) => {
+            // Nothing could be done automatically; let the user visit the
+            // list's page through the usual external link warning
+            Redirect::to(&format!("/redirect?url={}", urlencoding::encode(&url))).into_response()
+        }
+        Ok(outcome) => {
+            tracing::info!("Unsubscribed from {}: {:?}", list.label(), outcome);
+            Redirect::to(&format!(
+                "/thread/{}?unsubscribed={}",
+                id,
+                urlencoding::encode(&list.label())
+            ))
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to unsubscribe from {}: {}", list.label(), e);
+            error(
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to unsubscribe from {}: {}", list.label(), e),
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RsvpParams {
+    message: usize,
+}
+
+#[derive(Deserialize)]
+struct RsvpForm {
+    /// accept, tentative or decline
+    response: String,
+}
+
+async fn rsvp_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<RsvpParams>,
+    headers: HeaderMap,
+    Form(form): Form<RsvpForm>,
+) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            ThreadErrorTemplate {
+                message,
+                theme: theme.clone(),
+            },
+        )
+            .into_response()
+    };
+
+    let Some(mail_sender) = &state.mail_sender else {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Mail sending is not configured.".to_string(),
+        );
+    };
+    let Some(partstat) = PartStat::from_response(&form.response) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown response: {}", form.response),
+        );
+    };
+    let thread = match state.client.show(&format!("thread:{}", id)).await {
+        Ok(thread) => thread,
+        Err(e) => {
+            tracing::error!("Failed to load thread {}: {}", id, e);
+            return error(StatusCode::NOT_FOUND, "Thread not found".to_string());
+        }
+    };
+    let Some(message) = thread.get_messages().get(params.message).copied() else {
+        return error(StatusCode::NOT_FOUND, "Message not found".to_string());
+    };
+    let calendar = match load_invite(state.client.as_ref(), message).await {
+        Ok(Some(calendar)) => calendar,
+        Ok(None) => {
+            return error(
+                StatusCode::NOT_FOUND,
+                "This message has no calendar invitation".to_string(),
+            );
+        }
+        Err(e) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read invitation: {}", e),
+            );
+        }
+    };
+
+    // Respond as the identity that was invited
+    let identity = state.user_config.reply_identity(&message.headers);
+    match send_rsvp(
+        mail_sender.as_ref(),
+        message,
+        &calendar,
+        partstat,
+        identity.as_ref().map(|identity| identity.email.as_str()),
+    )
+    .await
+    {
+        Ok(message_id) => {
+            tracing::info!("Sent {} reply {}", partstat.as_str(), message_id);
+            Redirect::to(&format!("/thread/{}?rsvp={}", id, form.response)).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to send invitation reply: {}", e);
+            error(
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to send invitation reply: {}", e),
+            )
+        }
+    }
+}
+
 #[derive(Template)]
 #[template(path = "error.html")]
 struct ThreadErrorTemplate {
@@ -463,6 +1005,11 @@ async fn attachment_handler(
 
                     // Sanitize filename to prevent path traversal
                     let safe_filename = sanitize_filename(&filename);
+                    let safe_filename = if safe_filename.is_empty() {
+                        format!("attachment_{}", part_id)
+                    } else {
+                        safe_filename
+                    };
 
                     // Build response with appropriate headers
                     let mut headers = HeaderMap::new();
@@ -474,7 +1021,7 @@ async fn attachment_handler(
                     }
 
                     // Set content disposition
-                    let disposition = format!("attachment; filename=\"{}\"", safe_filename);
+                    let disposition = content_disposition(&safe_filename);
                     headers.insert(header::CONTENT_DISPOSITION, disposition.parse().unwrap());
 
                     // Add security headers
@@ -529,6 +1076,7 @@ async fn email_frame_handler(
                 let rendered_content = content_renderer::render_message_content_with_image_control(
                     message,
                     params.show_images,
+                    &format!("/attachment/{}/{}", thread_id, message_index),
                 );
 
                 // Determine theme colors
@@ -871,6 +1419,7 @@ struct RefreshQueryParams {
 #[derive(Serialize)]
 struct RefreshQueryResponse {
     messages: Vec<SearchItem>,
+    list_labels: HashMap<String, String>,
     timestamp: DateTime<Utc>,
 }
 
@@ -902,6 +1451,7 @@ async fn refresh_query_handler(
     };
 
     let response = RefreshQueryResponse {
+        list_labels: search_list_labels(state.client.as_ref(), &messages).await,
         messages,
         timestamp: Utc::now(),
     };
@@ -909,6 +1459,34 @@ async fn refresh_query_handler(
     Json(response)
 }
 
+#[derive(Serialize)]
+struct ThreadSummaryResponse {
+    #[serde(flatten)]
+    summary: ThreadSummary,
+    tooltip: String,
+}
+
+/// Summary of a thread for inbox tooltips, loaded when a row is hovered.
+async fn thread_summary_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.client.show(&format!("thread:{}", id)).await {
+        Ok(thread) => {
+            let summary = ThreadSummary::new(&thread, &state.user_config.addresses());
+            Json(ThreadSummaryResponse {
+                tooltip: summary.tooltip(),
+                summary,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to summarize thread {}: {}", id, e);
+            (StatusCode::NOT_FOUND, "Thread not found").into_response()
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct LoadMoreParams {
     q: Option<String>,
@@ -919,6 +1497,7 @@ struct LoadMoreParams {
 #[derive(Serialize)]
 struct LoadMoreResponse {
     messages: Vec<SearchItem>,
+    list_labels: HashMap<String, String>,
     has_more: bool,
     total_count: Option<usize>,
     offset: usize,
@@ -960,6 +1539,7 @@ async fn load_more_handler(
             );
 
             let response = LoadMoreResponse {
+                list_labels: search_list_labels(state.client.as_ref(), &messages).await,
                 messages,
                 has_more,
                 total_count,
@@ -974,6 +1554,7 @@ async fn load_more_handler(
 
             let error_response = LoadMoreResponse {
                 messages: vec![],
+                list_labels: HashMap::new(),
                 has_more: false,
                 total_count: None,
                 offset,
@@ -1164,17 +1745,40 @@ fn find_attachment_by_id(
 }
 
 fn sanitize_filename(filename: &str) -> String {
-    // Remove any path components and dangerous characters
+    // Keep only the last path component and drop control characters;
+    // non-ASCII names are fine since they are sent as `filename*`
     filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
         .chars()
-        .filter(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .filter(|c| !c.is_control())
         .collect::<String>()
-        .split('/')
-        .next_back()
-        .unwrap_or("attachment")
+        .trim()
+        .trim_start_matches('.')
         .to_string()
 }
 
+/// RFC 6266 `Content-Disposition` for a download: an ASCII `filename` for
+/// old clients and the exact name as UTF-8 in `filename*`.
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*={}",
+        fallback,
+        encode_extended_value(filename)
+    )
+}
+
 // Compose/Reply/Forward structures and handlers
 #[derive(Template)]
 #[template(path = "compose.html")]
@@ -1191,12 +1795,105 @@ struct ComposeTemplate {
     in_reply_to: String,
     references: String,
     original_message_id: String,
+    draft_id: String,
+    /// Identities to pick the From address from
+    identities: Vec<IdentityOption>,
+    markdown: bool,
+    /// When to send the message, as a `datetime-local` value
+    send_at: String,
+    /// How a forwarded message is included, see [`ForwardMode`]
+    forward_mode: String,
+    /// Names of the files attached to a forwarded message, or to the draft
+    /// being edited
+    original_attachments: Vec<String>,
+    /// The attachment size limit, for people
+    attachment_limit: String,
+    /// Whether to sign with OpenPGP; `None` for the identity's default
+    sign: Option<bool>,
+    encrypt: bool,
+    /// The recipient field that is not a valid address list, and why
+    invalid_address: Option<(String, String)>,
     error: Option<String>,
     theme: String,
 }
 
+impl ComposeTemplate {
+    /// Why the recipient field `field` (`To`, `Cc` or `Bcc`) is invalid
+    fn address_error(&self, field: &str) -> Option<&str> {
+        self.invalid_address
+            .as_ref()
+            .filter(|(invalid, _)| invalid == field)
+            .map(|(_, message)| message.as_str())
+    }
+
+    /// Whether the sign box starts out ticked: as submitted, or when the
+    /// selected identity has a signing key
+    fn sign_checked(&self) -> bool {
+        self.sign.unwrap_or_else(|| {
+            self.identities
+                .iter()
+                .any(|identity| identity.selected && identity.signs)
+        })
+    }
+}
+
+/// The recipient field and reason for a message that could not be built
+/// because of an invalid address, shown under that field.
+fn invalid_address(e: &crate::error::Error) -> Option<(String, String)> {
+    match e {
+        crate::error::Error::InvalidAddress { field, message } => {
+            Some((field.clone(), message.clone()))
+        }
+        _ => None,
+    }
+}
+
+/// The error shown above the compose form for a message that could not be
+/// built, unless it is shown under a recipient field.
+fn build_error(prefix: &str, e: crate::error::Error) -> Option<String> {
+    invalid_address(&e)
+        .is_none()
+        .then(|| format!("{}: {}", prefix, e))
+}
+
+/// A sending identity in the compose form's From picker
+struct IdentityOption {
+    mailbox: String,
+    signature: String,
+    /// Whether mail from this identity is signed unless unticked
+    signs: bool,
+    selected: bool,
+}
+
+/// The From picker, with the identity whose address is in `from`
+/// selected, or the default one when there is none
+fn identity_options(user_config: &UserConfig, from: &str) -> Vec<IdentityOption> {
+    let from = Mailbox::parse(from);
+    let identities = user_config.sending_identities();
+    let selected = from
+        .and_then(|from| {
+            identities
+                .iter()
+                .position(|identity| from.has_address(&identity.email))
+        })
+        .unwrap_or(0);
+    identities
+        .into_iter()
+        .enumerate()
+        .map(|(i, identity)| IdentityOption {
+            mailbox: identity.mailbox(),
+            signature: identity.signature.unwrap_or_default(),
+            signs: identity.signing_key.is_some(),
+            selected: i == selected,
+        })
+        .collect()
+}
+
 #[derive(Deserialize)]
 struct ComposeFormData {
+    /// The identity to send as; empty for the default one
+    #[serde(default)]
+    from: String,
     to: String,
     cc: Option<String>,
     bcc: Option<String>,
@@ -1205,6 +1902,157 @@ struct ComposeFormData {
     in_reply_to: Option<String>,
     references: Option<String>,
     original_message_id: Option<String>,
+    draft_id: Option<String>,
+    #[serde(default)]
+    markdown: bool,
+    /// Empty to send straight away
+    #[serde(default)]
+    send_at: String,
+    /// How to include the original when forwarding, see [`ForwardMode`]
+    #[serde(default)]
+    forward_mode: String,
+    #[serde(default)]
+    sign: bool,
+    #[serde(default)]
+    encrypt: bool,
+}
+
+/// A submitted compose form and the files attached to it.
+///
+/// The form is sent as `multipart/form-data` so that it can carry uploads;
+/// URL encoded forms, which have no attachments, are accepted as well.
+struct ComposeSubmission {
+    form: ComposeFormData,
+    attachments: Vec<Attachment>,
+}
+
+#[async_trait]
+impl FromRequest<AppState> for ComposeSubmission {
+    type Rejection = axum::response::Response;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+        if !is_multipart {
+            let Form(form) = Form::<ComposeFormData>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(ComposeSubmission {
+                form,
+                attachments: Vec::new(),
+            });
+        }
+
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mut fields = Vec::new();
+        let mut attachments = Vec::new();
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(IntoResponse::into_response)?
+        {
+            let name = field.name().unwrap_or_default().to_string();
+            match field.file_name().map(str::to_string) {
+                Some(filename) => {
+                    let content_type = field
+                        .content_type()
+                        .unwrap_or("application/octet-stream")
+                        .to_string();
+                    let data = field.bytes().await.map_err(IntoResponse::into_response)?;
+                    // An empty file input is sent as a nameless, empty file
+                    if filename.is_empty() && data.is_empty() {
+                        continue;
+                    }
+                    attachments.push(Attachment {
+                        filename,
+                        content_type,
+                        data: data.to_vec(),
+                    });
+                }
+                None => {
+                    let value = field.text().await.map_err(IntoResponse::into_response)?;
+                    fields.push((name, value));
+                }
+            }
+        }
+
+        // Decode the text fields the same way as a URL encoded form
+        let form = serde_urlencoded::to_string(&fields)
+            .ok()
+            .and_then(|encoded| serde_urlencoded::from_str::<ComposeFormData>(&encoded).ok())
+            .ok_or_else(|| {
+                (StatusCode::UNPROCESSABLE_ENTITY, "Invalid compose form").into_response()
+            })?;
+        Ok(ComposeSubmission { form, attachments })
+    }
+}
+
+/// Names of the files attached to `message`, as shown when forwarding it.
+fn attachment_names(message: &crate::thread::Message) -> Vec<String> {
+    message
+        .body
+        .iter()
+        .flat_map(crate::body::BodyPart::attachments)
+        .map(|part| {
+            part.filename
+                .clone()
+                .unwrap_or_else(|| format!("attachment_{}", part.id))
+        })
+        .collect()
+}
+
+impl ComposeFormData {
+    /// Whether to sign and encrypt the message
+    fn pgp(&self) -> PgpOptions {
+        PgpOptions {
+            sign: self.sign,
+            encrypt: self.encrypt,
+        }
+    }
+
+    /// The form's contents as a draft, replacing the copy saved as
+    /// `draft_id`
+    fn draft(&self) -> Draft {
+        let in_reply_to = self.in_reply_to.clone().unwrap_or_default();
+        Draft {
+            id: self.draft_id.clone().filter(|id| !id.is_empty()),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            cc: self.cc.clone().unwrap_or_default(),
+            bcc: self.bcc.clone().unwrap_or_default(),
+            subject: self.subject.clone(),
+            body: self.body.clone(),
+            in_reply_to: (!in_reply_to.is_empty()).then_some(in_reply_to),
+            references: self
+                .references
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            markdown: self.markdown,
+            // Files are uploaded with the message; see `draft_attachments`
+            attachments: Vec::new(),
+            pgp: Some(self.pgp()),
+        }
+    }
+}
+
+/// The attachments of the saved draft `draft_id`, such as those of a
+/// message taken back out of the outbox, which the form cannot upload again.
+async fn draft_attachments(
+    state: &AppState,
+    draft_id: Option<&str>,
+) -> crate::error::Result<Vec<Attachment>> {
+    match draft_id.filter(|id| !id.is_empty()) {
+        Some(id) => Ok(Draft::load(state.client.as_ref(), id).await?.attachments),
+        None => Ok(Vec::new()),
+    }
 }
 
 async fn compose_get_handler(
@@ -1223,12 +2071,7 @@ async fn compose_get_handler(
         .into_response();
     }
 
-    // Get default from address (currently unused but may be displayed in UI later)
-    let _from_email = state
-        .user_config
-        .email
-        .as_deref()
-        .unwrap_or("user@example.com");
+    let identity = state.user_config.default_identity();
 
     ComposeTemplate {
         title: "Compose New Email".to_string(),
@@ -1241,11 +2084,24 @@ async fn compose_get_handler(
         subject: "".to_string(),
         body: format!(
             "\n\n--\n{}",
-            state.user_config.signature.as_deref().unwrap_or("")
+            identity
+                .as_ref()
+                .and_then(|identity| identity.signature.as_deref())
+                .unwrap_or("")
         ),
         in_reply_to: "".to_string(),
         references: "".to_string(),
         original_message_id: "".to_string(),
+        draft_id: "".to_string(),
+        identities: identity_options(&state.user_config, ""),
+        markdown: false,
+        send_at: "".to_string(),
+        forward_mode: String::new(),
+        original_attachments: Vec::new(),
+        attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+        sign: None,
+        encrypt: false,
+        invalid_address: None,
         error: None,
         theme,
     }
@@ -1255,7 +2111,10 @@ async fn compose_get_handler(
 async fn compose_post_handler(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Form(form_data): Form<ComposeFormData>,
+    ComposeSubmission {
+        form: form_data,
+        attachments,
+    }: ComposeSubmission,
 ) -> impl IntoResponse {
     let theme = get_theme_from_headers(&headers);
 
@@ -1277,9 +2136,10 @@ async fn compose_post_handler(
         .subject(form_data.subject.clone())
         .body(form_data.body.clone());
 
-    // Add optional fields
-    if let Some(from_email) = &state.user_config.email {
-        builder = builder.from(from_email.clone());
+    // Send as the chosen identity
+    let identity = state.user_config.identity(&form_data.from);
+    if let Ok(Some(identity)) = &identity {
+        builder = builder.from(identity.mailbox());
     }
 
     if let Some(cc) = form_data.cc.as_ref() {
@@ -1310,16 +2170,53 @@ async fn compose_post_handler(
         }
     }
 
+    if form_data.markdown {
+        builder = builder.html_body(markdown_to_html(&form_data.body));
+    }
+
+    // Attach the draft's files and the uploaded ones
+    let kept = draft_attachments(&state, form_data.draft_id.as_deref()).await;
+    let kept_names: Vec<String> = kept
+        .iter()
+        .flatten()
+        .map(|attachment| attachment.filename.clone())
+        .collect();
+
     // Build and send the message
-    match builder.build() {
+    let built = identity.and(kept).and_then(|kept| {
+        let attachments: Vec<Attachment> = kept.into_iter().chain(attachments).collect();
+        state.config.attachment_limits.check(&attachments)?;
+        attachments
+            .into_iter()
+            .fold(builder, MessageBuilder::attachment)
+            .build()
+    });
+    match built {
         Ok(message) => {
-            match mail_sender.send(message).await {
-                Ok(message_id) => {
+            let sent = send_message(
+                &state,
+                mail_sender.as_ref(),
+                message,
+                form_data.draft_id.as_deref(),
+                &form_data.send_at,
+                form_data.pgp(),
+            )
+            .await;
+            match sent {
+                Ok(Delivery::Sent(message_id)) => {
                     tracing::info!("Successfully sent email with ID: {}", message_id);
                     // Redirect to inbox with success message
                     // TODO: Add flash message support for success notification
                     Redirect::to("/inbox").into_response()
                 }
+                Ok(Delivery::Held { id, until }) => {
+                    // Sent once the undo grace period is over
+                    undo_redirect("/inbox", &id, until)
+                }
+                Ok(_) => {
+                    // Scheduled, or kept in the outbox and retried in the background
+                    Redirect::to("/outbox").into_response()
+                }
                 Err(e) => {
                     tracing::error!("Failed to send email: {}", e);
                     ComposeTemplate {
@@ -1335,6 +2232,18 @@ async fn compose_post_handler(
                         in_reply_to: form_data.in_reply_to.unwrap_or_default(),
                         references: form_data.references.unwrap_or_default(),
                         original_message_id: form_data.original_message_id.unwrap_or_default(),
+                        draft_id: form_data.draft_id.unwrap_or_default(),
+                        identities: identity_options(&state.user_config, &form_data.from),
+                        markdown: form_data.markdown,
+                        send_at: form_data.send_at,
+                        forward_mode: String::new(),
+                        original_attachments: kept_names.clone(),
+                        attachment_limit: format_size(
+                            state.config.attachment_limits.max_total_size,
+                        ),
+                        sign: Some(form_data.sign),
+                        encrypt: form_data.encrypt,
+                        invalid_address: None,
                         error: Some(format!("Failed to send email: {}", e)),
                         theme,
                     }
@@ -1357,7 +2266,363 @@ async fn compose_post_handler(
                 in_reply_to: form_data.in_reply_to.unwrap_or_default(),
                 references: form_data.references.unwrap_or_default(),
                 original_message_id: form_data.original_message_id.unwrap_or_default(),
-                error: Some(format!("Failed to build email: {}", e)),
+                draft_id: form_data.draft_id.unwrap_or_default(),
+                identities: identity_options(&state.user_config, &form_data.from),
+                markdown: form_data.markdown,
+                send_at: form_data.send_at,
+                forward_mode: String::new(),
+                original_attachments: kept_names,
+                attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+                sign: Some(form_data.sign),
+                encrypt: form_data.encrypt,
+                invalid_address: invalid_address(&e),
+                error: build_error("Failed to build email", e),
+                theme,
+            }
+            .into_response()
+        }
+    }
+}
+
+/// Send a composed message through the outbox, or directly without one,
+/// removing the draft it was written in once it has gone out. With a
+/// `send_at` time the message is scheduled in the outbox instead, and with
+/// an undo grace period it is held there for that long first.
+async fn send_message(
+    state: &AppState,
+    mail_sender: &dyn MailSender,
+    message: ComposableMessage,
+    draft_id: Option<&str>,
+    send_at: &str,
+    pgp: PgpOptions,
+) -> crate::error::Result<Delivery> {
+    let draft_id = draft_id.filter(|id| !id.is_empty());
+    let message = if pgp.is_enabled() {
+        let identity = state
+            .user_config
+            .identity(message.from.as_deref().unwrap_or(""))?;
+        let signing_key = identity.and_then(|identity| identity.signing_key);
+        pgp::protect(&state.config.gpg, message, pgp, signing_key.as_deref()).await?
+    } else {
+        message
+    };
+    let client = state.client.as_ref();
+    let sent_folder = state.config.sent_folder.as_deref();
+
+    if !send_at.trim().is_empty() {
+        let send_at = parse_send_at(send_at, chrono::Local::now())?;
+        let Some(dir) = &state.config.outbox_dir else {
+            return Err(crate::error::NotmuchError::ConfigError(
+                "Scheduled sending needs an outbox directory".to_string(),
+            ));
+        };
+        Outbox::new(dir)
+            .schedule(client, message, draft_id.map(str::to_string), send_at)
+            .await?;
+        return Ok(Delivery::Scheduled(send_at));
+    }
+
+    let delay = state.config.undo_send_delay;
+    if let (Some(dir), Some(sender), false) = (
+        &state.config.outbox_dir,
+        &state.mail_sender,
+        delay.is_zero(),
+    ) {
+        let outbox = Outbox::new(dir);
+        let entry = outbox
+            .hold(client, message, draft_id.map(str::to_string), delay)
+            .await?;
+        outbox::spawn_delivery(
+            outbox,
+            entry.id.clone(),
+            delay,
+            sender.clone(),
+            state.client.clone(),
+            state.config.sent_folder.clone(),
+        );
+        return Ok(Delivery::Held {
+            id: entry.id,
+            until: entry.next_attempt,
+        });
+    }
+
+    if let Some(dir) = &state.config.outbox_dir {
+        return Outbox::new(dir)
+            .submit(
+                mail_sender,
+                client,
+                sent_folder,
+                message,
+                draft_id.map(str::to_string),
+            )
+            .await;
+    }
+
+    let message_id = send_and_file(mail_sender, client, message, sent_folder).await?;
+    if let Some(id) = draft_id
+        && let Err(e) = discard_draft(client, id).await
+    {
+        tracing::warn!("Failed to remove draft {}: {}", id, e);
+    }
+    Ok(Delivery::Sent(message_id))
+}
+
+/// Cookie offering to undo sending outbox entry `id`, read by the toast in
+/// base.html.
+const UNDO_COOKIE: &str = "whynot_undo";
+
+/// Redirect to `to`, where a toast offers to take back the message held in
+/// outbox entry `id` until `until`.
+fn undo_redirect(to: &str, id: &str, until: DateTime<Utc>) -> axum::response::Response {
+    let max_age = (until - Utc::now()).num_seconds().max(1);
+    let cookie = format!(
+        "{}={}.{}; Path=/; Max-Age={}; SameSite=Strict",
+        UNDO_COOKIE,
+        id,
+        until.timestamp_millis(),
+        max_age
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to(to)).into_response()
+}
+
+/// A saved draft in the Drafts list
+struct DraftItem {
+    url: String,
+    to: String,
+    subject: String,
+    date: String,
+}
+
+#[derive(Template)]
+#[template(path = "drafts.html")]
+struct DraftsTemplate {
+    drafts: Vec<DraftItem>,
+    theme: String,
+}
+
+async fn drafts_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+
+    match state.client.show_headers(DRAFTS_QUERY).await {
+        Ok(thread) => {
+            let mut messages: Vec<_> = thread
+                .get_messages()
+                .into_iter()
+                .filter(|message| message.tags.iter().any(|tag| tag == DRAFT_TAG))
+                .collect();
+            messages.sort_by_key(|message| std::cmp::Reverse(message.timestamp));
+            let drafts = messages
+                .into_iter()
+                .map(|message| DraftItem {
+                    url: format!("/drafts/{}", urlencoding::encode(&message.id)),
+                    to: message.headers.to.clone().unwrap_or_default(),
+                    subject: message.headers.subject.clone().unwrap_or_default(),
+                    date: message.date_relative.clone(),
+                })
+                .collect();
+            DraftsTemplate { drafts, theme }.into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to load drafts: {}", e);
+            ThreadErrorTemplate {
+                message: format!("Failed to load drafts: {}", e),
+                theme,
+            }
+            .into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DraftSaveResponse {
+    id: String,
+}
+
+/// Save the compose form as a draft; used by the form's autosave.
+async fn draft_save_handler(
+    State(state): State<AppState>,
+    Form(form_data): Form<ComposeFormData>,
+) -> impl IntoResponse {
+    let mut draft = form_data.draft();
+    if !draft.is_empty() || draft.id.is_some() {
+        // Keep the files of the copy this one replaces
+        let saved = match draft_attachments(&state, draft.id.as_deref()).await {
+            Ok(attachments) => {
+                draft.attachments = attachments;
+                draft
+                    .save(
+                        state.client.as_ref(),
+                        &state.config.drafts_folder,
+                        state.user_config.email.as_deref(),
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = saved {
+            tracing::error!("Failed to save draft: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save draft: {}", e),
+            )
+                .into_response();
+        }
+    }
+    Json(DraftSaveResponse {
+        id: draft.id.unwrap_or_default(),
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct DraftParams {
+    /// Time the message was scheduled for before it was taken out of the
+    /// outbox to be edited
+    send_at: Option<String>,
+}
+
+async fn draft_resume_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<DraftParams>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+
+    match Draft::load(state.client.as_ref(), &id).await {
+        Ok(draft) => ComposeTemplate {
+            title: "Edit Draft".to_string(),
+            action_url: "/compose".to_string(),
+            back_url: "/drafts".to_string(),
+            mode: "compose".to_string(),
+            to: draft.to,
+            cc: draft.cc,
+            bcc: draft.bcc,
+            subject: draft.subject,
+            body: draft.body,
+            in_reply_to: draft.in_reply_to.unwrap_or_default(),
+            references: draft.references.join(" "),
+            original_message_id: "".to_string(),
+            draft_id: id,
+            identities: identity_options(&state.user_config, &draft.from),
+            markdown: draft.markdown,
+            send_at: params.send_at.unwrap_or_default(),
+            forward_mode: String::new(),
+            original_attachments: draft
+                .attachments
+                .iter()
+                .map(|attachment| attachment.filename.clone())
+                .collect(),
+            attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+            sign: draft.pgp.map(|pgp| pgp.sign),
+            encrypt: draft.pgp.is_some_and(|pgp| pgp.encrypt),
+            invalid_address: None,
+            error: None,
+            theme,
+        }
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to load draft {}: {}", id, e);
+            ThreadErrorTemplate {
+                message: format!("Failed to load draft: {}", e),
+                theme,
+            }
+            .into_response()
+        }
+    }
+}
+
+async fn draft_delete_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = discard_draft(state.client.as_ref(), &id).await {
+        tracing::error!("Failed to delete draft {}: {}", id, e);
+    }
+    Redirect::to("/drafts")
+}
+
+/// An outbox entry in the queue view
+struct OutboxItem {
+    id: String,
+    to: String,
+    subject: String,
+    status: String,
+    attempts: u32,
+    next_attempt: String,
+    last_error: Option<String>,
+    sending: bool,
+    scheduled: bool,
+}
+
+impl From<OutboxEntry> for OutboxItem {
+    fn from(entry: OutboxEntry) -> Self {
+        OutboxItem {
+            to: entry.recipients(),
+            subject: entry.message.subject.clone(),
+            status: entry.status.label().to_string(),
+            attempts: entry.attempts,
+            next_attempt: entry
+                .next_attempt
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            last_error: entry.last_error,
+            sending: entry.status == OutboxStatus::Sending,
+            scheduled: entry.status == OutboxStatus::Scheduled,
+            id: entry.id,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "outbox.html")]
+struct OutboxTemplate {
+    entries: Vec<OutboxItem>,
+    error: Option<String>,
+    theme: String,
+}
+
+#[derive(Deserialize)]
+struct OutboxParams {
+    error: Option<String>,
+}
+
+fn outbox_redirect(result: crate::error::Result<()>) -> Redirect {
+    match result {
+        Ok(()) => Redirect::to("/outbox"),
+        Err(e) => Redirect::to(&format!(
+            "/outbox?error={}",
+            urlencoding::encode(&e.to_string())
+        )),
+    }
+}
+
+async fn outbox_handler(
+    State(state): State<AppState>,
+    Query(params): Query<OutboxParams>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let theme = get_theme_from_headers(&headers);
+    let Some(dir) = &state.config.outbox_dir else {
+        return ThreadErrorTemplate {
+            message: "The outbox is not configured.".to_string(),
+            theme,
+        }
+        .into_response();
+    };
+
+    match Outbox::new(dir).list().await {
+        Ok(entries) => OutboxTemplate {
+            entries: entries.into_iter().map(OutboxItem::from).collect(),
+            error: params.error,
+            theme,
+        }
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to read the outbox: {}", e);
+            ThreadErrorTemplate {
+                message: format!("Failed to read the outbox: {}", e),
                 theme,
             }
             .into_response()
@@ -1365,10 +2630,152 @@ async fn compose_post_handler(
     }
 }
 
+async fn outbox_cancel_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Some(dir) = &state.config.outbox_dir else {
+        return Redirect::to("/outbox");
+    };
+    let cancelled = Outbox::new(dir).cancel(&id).await;
+    if let Ok(entry) = &cancelled {
+        tracing::info!("Cancelled queued message {}", entry.message.message_id);
+    }
+    outbox_redirect(cancelled.map(|_| ()))
+}
+
+/// Send an entry again straight away, also after a permanent failure.
+async fn outbox_retry_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (Some(dir), Some(mail_sender)) = (&state.config.outbox_dir, &state.mail_sender) else {
+        return Redirect::to("/outbox");
+    };
+    let outbox = Outbox::new(dir);
+    if let Err(e) = outbox.retry(&id).await {
+        return outbox_redirect(Err(e));
+    }
+    let delivery = outbox
+        .deliver(
+            &id,
+            mail_sender.as_ref(),
+            state.client.as_ref(),
+            state.config.sent_folder.as_deref(),
+        )
+        .await;
+    if let Ok(delivery) = &delivery {
+        tracing::info!("Retried queued message {}: {:?}", id, delivery);
+    }
+    outbox_redirect(delivery.map(|_| ()))
+}
+
+/// Take back a message during its undo grace period, reopening it in the
+/// compose form.
+async fn outbox_undo_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let clear_cookie = format!("{}=; Path=/; Max-Age=0", UNDO_COOKIE);
+    let Some(dir) = &state.config.outbox_dir else {
+        return Redirect::to("/outbox").into_response();
+    };
+    let reopened = Outbox::new(dir)
+        .reopen(&id, state.client.as_ref(), &state.config.drafts_folder)
+        .await;
+    let redirect = match reopened {
+        Ok((_, draft)) => Redirect::to(&format!(
+            "/drafts/{}",
+            urlencoding::encode(&draft.id.unwrap_or_default())
+        )),
+        Err(e) => {
+            tracing::info!("Could not undo sending {}: {}", id, e);
+            outbox_redirect(Err(crate::error::NotmuchError::InvalidInput(format!(
+                "Too late to undo: {}",
+                e
+            ))))
+        }
+    };
+    ([(header::SET_COOKIE, clear_cookie)], redirect).into_response()
+}
+
+/// Take an entry out of the outbox and reopen it in the compose form as a
+/// draft, keeping the time it was scheduled for.
+async fn outbox_edit_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Some(dir) = &state.config.outbox_dir else {
+        return Redirect::to("/outbox");
+    };
+    let reopened = Outbox::new(dir)
+        .reopen(&id, state.client.as_ref(), &state.config.drafts_folder)
+        .await;
+    match reopened {
+        Ok((entry, draft)) => {
+            let mut url = format!(
+                "/drafts/{}",
+                urlencoding::encode(&draft.id.unwrap_or_default())
+            );
+            if entry.status == OutboxStatus::Scheduled {
+                let send_at = entry.next_attempt.with_timezone(&chrono::Local);
+                url.push_str(&format!("?send_at={}", send_at.format("%Y-%m-%dT%H:%M")));
+            }
+            Redirect::to(&url)
+        }
+        Err(e) => outbox_redirect(Err(e)),
+    }
+}
+
 #[derive(Deserialize)]
 struct ReplyParams {
     message: usize,
     all: Option<bool>,
+    /// Reply to the mailing list instead of the author
+    list: Option<bool>,
+}
+
+impl ReplyParams {
+    fn reply_all(&self) -> bool {
+        self.all.unwrap_or(false)
+    }
+
+    fn reply_list(&self) -> bool {
+        self.list.unwrap_or(false)
+    }
+
+    fn title(&self) -> String {
+        if self.reply_list() {
+            "Reply to List".to_string()
+        } else if self.reply_all() {
+            "Reply All".to_string()
+        } else {
+            "Reply".to_string()
+        }
+    }
+
+    fn mode(&self) -> String {
+        if self.reply_list() {
+            "reply_list".to_string()
+        } else if self.reply_all() {
+            "reply_all".to_string()
+        } else {
+            "reply".to_string()
+        }
+    }
+
+    fn action_url(&self, thread_id: &str) -> String {
+        let mut url = format!(
+            "/thread/{}/reply?message={}&all={}",
+            thread_id,
+            self.message,
+            self.reply_all()
+        );
+        if self.reply_list() {
+            url.push_str("&list=true");
+        }
+        url
+    }
 }
 
 async fn reply_get_handler(
@@ -1396,40 +2803,67 @@ async fn reply_get_handler(
 
             // Get the specific message to reply to
             if let Some(original_message) = messages.get(params.message) {
-                let reply_all = params.all.unwrap_or(false);
+                let reply_all = params.reply_all();
 
                 // Determine recipients
                 // If replying to own message, use original recipients instead of sender
-                let to = if let Some(user_email) = &state.user_config.email {
-                    if original_message.headers.from.contains(user_email) {
-                        // Replying to own message - use original recipients
-                        original_message.headers.to.clone()
-                    } else {
-                        // Normal reply - use original sender
-                        Some(original_message.headers.from.clone())
+                let headers = &original_message.headers;
+                let own_addresses = state.user_config.addresses();
+                let is_own = |mailbox: &Mailbox| {
+                    own_addresses
+                        .iter()
+                        .any(|address| mailbox.has_address(address))
+                };
+                // Reply as the identity the message was sent to
+                let identity = state.user_config.reply_identity(headers);
+                let own_message = headers.from_mailboxes().iter().any(is_own);
+                let list_address = ListInfo::from_headers(headers)
+                    .and_then(|list| list.post_address())
+                    .map(|mailbox| mailbox.to_string());
+                let to = if params.reply_list() {
+                    match list_address {
+                        Some(address) => Some(address),
+                        None => {
+                            return ThreadErrorTemplate {
+                                message:
+                                    "This message was not sent to a mailing list that accepts posts"
+                                        .to_string(),
+                                theme,
+                            }
+                            .into_response();
+                        }
                     }
+                } else if own_message {
+                    headers.to.clone()
                 } else {
-                    // No user email configured, use original sender
-                    Some(original_message.headers.from.clone())
+                    let recipients = headers.reply_recipients();
+                    if recipients.is_empty() {
+                        Some(headers.from.clone())
+                    } else {
+                        Some(Mailbox::format_list(&recipients))
+                    }
                 };
                 let mut cc = String::new();
 
                 if reply_all {
-                    // Add original To recipients to CC
-                    cc = original_message.headers.to.clone().unwrap_or_default();
-
-                    // CC field is not available in headers, skip for now
-                    // TODO: Parse CC from raw message headers if needed
-
-                    // Remove self from CC if present
-                    if let Some(user_email) = &state.user_config.email {
-                        cc = cc
-                            .split(',')
-                            .map(|s| s.trim())
-                            .filter(|email| !email.contains(user_email))
-                            .collect::<Vec<_>>()
-                            .join(", ");
+                    // Add original To and Cc recipients to CC, except ourselves
+                    // and anyone already in To
+                    let to_mailboxes = to.as_deref().map(Mailbox::parse_list).unwrap_or_default();
+                    let mut cc_mailboxes: Vec<Mailbox> = Vec::new();
+                    for mailbox in headers
+                        .to_mailboxes()
+                        .into_iter()
+                        .chain(headers.cc_mailboxes())
+                    {
+                        let seen = to_mailboxes
+                            .iter()
+                            .chain(cc_mailboxes.iter())
+                            .any(|m| m.has_address(&mailbox.address));
+                        if !seen && !is_own(&mailbox) {
+                            cc_mailboxes.push(mailbox);
+                        }
                     }
+                    cc = Mailbox::format_list(&cc_mailboxes);
                 }
 
                 // Prepare subject with Re: prefix if not already present
@@ -1456,21 +2890,16 @@ async fn reply_get_handler(
                     )
                 };
 
-                // Build reply body with quoted original
-                let quoted_body = original_message
-                    .get_text_content()
-                    .unwrap_or_default()
-                    .lines()
-                    .map(|line| format!("> {}", line))
-                    .collect::<Vec<_>>()
-                    .join("\n");
-
-                let body = format!(
-                    "\n\nOn {}, {} wrote:\n{}\n\n--\n{}",
-                    original_message.date_relative,
-                    original_message.headers.from,
-                    quoted_body,
-                    state.user_config.signature.as_deref().unwrap_or("")
+                // Build reply body with quoted original, converting
+                // HTML-only messages to text
+                let quoting = state.user_config.reply_quoting();
+                let converter = BuiltinConverter::new(TextRendererConfig::default());
+                let text = quote::reply_text(original_message, &converter).await;
+                let body = quoting.reply_body(
+                    &quoting.quote(original_message, &text),
+                    identity
+                        .as_ref()
+                        .and_then(|identity| identity.signature.as_deref()),
                 );
 
                 // Extract message ID for In-Reply-To
@@ -1500,24 +2929,11 @@ async fn reply_get_handler(
                 references.push(in_reply_to.clone());
                 let references_str = references.join(" ");
 
-                let title = if reply_all {
-                    "Reply All".to_string()
-                } else {
-                    "Reply".to_string()
-                };
-
                 ComposeTemplate {
-                    title,
-                    action_url: format!(
-                        "/thread/{}/reply?message={}&all={}",
-                        thread_id, params.message, reply_all
-                    ),
+                    title: params.title(),
+                    action_url: params.action_url(&thread_id),
                     back_url: format!("/thread/{}", thread_id),
-                    mode: if reply_all {
-                        "reply_all".to_string()
-                    } else {
-                        "reply".to_string()
-                    },
+                    mode: params.mode(),
                     to: to.unwrap_or_default(),
                     cc,
                     bcc: "".to_string(),
@@ -1526,6 +2942,21 @@ async fn reply_get_handler(
                     in_reply_to,
                     references: references_str,
                     original_message_id: "".to_string(),
+                    draft_id: "".to_string(),
+                    identities: identity_options(
+                        &state.user_config,
+                        &identity
+                            .map(|identity| identity.mailbox())
+                            .unwrap_or_default(),
+                    ),
+                    markdown: false,
+                    send_at: "".to_string(),
+                    forward_mode: String::new(),
+                    original_attachments: Vec::new(),
+                    attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+                    sign: None,
+                    encrypt: false,
+                    invalid_address: None,
                     error: None,
                     theme,
                 }
@@ -1554,7 +2985,10 @@ async fn reply_post_handler(
     Path(thread_id): Path<String>,
     Query(params): Query<ReplyParams>,
     headers: HeaderMap,
-    Form(form_data): Form<ComposeFormData>,
+    ComposeSubmission {
+        form: form_data,
+        attachments,
+    }: ComposeSubmission,
 ) -> impl IntoResponse {
     let theme = get_theme_from_headers(&headers);
 
@@ -1576,9 +3010,10 @@ async fn reply_post_handler(
         .subject(form_data.subject.clone())
         .body(form_data.body.clone());
 
-    // Add optional fields
-    if let Some(from_email) = &state.user_config.email {
-        builder = builder.from(from_email.clone());
+    // Send as the chosen identity
+    let identity = state.user_config.identity(&form_data.from);
+    if let Ok(Some(identity)) = &identity {
+        builder = builder.from(identity.mailbox());
     }
 
     if let Some(cc) = form_data.cc.as_ref() {
@@ -1609,34 +3044,49 @@ async fn reply_post_handler(
         }
     }
 
+    if form_data.markdown {
+        builder = builder.html_body(markdown_to_html(&form_data.body));
+    }
+
+    // Attach the uploaded files
+    let within_limits = state.config.attachment_limits.check(&attachments);
+    for attachment in attachments {
+        builder = builder.attachment(attachment);
+    }
+
     // Build and send the message
-    match builder.build() {
+    match identity.and(within_limits).and_then(|_| builder.build()) {
         Ok(message) => {
-            match mail_sender.send(message).await {
-                Ok(message_id) => {
+            let sent = send_message(
+                &state,
+                mail_sender.as_ref(),
+                message,
+                form_data.draft_id.as_deref(),
+                &form_data.send_at,
+                form_data.pgp(),
+            )
+            .await;
+            match sent {
+                Ok(Delivery::Sent(message_id)) => {
                     tracing::info!("Successfully sent reply with ID: {}", message_id);
                     // Redirect back to the thread
                     Redirect::to(&format!("/thread/{}", thread_id)).into_response()
                 }
+                Ok(Delivery::Held { id, until }) => {
+                    // Sent once the undo grace period is over
+                    undo_redirect(&format!("/thread/{}", thread_id), &id, until)
+                }
+                Ok(_) => {
+                    // Scheduled, or kept in the outbox and retried in the background
+                    Redirect::to("/outbox").into_response()
+                }
                 Err(e) => {
                     tracing::error!("Failed to send reply: {}", e);
-                    let reply_all = params.all.unwrap_or(false);
                     ComposeTemplate {
-                        title: if reply_all {
-                            "Reply All".to_string()
-                        } else {
-                            "Reply".to_string()
-                        },
-                        action_url: format!(
-                            "/thread/{}/reply?message={}&all={}",
-                            thread_id, params.message, reply_all
-                        ),
+                        title: params.title(),
+                        action_url: params.action_url(&thread_id),
                         back_url: format!("/thread/{}", thread_id),
-                        mode: if reply_all {
-                            "reply_all".to_string()
-                        } else {
-                            "reply".to_string()
-                        },
+                        mode: params.mode(),
                         to: form_data.to,
                         cc: form_data.cc.unwrap_or_default(),
                         bcc: form_data.bcc.unwrap_or_default(),
@@ -1645,6 +3095,18 @@ async fn reply_post_handler(
                         in_reply_to: form_data.in_reply_to.unwrap_or_default(),
                         references: form_data.references.unwrap_or_default(),
                         original_message_id: form_data.original_message_id.unwrap_or_default(),
+                        draft_id: form_data.draft_id.unwrap_or_default(),
+                        identities: identity_options(&state.user_config, &form_data.from),
+                        markdown: form_data.markdown,
+                        send_at: form_data.send_at,
+                        forward_mode: String::new(),
+                        original_attachments: Vec::new(),
+                        attachment_limit: format_size(
+                            state.config.attachment_limits.max_total_size,
+                        ),
+                        sign: Some(form_data.sign),
+                        encrypt: form_data.encrypt,
+                        invalid_address: None,
                         error: Some(format!("Failed to send reply: {}", e)),
                         theme,
                     }
@@ -1654,23 +3116,11 @@ async fn reply_post_handler(
         }
         Err(e) => {
             tracing::error!("Failed to build reply: {}", e);
-            let reply_all = params.all.unwrap_or(false);
             ComposeTemplate {
-                title: if reply_all {
-                    "Reply All".to_string()
-                } else {
-                    "Reply".to_string()
-                },
-                action_url: format!(
-                    "/thread/{}/reply?message={}&all={}",
-                    thread_id, params.message, reply_all
-                ),
+                title: params.title(),
+                action_url: params.action_url(&thread_id),
                 back_url: format!("/thread/{}", thread_id),
-                mode: if reply_all {
-                    "reply_all".to_string()
-                } else {
-                    "reply".to_string()
-                },
+                mode: params.mode(),
                 to: form_data.to,
                 cc: form_data.cc.unwrap_or_default(),
                 bcc: form_data.bcc.unwrap_or_default(),
@@ -1679,7 +3129,17 @@ async fn reply_post_handler(
                 in_reply_to: form_data.in_reply_to.unwrap_or_default(),
                 references: form_data.references.unwrap_or_default(),
                 original_message_id: form_data.original_message_id.unwrap_or_default(),
-                error: Some(format!("Failed to build reply: {}", e)),
+                draft_id: form_data.draft_id.unwrap_or_default(),
+                identities: identity_options(&state.user_config, &form_data.from),
+                markdown: form_data.markdown,
+                send_at: form_data.send_at,
+                forward_mode: String::new(),
+                original_attachments: Vec::new(),
+                attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+                sign: Some(form_data.sign),
+                encrypt: form_data.encrypt,
+                invalid_address: invalid_address(&e),
+                error: build_error("Failed to build reply", e),
                 theme,
             }
             .into_response()
@@ -1743,12 +3203,12 @@ async fn forward_get_handler(
 
                 // Build forward body with original message
                 let original_text = original_message
-                    .get_text_content()
-                    .unwrap_or("[No text content]");
+                    .display_text()
+                    .unwrap_or("[No text content]".into());
 
                 let body = format!(
                     "\n\n---------- Forwarded message ----------\nFrom: {}\nDate: {}\nSubject: {}\nTo: {}\n\n{}\n\n--\n{}",
-                    original_message.headers.from,
+                    original_message.headers.formatted_from(),
                     original_message.date_relative,
                     original_message
                         .headers
@@ -1761,10 +3221,13 @@ async fn forward_get_handler(
                         .as_deref()
                         .unwrap_or("Undisclosed recipients"),
                     original_text,
-                    state.user_config.signature.as_deref().unwrap_or("")
+                    state
+                        .user_config
+                        .default_identity()
+                        .and_then(|identity| identity.signature)
+                        .unwrap_or_default()
                 );
 
-                // Store original message ID for attachment forwarding (future feature)
                 let original_message_id = original_message.id.clone();
 
                 ComposeTemplate {
@@ -1780,6 +3243,16 @@ async fn forward_get_handler(
                     in_reply_to: "".to_string(),
                     references: "".to_string(),
                     original_message_id,
+                    draft_id: "".to_string(),
+                    identities: identity_options(&state.user_config, ""),
+                    markdown: false,
+                    send_at: "".to_string(),
+                    forward_mode: ForwardMode::default().as_str().to_string(),
+                    original_attachments: attachment_names(original_message),
+                    attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+                    sign: None,
+                    encrypt: false,
+                    invalid_address: None,
                     error: None,
                     theme,
                 }
@@ -1808,7 +3281,10 @@ async fn forward_post_handler(
     Path(thread_id): Path<String>,
     Query(params): Query<ForwardParams>,
     headers: HeaderMap,
-    Form(form_data): Form<ComposeFormData>,
+    ComposeSubmission {
+        form: form_data,
+        attachments,
+    }: ComposeSubmission,
 ) -> impl IntoResponse {
     let theme = get_theme_from_headers(&headers);
 
@@ -1830,9 +3306,10 @@ async fn forward_post_handler(
         .subject(form_data.subject.clone())
         .body(form_data.body.clone());
 
-    // Add optional fields
-    if let Some(from_email) = &state.user_config.email {
-        builder = builder.from(from_email.clone());
+    // Send as the chosen identity
+    let identity = state.user_config.identity(&form_data.from);
+    if let Ok(Some(identity)) = &identity {
+        builder = builder.from(identity.mailbox());
     }
 
     if let Some(cc) = form_data.cc.as_ref() {
@@ -1847,15 +3324,63 @@ async fn forward_post_handler(
         }
     }
 
+    if form_data.markdown {
+        builder = builder.html_body(markdown_to_html(&form_data.body));
+    }
+
+    // Carry the original's attachments, or the original itself, along
+    let forward_mode = ForwardMode::parse(&form_data.forward_mode).unwrap_or_default();
+    let (original_attachments, forwarded) =
+        match state.client.show(&format!("thread:{}", thread_id)).await {
+            Ok(thread) => match thread.get_messages().get(params.message) {
+                Some(original) => (
+                    attachment_names(original),
+                    forwarded_attachments(state.client.as_ref(), original, forward_mode).await,
+                ),
+                None => (
+                    Vec::new(),
+                    Err(crate::error::NotmuchError::InvalidInput(
+                        "Message not found in thread".to_string(),
+                    )),
+                ),
+            },
+            Err(e) => (Vec::new(), Err(e)),
+        };
+
     // Build and send the message
-    match builder.build() {
+    let built = identity.and(forwarded).and_then(|forwarded| {
+        let attachments: Vec<Attachment> = forwarded.into_iter().chain(attachments).collect();
+        state.config.attachment_limits.check(&attachments)?;
+        attachments
+            .into_iter()
+            .fold(builder, MessageBuilder::attachment)
+            .build()
+    });
+    match built {
         Ok(message) => {
-            match mail_sender.send(message).await {
-                Ok(message_id) => {
+            let sent = send_message(
+                &state,
+                mail_sender.as_ref(),
+                message,
+                form_data.draft_id.as_deref(),
+                &form_data.send_at,
+                form_data.pgp(),
+            )
+            .await;
+            match sent {
+                Ok(Delivery::Sent(message_id)) => {
                     tracing::info!("Successfully forwarded email with ID: {}", message_id);
                     // Redirect back to the thread
                     Redirect::to(&format!("/thread/{}", thread_id)).into_response()
                 }
+                Ok(Delivery::Held { id, until }) => {
+                    // Sent once the undo grace period is over
+                    undo_redirect(&format!("/thread/{}", thread_id), &id, until)
+                }
+                Ok(_) => {
+                    // Scheduled, or kept in the outbox and retried in the background
+                    Redirect::to("/outbox").into_response()
+                }
                 Err(e) => {
                     tracing::error!("Failed to forward email: {}", e);
                     ComposeTemplate {
@@ -1874,6 +3399,18 @@ async fn forward_post_handler(
                         in_reply_to: form_data.in_reply_to.unwrap_or_default(),
                         references: form_data.references.unwrap_or_default(),
                         original_message_id: form_data.original_message_id.unwrap_or_default(),
+                        draft_id: form_data.draft_id.unwrap_or_default(),
+                        identities: identity_options(&state.user_config, &form_data.from),
+                        markdown: form_data.markdown,
+                        send_at: form_data.send_at,
+                        forward_mode: forward_mode.as_str().to_string(),
+                        original_attachments,
+                        attachment_limit: format_size(
+                            state.config.attachment_limits.max_total_size,
+                        ),
+                        sign: Some(form_data.sign),
+                        encrypt: form_data.encrypt,
+                        invalid_address: None,
                         error: Some(format!("Failed to forward email: {}", e)),
                         theme,
                     }
@@ -1896,7 +3433,17 @@ async fn forward_post_handler(
                 in_reply_to: form_data.in_reply_to.unwrap_or_default(),
                 references: form_data.references.unwrap_or_default(),
                 original_message_id: form_data.original_message_id.unwrap_or_default(),
-                error: Some(format!("Failed to build forward message: {}", e)),
+                draft_id: form_data.draft_id.unwrap_or_default(),
+                identities: identity_options(&state.user_config, &form_data.from),
+                markdown: form_data.markdown,
+                send_at: form_data.send_at,
+                forward_mode: forward_mode.as_str().to_string(),
+                original_attachments,
+                attachment_limit: format_size(state.config.attachment_limits.max_total_size),
+                sign: Some(form_data.sign),
+                encrypt: form_data.encrypt,
+                invalid_address: invalid_address(&e),
+                error: build_error("Failed to build forward message", e),
                 theme,
             }
             .into_response()
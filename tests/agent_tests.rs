@@ -0,0 +1,332 @@
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use whynot::agent::{AgentEndpoint, AgentListener, AgentServer};
+use whynot::client::{ClientConfig, NotmuchClient, TagOperation, create_client};
+use whynot::error::{NotmuchError, Result};
+use whynot::mail_sender::{ComposableMessage, MailSender, MailSenderConfig, create_mail_sender};
+use whynot::search::SearchItem;
+use whynot::thread::{Message, Thread};
+
+const TOKEN: &str = "test-token";
+
+const THREAD_JSON: &str = r#"[[[{"id": "a@example.com", "match": true, "excluded": false,
+    "filename": ["/mail/cur/a"], "timestamp": 1700000000, "date_relative": "Today",
+    "tags": ["inbox", "unread"], "duplicate": 1,
+    "body": [{"id": 1, "content-type": "multipart/mixed", "content": [
+        {"id": 2, "content-type": "text/plain", "content": "Hello there"},
+        {"id": 3, "content-type": "application/pdf", "content-disposition": "attachment",
+         "filename": "report.pdf", "content-length": 4}]}],
+    "crypto": {}, "headers": {"Subject": "Hello", "From": "Alice <a@example.com>",
+    "To": "bob@example.com", "Date": "Tue, 14 Nov 2023 22:13:20 +0000"}}, []]]]"#;
+
+type InsertCall = (Vec<u8>, Option<String>, Vec<String>);
+
+#[derive(Default)]
+struct MockNotmuchClient {
+    tag_calls: Mutex<Vec<(String, Vec<String>)>>,
+    inserted: Mutex<Vec<InsertCall>>,
+}
+
+#[async_trait]
+impl NotmuchClient for MockNotmuchClient {
+    async fn search(&self, query: &str) -> Result<Vec<SearchItem>> {
+        if query == "fail" {
+            return Err(NotmuchError::DatabaseError("database locked".to_string()));
+        }
+        Ok(vec![SearchItem {
+            thread: "0000000000000001".to_string(),
+            timestamp: 1700000000,
+            date_relative: "Today".to_string(),
+            matched: 1,
+            total: 1,
+            authors: "Alice".to_string(),
+            subject: format!("Result for {}", query),
+            query: vec![Some("id:a@example.com".to_string()), None],
+            tags: vec!["inbox".to_string()],
+        }])
+    }
+
+    async fn search_paginated(
+        &self,
+        query: &str,
+        _offset: usize,
+        _limit: usize,
+    ) -> Result<(Vec<SearchItem>, Option<usize>)> {
+        Ok((self.search(query).await?, Some(42)))
+    }
+
+    async fn show(&self, _query: &str) -> Result<Thread> {
+        Ok(serde_json::from_str(THREAD_JSON)?)
+    }
+
+    async fn tag(&self, query: &str, tags: &[TagOperation]) -> Result<()> {
+        self.tag_calls.lock().unwrap().push((
+            query.to_string(),
+            tags.iter().map(|t| t.to_string()).collect(),
+        ));
+        Ok(())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn insert(&self, message: &[u8], folder: Option<&str>, tags: &[&str]) -> Result<String> {
+        self.inserted.lock().unwrap().push((
+            message.to_vec(),
+            folder.map(str::to_string),
+            tags.iter().map(|t| t.to_string()).collect(),
+        ));
+        Ok("inserted@example.com".to_string())
+    }
+
+    async fn config_get(&self, key: &str) -> Result<String> {
+        Ok(format!("value-of-{}", key))
+    }
+
+    async fn config_set(&self, _key: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_tags(&self) -> Result<Vec<String>> {
+        Ok(vec!["inbox".to_string(), "unread".to_string()])
+    }
+
+    async fn part(&self, _message_id: &str, part_id: u32) -> Result<Vec<u8>> {
+        Ok(vec![0, 159, 146, 150, part_id as u8])
+    }
+}
+
+#[derive(Default)]
+struct MockMailSender {
+    sent: Mutex<Vec<ComposableMessage>>,
+}
+
+#[async_trait]
+impl MailSender for MockMailSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String> {
+        let id = message.message_id.clone();
+        self.sent.lock().unwrap().push(message);
+        Ok(id)
+    }
+
+    async fn reply(
+        &self,
+        original: &Message,
+        reply: ComposableMessage,
+        _reply_all: bool,
+    ) -> Result<String> {
+        Ok(format!("reply-to-{}-{}", original.id, reply.body))
+    }
+
+    async fn forward(&self, original: &Message, _forward: ComposableMessage) -> Result<String> {
+        Ok(format!("forward-of-{}", original.id))
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        Ok("me@example.com".to_string())
+    }
+}
+
+async fn start_agent(server: AgentServer, endpoint: &str) -> String {
+    let listener = AgentListener::bind(&endpoint.parse::<AgentEndpoint>().unwrap())
+        .await
+        .unwrap();
+    let bound = listener.local_endpoint().unwrap().to_string();
+    tokio::spawn(server.serve(listener));
+    bound
+}
+
+#[tokio::test]
+async fn test_agent_client_over_tcp() {
+    let mock = Arc::new(MockNotmuchClient::default());
+    let server = AgentServer::new(mock.clone(), TOKEN.to_string());
+    let endpoint = start_agent(server, "127.0.0.1:0").await;
+
+    let client = create_client(ClientConfig::agent(endpoint, TOKEN.to_string())).unwrap();
+
+    let results = client.search("tag:inbox").await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].subject, "Result for tag:inbox");
+    assert_eq!(
+        results[0].query,
+        vec![Some("id:a@example.com".to_string()), None]
+    );
+
+    let (page, total) = client.search_paginated("tag:inbox", 0, 10).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(total, Some(42));
+
+    let expected: Thread = serde_json::from_str(THREAD_JSON).unwrap();
+    let thread = client.show("thread:0000000000000001").await.unwrap();
+    assert_eq!(thread, expected);
+    assert!(thread.get_messages()[0].has_attachments());
+
+    client
+        .tag(
+            "id:a@example.com",
+            &[
+                TagOperation::Add("todo".to_string()),
+                TagOperation::Remove("unread".to_string()),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        mock.tag_calls.lock().unwrap()[0],
+        (
+            "id:a@example.com".to_string(),
+            vec!["+todo".to_string(), "-unread".to_string()]
+        )
+    );
+
+    assert_eq!(
+        client.config_get("user.name").await.unwrap(),
+        "value-of-user.name"
+    );
+    assert_eq!(client.list_tags().await.unwrap(), vec!["inbox", "unread"]);
+    client.refresh().await.unwrap();
+    client.config_set("user.name", "Alice").await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_agent_client_over_unix_socket_with_binary_payloads() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("agent.sock");
+
+    let mock = Arc::new(MockNotmuchClient::default());
+    let server = AgentServer::new(mock.clone(), TOKEN.to_string());
+    let endpoint = start_agent(server, &format!("unix:{}", socket.display())).await;
+
+    let client = create_client(ClientConfig::agent(endpoint, TOKEN.to_string())).unwrap();
+
+    let raw = b"Subject: binary\r\n\r\n\xff\xfe body".to_vec();
+    let id = client
+        .insert(&raw, Some("Sent"), &["sent", "inbox"])
+        .await
+        .unwrap();
+    assert_eq!(id, "inserted@example.com");
+    let inserted = mock.inserted.lock().unwrap()[0].clone();
+    assert_eq!(inserted.0, raw);
+    assert_eq!(inserted.1.as_deref(), Some("Sent"));
+    assert_eq!(inserted.2, vec!["sent", "inbox"]);
+
+    let part = client.part("id:a@example.com", 3).await.unwrap();
+    assert_eq!(part, vec![0, 159, 146, 150, 3]);
+}
+
+#[tokio::test]
+async fn test_agent_rejects_invalid_token() {
+    let server = AgentServer::new(Arc::new(MockNotmuchClient::default()), TOKEN.to_string());
+    let endpoint = start_agent(server, "127.0.0.1:0").await;
+
+    let client = create_client(ClientConfig::agent(endpoint, "wrong".to_string())).unwrap();
+    let result = client.search("*").await;
+    assert!(matches!(result, Err(NotmuchError::ConfigError(_))));
+}
+
+#[tokio::test]
+async fn test_agent_propagates_errors() {
+    let server = AgentServer::new(Arc::new(MockNotmuchClient::default()), TOKEN.to_string());
+    let endpoint = start_agent(server, "127.0.0.1:0").await;
+
+    let client = create_client(ClientConfig::agent(endpoint, TOKEN.to_string())).unwrap();
+    match client.search("fail").await {
+        Err(NotmuchError::DatabaseError(msg)) => assert_eq!(msg, "database locked"),
+        other => panic!("unexpected result: {:?}", other.map(|r| r.len())),
+    }
+
+    // The connection stays usable after an operation error
+    assert_eq!(client.search("ok").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_agent_reconnects_after_restart() {
+    // Serve connections inline so aborting the task also drops them
+    async fn serve_inline(server: AgentServer, listener: tokio::net::TcpListener) {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            server.handle_connection(stream).await;
+        }
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = AgentServer::new(Arc::new(MockNotmuchClient::default()), TOKEN.to_string());
+    let handle = tokio::spawn(serve_inline(server.clone(), listener));
+
+    let client = create_client(ClientConfig::agent(addr.clone(), TOKEN.to_string())).unwrap();
+    assert_eq!(client.list_tags().await.unwrap().len(), 2);
+
+    handle.abort();
+    let _ = handle.await;
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    tokio::spawn(serve_inline(server, listener));
+
+    assert_eq!(client.list_tags().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_agent_mail_sender() {
+    let sender = Arc::new(MockMailSender::default());
+    let server = AgentServer::new(Arc::new(MockNotmuchClient::default()), TOKEN.to_string())
+        .with_mail_sender(sender.clone());
+    let endpoint = start_agent(server, "127.0.0.1:0").await;
+
+    let remote = create_mail_sender(MailSenderConfig::Agent {
+        endpoint,
+        token: TOKEN.to_string(),
+    })
+    .unwrap();
+
+    remote.test_connection().await.unwrap();
+    assert_eq!(remote.get_from_address().await.unwrap(), "me@example.com");
+
+    let message = ComposableMessage::builder()
+        .to("bob@example.com".to_string())
+        .subject("Report".to_string())
+        .body("See attached".to_string())
+        .attachment(whynot::mail_sender::message::Attachment {
+            filename: "report.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            data: vec![0x25, 0x50, 0x44, 0x46, 0xff],
+        })
+        .build()
+        .unwrap();
+    let message_id = message.message_id.clone();
+
+    assert_eq!(remote.send(message).await.unwrap(), message_id);
+    let sent = sender.sent.lock().unwrap()[0].clone();
+    assert_eq!(sent.to, vec!["bob@example.com"]);
+    assert_eq!(sent.attachments[0].data, vec![0x25, 0x50, 0x44, 0x46, 0xff]);
+
+    let thread: Thread = serde_json::from_str(THREAD_JSON).unwrap();
+    let original = thread.get_messages()[0].clone();
+    let reply = ComposableMessage::builder()
+        .to("a@example.com".to_string())
+        .body("thanks".to_string())
+        .build()
+        .unwrap();
+    assert_eq!(
+        remote.reply(&original, reply, false).await.unwrap(),
+        "reply-to-a@example.com-thanks"
+    );
+}
+
+#[tokio::test]
+async fn test_agent_without_mail_sender() {
+    let server = AgentServer::new(Arc::new(MockNotmuchClient::default()), TOKEN.to_string());
+    let endpoint = start_agent(server, "127.0.0.1:0").await;
+
+    let remote = create_mail_sender(MailSenderConfig::Agent {
+        endpoint,
+        token: TOKEN.to_string(),
+    })
+    .unwrap();
+    assert!(remote.test_connection().await.is_err());
+}
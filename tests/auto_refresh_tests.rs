@@ -12,6 +12,7 @@ async fn spawn_test_server() -> (SocketAddr, AppState) {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -149,6 +150,7 @@ async fn test_refresh_query_returns_current_messages() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -314,6 +316,7 @@ async fn test_refresh_query_preserves_thread_ids() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -403,6 +406,7 @@ async fn test_auto_refresh_javascript_generates_correct_thread_links() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use mail_parser::MimeHeaders;
+use whynot::body::{BodyContent, BodyPart};
+use whynot::client::{ClientConfig, LocalClient, NotmuchClient, TagOperation};
+use whynot::common::Headers;
+use whynot::error::{NotmuchError, Result};
+use whynot::mail_sender::message::forwarded_attachments;
+use whynot::mail_sender::{Attachment, AttachmentLimits, ComposableMessage, ForwardMode};
+use whynot::search::SearchItem;
+use whynot::test_utils::RecordingSender;
+use whynot::thread::{Message, MessageNode, Thread, ThreadLevel};
+use whynot::web::{AppState, WebConfig, create_app};
+
+const ORIGINAL: &[u8] =
+    b"From: bob@example.org\r\nTo: alice@example.com\r\nSubject: Report\r\n\r\nSee attached.\r\n";
+
+fn part(id: u32, content_type: &str, filename: Option<&str>, content: BodyContent) -> BodyPart {
+    BodyPart {
+        id,
+        content_type: content_type.to_string(),
+        content,
+        content_disposition: filename.map(|_| "attachment".to_string()),
+        content_id: None,
+        content_charset: None,
+        filename: filename.map(str::to_string),
+        content_transfer_encoding: None,
+        content_length: None,
+    }
+}
+
+/// The message being forwarded: a text body and a PDF attachment.
+fn original() -> Message {
+    Message {
+        id: "report@example.org".to_string(),
+        is_match: true,
+        excluded: false,
+        filename: vec![],
+        timestamp: 1704110400,
+        date_relative: "2024-01-01".to_string(),
+        tags: vec![],
+        duplicate: None,
+        body: vec![part(
+            1,
+            "multipart/mixed",
+            None,
+            BodyContent::Multipart(vec![
+                part(
+                    2,
+                    "text/plain",
+                    None,
+                    BodyContent::Text("See attached.".to_string()),
+                ),
+                part(3, "application/pdf", Some("report.pdf"), BodyContent::Empty),
+            ]),
+        )],
+        crypto: Default::default(),
+        headers: Headers {
+            subject: Some("Report".to_string()),
+            from: "bob@example.org".to_string(),
+            to: Some("alice@example.com".to_string()),
+            reply_to: None,
+            date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+            additional: HashMap::new(),
+        },
+    }
+}
+
+/// Serves `original()` as the only thread, with the parts `part` is asked
+/// for.
+struct FakeClient;
+
+#[async_trait::async_trait]
+impl NotmuchClient for FakeClient {
+    async fn search(&self, _query: &str) -> Result<Vec<SearchItem>> {
+        Ok(vec![])
+    }
+
+    async fn search_paginated(
+        &self,
+        _query: &str,
+        _offset: usize,
+        _limit: usize,
+    ) -> Result<(Vec<SearchItem>, Option<usize>)> {
+        Ok((vec![], Some(0)))
+    }
+
+    async fn show(&self, _query: &str) -> Result<Thread> {
+        Ok(Thread(vec![ThreadLevel(vec![MessageNode(
+            original(),
+            vec![],
+        )])]))
+    }
+
+    async fn tag(&self, _query: &str, _tags: &[TagOperation]) -> Result<()> {
+        Ok(())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn insert(
+        &self,
+        _message: &[u8],
+        _folder: Option<&str>,
+        _tags: &[&str],
+    ) -> Result<String> {
+        Err(NotmuchError::CommandFailed("Not supported".to_string()))
+    }
+
+    async fn config_get(&self, _key: &str) -> Result<String> {
+        Err(NotmuchError::CommandFailed("Not supported".to_string()))
+    }
+
+    async fn config_set(&self, _key: &str, _value: &str) -> Result<()> {
+        Err(NotmuchError::CommandFailed("Not supported".to_string()))
+    }
+
+    async fn list_tags(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    async fn part(&self, message_id: &str, part_id: u32) -> Result<Vec<u8>> {
+        assert_eq!(message_id, "id:report@example.org");
+        match part_id {
+            0 => Ok(ORIGINAL.to_vec()),
+            3 => Ok(b"%PDF-1.4 report".to_vec()),
+            _ => Err(NotmuchError::CommandFailed(format!("No part {}", part_id))),
+        }
+    }
+}
+
+async fn spawn_server(
+    client: Arc<dyn NotmuchClient>,
+    attachment_limits: AttachmentLimits,
+) -> (std::net::SocketAddr, Arc<RecordingSender>) {
+    let sender = Arc::new(RecordingSender::default());
+    let state = AppState {
+        client,
+        mail_sender: Some(sender.clone()),
+        config: WebConfig {
+            bind_address: ([127, 0, 0, 1], 0).into(),
+            base_url: "http://localhost".to_string(),
+            items_per_page: 10,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            attachment_limits,
+            ..Default::default()
+        },
+        user_config: Default::default(),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+    (addr, sender)
+}
+
+fn compose_form() -> reqwest::multipart::Form {
+    reqwest::multipart::Form::new()
+        .text("to", "bob@example.org")
+        .text("subject", "Hello")
+        .text("body", "Hi")
+}
+
+#[tokio::test]
+async fn test_forwarded_attachments_inline() {
+    let attachments = forwarded_attachments(&FakeClient, &original(), ForwardMode::Inline)
+        .await
+        .unwrap();
+
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].filename, "report.pdf");
+    assert_eq!(attachments[0].content_type, "application/pdf");
+    assert_eq!(attachments[0].data, b"%PDF-1.4 report");
+}
+
+#[tokio::test]
+async fn test_forward_as_attachment() {
+    let attachments = forwarded_attachments(&FakeClient, &original(), ForwardMode::Attachment)
+        .await
+        .unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].filename, "Report.eml");
+    assert_eq!(attachments[0].content_type, "message/rfc822");
+
+    let forward = ComposableMessage::forward_builder(&original())
+        .to("carol@example.net".to_string())
+        .attachment(attachments[0].clone())
+        .build()
+        .unwrap();
+    let rfc822 = forward.to_rfc822().unwrap();
+    let text = String::from_utf8_lossy(&rfc822);
+
+    // The original is embedded as it is rather than base64 encoded
+    assert!(text.contains("Content-Type: message/rfc822\r\n"));
+    assert!(text.contains("Content-Transfer-Encoding: 8bit\r\n"));
+    assert!(text.contains("Subject: Report\r\n\r\nSee attached.\r\n"));
+
+    let parsed = mail_parser::MessageParser::default()
+        .parse(&rfc822[..])
+        .unwrap();
+    let attachment = parsed.attachment(0).unwrap();
+    assert_eq!(attachment.attachment_name(), Some("Report.eml"));
+    let embedded = attachment.message().unwrap();
+    assert_eq!(embedded.subject(), Some("Report"));
+}
+
+#[test]
+fn test_attachments_are_wrapped_base64() {
+    let message = ComposableMessage::builder()
+        .to("bob@example.org".to_string())
+        .body("Hi".to_string())
+        .attachment(Attachment {
+            filename: "data.bin".to_string(),
+            content_type: "application/octet-stream".to_string(),
+            data: vec![0xAB; 1000],
+        })
+        .build()
+        .unwrap();
+    let rfc822 = message.to_rfc822().unwrap();
+
+    let text = String::from_utf8(rfc822.clone()).unwrap();
+    let encoded: Vec<&str> = text.lines().filter(|l| l.starts_with("q6ur")).collect();
+    assert_eq!(encoded.len(), 18);
+    assert!(encoded.iter().all(|line| line.len() <= 76));
+    let parsed = mail_parser::MessageParser::default()
+        .parse(&rfc822[..])
+        .unwrap();
+    assert_eq!(parsed.attachment(0).unwrap().contents(), &[0xAB; 1000][..]);
+}
+
+#[test]
+fn test_attachment_limits() {
+    let limits = AttachmentLimits { max_total_size: 10 };
+    let attachment = |size| Attachment {
+        filename: "a.txt".to_string(),
+        content_type: "text/plain".to_string(),
+        data: vec![b'a'; size],
+    };
+
+    assert!(limits.check(&[attachment(4), attachment(6)]).is_ok());
+    let error = limits
+        .check(&[attachment(4), attachment(7)])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("11 bytes, more than the limit of 10 bytes"));
+}
+
+#[tokio::test]
+async fn test_web_compose_uploads_attachments() {
+    // Nothing is filed or saved, so notmuch is never run
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some("/nonexistent/notmuch".into()),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap();
+    let (addr, sender) = spawn_server(
+        Arc::new(client),
+        AttachmentLimits {
+            max_total_size: 100,
+        },
+    )
+    .await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let page = http
+        .get(format!("http://{}/compose", addr))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(r#"enctype="multipart/form-data""#));
+    assert!(page.contains("Up to 100 bytes in total."));
+
+    let file = reqwest::multipart::Part::bytes(b"hello".to_vec())
+        .file_name("hello.txt")
+        .mime_str("text/plain")
+        .unwrap();
+    let response = http
+        .post(format!("http://{}/compose", addr))
+        .multipart(compose_form().part("attachments", file))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    {
+        let sent = sender.sent.lock().unwrap();
+        assert_eq!(sent[0].body, "Hi");
+        assert_eq!(sent[0].attachments.len(), 1);
+        assert_eq!(sent[0].attachments[0].filename, "hello.txt");
+        assert_eq!(sent[0].attachments[0].content_type, "text/plain");
+        assert_eq!(sent[0].attachments[0].data, b"hello");
+    }
+
+    // An empty file input adds nothing
+    let empty = reqwest::multipart::Part::bytes(Vec::new())
+        .file_name("")
+        .mime_str("application/octet-stream")
+        .unwrap();
+    http.post(format!("http://{}/compose", addr))
+        .multipart(compose_form().part("attachments", empty))
+        .send()
+        .await
+        .unwrap();
+    assert!(sender.sent.lock().unwrap()[1].attachments.is_empty());
+
+    // Too much is refused with the form shown again
+    let large = reqwest::multipart::Part::bytes(vec![b'a'; 101]).file_name("large.txt");
+    let page = http
+        .post(format!("http://{}/compose", addr))
+        .multipart(compose_form().part("attachments", large))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("more than the limit of 100 bytes"));
+    assert!(page.contains("Files have to be chosen again after an error."));
+    assert_eq!(sender.sent.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_web_forward_carries_attachments() {
+    let (addr, sender) = spawn_server(Arc::new(FakeClient), Default::default()).await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let page = http
+        .get(format!("http://{}/thread/t1/forward?message=0", addr))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Inline with attachments"));
+    assert!(page.contains("Original attachments: report.pdf"));
+
+    let forward = |mode: &'static str| {
+        http.post(format!("http://{}/thread/t1/forward?message=0", addr))
+            .multipart(compose_form().text("forward_mode", mode))
+            .send()
+    };
+
+    assert!(forward("inline").await.unwrap().status().is_redirection());
+    assert!(
+        forward("attachment")
+            .await
+            .unwrap()
+            .status()
+            .is_redirection()
+    );
+
+    let sent = sender.sent.lock().unwrap();
+    assert_eq!(sent[0].attachments.len(), 1);
+    assert_eq!(sent[0].attachments[0].filename, "report.pdf");
+    assert_eq!(sent[1].attachments.len(), 1);
+    assert_eq!(sent[1].attachments[0].content_type, "message/rfc822");
+    assert_eq!(sent[1].attachments[0].data, ORIGINAL);
+}
@@ -18,6 +18,7 @@ async fn spawn_test_server_with_notmuch(test_notmuch: &TestNotmuch) -> SocketAdd
         notmuch_path: None,
         database_path: Some(test_notmuch.database_path().clone()),
         mail_root: None,
+        format: Default::default(),
     };
 
     let client = create_client(client_config).unwrap();
@@ -29,6 +30,7 @@ async fn spawn_test_server_with_notmuch(test_notmuch: &TestNotmuch) -> SocketAdd
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
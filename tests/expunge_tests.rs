@@ -0,0 +1,276 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, ExpungeOptions, LocalClient, NotmuchClient};
+use whynot::test_utils::write_fake_notmuch;
+use whynot::web::{AppState, WebConfig, create_app};
+
+fn create_message(mail_root: &Path, relative: &str) -> PathBuf {
+    let path = mail_root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "Subject: test\n\nbody\n").unwrap();
+    path
+}
+
+struct Fixture {
+    bin_dir: TempDir,
+    mail_root: TempDir,
+    client: LocalClient,
+    deleted: PathBuf,
+    duplicate: PathBuf,
+    flagged: PathBuf,
+}
+
+fn setup() -> Fixture {
+    let bin_dir = TempDir::new().unwrap();
+    let mail_root = TempDir::new().unwrap();
+    let notmuch = write_fake_notmuch(bin_dir.path());
+
+    let deleted = create_message(mail_root.path(), "Trash/cur/1.host:2,S");
+    let duplicate = create_message(mail_root.path(), "INBOX/cur/1.host:2,S");
+    let flagged = create_message(mail_root.path(), "Trash/cur/2.host:2,FS");
+    let json = serde_json::json!([
+        [[
+            {"id": "old@example.com", "match": true, "filename": [deleted, duplicate],
+             "tags": ["deleted"], "headers": {"Subject": "Old newsletter"}},
+            []
+        ]],
+        [[
+            {"id": "keep@example.com", "match": true, "filename": [flagged],
+             "tags": ["deleted", "flagged"], "headers": {"Subject": "Contract"}},
+            []
+        ]]
+    ]);
+    std::fs::write(bin_dir.path().join("show.json"), json.to_string()).unwrap();
+
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some(notmuch),
+        database_path: None,
+        mail_root: Some(mail_root.path().to_path_buf()),
+        format: Default::default(),
+    })
+    .unwrap();
+
+    Fixture {
+        bin_dir,
+        mail_root,
+        client,
+        deleted,
+        duplicate,
+        flagged,
+    }
+}
+
+fn reindexed(fixture: &Fixture) -> bool {
+    std::fs::read_to_string(fixture.bin_dir.path().join("calls.log"))
+        .unwrap_or_default()
+        .lines()
+        .any(|line| line == "new")
+}
+
+#[tokio::test]
+async fn test_dry_run_does_not_touch_files() {
+    let fixture = setup();
+    let report = fixture
+        .client
+        .expunge_report("tag:deleted", &ExpungeOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(report.messages.len(), 1);
+    assert_eq!(report.messages[0].id, "old@example.com");
+    assert_eq!(
+        report.messages[0].subject.as_deref(),
+        Some("Old newsletter")
+    );
+    assert_eq!(report.file_count(), 2);
+    assert_eq!(report.protected.len(), 1);
+    assert_eq!(report.protected[0].id, "keep@example.com");
+
+    assert!(fixture.deleted.exists());
+    assert!(fixture.duplicate.exists());
+    assert!(!reindexed(&fixture));
+}
+
+#[tokio::test]
+async fn test_expunge_deletes_unprotected_files() {
+    let fixture = setup();
+    let options = ExpungeOptions::default();
+    let report = fixture
+        .client
+        .expunge_report("tag:deleted", &options)
+        .await
+        .unwrap();
+
+    let outcome = fixture
+        .client
+        .expunge("tag:deleted", &options, &report.token)
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.messages, 1);
+    assert_eq!(outcome.files, 2);
+    assert!(!fixture.deleted.exists());
+    assert!(!fixture.duplicate.exists());
+    assert!(fixture.flagged.exists());
+    assert!(reindexed(&fixture));
+}
+
+#[tokio::test]
+async fn test_expunge_requires_matching_token() {
+    let fixture = setup();
+    let options = ExpungeOptions::default();
+
+    let result = fixture
+        .client
+        .expunge("tag:deleted", &options, "not-the-token")
+        .await;
+    assert!(result.is_err());
+
+    // A token from a dry run with different options is rejected too
+    let report = fixture
+        .client
+        .expunge_report("tag:deleted", &options)
+        .await
+        .unwrap();
+    let trash = ExpungeOptions {
+        trash_dir: Some(fixture.mail_root.path().join(".trash")),
+        ..ExpungeOptions::default()
+    };
+    assert!(
+        fixture
+            .client
+            .expunge("tag:deleted", &trash, &report.token)
+            .await
+            .is_err()
+    );
+
+    assert!(fixture.deleted.exists());
+    assert!(fixture.duplicate.exists());
+    assert!(!reindexed(&fixture));
+}
+
+#[tokio::test]
+async fn test_expunge_moves_files_to_trash_dir() {
+    let fixture = setup();
+    let trash_dir = TempDir::new().unwrap();
+    let options = ExpungeOptions {
+        trash_dir: Some(trash_dir.path().to_path_buf()),
+        ..ExpungeOptions::default()
+    };
+    let report = fixture
+        .client
+        .expunge_report("tag:deleted", &options)
+        .await
+        .unwrap();
+
+    let outcome = fixture
+        .client
+        .expunge("tag:deleted", &options, &report.token)
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.files, 2);
+    assert_eq!(outcome.trash_dir.as_deref(), Some(trash_dir.path()));
+    assert!(!fixture.deleted.exists());
+    // Both copies share a file name, so the second one gets a suffix
+    assert!(trash_dir.path().join("1.host:2,S").exists());
+    assert!(trash_dir.path().join("1.host:2,S.1").exists());
+}
+
+#[tokio::test]
+async fn test_trash_dir_inside_mail_root_is_rejected() {
+    let fixture = setup();
+    let options = ExpungeOptions {
+        trash_dir: Some(fixture.mail_root.path().join("Trash/../.trash")),
+        ..ExpungeOptions::default()
+    };
+
+    let result = fixture.client.expunge_report("tag:deleted", &options).await;
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("outside the mail root")
+    );
+    assert!(fixture.deleted.exists());
+    assert!(!fixture.mail_root.path().join(".trash").exists());
+    assert!(!reindexed(&fixture));
+}
+
+#[tokio::test]
+async fn test_settings_page_expunge_flow() {
+    let fixture = setup();
+    let deleted = fixture.deleted.clone();
+    let flagged = fixture.flagged.clone();
+
+    let config = WebConfig {
+        bind_address: ([127, 0, 0, 1], 0).into(),
+        base_url: "http://localhost".to_string(),
+        items_per_page: 10,
+        auto_refresh_interval: 30,
+        initial_page_size: 20,
+        pagination_size: 10,
+        infinite_scroll_enabled: true,
+        ..Default::default()
+    };
+    let state = AppState {
+        client: Arc::new(fixture.client),
+        mail_sender: None,
+        config,
+        user_config: whynot::config::UserConfig::default(),
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+
+    let http = reqwest::Client::new();
+
+    let settings = http
+        .get(format!("http://{}/settings", addr))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(settings.contains("Expunge Deleted Mail"));
+    assert!(settings.contains("tag:deleted AND date:..30d"));
+
+    // Dry run lists the messages and offers a confirmation form
+    let dry_run = http
+        .post(format!("http://{}/settings/expunge", addr))
+        .form(&[("query", "tag:deleted")])
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(dry_run.contains("Old newsletter"));
+    assert!(dry_run.contains("protected messages will be kept"));
+    assert!(deleted.exists());
+
+    let token = dry_run
+        .split("name=\"token\" value=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("confirmation token in dry run page")
+        .to_string();
+
+    let confirmed = http
+        .post(format!("http://{}/settings/expunge", addr))
+        .form(&[("query", "tag:deleted"), ("token", token.as_str())])
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(confirmed.contains("Deleted 2 files of 1 messages."));
+    assert!(!deleted.exists());
+    assert!(flagged.exists());
+}
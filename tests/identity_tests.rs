@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use whynot::client::{ClientConfig, LocalClient, NotmuchClient};
+use whynot::common::Headers;
+use whynot::config::Config;
+use whynot::mail_sender::{ComposableMessage, create_mail_sender};
+use whynot::test_utils::RecordingSender;
+use whynot::tui::app::App;
+use whynot::web::{AppState, WebConfig, create_app};
+
+const IDENTITIES: &str = r#"
+[user]
+name = "Alice"
+email = "alice@example.com"
+
+[[identities]]
+name = "Alice"
+email = "alice@example.com"
+signature = "Alice"
+
+[[identities]]
+name = "Alice Smith"
+email = "alice@work.example.com"
+signature = "Alice Smith, Example Corp"
+msmtp_account = "work"
+sent_folder = "Work/Sent"
+"#;
+
+/// A fake msmtp that appends its arguments to `args` in `dir`
+fn write_fake_msmtp(dir: &Path) -> PathBuf {
+    let script = dir.join("msmtp");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho \"$@\" >> {dir}/args\ncat > /dev/null\n",
+            dir = dir.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+fn headers(to: &str, cc: Option<&str>, delivered_to: Option<&str>) -> Headers {
+    let mut additional = HashMap::new();
+    if let Some(cc) = cc {
+        additional.insert("Cc".to_string(), cc.to_string());
+    }
+    if let Some(delivered_to) = delivered_to {
+        additional.insert("Delivered-To".to_string(), delivered_to.to_string());
+    }
+    Headers {
+        subject: Some("Hello".to_string()),
+        from: "bob@example.org".to_string(),
+        to: Some(to.to_string()),
+        reply_to: None,
+        date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+        additional,
+    }
+}
+
+fn message(from: &str) -> ComposableMessage {
+    ComposableMessage::builder()
+        .from(from.to_string())
+        .to("bob@example.org".to_string())
+        .subject("Hello".to_string())
+        .body("Hi".to_string())
+        .build()
+        .unwrap()
+}
+
+/// A client for tests that use no Sent folder or drafts, so notmuch is
+/// never run.
+fn client() -> LocalClient {
+    LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some("/nonexistent/notmuch".into()),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_identities_from_config() {
+    let config: Config = toml::from_str(IDENTITIES).unwrap();
+    let user = config.user_config();
+
+    let identities = user.sending_identities();
+    assert_eq!(identities.len(), 2);
+    assert_eq!(
+        identities[1].mailbox(),
+        "Alice Smith <alice@work.example.com>"
+    );
+    assert_eq!(identities[1].msmtp_account.as_deref(), Some("work"));
+    assert_eq!(
+        user.addresses(),
+        vec!["alice@example.com", "alice@work.example.com"]
+    );
+
+    // An empty From picks the default identity
+    let default = user.identity("").unwrap().unwrap();
+    assert_eq!(default.email, "alice@example.com");
+    let work = user
+        .identity("Alice <ALICE@work.example.com>")
+        .unwrap()
+        .unwrap();
+    assert_eq!(work.signature.as_deref(), Some("Alice Smith, Example Corp"));
+
+    // Mail is never sent from an address that is not configured
+    assert!(user.identity("mallory@example.net").is_err());
+}
+
+#[test]
+fn test_user_is_the_only_identity_without_identities() {
+    let config: Config = toml::from_str(
+        r#"
+[user]
+name = "Alice"
+email = "alice@example.com"
+signature = "-- Alice"
+"#,
+    )
+    .unwrap();
+    let user = config.user_config();
+
+    let identities = user.sending_identities();
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0].mailbox(), "Alice <alice@example.com>");
+    assert_eq!(identities[0].signature.as_deref(), Some("-- Alice"));
+    assert_eq!(
+        user.reply_identity(&headers("bob@example.org", None, None))
+            .unwrap()
+            .email,
+        "alice@example.com"
+    );
+}
+
+#[test]
+fn test_reply_identity_matches_recipients() {
+    let config: Config = toml::from_str(IDENTITIES).unwrap();
+    let user = config.user_config();
+    let reply_as = |headers: &Headers| user.reply_identity(headers).unwrap().email;
+
+    assert_eq!(
+        reply_as(&headers("Alice Smith <alice@work.example.com>", None, None)),
+        "alice@work.example.com"
+    );
+    assert_eq!(
+        reply_as(&headers(
+            "team@example.org",
+            Some("carol@example.org, alice@work.example.com"),
+            None
+        )),
+        "alice@work.example.com"
+    );
+    // Mail that reached us through a list or alias
+    assert_eq!(
+        reply_as(&headers(
+            "dev@lists.example.org",
+            None,
+            Some("alice@work.example.com")
+        )),
+        "alice@work.example.com"
+    );
+    // Otherwise the default identity
+    assert_eq!(
+        reply_as(&headers("dev@lists.example.org", None, None)),
+        "alice@example.com"
+    );
+}
+
+#[tokio::test]
+async fn test_identity_sends_through_its_msmtp_account() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let msmtp = write_fake_msmtp(dir.path());
+    let mut config: Config = toml::from_str(IDENTITIES).unwrap();
+    config.mail.sending.msmtp_path = Some(msmtp.display().to_string());
+
+    let sender = config
+        .route_identities(create_mail_sender(config.to_mail_sender_config().unwrap()).unwrap())
+        .unwrap();
+
+    let work = message("Alice Smith <alice@work.example.com>");
+    assert_eq!(sender.sent_folder_for(&work).as_deref(), Some("Work/Sent"));
+    sender.send(work).await.unwrap();
+    let personal = message("Alice <alice@example.com>");
+    assert_eq!(sender.sent_folder_for(&personal), None);
+    sender.send(personal).await.unwrap();
+
+    let args = std::fs::read_to_string(dir.path().join("args")).unwrap();
+    let lines: Vec<&str> = args.lines().collect();
+    assert_eq!(lines, vec!["-a work bob@example.org", "bob@example.org"]);
+}
+
+#[test]
+fn test_msmtp_account_needs_an_msmtp_sender() {
+    let mut config: Config = toml::from_str(IDENTITIES).unwrap();
+    config.mail.sending.connection_type = Some("smtp".to_string());
+    config.mail.sending.host = Some("smtp.example.com".to_string());
+
+    let sender = create_mail_sender(config.to_mail_sender_config().unwrap()).unwrap();
+    assert!(config.route_identities(sender).is_err());
+}
+
+#[tokio::test]
+async fn test_web_compose_picks_identity() {
+    let config: Config = toml::from_str(IDENTITIES).unwrap();
+    let sender = Arc::new(RecordingSender::default());
+    let state = AppState {
+        client: Arc::new(client()),
+        mail_sender: Some(sender.clone()),
+        config: WebConfig {
+            bind_address: ([127, 0, 0, 1], 0).into(),
+            base_url: "http://localhost".to_string(),
+            items_per_page: 10,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            ..Default::default()
+        },
+        user_config: config.user_config(),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // The picker offers every identity, with the default one's signature
+    let page = http
+        .get(format!("http://{}/compose", addr))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(r#"<select id="from" name="from""#));
+    assert!(page.contains("Alice Smith &lt;alice@work.example.com&gt;"));
+    assert!(page.contains("--\nAlice</textarea>"));
+
+    let send = |from: &'static str| {
+        http.post(format!("http://{}/compose", addr))
+            .form(&[
+                ("from", from),
+                ("to", "bob@example.org"),
+                ("subject", "Hello"),
+                ("body", "Hi"),
+            ])
+            .send()
+    };
+    let response = send("Alice Smith <alice@work.example.com>").await.unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(
+        sender.sent.lock().unwrap()[0].from.as_deref(),
+        Some("Alice Smith <alice@work.example.com>")
+    );
+
+    // Unknown addresses are refused
+    let page = send("mallory@example.net")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("No identity configured for mallory@example.net"));
+    assert_eq!(sender.sent.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_tui_reply_picks_identity_and_ctrl_f_switches_it() {
+    let config: Config = toml::from_str(IDENTITIES).unwrap();
+    let client: Arc<dyn NotmuchClient> = Arc::new(client());
+    let mut app = App::new(client, Some(Arc::new(RecordingSender::default())), &config)
+        .await
+        .unwrap();
+
+    // New mail is sent as the default identity
+    app.start_compose_new();
+    assert_eq!(app.compose_form.from, "Alice <alice@example.com>");
+
+    // Replies go out as the identity the message was sent to
+    app.current_email = Some(
+        serde_json::from_value(serde_json::json!({
+            "id": "m1@example.org", "match": true, "excluded": false, "filename": [],
+            "timestamp": 1704110400, "date_relative": "2024-01-01",
+            "tags": ["inbox"], "crypto": {},
+            "body": [{"id": 1, "content-type": "text/plain", "content": "Hi"}],
+            "headers": {"Subject": "Plans", "From": "bob@example.org",
+                        "To": "team@example.org", "Cc": "alice@work.example.com",
+                        "Date": "Mon, 1 Jan 2024 12:00:00 +0100"}
+        }))
+        .unwrap(),
+    );
+    app.start_compose_reply(false).await;
+    assert_eq!(
+        app.compose_form.from,
+        "Alice Smith <alice@work.example.com>"
+    );
+
+    // Ctrl+F cycles through the identities
+    app.compose_next_identity();
+    assert_eq!(app.compose_form.from, "Alice <alice@example.com>");
+    app.compose_next_identity();
+    assert_eq!(
+        app.compose_form.from,
+        "Alice Smith <alice@work.example.com>"
+    );
+    assert_eq!(app.compose_form.draft().from, app.compose_form.from);
+}
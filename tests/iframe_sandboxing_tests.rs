@@ -15,6 +15,7 @@ async fn spawn_test_server_with_notmuch(test_notmuch: TestNotmuch) -> (SocketAdd
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -17,6 +17,7 @@ async fn spawn_test_server() -> (SocketAddr, AppState) {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -130,6 +131,7 @@ async fn test_thread_view_shows_image_toggle_ui() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -216,6 +218,7 @@ async fn test_email_frame_respects_show_images_parameter() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -20,6 +20,7 @@ mod interactive_tests {
             body: String::new(),
             current_field: whynot::tui::app::ComposeField::Body,
             markdown_mode: false, // Start disabled
+            ..Default::default()
         };
         
         // Initially disabled
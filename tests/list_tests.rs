@@ -0,0 +1,224 @@
+use axum::{Router, http::HeaderMap, http::StatusCode, routing::post};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, LocalClient};
+use whynot::common::Headers;
+use whynot::list::{ListInfo, Unsubscribed, Unsubscriber, list_labels};
+use whynot::search::SearchItem;
+use whynot::test_utils::RecordingSender;
+
+fn list(unsubscribe: &str, one_click: bool) -> ListInfo {
+    let mut additional = HashMap::new();
+    additional.insert("List-Id".to_string(), "<dev.lists.example.com>".to_string());
+    additional.insert("List-Unsubscribe".to_string(), unsubscribe.to_string());
+    if one_click {
+        additional.insert(
+            "List-Unsubscribe-Post".to_string(),
+            "List-Unsubscribe=One-Click".to_string(),
+        );
+    }
+    ListInfo::from_headers(&Headers {
+        subject: Some("Release".to_string()),
+        from: "Alice <alice@example.com>".to_string(),
+        to: Some("dev@lists.example.com".to_string()),
+        reply_to: None,
+        date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+        additional,
+    })
+    .unwrap()
+}
+
+type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+/// A list server that records one-click requests on `/unsubscribe` and
+/// fails them on `/broken`.
+async fn spawn_list_server() -> (std::net::SocketAddr, Requests) {
+    let requests: Requests = Arc::default();
+    let recorded = requests.clone();
+    let app = Router::new()
+        .route(
+            "/unsubscribe",
+            post(move |headers: HeaderMap, body: String| async move {
+                let content_type = headers["content-type"].to_str().unwrap().to_string();
+                recorded.lock().unwrap().push((content_type, body));
+                StatusCode::OK
+            }),
+        )
+        .route(
+            "/broken",
+            post(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (addr, requests)
+}
+
+#[tokio::test]
+async fn test_one_click_unsubscribe() {
+    let (addr, requests) = spawn_list_server().await;
+    let url = format!("http://{}/unsubscribe", addr);
+    let list = list(
+        &format!("<mailto:leave@lists.example.com>, <{}>", url),
+        true,
+    );
+    let sender = RecordingSender::default();
+
+    let outcome = Unsubscriber::new(reqwest::Client::new())
+        .allow_http(true)
+        .unsubscribe(&list, Some(&sender))
+        .await
+        .unwrap();
+
+    assert_eq!(outcome, Unsubscribed::OneClick(url));
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![(
+            "application/x-www-form-urlencoded".to_string(),
+            "List-Unsubscribe=One-Click".to_string()
+        )]
+    );
+    // One-click is preferred over mailing the list
+    assert!(sender.sent.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_one_click_failure_is_reported() {
+    let (addr, _) = spawn_list_server().await;
+    let list = list(&format!("<http://{}/broken>", addr), true);
+
+    let result = Unsubscriber::default()
+        .allow_http(true)
+        .unsubscribe(&list, None)
+        .await;
+    assert!(result.unwrap_err().to_string().contains("500"));
+}
+
+#[tokio::test]
+async fn test_mailto_unsubscribe() {
+    // One-click requires https, so the http URI is not POSTed to
+    let list = list(
+        "<http://lists.example.com/leave>, <mailto:leave@lists.example.com?subject=unsubscribe%20me>",
+        true,
+    );
+    let sender = RecordingSender::default();
+
+    let outcome = Unsubscriber::default()
+        .unsubscribe(&list, Some(&sender))
+        .await
+        .unwrap();
+
+    let sent = sender.sent.lock().unwrap();
+    assert_eq!(outcome, Unsubscribed::Mail(sent[0].message_id.clone()));
+    assert_eq!(sent[0].to, vec!["leave@lists.example.com"]);
+    assert_eq!(sent[0].subject, "unsubscribe me");
+    assert_eq!(sent[0].from.as_deref(), Some("alice@example.com"));
+}
+
+#[tokio::test]
+async fn test_browser_unsubscribe_fallback() {
+    let list = list(
+        "<https://lists.example.com/leave>, <mailto:leave@lists.example.com>",
+        false,
+    );
+
+    // Without a mail sender only the web page is left
+    let outcome = Unsubscriber::default()
+        .unsubscribe(&list, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        Unsubscribed::Browser("https://lists.example.com/leave".to_string())
+    );
+}
+
+fn search_item(thread: &str, query: &str) -> SearchItem {
+    SearchItem {
+        thread: thread.to_string(),
+        timestamp: 1704110400,
+        date_relative: "2024-01-01".to_string(),
+        matched: 1,
+        total: 1,
+        authors: "Alice".to_string(),
+        subject: "Release".to_string(),
+        query: vec![Some(query.to_string()), None],
+        tags: vec!["inbox".to_string()],
+    }
+}
+
+#[tokio::test]
+async fn test_list_labels() {
+    let dir = TempDir::new().unwrap();
+    let script = dir.path().join("notmuch");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho \"$@\" > {args}\ncat {json}\n",
+            args = dir.path().join("args").display(),
+            json = dir.path().join("show.json").display(),
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let message = |id: &str, timestamp: i64, list_id: Option<&str>| {
+        let mut headers = serde_json::json!({
+            "Subject": "Release", "From": "Alice <alice@example.com>",
+            "Date": "Mon, 1 Jan 2024 12:00:00 +0100"
+        });
+        if let Some(list_id) = list_id {
+            headers["List-Id"] = list_id.into();
+        }
+        serde_json::json!({
+            "id": id, "match": true, "excluded": false, "filename": [],
+            "timestamp": timestamp, "date_relative": "2024-01-01",
+            "tags": ["inbox"], "headers": headers
+        })
+    };
+    let show = serde_json::json!([
+        [[
+            message("a@example.com", 1, Some("Old name <dev.lists.example.com>")),
+            [[
+                message(
+                    "b@example.com",
+                    2,
+                    Some("Developers <dev.lists.example.com>")
+                ),
+                []
+            ]]
+        ]],
+        [[message("c@example.com", 3, None), []]]
+    ]);
+    std::fs::write(dir.path().join("show.json"), show.to_string()).unwrap();
+
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some(script),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap();
+    let items = vec![
+        search_item("0000000000000001", "id:a@example.com id:b@example.com"),
+        search_item("0000000000000002", "id:c@example.com"),
+    ];
+
+    let labels = list_labels(&client, &items).await.unwrap();
+    assert_eq!(
+        labels,
+        HashMap::from([("0000000000000001".to_string(), "Developers".to_string())])
+    );
+
+    // Headers only, for all listed threads at once
+    let args = std::fs::read_to_string(dir.path().join("args")).unwrap();
+    assert_eq!(
+        args.trim(),
+        "show --format=json --body=false --entire-thread=false thread:0000000000000001 or thread:0000000000000002"
+    );
+}
@@ -1,5 +1,7 @@
+use mail_parser::MimeHeaders;
 use tokio::test;
-use whynot::error::Result;
+use whynot::error::{Error, Result};
+use whynot::mail_sender::message::parse_address_list;
 use whynot::mail_sender::{ComposableMessage, MailSender, MailSenderConfig, create_mail_sender};
 use whynot::thread::Message;
 
@@ -8,6 +10,7 @@ async fn test_create_mail_sender_local() {
     let config = MailSenderConfig::Local {
         msmtp_path: None,
         config_path: None,
+        account: None,
     };
 
     let result = create_mail_sender(config);
@@ -24,6 +27,7 @@ async fn test_create_mail_sender_remote() {
         identity_file: None,
         msmtp_path: None,
         config_path: None,
+        account: None,
     };
 
     let result = create_mail_sender(config);
@@ -78,6 +82,38 @@ async fn test_message_to_rfc822() {
     assert!(content.contains("Test body content"));
 }
 
+#[test]
+async fn test_message_with_calendar_part() {
+    let calendar = "BEGIN:VCALENDAR\r\nMETHOD:REPLY\r\nSUMMARY:Café\r\nEND:VCALENDAR\r\n";
+    let message = ComposableMessage::builder()
+        .to("organizer@example.com".to_string())
+        .from("sender@example.com".to_string())
+        .subject("Accepted: Café".to_string())
+        .body("Accepted".to_string())
+        .calendar("REPLY".to_string(), calendar.to_string())
+        .build()
+        .unwrap();
+
+    let rfc822 = message.to_rfc822().unwrap();
+    let content = String::from_utf8(rfc822.clone()).unwrap();
+    assert!(content.contains("Content-Type: multipart/alternative;"));
+    assert!(content.contains("Content-Type: text/calendar; charset=utf-8; method=REPLY\r\n"));
+
+    // The calendar survives encoding byte for byte
+    let parsed = mail_parser::MessageParser::default()
+        .parse(&rfc822)
+        .unwrap();
+    let part = parsed
+        .parts
+        .iter()
+        .find(|part| {
+            part.content_type()
+                .is_some_and(|ct| ct.subtype() == Some("calendar"))
+        })
+        .unwrap();
+    assert_eq!(part.text_contents(), Some(calendar));
+}
+
 #[test]
 async fn test_message_with_multiple_recipients() {
     let message = ComposableMessage::builder()
@@ -99,6 +135,111 @@ async fn test_message_with_multiple_recipients() {
     assert!(content.contains("Bcc: bcc@example.com\r\n"));
 }
 
+#[test]
+async fn test_parse_address_list() {
+    let list = parse_address_list(
+        "\"Doe, John\" <john@example.com>, Jane Q. Public <jane@example.com>,, \
+         bob@example.com (Bob Smith), Team: carol@example.com, <dave@example.com>;, \
+         undisclosed-recipients:;, \"odd name\"@example.com, erik@bücher.example",
+    )
+    .unwrap();
+    let formatted: Vec<String> = list.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        formatted,
+        vec![
+            "\"Doe, John\" <john@example.com>",
+            "\"Jane Q. Public\" <jane@example.com>",
+            "Bob Smith <bob@example.com>",
+            "carol@example.com",
+            "dave@example.com",
+            "\"odd name\"@example.com",
+            "erik@xn--bcher-kva.example",
+        ]
+    );
+
+    // Comments may nest and sit anywhere whitespace can
+    let list = parse_address_list("(work) Ann (A. (Ann)) <ann@[192.0.2.1]>").unwrap();
+    assert_eq!(list[0].name.as_deref(), Some("Ann"));
+    assert_eq!(list[0].address, "ann@[192.0.2.1]");
+    assert!(parse_address_list("").unwrap().is_empty());
+}
+
+#[test]
+async fn test_parse_address_list_rejects_invalid_addresses() {
+    let error = |input: &str| parse_address_list(input).unwrap_err();
+    assert_eq!(error("bob"), "bob is not an email address");
+    assert_eq!(error("bob@"), "bob@ has no domain");
+    assert_eq!(error("@example.com"), "nothing before the @");
+    assert_eq!(
+        error("bob@example.com alice@example.com"),
+        "missing comma after bob@example.com"
+    );
+    assert_eq!(
+        error("bob@exa_mple.com"),
+        "bob@exa_mple.com has an invalid domain"
+    );
+    assert_eq!(
+        error("bob@-example.com"),
+        "bob@-example.com has an invalid domain"
+    );
+    assert_eq!(
+        error("bob..smith@example.com"),
+        "bob..smith@ is not a valid address"
+    );
+    assert_eq!(
+        error("Bob <bob@example.com"),
+        "missing > after bob@example.com"
+    );
+    assert_eq!(
+        error("\"Bob <bob@example.com>"),
+        "unclosed quote in \"Bob <bob@example.com>"
+    );
+    assert_eq!(error("bob@example.com (Bob"), "unclosed ( in comment");
+    assert_eq!(
+        error("Team: bob@example.com"),
+        "group Team is missing its closing ;"
+    );
+    assert_eq!(error("<>"), "empty address <>");
+    assert!(error(&format!("{}@example.com", "a".repeat(65))).ends_with("is too long"));
+}
+
+#[test]
+async fn test_builder_rejects_invalid_recipients_by_field() {
+    let result = ComposableMessage::builder()
+        .to("\"Roe, Rick\" <rick@example.com>, ann@example.com".to_string())
+        .cc("carol@example.com carol@example.org".to_string())
+        .body("Hi".to_string())
+        .build();
+    match result {
+        Err(Error::InvalidAddress { field, message }) => {
+            assert_eq!(field, "Cc");
+            assert_eq!(message, "missing comma after carol@example.com");
+        }
+        other => panic!("expected an invalid Cc address, got {:?}", other),
+    }
+
+    // Valid lists are split into one mailbox each
+    let message = ComposableMessage::builder()
+        .to("\"Roe, Rick\" <rick@example.com>, ann@bücher.example".to_string())
+        .build()
+        .unwrap();
+    assert_eq!(
+        message.to,
+        vec![
+            "\"Roe, Rick\" <rick@example.com>",
+            "ann@xn--bcher-kva.example"
+        ]
+    );
+
+    // An empty group leaves no recipients
+    assert!(matches!(
+        ComposableMessage::builder()
+            .to("undisclosed-recipients:;".to_string())
+            .build(),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
 #[test]
 async fn test_reply_builder() {
     use std::collections::HashMap;
@@ -134,6 +275,7 @@ async fn test_reply_builder() {
             content: whynot::body::BodyContent::Text("Original message body".to_string()),
             filename: None,
             content_id: None,
+            content_charset: None,
             content_length: None,
             content_disposition: None,
             content_transfer_encoding: None,
@@ -156,11 +298,125 @@ async fn test_reply_builder() {
     assert!(
         reply
             .body
-            .contains("On 2024-01-01, original@example.com wrote:")
+            .contains("On Mon, 01 Jan 2024 12:00, original@example.com wrote:")
     );
     assert!(reply.body.contains("> Original message body"));
 }
 
+#[test]
+async fn test_reply_all_builder_parses_address_lists() {
+    use std::collections::HashMap;
+    use whynot::common::Headers;
+
+    let mut headers = Headers {
+        subject: Some("Planning".to_string()),
+        from: "\"Doe, John\" <john@example.com>".to_string(),
+        to: Some("\"Smith, Jane\" <jane@example.com>, john@example.com".to_string()),
+        reply_to: None,
+        date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+        additional: HashMap::new(),
+    };
+    headers.additional.insert(
+        "Cc".to_string(),
+        "Team: \"Roe, Rick\" <rick@example.com>;".to_string(),
+    );
+
+    let original = Message {
+        id: "<planning@example.com>".to_string(),
+        is_match: false,
+        excluded: false,
+        filename: vec![],
+        timestamp: 1704110400,
+        date_relative: "2024-01-01".to_string(),
+        tags: vec![],
+        duplicate: None,
+        body: vec![],
+        crypto: Default::default(),
+        headers,
+    };
+
+    let reply = ComposableMessage::reply_builder(&original, true)
+        .build()
+        .unwrap();
+    assert_eq!(
+        reply.to,
+        vec![
+            "\"Doe, John\" <john@example.com>",
+            "\"Smith, Jane\" <jane@example.com>"
+        ]
+    );
+    assert_eq!(reply.cc, vec!["\"Roe, Rick\" <rick@example.com>"]);
+    assert!(
+        reply
+            .body
+            .contains("On Mon, 01 Jan 2024 12:00, \"Doe, John\" <john@example.com> wrote:")
+    );
+
+    // A plain reply goes to Reply-To when the sender set one
+    let mut original = original;
+    original.headers.reply_to = Some("Planning list <planning@lists.example.com>".to_string());
+    let reply = ComposableMessage::reply_builder(&original, false)
+        .build()
+        .unwrap();
+    assert_eq!(reply.to, vec!["Planning list <planning@lists.example.com>"]);
+}
+
+#[test]
+async fn test_list_reply_builder() {
+    use std::collections::HashMap;
+    use whynot::common::Headers;
+
+    let mut headers = Headers {
+        subject: Some("[dev] Release plan".to_string()),
+        from: "Alice <alice@example.com>".to_string(),
+        to: Some("dev@lists.example.com".to_string()),
+        reply_to: Some("alice@example.com".to_string()),
+        date: "Mon, 1 Jan 2024 12:00:00 +0000".to_string(),
+        additional: HashMap::new(),
+    };
+    headers.additional.insert(
+        "List-Id".to_string(),
+        "Developers <dev.lists.example.com>".to_string(),
+    );
+    headers.additional.insert(
+        "List-Post".to_string(),
+        "<mailto:dev@lists.example.com>".to_string(),
+    );
+
+    let mut original = Message {
+        id: "<plan@example.com>".to_string(),
+        is_match: false,
+        excluded: false,
+        filename: vec![],
+        timestamp: 1704110400,
+        date_relative: "2024-01-01".to_string(),
+        tags: vec![],
+        duplicate: None,
+        body: vec![],
+        crypto: Default::default(),
+        headers,
+    };
+
+    let reply = ComposableMessage::list_reply_builder(&original)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(reply.to, vec!["dev@lists.example.com"]);
+    assert!(reply.cc.is_empty());
+    assert_eq!(reply.subject, "Re: [dev] Release plan");
+    assert_eq!(reply.in_reply_to, Some("<plan@example.com>".to_string()));
+
+    // Announcement lists don't take replies
+    original
+        .headers
+        .additional
+        .insert("List-Post".to_string(), "NO".to_string());
+    assert!(ComposableMessage::list_reply_builder(&original).is_err());
+
+    original.headers.additional.clear();
+    assert!(ComposableMessage::list_reply_builder(&original).is_err());
+}
+
 #[test]
 async fn test_forward_builder() {
     use std::collections::HashMap;
@@ -192,6 +448,7 @@ async fn test_forward_builder() {
             content: whynot::body::BodyContent::Text("Original message body".to_string()),
             filename: None,
             content_id: None,
+            content_charset: None,
             content_length: None,
             content_disposition: None,
             content_transfer_encoding: None,
@@ -349,3 +606,37 @@ async fn test_mock_mail_sender_reply() {
         Some("<original@example.com>".to_string())
     );
 }
+
+#[test]
+async fn test_to_rfc822_is_repeatable() {
+    // The Sent copy is rendered separately from the submitted message, so
+    // the output (including MIME boundaries) must not change between calls
+    let message = ComposableMessage::builder()
+        .to("bob@example.com".to_string())
+        .subject("Report".to_string())
+        .body("See attached".to_string())
+        .html_body("<p>See attached</p>".to_string())
+        .build()
+        .unwrap();
+    let first = message.to_rfc822().unwrap();
+    assert_eq!(first, message.to_rfc822().unwrap());
+
+    let other = ComposableMessage::builder()
+        .to("bob@example.com".to_string())
+        .subject("Report".to_string())
+        .body("See attached".to_string())
+        .html_body("<p>See attached</p>".to_string())
+        .build()
+        .unwrap();
+    let boundary = |raw: &[u8]| {
+        let raw = String::from_utf8_lossy(raw).to_string();
+        raw.split("boundary=\"")
+            .nth(1)
+            .unwrap()
+            .split('"')
+            .next()
+            .unwrap()
+            .to_string()
+    };
+    assert_ne!(boundary(&first), boundary(&other.to_rfc822().unwrap()));
+}
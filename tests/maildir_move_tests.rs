@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, LocalClient, NotmuchClient};
+use whynot::test_utils::write_fake_notmuch;
+use whynot::web::{AppState, WebConfig, create_app};
+
+fn create_message(mail_root: &Path, relative: &str) -> PathBuf {
+    let path = mail_root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "Subject: test\n\nbody\n").unwrap();
+    path
+}
+
+fn setup(show_json: impl Fn(&Path) -> String) -> (TempDir, TempDir, LocalClient) {
+    let bin_dir = TempDir::new().unwrap();
+    let mail_root = TempDir::new().unwrap();
+    let notmuch = write_fake_notmuch(bin_dir.path());
+    std::fs::write(
+        bin_dir.path().join("show.json"),
+        show_json(mail_root.path()),
+    )
+    .unwrap();
+
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some(notmuch),
+        database_path: None,
+        mail_root: Some(mail_root.path().to_path_buf()),
+        format: Default::default(),
+    })
+    .unwrap();
+
+    (bin_dir, mail_root, client)
+}
+
+fn calls(bin_dir: &TempDir) -> String {
+    std::fs::read_to_string(bin_dir.path().join("calls.log")).unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_move_to_folder_renames_files_and_reindexes() {
+    let (bin_dir, mail_root, client) = setup(|root| {
+        let seen = create_message(root, "INBOX/cur/1700000000.1_1.host,U=42:2,S");
+        let unseen = create_message(root, "INBOX/new/1700000001.2_1.host,U=43");
+        serde_json::json!([[[
+            {"id": "a@example.com", "match": true, "filename": [seen], "tags": ["inbox", "replied"]},
+            [[{"id": "b@example.com", "match": true, "filename": [unseen], "tags": ["inbox", "unread"]}, []]]
+        ]]])
+        .to_string()
+    });
+
+    let moved = client
+        .move_to_folder("thread:0000000000000001", "Archive")
+        .await
+        .unwrap();
+    assert_eq!(moved, 2);
+
+    let root = mail_root.path();
+    assert!(!root.join("INBOX/cur/1700000000.1_1.host,U=42:2,S").exists());
+    assert!(!root.join("INBOX/new/1700000001.2_1.host,U=43").exists());
+    assert!(root.join("Archive/cur/1700000000.1_1.host:2,RS").exists());
+    assert!(root.join("Archive/new/1700000001.2_1.host").exists());
+    assert!(root.join("Archive/tmp").is_dir());
+
+    let calls = calls(&bin_dir);
+    assert!(calls.contains("show --format=json --body=false --entire-thread=false"));
+    assert!(calls.lines().any(|line| line == "new"));
+}
+
+#[tokio::test]
+async fn test_move_to_folder_skips_files_already_in_target() {
+    let (bin_dir, mail_root, client) = setup(|root| {
+        let archived = create_message(root, "Archive/cur/1700000000.1_1.host:2,S");
+        serde_json::json!([[[
+            {"id": "a@example.com", "match": true, "filename": [archived], "tags": []},
+            []
+        ]]])
+        .to_string()
+    });
+
+    let moved = client
+        .move_to_folder("id:a@example.com", "Archive")
+        .await
+        .unwrap();
+    assert_eq!(moved, 0);
+    assert!(
+        mail_root
+            .path()
+            .join("Archive/cur/1700000000.1_1.host:2,S")
+            .exists()
+    );
+    assert!(!calls(&bin_dir).lines().any(|line| line == "new"));
+}
+
+#[tokio::test]
+async fn test_move_to_folder_moves_nothing_when_a_destination_exists() {
+    let (bin_dir, mail_root, client) = setup(|root| {
+        let first = create_message(root, "INBOX/cur/1700000000.1_1.host:2,S");
+        let second = create_message(root, "INBOX/cur/1700000001.2_1.host:2,S");
+        create_message(root, "Archive/cur/1700000001.2_1.host:2,S");
+        serde_json::json!([[[
+            {"id": "a@example.com", "match": true, "filename": [first], "tags": []},
+            [[{"id": "b@example.com", "match": true, "filename": [second], "tags": []}, []]]
+        ]]])
+        .to_string()
+    });
+
+    let result = client
+        .move_to_folder("thread:0000000000000001", "Archive")
+        .await;
+    assert!(result.is_err());
+
+    let root = mail_root.path();
+    assert!(root.join("INBOX/cur/1700000000.1_1.host:2,S").exists());
+    assert!(root.join("INBOX/cur/1700000001.2_1.host:2,S").exists());
+    assert!(!root.join("Archive/cur/1700000000.1_1.host:2,S").exists());
+    assert!(!calls(&bin_dir).lines().any(|line| line == "new"));
+}
+
+#[tokio::test]
+async fn test_move_to_folder_rejects_invalid_folder() {
+    let (bin_dir, _mail_root, client) = setup(|_| "[]".to_string());
+
+    for folder in ["", "../outside", "/tmp/Archive"] {
+        assert!(client.move_to_folder("tag:inbox", folder).await.is_err());
+    }
+    assert!(calls(&bin_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_archive_keeps_thread_in_inbox() {
+    let (bin_dir, _mail_root, client) = setup(|_| "[]".to_string());
+    let config = WebConfig {
+        bind_address: ([127, 0, 0, 1], 0).into(),
+        base_url: "http://localhost".to_string(),
+        items_per_page: 10,
+        auto_refresh_interval: 30,
+        initial_page_size: 20,
+        pagination_size: 10,
+        infinite_scroll_enabled: true,
+        archive_folder: Some("../outside".to_string()),
+        ..Default::default()
+    };
+    let state = AppState {
+        client: Arc::new(client),
+        mail_sender: None,
+        config,
+        user_config: whynot::config::UserConfig::default(),
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/thread/0000000000000001/archive", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 500);
+    assert!(!calls(&bin_dir).lines().any(|line| line.starts_with("tag")));
+}
@@ -192,4 +192,74 @@ fn extract_boundary(rfc822: &str) -> Option<String> {
         }
     }
     None
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_plain_text_is_sent_flowed() {
+    let paragraph = "A paragraph long enough to be wrapped for sending. ".repeat(3);
+    let message = ComposableMessage::builder()
+        .to("test@example.com".to_string())
+        .subject("Flowed".to_string())
+        .body(format!("{}\n\n> quoted\nFrom me", paragraph.trim_end()))
+        .build()
+        .unwrap();
+
+    let rfc822_bytes = message.to_rfc822().unwrap();
+    let rfc822 = std::str::from_utf8(&rfc822_bytes).unwrap();
+    assert!(rfc822.contains("Content-Type: text/plain; charset=utf-8; format=flowed\r\n"));
+    // Soft breaks end in a space, which is encoded so it survives transport
+    assert!(rfc822.contains("Content-Transfer-Encoding: quoted-printable\r\n"));
+    assert!(rfc822.contains("=20\r\n"));
+    assert!(rfc822.lines().all(|line| line.len() <= 76));
+    // Lines that would read as quotes or be mangled are space-stuffed
+    assert!(rfc822.contains("\r\n> quoted\r\n From me\r\n"));
+
+    let parsed = mail_parser::MessageParser::default()
+        .parse(&rfc822_bytes[..])
+        .unwrap();
+    let body = parsed.body_text(0).unwrap().replace("\r\n", "\n");
+    assert_eq!(
+        whynot::flowed::decode(&body, false),
+        format!("{}\n\n> quoted\nFrom me\n", paragraph.trim_end())
+    );
+}
+
+#[test]
+fn test_text_encoding_fits_the_content() {
+    let encoded = |body: &str, html: Option<&str>| {
+        let mut builder = ComposableMessage::builder()
+            .to("test@example.com".to_string())
+            .body(body.to_string());
+        if let Some(html) = html {
+            builder = builder.html_body(html.to_string());
+        }
+        String::from_utf8(builder.build().unwrap().to_rfc822().unwrap()).unwrap()
+    };
+
+    // Short ASCII lines go as they are
+    let ascii = encoded("Hello there.", Some("<p class=\"x\">Hello there.</p>"));
+    assert_eq!(ascii.matches("Content-Transfer-Encoding: 7bit").count(), 2);
+    assert!(ascii.contains("<p class=\"x\">Hello there.</p>"));
+
+    // Mostly Latin text is quoted-printable, which keeps it readable
+    let latin = encoded("Caf\u{e9} at noon", Some("<p>Caf\u{e9} at noon</p>"));
+    assert_eq!(
+        latin
+            .matches("Content-Transfer-Encoding: quoted-printable")
+            .count(),
+        2
+    );
+    assert!(latin.contains("Caf=C3=A9 at noon\r\n"));
+    assert!(latin.contains("<p>Caf=C3=A9 at noon</p>\r\n"));
+
+    // Other scripts are shorter as base64
+    let greek = encoded(&"\u{3b1}\u{3b2}\u{3b3} ".repeat(10), None);
+    assert!(greek.contains("Content-Transfer-Encoding: base64"));
+    let parsed = mail_parser::MessageParser::default()
+        .parse(greek.as_bytes())
+        .unwrap();
+    assert_eq!(
+        parsed.body_text(0).unwrap().trim_end(),
+        "\u{3b1}\u{3b2}\u{3b3} ".repeat(10).trim_end()
+    );
+}
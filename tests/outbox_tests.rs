@@ -0,0 +1,756 @@
+//! Tests for the outbox spool and its queue view.
+
+use std::collections::VecDeque;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, LocalClient, NotmuchClient};
+use whynot::config::Config;
+use whynot::error::{NotmuchError, Result};
+use whynot::mail_sender::{ComposableMessage, MailSender};
+use whynot::outbox::{Delivery, Outbox, OutboxStatus};
+use whynot::thread::Message;
+use whynot::tui::app::{App, AppState as TuiState, ComposeField};
+use whynot::web::{AppState, WebConfig, create_app};
+
+/// A sender that answers with scripted results, then succeeds.
+#[derive(Default)]
+struct ScriptedSender {
+    replies: Mutex<VecDeque<Result<()>>>,
+    sent: Mutex<Vec<ComposableMessage>>,
+}
+
+impl ScriptedSender {
+    fn failing(errors: Vec<NotmuchError>) -> Arc<Self> {
+        Arc::new(ScriptedSender {
+            replies: Mutex::new(errors.into_iter().map(Err).collect()),
+            ..Default::default()
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl MailSender for ScriptedSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String> {
+        if let Some(reply) = self.replies.lock().unwrap().pop_front() {
+            reply?;
+        }
+        let message_id = message.message_id.clone();
+        self.sent.lock().unwrap().push(message);
+        Ok(message_id)
+    }
+
+    async fn reply(&self, _: &Message, reply: ComposableMessage, _: bool) -> Result<String> {
+        self.send(reply).await
+    }
+
+    async fn forward(&self, _: &Message, forward: ComposableMessage) -> Result<String> {
+        self.send(forward).await
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        Ok("me@example.com".to_string())
+    }
+}
+
+fn unreachable() -> NotmuchError {
+    NotmuchError::SshError(
+        "SSH msmtp command failed: ssh: connect to host mail port 22: No route to host".to_string(),
+    )
+}
+
+fn rejected() -> NotmuchError {
+    NotmuchError::SmtpRejected {
+        code: 550,
+        message: "Permanent SMTP failure after RCPT TO:<bob@example.com>: 550 5.1.1 No such user"
+            .to_string(),
+    }
+}
+
+/// A client that is never asked for anything, as no Sent folder is used.
+fn client() -> LocalClient {
+    LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some("/nonexistent/notmuch".into()),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap()
+}
+
+/// A client whose fake `notmuch` keeps the last inserted message and
+/// returns it for raw `show`, which is all saving and loading drafts needs.
+fn drafts_client(dir: &Path) -> LocalClient {
+    let script = dir.join("notmuch");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\ncase \"$1\" in\n  insert) cat > {eml} ;;\n  show) cat {eml} ;;\nesac\n",
+            eml = dir.join("insert.eml").display(),
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some(script),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap()
+}
+
+/// Serve the web app with `spool` as its outbox.
+async fn spawn_server(
+    client: LocalClient,
+    sender: Arc<ScriptedSender>,
+    spool: &Path,
+    undo_send_delay: Duration,
+) -> std::net::SocketAddr {
+    let client: Arc<dyn NotmuchClient> = Arc::new(client);
+    let state = AppState {
+        client,
+        mail_sender: Some(sender),
+        config: WebConfig {
+            bind_address: ([127, 0, 0, 1], 0).into(),
+            base_url: "http://localhost".to_string(),
+            items_per_page: 10,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            outbox_dir: Some(spool.to_path_buf()),
+            undo_send_delay,
+            ..Default::default()
+        },
+        user_config: Default::default(),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+    addr
+}
+
+fn message(subject: &str) -> ComposableMessage {
+    ComposableMessage::builder()
+        .to("bob@example.com".to_string())
+        .subject(subject.to_string())
+        .body("Hi Bob".to_string())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_submit_sends_and_empties_the_outbox() {
+    let spool = TempDir::new().unwrap();
+    let outbox = Outbox::new(spool.path());
+    let sender = Arc::new(ScriptedSender::default());
+
+    let message = message("Hello");
+    let message_id = message.message_id.clone();
+    let delivery = outbox
+        .submit(sender.as_ref(), &client(), None, message, None)
+        .await
+        .unwrap();
+
+    assert_eq!(delivery, Delivery::Sent(message_id));
+    assert_eq!(sender.sent.lock().unwrap().len(), 1);
+    assert!(outbox.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_transient_failure_is_kept_and_retried() {
+    let spool = TempDir::new().unwrap();
+    let outbox = Outbox::new(spool.path());
+    let sender = ScriptedSender::failing(vec![unreachable()]);
+    let client = client();
+
+    let delivery = outbox
+        .submit(sender.as_ref(), &client, None, message("Offline"), None)
+        .await
+        .unwrap();
+    let Delivery::Queued { retry_at, error } = delivery else {
+        panic!("expected the message to be queued, got {:?}", delivery);
+    };
+    assert!(error.contains("No route to host"));
+    assert!(retry_at > chrono::Utc::now());
+
+    // The spool survives on disk with the failure recorded
+    let entries = Outbox::new(spool.path()).list().await.unwrap();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.status, OutboxStatus::Queued);
+    assert_eq!(entry.attempts, 1);
+    assert_eq!(entry.message.subject, "Offline");
+    assert!(entry.last_error.as_deref().unwrap().contains("No route"));
+
+    // Not due yet because of the backoff
+    let results = outbox
+        .deliver_due(sender.as_ref(), &client, None)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+    assert!(sender.sent.lock().unwrap().is_empty());
+
+    // Retrying makes it due, and the worker sends it
+    outbox.retry(&entry.id).await.unwrap();
+    let results = outbox
+        .deliver_due(sender.as_ref(), &client, None)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0].1, Ok(Delivery::Sent(_))));
+    assert_eq!(sender.sent.lock().unwrap().len(), 1);
+    assert!(outbox.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_permanent_failure_is_not_retried() {
+    let spool = TempDir::new().unwrap();
+    let outbox = Outbox::new(spool.path());
+    let client = client();
+
+    // Rejected on the first attempt: the caller gets the error back
+    let sender = ScriptedSender::failing(vec![rejected()]);
+    let err = outbox
+        .submit(sender.as_ref(), &client, None, message("Typo"), None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("550 5.1.1"), "{}", err);
+    assert!(outbox.list().await.unwrap().is_empty());
+
+    // Rejected on a retry: kept as failed until retried or cancelled
+    let sender = ScriptedSender::failing(vec![unreachable(), rejected()]);
+    outbox
+        .submit(sender.as_ref(), &client, None, message("Later"), None)
+        .await
+        .unwrap();
+    let id = outbox.list().await.unwrap()[0].id.clone();
+    let delivery = outbox
+        .deliver(&id, sender.as_ref(), &client, None)
+        .await
+        .unwrap();
+    assert!(matches!(delivery, Delivery::Failed(_)));
+
+    let entry = outbox.get(&id).await.unwrap();
+    assert_eq!(entry.status, OutboxStatus::Failed);
+    assert_eq!(entry.attempts, 2);
+    assert!(!entry.is_due(chrono::Utc::now() + chrono::Duration::days(1)));
+
+    let cancelled = outbox.cancel(&id).await.unwrap();
+    assert_eq!(cancelled.message.subject, "Later");
+    assert!(outbox.list().await.unwrap().is_empty());
+    assert!(outbox.cancel(&id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_web_queues_unsent_mail_in_the_outbox() {
+    let spool = TempDir::new().unwrap();
+    let sender = ScriptedSender::failing(vec![unreachable()]);
+    let addr = spawn_server(client(), sender.clone(), spool.path(), Duration::ZERO).await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = http
+        .post(format!("http://{}/compose", addr))
+        .form(&[
+            ("to", "bob@example.com"),
+            ("subject", "Offline"),
+            ("body", "Hi Bob"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["location"].to_str().unwrap(), "/outbox");
+
+    let page = http
+        .get(format!("http://{}/outbox", addr))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Offline"));
+    assert!(page.contains("No route to host"));
+    let id = Outbox::new(spool.path()).list().await.unwrap()[0]
+        .id
+        .clone();
+    assert!(page.contains(&format!("/outbox/{}/retry", id)));
+
+    // Retrying now sends it
+    let response = http
+        .post(format!("http://{}/outbox/{}/retry", addr, id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["location"].to_str().unwrap(), "/outbox");
+    assert_eq!(sender.sent.lock().unwrap().len(), 1);
+    assert!(Outbox::new(spool.path()).list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_scheduled_message_waits_until_due() {
+    let spool = TempDir::new().unwrap();
+    let outbox = Outbox::new(spool.path());
+    let sender = Arc::new(ScriptedSender::default());
+    let client = client();
+
+    let send_at = chrono::Utc::now() + chrono::Duration::hours(10);
+    let entry = outbox
+        .schedule(&client, message("Morning"), None, send_at)
+        .await
+        .unwrap();
+    assert_eq!(entry.status, OutboxStatus::Scheduled);
+    assert_eq!(entry.message.date, send_at);
+
+    // Still there after a restart, and not sent before its time
+    let outbox = Outbox::new(spool.path());
+    let entries = outbox.list().await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].status, OutboxStatus::Scheduled);
+    assert_eq!(entries[0].next_attempt, send_at);
+    let results = outbox
+        .deliver_due(sender.as_ref(), &client, None)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+    assert!(!entries[0].is_due(send_at - chrono::Duration::seconds(1)));
+    assert!(entries[0].is_due(send_at));
+
+    // Sending it early
+    outbox.retry(&entry.id).await.unwrap();
+    let results = outbox
+        .deliver_due(sender.as_ref(), &client, None)
+        .await
+        .unwrap();
+    assert!(matches!(results[0].1, Ok(Delivery::Sent(_))));
+    assert_eq!(sender.sent.lock().unwrap()[0].subject, "Morning");
+}
+
+#[tokio::test]
+async fn test_web_schedules_and_edits_messages() {
+    let spool = TempDir::new().unwrap();
+    let bin_dir = TempDir::new().unwrap();
+    let sender = Arc::new(ScriptedSender::default());
+    let addr = spawn_server(
+        drafts_client(bin_dir.path()),
+        sender.clone(),
+        spool.path(),
+        Duration::ZERO,
+    )
+    .await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = http
+        .post(format!("http://{}/compose", addr))
+        .form(&[
+            ("to", "bob@example.com"),
+            ("subject", "Good morning"),
+            ("body", "Written last night"),
+            ("send_at", "2099-01-01T08:00"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["location"].to_str().unwrap(), "/outbox");
+    assert!(sender.sent.lock().unwrap().is_empty());
+
+    let page = http
+        .get(format!("http://{}/outbox", addr))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Good morning"));
+    assert!(page.contains("for 2099-01-01 08:00:00"));
+    assert!(page.contains("Send now"));
+    let id = Outbox::new(spool.path()).list().await.unwrap()[0]
+        .id
+        .clone();
+
+    // Editing takes it out of the outbox and reopens it as a draft
+    let response = http
+        .post(format!("http://{}/outbox/{}/edit", addr, id))
+        .send()
+        .await
+        .unwrap();
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    assert!(location.starts_with("/drafts/"), "{}", location);
+    assert!(
+        location.ends_with("?send_at=2099-01-01T08:00"),
+        "{}",
+        location
+    );
+    assert!(Outbox::new(spool.path()).list().await.unwrap().is_empty());
+
+    let page = http
+        .get(format!("http://{}{}", addr, location))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("value=\"Good morning\""));
+    assert!(page.contains("Written last night"));
+    assert!(page.contains("value=\"2099-01-01T08:00\""));
+
+    // A time in the past is refused on the form
+    let page = http
+        .post(format!("http://{}/compose", addr))
+        .form(&[
+            ("to", "bob@example.com"),
+            ("subject", "Too late"),
+            ("body", "Oops"),
+            ("send_at", "2001-01-01T08:00"),
+        ])
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("is in the past"), "{}", page);
+    assert!(Outbox::new(spool.path()).list().await.unwrap().is_empty());
+}
+
+/// A TUI app sending through `sender` with `spool` as its outbox.
+async fn tui_app(spool: &Path, sender: Arc<ScriptedSender>, undo_send_seconds: u64) -> App {
+    let mut config = Config::default();
+    config.mail.sending.outbox_dir = Some(spool.display().to_string());
+    config.mail.sending.undo_send_seconds = Some(undo_send_seconds);
+    let client: Arc<dyn NotmuchClient> = Arc::new(client());
+    App::new(client, Some(sender), &config).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_tui_send_later_schedules_in_outbox() {
+    let spool = TempDir::new().unwrap();
+    let sender = Arc::new(ScriptedSender::default());
+    let mut app = tui_app(spool.path(), sender.clone(), 0).await;
+
+    app.start_compose_new();
+    app.compose_form.to = "bob@example.com".to_string();
+    app.compose_form.subject = "Tomorrow".to_string();
+    app.compose_form.current_field = ComposeField::Body;
+
+    // Ctrl+L jumps to the send-at field and back
+    app.compose_toggle_send_at();
+    assert!(matches!(
+        app.compose_form.current_field,
+        ComposeField::SendAt
+    ));
+    for c in "+2h".chars() {
+        app.compose_handle_char(c);
+    }
+    assert_eq!(app.compose_form.send_at, "+2h");
+    assert!(app.compose_form.body.is_empty());
+    app.compose_toggle_send_at();
+    assert!(matches!(app.compose_form.current_field, ComposeField::Body));
+
+    app.send_composed_email().await.unwrap();
+    assert!(matches!(app.state, TuiState::EmailList));
+    let status = app.status_message.clone().unwrap();
+    assert!(status.starts_with("Scheduled message for"), "{}", status);
+
+    let entries = Outbox::new(spool.path()).list().await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].status, OutboxStatus::Scheduled);
+    assert!(entries[0].next_attempt > chrono::Utc::now() + chrono::Duration::minutes(119));
+    assert!(sender.sent.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_web_undo_send() {
+    let spool = TempDir::new().unwrap();
+    let bin_dir = TempDir::new().unwrap();
+    let sender = Arc::new(ScriptedSender::default());
+    let addr = spawn_server(
+        drafts_client(bin_dir.path()),
+        sender.clone(),
+        spool.path(),
+        Duration::from_secs(1),
+    )
+    .await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let send = |subject: &'static str| {
+        http.post(format!("http://{}/compose", addr))
+            .form(&[
+                ("to", "bob@example.com"),
+                ("cc", "carol@example.com"),
+                ("subject", subject),
+                ("body", "Forgot the attachment"),
+                ("markdown", "true"),
+            ])
+            .send()
+    };
+
+    // Sending holds the message and offers to undo it on the next page
+    let response = send("Oops").await.unwrap();
+    assert_eq!(response.headers()["location"].to_str().unwrap(), "/inbox");
+    let cookie = response.headers()["set-cookie"].to_str().unwrap();
+    let id = Outbox::new(spool.path()).list().await.unwrap()[0]
+        .id
+        .clone();
+    assert!(
+        cookie.starts_with(&format!("whynot_undo={}.", id)),
+        "{}",
+        cookie
+    );
+    assert!(sender.sent.lock().unwrap().is_empty());
+
+    // Undoing reopens the compose form with every field intact
+    let response = http
+        .post(format!("http://{}/outbox/{}/undo", addr, id))
+        .send()
+        .await
+        .unwrap();
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    assert!(location.starts_with("/drafts/"), "{}", location);
+    assert!(
+        response.headers()["set-cookie"]
+            .to_str()
+            .unwrap()
+            .contains("Max-Age=0")
+    );
+    let page = http
+        .get(format!("http://{}{}", addr, location))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("value=\"bob@example.com\""));
+    assert!(page.contains("value=\"carol@example.com\""));
+    assert!(page.contains("value=\"Oops\""));
+    assert!(page.contains("Forgot the attachment"));
+    assert!(page.contains("name=\"markdown\" value=\"true\" checked"));
+
+    // Without undo it goes out once the grace period is over
+    let response = send("Fixed").await.unwrap();
+    let cookie = response.headers()["set-cookie"].to_str().unwrap();
+    let id = cookie["whynot_undo=".len()..].split('.').next().unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let sent = sender.sent.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].subject, "Fixed");
+    assert!(Outbox::new(spool.path()).list().await.unwrap().is_empty());
+
+    // And can no longer be taken back
+    let response = http
+        .post(format!("http://{}/outbox/{}/undo", addr, id))
+        .send()
+        .await
+        .unwrap();
+    let location = response.headers()["location"].to_str().unwrap();
+    assert!(location.starts_with("/outbox?error="), "{}", location);
+    assert!(location.contains("Too%20late%20to%20undo"), "{}", location);
+}
+
+#[tokio::test]
+async fn test_tui_undo_send_reopens_compose_form() {
+    let spool = TempDir::new().unwrap();
+    let sender = Arc::new(ScriptedSender::default());
+    let mut app = tui_app(spool.path(), sender.clone(), 30).await;
+    let outbox = Outbox::new(spool.path());
+
+    app.start_compose_new();
+    app.compose_form.to = "bob@example.com".to_string();
+    app.compose_form.cc = "carol@example.com".to_string();
+    app.compose_form.subject = "Oops".to_string();
+    app.compose_form.body = "Forgot the attachment".to_string();
+    app.compose_form.markdown_mode = true;
+
+    // Sending holds the message back for the grace period
+    app.send_composed_email().await.unwrap();
+    assert!(matches!(app.state, TuiState::EmailList));
+    let pending = app.pending_send.as_ref().unwrap();
+    assert!(pending.seconds_left() > 25 && pending.seconds_left() <= 30);
+    assert_eq!(outbox.list().await.unwrap().len(), 1);
+
+    // Undoing reopens the form as it was
+    app.undo_send().await;
+    assert!(matches!(app.state, TuiState::Compose));
+    assert!(app.pending_send.is_none());
+    assert_eq!(app.compose_form.to, "bob@example.com");
+    assert_eq!(app.compose_form.cc, "carol@example.com");
+    assert_eq!(app.compose_form.subject, "Oops");
+    assert_eq!(app.compose_form.body, "Forgot the attachment");
+    assert!(app.compose_form.markdown_mode);
+    assert!(outbox.list().await.unwrap().is_empty());
+    assert!(sender.sent.lock().unwrap().is_empty());
+
+    // Sent for real once the grace period is cut short
+    app.send_composed_email().await.unwrap();
+    app.flush_pending_send().await;
+    assert!(app.pending_send.is_none());
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Email sent successfully")
+    );
+    assert!(outbox.list().await.unwrap().is_empty());
+    assert_eq!(sender.sent.lock().unwrap().len(), 1);
+
+    // Nothing left to undo
+    app.undo_send().await;
+    assert!(matches!(app.state, TuiState::EmailList));
+}
+
+/// A sender that, like another process sharing the spool, recovers stale
+/// claims while a message is being sent.
+struct RecoveringSender {
+    outbox: Outbox,
+    recovered: Mutex<Vec<usize>>,
+}
+
+#[async_trait::async_trait]
+impl MailSender for RecoveringSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String> {
+        let recovered = self.outbox.recover().await?;
+        self.recovered.lock().unwrap().push(recovered);
+        Ok(message.message_id)
+    }
+
+    async fn reply(&self, _: &Message, reply: ComposableMessage, _: bool) -> Result<String> {
+        self.send(reply).await
+    }
+
+    async fn forward(&self, _: &Message, forward: ComposableMessage) -> Result<String> {
+        self.send(forward).await
+    }
+
+    async fn test_connection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_from_address(&self) -> Result<String> {
+        Ok("me@example.com".to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_claiming_an_old_entry_is_not_stale() {
+    let spool = TempDir::new().unwrap();
+    let outbox = Outbox::new(spool.path());
+    let client = client();
+
+    // Written an hour ago, as scheduled messages and long backoffs are
+    let entry = outbox.enqueue(message("Old"), None).await.unwrap();
+    let path = spool.path().join(format!("{}.json", entry.id));
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - Duration::from_secs(60 * 60))
+        .unwrap();
+
+    let sender = RecoveringSender {
+        outbox: outbox.clone(),
+        recovered: Mutex::new(Vec::new()),
+    };
+    let delivery = outbox
+        .deliver(&entry.id, &sender, &client, None)
+        .await
+        .unwrap();
+
+    assert!(matches!(delivery, Delivery::Sent(_)));
+    assert_eq!(*sender.recovered.lock().unwrap(), vec![0]);
+    assert!(outbox.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_web_undo_keeps_attachments() {
+    let spool = TempDir::new().unwrap();
+    let bin_dir = TempDir::new().unwrap();
+    let sender = Arc::new(ScriptedSender::default());
+    let addr = spawn_server(
+        drafts_client(bin_dir.path()),
+        sender.clone(),
+        spool.path(),
+        Duration::from_secs(1),
+    )
+    .await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let file = reqwest::multipart::Part::bytes(b"%PDF-1.4 plan".to_vec())
+        .file_name("plan.pdf")
+        .mime_str("application/pdf")
+        .unwrap();
+    let form = reqwest::multipart::Form::new()
+        .text("to", "bob@example.com")
+        .text("subject", "The plan")
+        .text("body", "Attached")
+        .part("attachments", file);
+    http.post(format!("http://{}/compose", addr))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    let id = Outbox::new(spool.path()).list().await.unwrap()[0]
+        .id
+        .clone();
+
+    // Undoing keeps the attachment in the draft
+    let response = http
+        .post(format!("http://{}/outbox/{}/undo", addr, id))
+        .send()
+        .await
+        .unwrap();
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    let page = http
+        .get(format!("http://{}{}", addr, location))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Attached: plan.pdf"), "{}", page);
+
+    // And sending the draft again sends it along
+    let draft_id = location
+        .trim_start_matches("/drafts/")
+        .split('?')
+        .next()
+        .unwrap();
+    let draft_id = urlencoding::decode(draft_id).unwrap().into_owned();
+    let form = reqwest::multipart::Form::new()
+        .text("to", "bob@example.com")
+        .text("subject", "The plan")
+        .text("body", "Attached, really")
+        .text("draft_id", draft_id);
+    http.post(format!("http://{}/compose", addr))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let sent = sender.sent.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].attachments.len(), 1);
+    assert_eq!(sent[0].attachments[0].filename, "plan.pdf");
+    assert_eq!(sent[0].attachments[0].data, b"%PDF-1.4 plan");
+}
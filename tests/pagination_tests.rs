@@ -64,6 +64,7 @@ async fn spawn_test_server() -> TestServer {
             initial_page_size: 3,
             pagination_size: 2,
             infinite_scroll_enabled: true,
+            ..Default::default()
         };
 
         let state = AppState {
@@ -93,6 +94,7 @@ async fn spawn_test_server() -> TestServer {
             initial_page_size: 3,
             pagination_size: 2,
             infinite_scroll_enabled: true,
+            ..Default::default()
         };
 
         let state = AppState {
@@ -210,6 +212,7 @@ async fn test_infinite_scroll_with_many_messages() {
         initial_page_size: 5, // Load only 5 initially
         pagination_size: 3,   // Load 3 more at a time
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -395,6 +398,7 @@ async fn test_pagination_integrates_with_auto_refresh() {
         initial_page_size: 5,
         pagination_size: 3,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
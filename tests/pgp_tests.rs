@@ -0,0 +1,375 @@
+use std::process::Command;
+use std::sync::Arc;
+
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, LocalClient};
+use whynot::config::Config;
+use whynot::draft::Draft;
+use whynot::mail_sender::{ComposableMessage, Gpg, PgpOptions, pgp};
+use whynot::test_utils::RecordingSender;
+use whynot::web::{AppState, WebConfig, create_app};
+
+/// A throwaway keyring with keys for Alice and Bob
+struct Keyring {
+    dir: TempDir,
+}
+
+impl Keyring {
+    fn new() -> Self {
+        let keyring = Keyring {
+            dir: TempDir::new().unwrap(),
+        };
+        for user in ["Alice <alice@example.com>", "Bob <bob@example.org>"] {
+            let status = keyring
+                .gpg_command()
+                .args(["--passphrase", "", "--quick-gen-key", user])
+                .args(["future-default", "default", "never"])
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        keyring
+    }
+
+    fn gpg(&self) -> Gpg {
+        Gpg::default().with_homedir(self.dir.path())
+    }
+
+    fn gpg_command(&self) -> Command {
+        let mut cmd = Command::new("gpg");
+        cmd.args(["--batch", "--no-tty", "--homedir"])
+            .arg(self.dir.path())
+            .stderr(std::process::Stdio::null());
+        cmd
+    }
+}
+
+impl Drop for Keyring {
+    fn drop(&mut self) {
+        // Stop the agent gpg started for the keyring
+        let _ = Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(self.dir.path())
+            .args(["--kill", "gpg-agent"])
+            .status();
+    }
+}
+
+fn message(to: &[&str], bcc: &[&str]) -> ComposableMessage {
+    let mut builder = ComposableMessage::builder()
+        .from("Alice <alice@example.com>".to_string())
+        .subject("Secret plans".to_string())
+        .body("Meet at noon.\nBring the map.".to_string());
+    for address in to {
+        builder = builder.to(address.to_string());
+    }
+    for address in bcc {
+        builder = builder.bcc(address.to_string());
+    }
+    builder.build().unwrap()
+}
+
+fn rfc822(message: &ComposableMessage) -> String {
+    String::from_utf8(message.to_rfc822().unwrap()).unwrap()
+}
+
+/// The parts of a multipart body between `--boundary` lines, without the
+/// CRLF that belongs to each delimiter
+fn parts(rfc822: &str, content_type: &str) -> Vec<String> {
+    let header = rfc822
+        .lines()
+        .find(|line| line.starts_with(&format!("Content-Type: {}", content_type)))
+        .unwrap_or_else(|| panic!("no {} in {}", content_type, rfc822));
+    let boundary = header
+        .split("boundary=\"")
+        .nth(1)
+        .unwrap()
+        .trim_end_matches('"');
+    let delimiter = format!("\r\n--{}", boundary);
+    let mut parts: Vec<String> = rfc822
+        .split(&delimiter)
+        .skip(1)
+        .map(|part| part.strip_prefix("\r\n").unwrap_or(part).to_string())
+        .collect();
+    // The closing delimiter
+    parts.pop();
+    parts
+}
+
+#[tokio::test]
+async fn test_find_key() {
+    let keyring = Keyring::new();
+    let gpg = keyring.gpg();
+
+    let alice = gpg.find_key("alice@example.com").await.unwrap();
+    assert_eq!(alice.map(|fpr| fpr.len()), Some(40));
+    assert_ne!(
+        gpg.find_key("alice@example.com").await.unwrap(),
+        gpg.find_key("bob@example.org").await.unwrap()
+    );
+    assert_eq!(gpg.find_key("carol@example.net").await.unwrap(), None);
+    // Only whole addresses match
+    assert_eq!(gpg.find_key("example.com").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_signed_message_verifies() {
+    let keyring = Keyring::new();
+    let options = PgpOptions {
+        sign: true,
+        encrypt: false,
+    };
+    let message = pgp::protect(
+        &keyring.gpg(),
+        message(&["bob@example.org"], &[]),
+        options,
+        None,
+    )
+    .await
+    .unwrap();
+    let rfc822 = rfc822(&message);
+
+    assert!(rfc822.contains("MIME-Version: 1.0\r\n"));
+    assert!(rfc822.contains(
+        "Content-Type: multipart/signed; micalg=pgp-sha256; protocol=\"application/pgp-signature\""
+    ));
+    let parts = parts(&rfc822, "multipart/signed");
+    assert_eq!(parts.len(), 2);
+    assert!(parts[0].contains("Meet at noon.\r\nBring the map."));
+    assert!(parts[1].starts_with("Content-Type: application/pgp-signature"));
+
+    // The signature covers the first part exactly as sent
+    let dir = TempDir::new().unwrap();
+    let signed = dir.path().join("signed");
+    let signature = dir.path().join("signature.asc");
+    std::fs::write(&signed, &parts[0]).unwrap();
+    std::fs::write(&signature, parts[1].split_once("\r\n\r\n").unwrap().1).unwrap();
+    let output = keyring
+        .gpg_command()
+        .stderr(std::process::Stdio::piped())
+        .arg("--verify")
+        .args([&signature, &signed])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Alice <alice@example.com>"));
+}
+
+fn decrypt(keyring: &Keyring, rfc822: &str) -> String {
+    let parts = parts(rfc822, "multipart/encrypted");
+    assert_eq!(parts.len(), 2);
+    assert!(parts[0].starts_with("Content-Type: application/pgp-encrypted"));
+    assert!(parts[0].ends_with("Version: 1\r\n"));
+    let armored = parts[1].split_once("\r\n\r\n").unwrap().1;
+    assert!(armored.starts_with("-----BEGIN PGP MESSAGE-----"));
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("encrypted.asc");
+    std::fs::write(&path, armored).unwrap();
+    let output = keyring
+        .gpg_command()
+        .stderr(std::process::Stdio::piped())
+        .arg("--decrypt")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn test_encrypted_message_decrypts() {
+    let keyring = Keyring::new();
+    let options = PgpOptions {
+        sign: true,
+        encrypt: true,
+    };
+    let message = pgp::protect(
+        &keyring.gpg(),
+        message(&["bob@example.org"], &[]),
+        options,
+        None,
+    )
+    .await
+    .unwrap();
+    let rfc822 = rfc822(&message);
+
+    // Only the headers are in the clear
+    assert!(rfc822.contains("Subject: Secret plans"));
+    assert!(!rfc822.contains("Meet at noon"));
+    assert!(
+        rfc822
+            .contains("Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"")
+    );
+
+    let decrypted = decrypt(&keyring, &rfc822);
+    assert!(decrypted.starts_with("Content-Type: text/plain"));
+    assert!(decrypted.contains("Meet at noon.\r\nBring the map."));
+}
+
+#[tokio::test]
+async fn test_encryption_needs_every_recipients_key() {
+    let keyring = Keyring::new();
+    let options = PgpOptions {
+        sign: false,
+        encrypt: true,
+    };
+    let error = pgp::protect(
+        &keyring.gpg(),
+        message(
+            &["Bob <bob@example.org>", "carol@example.net"],
+            &["dave@example.net"],
+        ),
+        options,
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("No OpenPGP key for carol@example.net, dave@example.net")
+    );
+}
+
+#[tokio::test]
+async fn test_unprotected_message_is_unchanged() {
+    let plain = message(&["bob@example.org"], &[]);
+    let message = pgp::protect(&Gpg::default(), plain.clone(), PgpOptions::default(), None)
+        .await
+        .unwrap();
+    assert_eq!(message.pgp_body, None);
+    assert_eq!(message.to_rfc822().unwrap(), plain.to_rfc822().unwrap());
+}
+
+#[tokio::test]
+async fn test_reopened_message_stays_protected() {
+    let keyring = Keyring::new();
+    let options = PgpOptions {
+        sign: false,
+        encrypt: true,
+    };
+    let message = pgp::protect(
+        &keyring.gpg(),
+        message(&["bob@example.org"], &[]),
+        options,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // As when sending is undone, or a scheduled message is edited
+    let draft = Draft::from_message(&message);
+    assert_eq!(draft.pgp, Some(options));
+    let raw = draft.to_message(None).to_rfc822().unwrap();
+    let resumed = Draft::from_rfc822("d1@whynot", &raw).unwrap();
+    assert_eq!(resumed.pgp, Some(options));
+    assert_eq!(resumed.body, "Meet at noon.\nBring the map.");
+}
+
+async fn spawn_server(config: &Config, gpg: Gpg, sender: Arc<RecordingSender>) -> String {
+    // No Sent folder or drafts are used, so notmuch is never run
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some("/nonexistent/notmuch".into()),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap();
+    let state = AppState {
+        client: Arc::new(client),
+        mail_sender: Some(sender),
+        config: WebConfig {
+            bind_address: ([127, 0, 0, 1], 0).into(),
+            base_url: "http://localhost".to_string(),
+            items_per_page: 10,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            gpg,
+            ..Default::default()
+        },
+        user_config: config.user_config(),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_web_compose_signs_with_identity_key() {
+    let keyring = Keyring::new();
+    let alice = keyring
+        .gpg()
+        .find_key("alice@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    let config: Config = toml::from_str(&format!(
+        r#"
+[user]
+name = "Alice"
+email = "alice@example.com"
+signing_key = "{}"
+"#,
+        alice
+    ))
+    .unwrap();
+    let sender = Arc::new(RecordingSender::default());
+    let base = spawn_server(&config, keyring.gpg(), sender.clone()).await;
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // Identities with a signing key sign by default
+    let page = http
+        .get(format!("{}/compose", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(r#"name="sign" value="true" checked"#));
+    assert!(!page.contains(r#"name="encrypt" value="true" checked"#));
+
+    let response = http
+        .post(format!("{}/compose", base))
+        .form(&[
+            ("to", "bob@example.org"),
+            ("subject", "Signed"),
+            ("body", "Hi Bob"),
+            ("sign", "true"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    let rfc822 = rfc822(&sender.sent.lock().unwrap()[0]);
+    assert!(rfc822.contains("Content-Type: multipart/signed"));
+
+    // Encrypting to someone without a key is refused
+    let page = http
+        .post(format!("{}/compose", base))
+        .form(&[
+            ("to", "carol@example.net"),
+            ("subject", "Secret"),
+            ("body", "Hi Carol"),
+            ("sign", "true"),
+            ("encrypt", "true"),
+        ])
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("No OpenPGP key for carol@example.net"));
+    assert!(page.contains(r#"name="encrypt" value="true" checked"#));
+    assert_eq!(sender.sent.lock().unwrap().len(), 1);
+}
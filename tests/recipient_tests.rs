@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use whynot::client::{ClientConfig, LocalClient, NotmuchClient};
+use whynot::config::Config;
+use whynot::error::Error;
+use whynot::mail_sender::MailSender;
+use whynot::test_utils::RecordingSender;
+use whynot::tui::app::{App, ComposeField};
+use whynot::web::{AppState, WebConfig, create_app};
+
+/// A client for a notmuch that is never run
+fn client() -> Arc<dyn NotmuchClient> {
+    Arc::new(
+        LocalClient::new(ClientConfig::Local {
+            notmuch_path: Some("/nonexistent/notmuch".into()),
+            database_path: None,
+            mail_root: None,
+            format: Default::default(),
+        })
+        .unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_web_compose_shows_invalid_recipients_by_field() {
+    let sender = Arc::new(RecordingSender::default());
+    let state = AppState {
+        client: client(),
+        mail_sender: Some(sender.clone()),
+        config: WebConfig {
+            bind_address: ([127, 0, 0, 1], 0).into(),
+            base_url: "http://localhost".to_string(),
+            items_per_page: 10,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            ..Default::default()
+        },
+        user_config: Config::default().user_config(),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let send = |to: &'static str, bcc: &'static str| {
+        http.post(format!("http://{}/compose", addr))
+            .form(&[
+                ("to", to),
+                ("bcc", bcc),
+                ("subject", "Hello"),
+                ("body", "Hi"),
+            ])
+            .send()
+    };
+
+    // The reason is shown under the field, which keeps what was typed
+    let page = send("bob@example.org", "carol@")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(r#"<small class="form-hint field-error">carol@ has no domain</small>"#));
+    assert!(page.contains(r#"value="carol@""#));
+    assert!(page.contains("form-input form-input-invalid"));
+    assert!(!page.contains(r#"class="error-message""#));
+    assert!(sender.sent.lock().unwrap().is_empty());
+
+    // Display names may contain commas
+    let response = send("\"Doe, John\" <john@example.com>, jane@example.com", "")
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(
+        sender.sent.lock().unwrap()[0].to,
+        vec!["\"Doe, John\" <john@example.com>", "jane@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn test_tui_compose_shows_invalid_recipients_by_field() {
+    let sender = Arc::new(RecordingSender::default());
+    let mut app = App::new(
+        client(),
+        Some(sender as Arc<dyn MailSender>),
+        &Config::default(),
+    )
+    .await
+    .unwrap();
+    app.start_compose_new();
+    app.compose_form.to = "\"Doe, John\" <john@example.com>".to_string();
+    app.compose_form.cc = "ann@example.com bob@example.com".to_string();
+
+    let error = app.send_composed_email().await.unwrap_err();
+    assert!(matches!(error, Error::InvalidAddress { ref field, .. } if field == "Cc"));
+    // The form moves to the field, which shows why
+    assert_eq!(app.compose_form.current_field, ComposeField::Cc);
+    assert_eq!(
+        app.compose_form.address_error,
+        Some((
+            ComposeField::Cc,
+            "missing comma after ann@example.com".to_string()
+        ))
+    );
+
+    // Editing the field clears the error
+    app.compose_handle_backspace();
+    assert_eq!(app.compose_form.address_error, None);
+}
@@ -11,6 +11,7 @@ fn test_create_remote_client() {
         port: Some(2222),
         identity_file: Some(PathBuf::from("/home/user/.ssh/id_rsa")),
         notmuch_path: None,
+        format: Default::default(),
     };
 
     let client = create_client(config);
@@ -25,6 +26,7 @@ fn test_create_remote_client_minimal() {
         port: None,
         identity_file: None,
         notmuch_path: None,
+        format: Default::default(),
     };
 
     let client = create_client(config);
@@ -50,6 +52,7 @@ fn test_create_remote_client_minimal() {
 //         port: None,
 //         identity_file: Some("/home/you/.ssh/id_rsa".into()),
 //         notmuch_path: None,
+//         format: Default::default(),
 //     };
 //
 //     let client = create_client(config).unwrap();
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use whynot::client::{ClientConfig, LocalClient};
+use whynot::config::Config;
+use whynot::mail_sender::{ComposableMessage, ReplyQuoting};
+use whynot::thread::Message;
+use whynot::tui::app::{App, ComposeField};
+
+fn message(content_type: &str, content: &str) -> Message {
+    serde_json::from_value(serde_json::json!({
+        "id": "m1@example.org", "match": true, "excluded": false, "filename": [],
+        "timestamp": 1704110400, "date_relative": "2024-01-01",
+        "tags": ["inbox"], "crypto": {},
+        "body": [{"id": 1, "content-type": content_type, "content": content}],
+        "headers": {"Subject": "Plans", "From": "Bob Smith <bob@example.org>",
+                    "To": "alice@example.com",
+                    "Date": "Mon, 1 Jan 2024 12:00:00 +0100"}
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_html_only_message_is_quoted_as_text() {
+    let original = message(
+        "text/html",
+        "<html><body><p>Lunch at <b>noon</b>?</p><p>Bob</p></body></html>",
+    );
+    let reply = ComposableMessage::reply_builder(&original, false)
+        .build()
+        .unwrap();
+
+    assert!(
+        reply
+            .body
+            .starts_with("On Mon, 01 Jan 2024 12:00, Bob Smith <bob@example.org> wrote:\n")
+    );
+    assert!(reply.body.contains("> Lunch at noon?"));
+    assert!(!reply.body.contains("<b>"));
+}
+
+#[test]
+fn test_attribution_and_signature_trimming() {
+    let original = message(
+        "text/plain",
+        "See you there.\n\n-- \nBob\nbob.example.org\n",
+    );
+    let quoting = ReplyQuoting {
+        attribution: "{name} <{email}> wrote on {date}:".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        quoting.quote(&original, "See you there.\n\n-- \nBob\n"),
+        "Bob Smith <bob@example.org> wrote on Mon, 01 Jan 2024 12:00:\n> See you there.\n"
+    );
+
+    let untrimmed = ReplyQuoting {
+        trim_signature: false,
+        ..quoting
+    };
+    assert_eq!(
+        untrimmed.quote(&original, "Hi\n> earlier\n-- \nBob"),
+        "Bob Smith <bob@example.org> wrote on Mon, 01 Jan 2024 12:00:\n> Hi\n>> earlier\n> -- \n> Bob\n"
+    );
+}
+
+#[test]
+fn test_attribution_date_without_date_header() {
+    let mut original = message("text/plain", "Hi");
+    original.headers.date = "sometime".to_string();
+    assert_eq!(
+        ReplyQuoting::default().attribution(&original),
+        "On 2024-01-01, Bob Smith <bob@example.org> wrote:"
+    );
+}
+
+#[test]
+fn test_reply_goes_above_or_below_the_quote() {
+    let quote = "On Monday, Bob wrote:\n> Lunch?\n";
+    let bottom = ReplyQuoting::default();
+    assert_eq!(
+        bottom.reply_body(quote, Some("Alice")),
+        "On Monday, Bob wrote:\n> Lunch?\n\n\n\n-- \nAlice"
+    );
+    assert_eq!(
+        bottom.join("Sure.", quote),
+        "On Monday, Bob wrote:\n> Lunch?\n\nSure.\n"
+    );
+
+    let top = ReplyQuoting {
+        top_posting: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        top.reply_body(quote, Some("Alice")),
+        "\n\n-- \nAlice\n\nOn Monday, Bob wrote:\n> Lunch?\n"
+    );
+    assert_eq!(
+        top.join("Sure.\n", quote),
+        "Sure.\n\nOn Monday, Bob wrote:\n> Lunch?\n"
+    );
+    assert_eq!(top.join("", quote), quote);
+}
+
+#[tokio::test]
+async fn test_tui_reply_quotes_the_original() {
+    let config: Config = toml::from_str(
+        r#"
+[user]
+name = "Alice"
+email = "alice@example.com"
+reply_attribution = "{name} wrote:"
+reply_position = "top"
+"#,
+    )
+    .unwrap();
+    // Replying only reads the current email, so notmuch is never run
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some("/nonexistent/notmuch".into()),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap();
+    let sender =
+        whynot::mail_sender::create_mail_sender(whynot::mail_sender::MailSenderConfig::Local {
+            msmtp_path: Some("/nonexistent/msmtp".into()),
+            config_path: None,
+            account: None,
+        })
+        .unwrap();
+    let mut app = App::new(Arc::new(client), Some(Arc::from(sender)), &config)
+        .await
+        .unwrap();
+    app.current_email = Some(message(
+        "text/html",
+        "<p>Lunch at noon?</p><p>-- <br>Bob</p>",
+    ));
+
+    app.start_compose_reply(false).await;
+    assert_eq!(app.compose_form.body, "");
+    assert_eq!(
+        app.compose_form.quote,
+        "Bob Smith wrote:\n> Lunch at noon?\n"
+    );
+
+    // What is typed goes above the quote
+    app.compose_form.current_field = ComposeField::Body;
+    for c in "Yes!".chars() {
+        app.compose_handle_char(c);
+    }
+    assert_eq!(
+        app.compose_form.full_body(),
+        "Yes!\n\nBob Smith wrote:\n> Lunch at noon?\n"
+    );
+    assert_eq!(app.compose_form.draft().body, app.compose_form.full_body());
+}
@@ -0,0 +1,144 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, LocalClient, NotmuchClient, OutputFormat};
+use whynot::search::SearchResult;
+use whynot::sexp;
+use whynot::test_utils::write_fake_notmuch;
+use whynot::thread::Thread;
+
+const FIXTURES: &[&str] = &[
+    "search-example-1",
+    "search-example-2-attachments",
+    "thread-example-1",
+];
+
+/// All notmuch JSON outputs in a fixture: the fenced blocks holding an array.
+fn json_blocks(name: &str) -> Vec<String> {
+    let path = format!("examples/notmuch/{}.md", name);
+    let content = fs::read_to_string(path).expect("Failed to read fixture");
+    content
+        .split("```")
+        .skip(1)
+        .step_by(2)
+        .map(|block| block.split_once('\n').map_or("", |(_, body)| body).trim())
+        .filter(|body| body.starts_with('['))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The notmuch `--format=sexp` output for a fixture's search or show command.
+fn sexp_fixture(name: &str, command: &str) -> String {
+    let path = format!("tests/fixtures/sexp/{}.{}.sexp", name, command);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+fn is_search_output(json: &str) -> bool {
+    let value: Value = serde_json::from_str(json).unwrap();
+    value[0].is_object()
+}
+
+#[test]
+fn test_fixtures_parse_identically_from_json_and_sexp() {
+    let mut searches = 0;
+    let mut threads = 0;
+
+    for fixture in FIXTURES {
+        for json in json_blocks(fixture) {
+            if is_search_output(&json) {
+                let sexp_output = sexp_fixture(fixture, "search");
+                let from_json: SearchResult = serde_json::from_str(&json).unwrap();
+                let from_sexp: SearchResult =
+                    sexp::from_str(&sexp_output).unwrap_or_else(|e| panic!("{}: {}", fixture, e));
+                assert_eq!(from_json, from_sexp, "search output in {}", fixture);
+                searches += 1;
+            } else {
+                let sexp_output = sexp_fixture(fixture, "show");
+                let from_json: Thread = serde_json::from_str(&json).unwrap();
+                let from_sexp: Thread =
+                    sexp::from_str(&sexp_output).unwrap_or_else(|e| panic!("{}: {}", fixture, e));
+                assert_eq!(from_json, from_sexp, "thread output in {}", fixture);
+                threads += 1;
+            }
+        }
+    }
+
+    assert_eq!(searches, 2);
+    assert_eq!(threads, 3);
+}
+
+#[test]
+fn test_sexp_fixture_details() {
+    let sexp_output = sexp_fixture("thread-example-1", "show");
+    assert!(sexp_output.starts_with("((((:id \"user@example.com\" :match t :excluded nil"));
+    let thread: Thread = sexp::from_str(&sexp_output).unwrap();
+
+    let messages = thread.get_messages();
+    let first = messages[0];
+    assert_eq!(first.id, "user@example.com");
+    assert_eq!(
+        first.headers.subject.as_deref(),
+        Some("Uppföljningsmöte mellan Alice och Bob, Finance Company AB")
+    );
+    assert_eq!(first.headers.from, "\"Bob Wilson\" <user@example.com>");
+    assert_eq!(first.duplicate, Some(1));
+    assert!(first.has_attachments());
+
+    let search: SearchResult =
+        sexp::from_str(&sexp_fixture("search-example-2-attachments", "search")).unwrap();
+    assert_eq!(search.0[0].thread, "00000000000306d5");
+    assert_eq!(search.0[0].subject, "Din faktura från Personal AI");
+    assert!(search.0[0].tags.contains(&"attachment".to_string()));
+}
+
+fn client(notmuch: &Path, format: OutputFormat) -> LocalClient {
+    LocalClient::new(
+        ClientConfig::Local {
+            notmuch_path: Some(notmuch.to_path_buf()),
+            database_path: None,
+            mail_root: None,
+            format: Default::default(),
+        }
+        .with_format(format),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_client_requests_configured_format() {
+    let dir = TempDir::new().unwrap();
+    let notmuch = write_fake_notmuch(dir.path());
+
+    let json = &json_blocks("thread-example-1")[0];
+    let sexp_output = sexp_fixture("thread-example-1", "show");
+    fs::write(dir.path().join("show.json"), json).unwrap();
+    fs::write(dir.path().join("show.sexp"), &sexp_output).unwrap();
+
+    let from_json = client(&notmuch, OutputFormat::Json)
+        .show("thread:00000000000305e4")
+        .await
+        .unwrap();
+    let from_sexp = client(&notmuch, OutputFormat::Sexp)
+        .show("thread:00000000000305e4")
+        .await
+        .unwrap();
+    assert_eq!(from_json, from_sexp);
+
+    // Serving JSON to a sexp client fails, so the flag really was passed
+    fs::write(dir.path().join("show.sexp"), json).unwrap();
+    assert!(
+        client(&notmuch, OutputFormat::Sexp)
+            .show("thread:00000000000305e4")
+            .await
+            .is_err()
+    );
+}
+
+#[test]
+fn test_output_format_from_str() {
+    assert_eq!("sexp".parse::<OutputFormat>().unwrap(), OutputFormat::Sexp);
+    assert_eq!("JSON".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+    assert!("xml".parse::<OutputFormat>().is_err());
+    assert_eq!(OutputFormat::Sexp.arg(), "--format=sexp");
+}
@@ -0,0 +1,411 @@
+//! Tests for the built-in SMTP sender against a scripted local server.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use whynot::error::NotmuchError;
+use whynot::mail_sender::{
+    ComposableMessage, MailSender, MailSenderConfig, SmtpMailSender, SmtpTls,
+};
+
+const CERT: &[u8] = include_bytes!("fixtures/smtp/localhost.pem");
+const KEY: &[u8] = include_bytes!("fixtures/smtp/localhost-key.pem");
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// How the fake server behaves.
+#[derive(Clone, Default)]
+struct Script {
+    implicit_tls: bool,
+    starttls: bool,
+    pipelining: bool,
+    auth: Option<&'static str>,
+    rcpt_reply: Option<&'static str>,
+    data_reply: Option<&'static str>,
+}
+
+/// What the fake server saw.
+#[derive(Debug, Default)]
+struct Session {
+    commands: Vec<String>,
+    data: String,
+    pipelined: bool,
+    tls: bool,
+    authenticated: bool,
+}
+
+fn acceptor() -> tokio_native_tls::TlsAcceptor {
+    let identity = native_tls::Identity::from_pkcs8(CERT, KEY).unwrap();
+    native_tls::TlsAcceptor::new(identity).unwrap().into()
+}
+
+/// Accept a single connection and play `script`, returning what the
+/// client sent once it disconnects.
+async fn spawn_smtp_server(script: Script) -> (u16, JoinHandle<Session>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut session = Session::default();
+        let mut stream: Box<dyn Stream> = Box::new(tcp);
+        if script.implicit_tls {
+            stream = Box::new(acceptor().accept(stream).await.unwrap());
+            session.tls = true;
+        }
+        let mut reader = BufReader::new(stream);
+        reader
+            .get_mut()
+            .write_all(b"220 fake.example.com ESMTP\r\n")
+            .await
+            .unwrap();
+        let mut accepted = 0;
+
+        loop {
+            let Some(line) = read_line(&mut reader).await else {
+                break;
+            };
+            session.commands.push(line.clone());
+            let upper = line.to_ascii_uppercase();
+            let reply: String = if upper.starts_with("EHLO") {
+                let mut extensions = vec!["fake.example.com", "8BITMIME"];
+                if script.pipelining {
+                    extensions.push("PIPELINING");
+                }
+                if script.starttls && !session.tls {
+                    extensions.push("STARTTLS");
+                }
+                if let Some(auth) = script.auth
+                    && (session.tls || !script.starttls)
+                {
+                    extensions.push(auth);
+                }
+                let last = extensions.len() - 1;
+                extensions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ext)| format!("250{}{}\r\n", if i == last { ' ' } else { '-' }, ext))
+                    .collect()
+            } else if upper == "STARTTLS" {
+                reader
+                    .get_mut()
+                    .write_all(b"220 Go ahead\r\n")
+                    .await
+                    .unwrap();
+                let tls = acceptor().accept(reader.into_inner()).await.unwrap();
+                reader = BufReader::new(Box::new(tls) as Box<dyn Stream>);
+                session.tls = true;
+                continue;
+            } else if let Some(token) = line.strip_prefix("AUTH PLAIN ") {
+                let decoded = STANDARD.decode(token).unwrap();
+                session.authenticated = decoded == b"\0alice\0s3cret";
+                auth_reply(session.authenticated)
+            } else if upper == "AUTH LOGIN" {
+                reader
+                    .get_mut()
+                    .write_all(b"334 VXNlcm5hbWU6\r\n")
+                    .await
+                    .unwrap();
+                let user = read_line(&mut reader).await.unwrap();
+                reader
+                    .get_mut()
+                    .write_all(b"334 UGFzc3dvcmQ6\r\n")
+                    .await
+                    .unwrap();
+                let password = read_line(&mut reader).await.unwrap();
+                session.authenticated = STANDARD.decode(user).unwrap() == b"alice"
+                    && STANDARD.decode(password).unwrap() == b"s3cret";
+                auth_reply(session.authenticated)
+            } else if upper.starts_with("MAIL FROM:") {
+                session.pipelined = !reader.buffer().is_empty();
+                "250 2.1.0 Ok\r\n".to_string()
+            } else if upper.starts_with("RCPT TO:") {
+                match script.rcpt_reply {
+                    Some(reply) => format!("{}\r\n", reply),
+                    None => {
+                        accepted += 1;
+                        "250 2.1.5 Ok\r\n".to_string()
+                    }
+                }
+            } else if upper == "DATA" {
+                if accepted == 0 {
+                    "554 5.5.1 No valid recipients\r\n".to_string()
+                } else {
+                    reader
+                        .get_mut()
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .await
+                        .unwrap();
+                    while let Some(line) = read_line(&mut reader).await {
+                        if line == "." {
+                            break;
+                        }
+                        session.data.push_str(&line);
+                        session.data.push('\n');
+                    }
+                    format!("{}\r\n", script.data_reply.unwrap_or("250 2.0.0 Queued"))
+                }
+            } else if upper == "QUIT" {
+                reader.get_mut().write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                "502 5.5.2 Command not recognized\r\n".to_string()
+            };
+            reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+        }
+        session
+    });
+    (port, handle)
+}
+
+async fn read_line(reader: &mut BufReader<Box<dyn Stream>>) -> Option<String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line).await {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+fn auth_reply(ok: bool) -> String {
+    if ok {
+        "235 2.7.0 Authentication successful\r\n".to_string()
+    } else {
+        "535 5.7.8 Authentication credentials invalid\r\n".to_string()
+    }
+}
+
+fn sender(port: u16, tls: SmtpTls, user: Option<&str>) -> SmtpMailSender {
+    SmtpMailSender::new(MailSenderConfig::Smtp {
+        host: "localhost".to_string(),
+        port: Some(port),
+        tls,
+        user: user.map(str::to_string),
+        password_command: Some("printf 's3cret\\nignored\\n'".to_string()),
+        password_env: None,
+        from: Some("Alice <alice@example.com>".to_string()),
+        ca_cert: Some(PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/smtp/localhost.pem"
+        ))),
+        allow_insecure_auth: false,
+    })
+    .unwrap()
+}
+
+fn message() -> ComposableMessage {
+    ComposableMessage::builder()
+        .to("Bob <bob@example.com>".to_string())
+        .bcc("carol@example.com".to_string())
+        .subject("Hello".to_string())
+        .body("Hi Bob,\n.\nA line with a dot.".to_string())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_smtp_send_pipelined_without_tls() {
+    let (port, server) = spawn_smtp_server(Script {
+        pipelining: true,
+        ..Default::default()
+    })
+    .await;
+
+    let message = message();
+    let message_id = message.message_id.clone();
+    let sent = sender(port, SmtpTls::None, None)
+        .send(message)
+        .await
+        .unwrap();
+    assert_eq!(sent, message_id);
+
+    let session = server.await.unwrap();
+    assert!(session.pipelined, "envelope should be pipelined");
+    assert!(!session.tls);
+    assert_eq!(session.commands[0], "EHLO example.com");
+    assert_eq!(
+        session.commands[1],
+        "MAIL FROM:<alice@example.com> BODY=8BITMIME"
+    );
+    assert!(
+        session
+            .commands
+            .contains(&"RCPT TO:<bob@example.com>".to_string())
+    );
+    assert!(
+        session
+            .commands
+            .contains(&"RCPT TO:<carol@example.com>".to_string())
+    );
+    assert_eq!(session.commands.last().unwrap(), "QUIT");
+
+    // The From default is applied, Bcc is hidden and dots are unstuffed
+    // by the server back to the original text
+    assert!(session.data.contains("From: Alice <alice@example.com>\n"));
+    assert!(session.data.contains("To: Bob <bob@example.com>\n"));
+    assert!(!session.data.contains("Bcc:"));
+    assert!(session.data.contains("\n..\n"), "lone dot must be stuffed");
+}
+
+#[tokio::test]
+async fn test_smtp_starttls_with_auth_plain() {
+    let (port, server) = spawn_smtp_server(Script {
+        starttls: true,
+        auth: Some("AUTH PLAIN LOGIN"),
+        ..Default::default()
+    })
+    .await;
+
+    sender(port, SmtpTls::Starttls, Some("alice"))
+        .send(message())
+        .await
+        .unwrap();
+
+    let session = server.await.unwrap();
+    assert!(session.tls);
+    assert!(session.authenticated);
+    assert!(!session.pipelined);
+    assert_eq!(session.commands[1], "STARTTLS");
+    assert!(
+        session
+            .commands
+            .iter()
+            .any(|c| c.starts_with("AUTH PLAIN "))
+    );
+}
+
+#[tokio::test]
+async fn test_smtp_implicit_tls_with_auth_login() {
+    let (port, server) = spawn_smtp_server(Script {
+        implicit_tls: true,
+        auth: Some("AUTH LOGIN"),
+        ..Default::default()
+    })
+    .await;
+
+    sender(port, SmtpTls::Tls, Some("alice"))
+        .send(message())
+        .await
+        .unwrap();
+
+    let session = server.await.unwrap();
+    assert!(session.tls);
+    assert!(session.authenticated);
+    assert!(session.commands.contains(&"AUTH LOGIN".to_string()));
+}
+
+#[tokio::test]
+async fn test_smtp_requires_starttls_when_configured() {
+    let (port, _server) = spawn_smtp_server(Script::default()).await;
+
+    let err = sender(port, SmtpTls::Starttls, None)
+        .send(message())
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("does not offer STARTTLS"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_smtp_refuses_auth_without_tls() {
+    let result = SmtpMailSender::new(MailSenderConfig::Smtp {
+        host: "localhost".to_string(),
+        port: None,
+        tls: SmtpTls::None,
+        user: Some("alice".to_string()),
+        password_command: Some("printf 's3cret\\n'".to_string()),
+        password_env: None,
+        from: Some("alice@example.com".to_string()),
+        ca_cert: None,
+        allow_insecure_auth: false,
+    });
+    let err = result.err().expect("plain-text auth must be refused");
+    assert!(matches!(err, NotmuchError::ConfigError(_)));
+    assert!(err.to_string().contains("without TLS"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_smtp_rejected_credentials() {
+    let (port, _server) = spawn_smtp_server(Script {
+        auth: Some("AUTH PLAIN"),
+        ..Default::default()
+    })
+    .await;
+
+    let sender = SmtpMailSender::new(MailSenderConfig::Smtp {
+        host: "localhost".to_string(),
+        port: Some(port),
+        tls: SmtpTls::None,
+        user: Some("alice".to_string()),
+        password_command: None,
+        password_env: Some("PATH".to_string()),
+        from: Some("alice@example.com".to_string()),
+        ca_cert: None,
+        allow_insecure_auth: true,
+    })
+    .unwrap();
+    let err = sender.send(message()).await.unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Permanent SMTP failure after authentication: 535"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_smtp_temporary_and_permanent_failures() {
+    let (port, server) = spawn_smtp_server(Script {
+        pipelining: true,
+        rcpt_reply: Some("451 4.3.0 Try again later"),
+        ..Default::default()
+    })
+    .await;
+    let err = sender(port, SmtpTls::None, None)
+        .send(message())
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Temporary SMTP failure after RCPT TO:<bob@example.com>: 451 4.3.0"),
+        "{}",
+        err
+    );
+    assert!(matches!(err, NotmuchError::SmtpRejected { code: 451, .. }));
+    // The connection is dropped instead of sending any message data
+    assert!(server.await.unwrap().data.is_empty());
+
+    let (port, _server) = spawn_smtp_server(Script {
+        data_reply: Some("554 5.7.1 Message rejected as spam"),
+        ..Default::default()
+    })
+    .await;
+    let err = sender(port, SmtpTls::None, None)
+        .send(message())
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Permanent SMTP failure after message data: 554 5.7.1"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_smtp_test_connection() {
+    let (port, server) = spawn_smtp_server(Script::default()).await;
+    sender(port, SmtpTls::None, None)
+        .test_connection()
+        .await
+        .unwrap();
+    assert_eq!(
+        server.await.unwrap().commands,
+        vec!["EHLO example.com", "QUIT"]
+    );
+}
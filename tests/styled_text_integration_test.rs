@@ -34,6 +34,7 @@ async fn test_styled_text_vs_plain_text() {
         filename: None,
         content_disposition: None,
         content_id: None,
+        content_charset: None,
         content_transfer_encoding: None,
         content_length: None,
     };
@@ -153,6 +154,7 @@ async fn test_plain_text_unchanged() {
         filename: None,
         content_disposition: None,
         content_id: None,
+        content_charset: None,
         content_transfer_encoding: None,
         content_length: None,
     };
@@ -0,0 +1,555 @@
+use std::sync::Arc;
+use tempfile::TempDir;
+use whynot::client::{ClientConfig, LocalClient};
+use whynot::mail_sender::MailSender;
+use whynot::test_utils::{RecordingSender, write_fake_notmuch};
+use whynot::web::{AppState, WebConfig, create_app};
+
+fn message(id: &str, from: &str, body: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": id, "match": true, "excluded": false, "filename": [],
+        "timestamp": 1704110400, "date_relative": "2024-01-01",
+        "tags": ["inbox"], "duplicate": 1, "crypto": {},
+        "body": [{"id": 1, "content-type": "text/plain", "content": body}],
+        "headers": {"Subject": "Plans", "From": from, "To": "team@example.com",
+                    "Date": "Mon, 1 Jan 2024 12:00:00 +0100"}
+    })
+}
+
+/// Serve the web app with a fake notmuch that shows `thread`.
+async fn spawn_server(thread: &serde_json::Value) -> (TempDir, std::net::SocketAddr) {
+    spawn_server_with_sender(thread, None).await
+}
+
+async fn spawn_server_with_sender(
+    thread: &serde_json::Value,
+    mail_sender: Option<Arc<dyn MailSender>>,
+) -> (TempDir, std::net::SocketAddr) {
+    let bin_dir = TempDir::new().unwrap();
+    let notmuch = write_fake_notmuch(bin_dir.path());
+    std::fs::write(bin_dir.path().join("show.json"), thread.to_string()).unwrap();
+
+    let client = LocalClient::new(ClientConfig::Local {
+        notmuch_path: Some(notmuch),
+        database_path: None,
+        mail_root: None,
+        format: Default::default(),
+    })
+    .unwrap();
+
+    let state = AppState {
+        client: Arc::new(client),
+        mail_sender,
+        config: WebConfig {
+            bind_address: ([127, 0, 0, 1], 0).into(),
+            base_url: "http://localhost".to_string(),
+            items_per_page: 10,
+            auto_refresh_interval: 30,
+            initial_page_size: 20,
+            pagination_size: 10,
+            infinite_scroll_enabled: true,
+            sent_folder: Some("Sent".to_string()),
+            ..Default::default()
+        },
+        user_config: whynot::config::UserConfig {
+            email: Some("carol@example.com".to_string()),
+            trusted_authserv_ids: Some(vec!["mx.example.com".to_string()]),
+            ..Default::default()
+        },
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_app(state)).await.unwrap();
+    });
+
+    (bin_dir, addr)
+}
+
+#[tokio::test]
+async fn test_thread_view_nests_replies() {
+    // alice
+    // ├── "Doe, John"
+    // │   └── alice
+    // └── carol
+    let json = serde_json::json!([[[
+        message("a@example.com", "Alice <alice@example.com>", "First"),
+        [
+            [
+                message(
+                    "b@example.com",
+                    "\"Doe, John\" <john@example.com>",
+                    "Second"
+                ),
+                [[
+                    message("c@example.com", "Alice <alice@example.com>", "Third"),
+                    []
+                ]]
+            ],
+            [
+                message("d@example.com", "Carol <carol@example.com>", "Fourth"),
+                []
+            ]
+        ]
+    ]]]);
+    let (_bin_dir, addr) = spawn_server(&json).await;
+
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Messages appear in reply order with their nesting level
+    let first = body.find("First").unwrap();
+    let second = body.find("Second").unwrap();
+    let third = body.find("Third").unwrap();
+    let fourth = body.find("Fourth").unwrap();
+    assert!(first < second && second < third && third < fourth);
+
+    assert!(body.contains(r#"id="message-1" style="--thread-indent: 0""#));
+    assert!(body.contains(r#"id="message-2" style="--thread-indent: 1""#));
+    assert!(body.contains(r#"id="message-3" style="--thread-indent: 2""#));
+    assert!(body.contains(r#"id="message-4" style="--thread-indent: 1""#));
+
+    assert!(body.contains("In reply to Doe, John"));
+    assert_eq!(body.matches("In reply to Alice").count(), 2);
+}
+
+#[tokio::test]
+async fn test_thread_summary_in_header_and_api() {
+    let json = serde_json::json!([[[
+        message("a@example.com", "Alice <alice@example.com>", "First"),
+        [
+            [
+                message("b@example.com", "Bob <bob@example.com>", "Second"),
+                []
+            ],
+            [
+                message("c@example.com", "Alice <alice@example.com>", "Third"),
+                []
+            ],
+            [
+                message("d@example.com", "Carol <carol@example.com>", "Fourth"),
+                []
+            ]
+        ]
+    ]]]);
+    let (_bin_dir, addr) = spawn_server(&json).await;
+
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("Alice (2), Bob, Carol"));
+    assert!(body.contains("4 messages"));
+    assert!(body.contains("You sent the last message"));
+
+    let summary: serde_json::Value = reqwest::get(format!(
+        "http://{}/api/thread-summary/0000000000000001",
+        addr
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(summary["messages"], 4);
+    assert_eq!(summary["participants"][0]["messages"], 2);
+    assert_eq!(summary["sent_by_user"], true);
+    assert_eq!(summary["latest_tags"], serde_json::json!(["inbox"]));
+    assert!(
+        summary["tooltip"]
+            .as_str()
+            .unwrap()
+            .starts_with("Alice (2), Bob, Carol\n4 messages")
+    );
+}
+
+#[tokio::test]
+async fn test_encoded_attachment_filename() {
+    let mut with_attachment = message("a@example.com", "Alice <alice@example.com>", "See attached");
+    with_attachment["body"] = serde_json::json!([{
+        "id": 1, "content-type": "multipart/mixed", "content": [
+            {"id": 2, "content-type": "text/plain", "content": "See attached"},
+            {"id": 3, "content-type": "application/pdf", "content-disposition": "attachment",
+             "filename": "=?ISO-2022-JP?B?GyRCRnxLXDhsGyhCLnBkZg==?=", "content-length": 4}
+        ]
+    }]);
+    let (_bin_dir, addr) = spawn_server(&serde_json::json!([[[with_attachment, []]]])).await;
+
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("日本語.pdf"));
+    assert!(!body.contains("ISO-2022-JP"));
+
+    let response = reqwest::get(format!("http://{}/attachment/0000000000000001/0/3", addr))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"___.pdf\"; filename*=UTF-8''%E6%97%A5%E6%9C%AC%E8%AA%9E.pdf"
+    );
+    assert_eq!(response.text().await.unwrap(), "part");
+}
+
+#[tokio::test]
+async fn test_mailing_list_actions() {
+    let mut list_message = message("a@example.com", "Alice <alice@example.com>", "Release");
+    let headers = &mut list_message["headers"];
+    headers["List-Id"] = "Developers <dev.lists.example.com>".into();
+    headers["List-Post"] = "<mailto:dev@lists.example.com>".into();
+    headers["List-Unsubscribe"] = "<https://lists.example.com/leave?u=1&l=dev>".into();
+    let json = serde_json::json!([[[
+        list_message,
+        [[
+            message("b@example.com", "Bob <bob@example.com>", "Thanks"),
+            []
+        ]]
+    ]]]);
+    let (_bin_dir, addr) = spawn_server(&json).await;
+
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        body.contains(
+            r#"<span class="list-badge" title="dev.lists.example.com">Developers</span>"#
+        )
+    );
+    assert_eq!(body.matches("Reply to List").count(), 1);
+    assert!(body.contains("/thread/0000000000000001/reply?message=0&list=true"));
+    assert!(body.contains(r#"action="/thread/0000000000000001/unsubscribe?message=0""#));
+
+    // Without one-click support or a mail sender, the user is sent on to the
+    // list's web page through the external link warning
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!(
+            "http://{}/thread/0000000000000001/unsubscribe?message=0",
+            addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(
+        response.headers()["location"],
+        "/redirect?url=https%3A%2F%2Flists.example.com%2Fleave%3Fu%3D1%26l%3Ddev"
+    );
+
+    // The reply is not from a list
+    let response = client
+        .post(format!(
+            "http://{}/thread/0000000000000001/unsubscribe?message=1",
+            addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+const INVITE: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nMETHOD:REQUEST\r\n\
+BEGIN:VEVENT\r\nUID:review-1@example.com\r\nSEQUENCE:1\r\n\
+DTSTART:20240205T130000Z\r\nDTEND:20240205T140000Z\r\n\
+RRULE:FREQ=WEEKLY;COUNT=4\r\nSUMMARY:Design review\r\nLOCATION:Room 4\r\n\
+ORGANIZER;CN=Alice:mailto:alice@example.com\r\n\
+ATTENDEE;PARTSTAT=ACCEPTED;CN=Alice:mailto:alice@example.com\r\n\
+ATTENDEE;PARTSTAT=NEEDS-ACTION;RSVP=TRUE;CN=Carol:mailto:carol@example.com\r\n\
+END:VEVENT\r\nEND:VCALENDAR\r\n";
+
+#[tokio::test]
+async fn test_calendar_invite_card_and_rsvp() {
+    let mut invite = message("a@example.com", "Alice <alice@example.com>", "");
+    invite["body"] = serde_json::json!([{
+        "id": 1, "content-type": "multipart/alternative", "content": [
+            {"id": 2, "content-type": "text/plain", "content": "You are invited"},
+            {"id": 3, "content-type": "text/calendar", "content": INVITE}
+        ]
+    }]);
+    let json = serde_json::json!([[[invite, []]]]);
+    let sender = Arc::new(RecordingSender::default());
+    let (_bin_dir, addr) = spawn_server_with_sender(&json, Some(sender.clone())).await;
+
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains(r#"<div class="invite-card">"#));
+    assert!(body.contains("Design review"));
+    assert!(body.contains("Weekly, 4 times"));
+    assert!(body.contains("Room 4"));
+    assert!(
+        body.contains(r#"<span class="invite-attendee" title="Awaiting response">Carol</span>"#)
+    );
+    assert!(body.contains(r#"action="/thread/0000000000000001/rsvp?message=0""#));
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!(
+            "http://{}/thread/0000000000000001/rsvp?message=0",
+            addr
+        ))
+        .form(&[("response", "accept")])
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(
+        response.headers()["location"],
+        "/thread/0000000000000001?rsvp=accept"
+    );
+
+    {
+        let sent = sender.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].from.as_deref(), Some("carol@example.com"));
+        assert_eq!(sent[0].to, vec!["Alice <alice@example.com>"]);
+        assert_eq!(sent[0].subject, "Accepted: Design review");
+        assert_eq!(sent[0].in_reply_to.as_deref(), Some("a@example.com"));
+        let calendar = sent[0].calendar.as_ref().unwrap();
+        assert_eq!(calendar.method, "REPLY");
+        assert!(calendar.data.contains("\r\nUID:review-1@example.com\r\n"));
+        assert!(calendar.data.contains("\r\nSEQUENCE:1\r\n"));
+        assert!(
+            calendar
+                .data
+                .contains("\r\nATTENDEE;PARTSTAT=ACCEPTED;CN=Carol:mailto:carol@example.com\r\n")
+        );
+    }
+
+    let body = client
+        .get(format!(
+            "http://{}/thread/0000000000000001?rsvp=accept",
+            addr
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("Response sent: Accepted"));
+}
+
+#[tokio::test]
+async fn test_rsvp_requires_mail_sender() {
+    let mut invite = message("a@example.com", "Alice <alice@example.com>", "");
+    invite["body"] = serde_json::json!([
+        {"id": 1, "content-type": "text/calendar", "content": INVITE}
+    ]);
+    let json = serde_json::json!([[[invite, []]]]);
+    let (_bin_dir, addr) = spawn_server(&json).await;
+
+    // The card is shown, but without response buttons
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("Design review"));
+    assert!(!body.contains("/rsvp?message=0"));
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "http://{}/thread/0000000000000001/rsvp?message=0",
+            addr
+        ))
+        .form(&[("response", "decline")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+}
+
+#[tokio::test]
+async fn test_authentication_badge_and_phishing_warnings() {
+    let mut genuine = message("a@example.com", "Alice <alice@example.com>", "Hi");
+    genuine["headers"]["Authentication-Results"] =
+        "mx.example.com; spf=pass smtp.mailfrom=example.com; dkim=pass header.d=example.com; dmarc=pass header.from=example.com".into();
+    // Imitates the user's own domain, with an address in the display name
+    let mut phishing = message(
+        "b@example.com",
+        "\"billing@example.com\" <billing@examp1e.com>",
+        "Pay now",
+    );
+    phishing["headers"]["Authentication-Results"] =
+        "mx.example.com; spf=fail smtp.mailfrom=examp1e.com; dkim=none; dmarc=fail header.from=examp1e.com".into();
+    // Results the sender made up themselves
+    let mut forged = message("c@example.com", "Bank <alerts@bank.example>", "Urgent");
+    forged["headers"]["Authentication-Results"] =
+        "x; dkim=pass header.d=bank.example; dmarc=pass header.from=bank.example".into();
+    let json = serde_json::json!([[[genuine, [[phishing, []], [forged, []]]]]]);
+    let (_bin_dir, addr) = spawn_server(&json).await;
+
+    let body = reqwest::get(format!("http://{}/thread/0000000000000001", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains(
+        r#"<span class="auth-badge auth-verified" title="SPF pass, DKIM pass (example.com), DMARC pass">Verified</span>"#
+    ));
+    assert!(body.contains(r#"<span class="auth-badge auth-failed""#));
+    assert_eq!(body.matches(r#"auth-badge auth-verified""#).count(), 1);
+    assert!(body.contains(
+        "The sender name shows billing@example.com but the message is from billing@examp1e.com"
+    ));
+    assert!(body.contains("The sender domain examp1e.com looks like example.com but is not"));
+    assert_eq!(body.matches(r#"<div class="phishing-warning">"#).count(), 2);
+}
+
+#[tokio::test]
+async fn test_sent_reply_is_filed_in_sent_folder() {
+    let json = serde_json::json!([[[
+        message("a@example.com", "Alice <alice@example.com>", "Lunch?"),
+        []
+    ]]]);
+    let sender = Arc::new(RecordingSender::default());
+    let (bin_dir, addr) = spawn_server_with_sender(&json, Some(sender.clone())).await;
+
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(format!(
+            "http://{}/thread/0000000000000001/reply?message=0",
+            addr
+        ))
+        .form(&[
+            ("to", "Alice <alice@example.com>"),
+            ("subject", "Re: Plans"),
+            ("body", "Sounds good"),
+            ("in_reply_to", "<a@example.com>"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+
+    let sent = sender.sent.lock().unwrap()[0].clone();
+    let args = std::fs::read_to_string(bin_dir.path().join("insert.args")).unwrap();
+    assert_eq!(
+        args.trim(),
+        "insert --folder Sent --create-folder +sent -unread"
+    );
+    let filed = std::fs::read(bin_dir.path().join("insert.eml")).unwrap();
+    assert_eq!(filed, sent.to_rfc822().unwrap());
+}
+
+#[tokio::test]
+async fn test_draft_is_saved_resumed_and_removed_after_sending() {
+    let json = serde_json::json!([[[
+        message("a@example.com", "Alice <alice@example.com>", "Lunch?"),
+        []
+    ]]]);
+    let sender = Arc::new(RecordingSender::default());
+    let (bin_dir, addr) = spawn_server_with_sender(&json, Some(sender.clone())).await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // Autosave of a half-written reply
+    let saved: serde_json::Value = client
+        .post(format!("http://{}/drafts/save", addr))
+        .form(&[
+            ("to", "Alice <alice@example.com>"),
+            ("cc", "bob@example.com"),
+            ("subject", "Re: Plans"),
+            ("body", "*Sounds* good"),
+            ("in_reply_to", "<a@example.com>"),
+            ("references", "<a@example.com>"),
+            ("markdown", "true"),
+            ("draft_id", ""),
+        ])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = saved["id"].as_str().unwrap().to_string();
+    assert!(!id.is_empty() && !id.starts_with('<'), "{}", id);
+    let args = std::fs::read_to_string(bin_dir.path().join("insert.args")).unwrap();
+    assert_eq!(
+        args.trim(),
+        "insert --folder Drafts --create-folder +draft -unread -inbox"
+    );
+
+    // Reopening it restores every field
+    let body = client
+        .get(format!("http://{}/drafts/{}", addr, id))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains(r#"value="Alice &lt;alice@example.com&gt;""#));
+    assert!(body.contains(r#"value="bob@example.com""#));
+    assert!(body.contains(r#"value="Re: Plans""#));
+    assert!(body.contains("*Sounds* good</textarea>"));
+    assert!(body.contains(r#"name="in_reply_to" value="&lt;a@example.com&gt;""#));
+    assert!(body.contains(&format!(r#"name="draft_id" value="{}""#, id)));
+    assert!(body.contains(r#"name="markdown" value="true" checked"#));
+
+    // Sending it removes the saved copy
+    let draft_file = bin_dir.path().join("draft.eml");
+    std::fs::write(&draft_file, "draft").unwrap();
+    let mut draft = message(&id, "carol@example.com", "*Sounds* good");
+    draft["tags"] = serde_json::json!(["draft"]);
+    draft["filename"] = serde_json::json!([draft_file.display().to_string()]);
+    std::fs::write(
+        bin_dir.path().join("show.json"),
+        serde_json::json!([[[draft, []]]]).to_string(),
+    )
+    .unwrap();
+
+    let response = client
+        .post(format!("http://{}/compose", addr))
+        .form(&[
+            ("to", "Alice <alice@example.com>"),
+            ("subject", "Re: Plans"),
+            ("body", "*Sounds* good"),
+            ("in_reply_to", "<a@example.com>"),
+            ("references", "<a@example.com>"),
+            ("markdown", "true"),
+            ("draft_id", id.as_str()),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_redirection());
+
+    let sent = sender.sent.lock().unwrap()[0].clone();
+    assert_eq!(sent.in_reply_to.as_deref(), Some("<a@example.com>"));
+    assert!(sent.html_body.unwrap().contains("<em>Sounds</em>"));
+    assert!(
+        !draft_file.exists(),
+        "draft should be removed after sending"
+    );
+}
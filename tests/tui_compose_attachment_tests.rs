@@ -0,0 +1,315 @@
+use std::sync::Arc;
+
+use whynot::{
+    client::{NotmuchClient, TagOperation},
+    config::Config,
+    error::NotmuchError,
+    mail_sender::{ComposableMessage, MailSender, message::guess_content_type},
+    outbox::Outbox,
+    thread::Message,
+    tui::app::{App, AppState, AttachmentEntry, ComposeField},
+};
+
+/// Serves the parts of the message being replied to
+struct MockNotmuchClient;
+
+#[async_trait::async_trait]
+impl NotmuchClient for MockNotmuchClient {
+    async fn search(&self, _query: &str) -> Result<Vec<whynot::search::SearchItem>, NotmuchError> {
+        Ok(vec![])
+    }
+
+    async fn search_paginated(
+        &self,
+        _query: &str,
+        _offset: usize,
+        _limit: usize,
+    ) -> Result<(Vec<whynot::search::SearchItem>, Option<usize>), NotmuchError> {
+        Ok((vec![], Some(0)))
+    }
+
+    async fn show(&self, _query: &str) -> Result<whynot::thread::Thread, NotmuchError> {
+        Err(NotmuchError::CommandFailed("Mock client".to_string()))
+    }
+
+    async fn tag(&self, _query: &str, _tags: &[TagOperation]) -> Result<(), NotmuchError> {
+        Err(NotmuchError::CommandFailed("Mock client".to_string()))
+    }
+
+    async fn refresh(&self) -> Result<(), NotmuchError> {
+        Err(NotmuchError::CommandFailed("Mock client".to_string()))
+    }
+
+    async fn insert(
+        &self,
+        _message: &[u8],
+        _folder: Option<&str>,
+        _tags: &[&str],
+    ) -> Result<String, NotmuchError> {
+        Err(NotmuchError::CommandFailed("Mock client".to_string()))
+    }
+
+    async fn config_get(&self, _key: &str) -> Result<String, NotmuchError> {
+        Err(NotmuchError::CommandFailed("Mock client".to_string()))
+    }
+
+    async fn config_set(&self, _key: &str, _value: &str) -> Result<(), NotmuchError> {
+        Err(NotmuchError::CommandFailed("Mock client".to_string()))
+    }
+
+    async fn list_tags(&self) -> Result<Vec<String>, NotmuchError> {
+        Ok(vec![])
+    }
+
+    async fn part(&self, message_id: &str, part_id: u32) -> Result<Vec<u8>, NotmuchError> {
+        match (message_id, part_id) {
+            ("id:m1@example.org", 3) => Ok(b"%PDF-1.4 report".to_vec()),
+            _ => Err(NotmuchError::CommandFailed("No such part".to_string())),
+        }
+    }
+}
+
+struct MockMailSender;
+
+#[async_trait::async_trait]
+impl MailSender for MockMailSender {
+    async fn send(&self, message: ComposableMessage) -> Result<String, NotmuchError> {
+        Ok(message.message_id)
+    }
+
+    async fn reply(
+        &self,
+        _original: &Message,
+        reply: ComposableMessage,
+        _reply_all: bool,
+    ) -> Result<String, NotmuchError> {
+        Ok(reply.message_id)
+    }
+
+    async fn forward(
+        &self,
+        _original: &Message,
+        forward: ComposableMessage,
+    ) -> Result<String, NotmuchError> {
+        Ok(forward.message_id)
+    }
+
+    async fn test_connection(&self) -> Result<(), NotmuchError> {
+        Ok(())
+    }
+
+    async fn get_from_address(&self) -> Result<String, NotmuchError> {
+        Ok("test@example.com".to_string())
+    }
+}
+
+async fn app(config: &Config) -> App {
+    App::new(
+        Arc::new(MockNotmuchClient) as Arc<dyn NotmuchClient>,
+        Some(Arc::new(MockMailSender) as Arc<dyn MailSender>),
+        config,
+    )
+    .await
+    .unwrap()
+}
+
+fn type_path(app: &mut App, path: &str) {
+    app.compose_form.attach_path.clear();
+    for c in path.chars() {
+        app.compose_handle_char(c);
+    }
+}
+
+#[test]
+fn test_guess_content_type() {
+    // Known extensions decide, even for ZIP-based formats
+    assert_eq!(guess_content_type("notes.TXT", b"\x00\x01"), "text/plain");
+    assert_eq!(
+        guess_content_type("report.docx", b"PK\x03\x04rest"),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    );
+    // Otherwise the contents do
+    assert_eq!(guess_content_type("scan", b"%PDF-1.7"), "application/pdf");
+    assert_eq!(
+        guess_content_type("photo.unknown", b"\x89PNG\r\n\x1a\n...."),
+        "image/png"
+    );
+    assert_eq!(
+        guess_content_type("archive", b"PK\x03\x04"),
+        "application/zip"
+    );
+    assert_eq!(guess_content_type("README", b"Hello\n"), "text/plain");
+    assert_eq!(
+        guess_content_type("blob.bin", b"\x00\xff\x10"),
+        "application/octet-stream"
+    );
+}
+
+#[tokio::test]
+async fn test_attach_files_with_completion() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("report-2024.pdf"), b"%PDF-1.4 quarterly").unwrap();
+    std::fs::write(dir.path().join("report-draft.txt"), b"not yet").unwrap();
+    std::fs::create_dir(dir.path().join("photos")).unwrap();
+    std::fs::write(dir.path().join("photos/cat.jpg"), b"\xff\xd8\xff\xe0meow").unwrap();
+    std::fs::write(dir.path().join(".hidden"), b"").unwrap();
+    let base = format!("{}/", dir.path().display());
+
+    let spool = tempfile::TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.mail.sending.outbox_dir = Some(spool.path().display().to_string());
+    let mut app = app(&config).await;
+    app.start_compose_new();
+    app.compose_form.to = "bob@example.com".to_string();
+    app.compose_form.subject = "Files".to_string();
+
+    // Ctrl+A opens the attachments
+    app.compose_toggle_attach();
+    assert!(matches!(
+        app.compose_form.current_field,
+        ComposeField::Attach
+    ));
+
+    // Tab completes as far as the names agree, then lists the choices
+    type_path(&mut app, &format!("{}re", base));
+    app.compose_complete_path();
+    assert_eq!(app.compose_form.attach_path, format!("{}report-", base));
+    app.compose_complete_path();
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("report-2024.pdf  report-draft.txt")
+    );
+    app.compose_handle_char('2');
+    app.compose_complete_path();
+    assert_eq!(
+        app.compose_form.attach_path,
+        format!("{}report-2024.pdf", base)
+    );
+    app.compose_attach().await.unwrap();
+    assert!(app.compose_form.attach_path.is_empty());
+
+    // Directories complete with a slash
+    type_path(&mut app, &format!("{}ph", base));
+    app.compose_complete_path();
+    assert_eq!(app.compose_form.attach_path, format!("{}photos/", base));
+    app.compose_complete_path();
+    app.compose_attach().await.unwrap();
+
+    let labels: Vec<String> = app
+        .compose_form
+        .attachment_entries()
+        .iter()
+        .map(AttachmentEntry::label)
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            "report-2024.pdf (application/pdf, 18 bytes)",
+            "cat.jpg (image/jpeg, 8 bytes)",
+        ]
+    );
+
+    // Missing files are reported and nothing is attached
+    type_path(&mut app, &format!("{}missing.pdf", base));
+    assert!(app.compose_attach().await.is_err());
+    assert_eq!(app.compose_form.attachments.len(), 2);
+
+    // Del removes the selected attachment
+    app.compose_attachment_down();
+    app.compose_remove_attachment();
+    assert_eq!(app.status_message.as_deref(), Some("Removed cat.jpg"));
+    assert_eq!(app.compose_form.attachments.len(), 1);
+    assert_eq!(app.compose_form.selected_attachment, 0);
+
+    // Ctrl+A goes back to the body
+    app.compose_toggle_attach();
+    assert!(matches!(app.compose_form.current_field, ComposeField::Body));
+
+    app.send_composed_email().await.unwrap();
+    assert!(matches!(app.state, AppState::EmailList));
+    let entries = Outbox::new(spool.path()).list().await.unwrap();
+    let attachments = &entries[0].message.attachments;
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].filename, "report-2024.pdf");
+    assert_eq!(attachments[0].content_type, "application/pdf");
+    assert_eq!(attachments[0].data, b"%PDF-1.4 quarterly");
+
+    // Undoing brings the attachments back with the form
+    app.undo_send().await;
+    assert!(matches!(app.state, AppState::Compose));
+    assert_eq!(app.compose_form.attachments.len(), 1);
+}
+
+#[tokio::test]
+async fn test_attach_part_of_original() {
+    let mut app = app(&Config::default()).await;
+    app.current_email = Some(
+        serde_json::from_value(serde_json::json!({
+            "id": "m1@example.org", "match": true, "excluded": false, "filename": [],
+            "timestamp": 1704110400, "date_relative": "2024-01-01",
+            "tags": ["inbox"], "crypto": {},
+            "body": [{"id": 1, "content-type": "multipart/mixed", "content": [
+                {"id": 2, "content-type": "text/plain", "content": "See attached"},
+                {"id": 3, "content-type": "application/pdf",
+                 "content-disposition": "attachment", "filename": "report.pdf",
+                 "content-length": 2048}
+            ]}],
+            "headers": {"Subject": "Report", "From": "bob@example.org",
+                        "To": "alice@example.com",
+                        "Date": "Mon, 1 Jan 2024 12:00:00 +0100"}
+        }))
+        .unwrap(),
+    );
+    app.start_compose_reply(false).await;
+    app.compose_toggle_attach();
+
+    // The original's attachments are offered until attached
+    let entries = app.compose_form.attachment_entries();
+    assert_eq!(entries.len(), 1);
+    assert!(matches!(entries[0], AttachmentEntry::Original(_)));
+    assert_eq!(entries[0].label(), "report.pdf (application/pdf, 2.0 KB)");
+
+    // Enter without a path attaches the selected one
+    app.compose_attach().await.unwrap();
+    assert_eq!(app.compose_form.attachments.len(), 1);
+    let attached = &app.compose_form.attachments[0];
+    assert_eq!(attached.original_part, Some(3));
+    assert_eq!(attached.attachment.data, b"%PDF-1.4 report");
+    let entries = app.compose_form.attachment_entries();
+    assert_eq!(entries.len(), 1);
+    assert!(matches!(entries[0], AttachmentEntry::Attached(_)));
+
+    // Removing it offers it again
+    app.compose_remove_attachment();
+    assert!(app.compose_form.attachments.is_empty());
+    assert!(matches!(
+        app.compose_form.attachment_entries()[0],
+        AttachmentEntry::Original(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_attachments_are_limited_in_size() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("big.bin");
+    std::fs::write(&path, vec![0u8; 1024 * 1024]).unwrap();
+
+    let mut config = Config::default();
+    config.mail.sending.max_attachment_mb = Some(1);
+    let mut app = app(&config).await;
+    app.start_compose_new();
+    app.compose_toggle_attach();
+
+    type_path(&mut app, &path.display().to_string());
+    app.compose_attach().await.unwrap();
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Attached big.bin (1.0 MB)")
+    );
+
+    // A second copy would go over the limit
+    type_path(&mut app, &path.display().to_string());
+    let error = app.compose_attach().await.unwrap_err();
+    assert!(error.to_string().contains("more than the limit of 1.0 MB"));
+    assert_eq!(app.compose_form.attachments.len(), 1);
+}
@@ -1,5 +1,6 @@
 //! Basic integration tests for TUI compose functionality
 
+use whynot::draft::Draft;
 use whynot::tui::app::{ComposeField, ComposeForm, ComposeMode};
 
 #[test]
@@ -46,3 +47,30 @@ fn test_compose_mode_enum() {
     let mode = ComposeMode::Forward("test-id".to_string());
     assert!(matches!(mode, ComposeMode::Forward(_)));
 }
+
+#[test]
+fn test_compose_form_draft_replaces_saved_copy() {
+    let mut form = ComposeForm {
+        to: "bob@example.com".to_string(),
+        subject: "Re: Plans".to_string(),
+        body: "Draft body".to_string(),
+        markdown_mode: true,
+        in_reply_to: Some("<a@example.com>".to_string()),
+        references: vec!["<a@example.com>".to_string()],
+        ..Default::default()
+    };
+    let draft = form.draft();
+    assert_eq!(draft.id, None);
+    assert!(draft.markdown);
+    assert_eq!(draft.in_reply_to.as_deref(), Some("<a@example.com>"));
+
+    // Once saved, the next save replaces that copy
+    form.saved_draft = Some(Draft {
+        id: Some("d1@whynot".to_string()),
+        ..draft
+    });
+    form.body.push_str(", edited");
+    let draft = form.draft();
+    assert_eq!(draft.id.as_deref(), Some("d1@whynot"));
+    assert_ne!(form.saved_draft.as_ref(), Some(&draft));
+}
@@ -101,7 +101,7 @@ async fn test_compose_mode_indicator_shows_plain_by_default() {
     config.ui.tui.markdown_compose = Some(false);
 
     let client = Arc::new(MockNotmuchClient) as Arc<dyn whynot::client::NotmuchClient>;
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(Arc::new(MockMailSender) as Arc<dyn MailSender>);
     let mut app = App::new(client, mail_sender, &config).await.unwrap();
     
     // Start compose mode
@@ -133,7 +133,7 @@ async fn test_compose_mode_indicator_shows_markdown_when_enabled() {
     config.ui.tui.markdown_compose = Some(true);
 
     let client = Arc::new(MockNotmuchClient) as Arc<dyn whynot::client::NotmuchClient>;
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(Arc::new(MockMailSender) as Arc<dyn MailSender>);
     let mut app = App::new(client, mail_sender, &config).await.unwrap();
     
     // Start compose mode (should start in markdown mode due to config)
@@ -163,7 +163,7 @@ async fn test_mode_indicator_updates_when_toggled() {
     config.ui.tui.markdown_compose = Some(false); // Start in plain mode
 
     let client = Arc::new(MockNotmuchClient) as Arc<dyn whynot::client::NotmuchClient>;
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(Arc::new(MockMailSender) as Arc<dyn MailSender>);
     let mut app = App::new(client, mail_sender, &config).await.unwrap();
     
     app.start_compose_new();
@@ -209,7 +209,7 @@ async fn test_compose_instructions_mention_ctrl_m_toggle() {
     config.ui.tui.markdown_compose = Some(false);
 
     let client = Arc::new(MockNotmuchClient) as Arc<dyn whynot::client::NotmuchClient>;
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(Arc::new(MockMailSender) as Arc<dyn MailSender>);
     let mut app = App::new(client, mail_sender, &config).await.unwrap();
     
     app.start_compose_new();
@@ -231,7 +231,7 @@ async fn test_mode_indicator_persists_across_field_navigation() {
     config.ui.tui.markdown_compose = Some(true); // Start in markdown mode
 
     let client = Arc::new(MockNotmuchClient) as Arc<dyn whynot::client::NotmuchClient>;
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(Arc::new(MockMailSender) as Arc<dyn MailSender>);
     let mut app = App::new(client, mail_sender, &config).await.unwrap();
     
     app.start_compose_new();
@@ -268,7 +268,7 @@ async fn test_different_compose_modes_show_indicator() {
     config.ui.tui.markdown_compose = Some(false);
 
     let client = Arc::new(MockNotmuchClient) as Arc<dyn whynot::client::NotmuchClient>;
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(Arc::new(MockMailSender) as Arc<dyn MailSender>);
     let mut app = App::new(client, mail_sender, &config).await.unwrap();
     
     // Test New mode with markdown enabled
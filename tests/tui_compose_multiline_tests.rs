@@ -101,7 +101,7 @@ impl MailSender for MockMailSender {
 #[tokio::test]
 async fn test_compose_multiline_body_input() {
     let client = std::sync::Arc::new(MockNotmuchClient);
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(std::sync::Arc::new(MockMailSender) as std::sync::Arc<dyn MailSender>);
     let config = Config::default();
     let mut app = App::new(
         client as std::sync::Arc<dyn NotmuchClient>,
@@ -159,7 +159,7 @@ async fn test_compose_multiline_body_input() {
 #[tokio::test]
 async fn test_compose_enter_behavior_in_non_body_fields() {
     let client = std::sync::Arc::new(MockNotmuchClient);
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(std::sync::Arc::new(MockMailSender) as std::sync::Arc<dyn MailSender>);
     let config = Config::default();
     let mut app = App::new(
         client as std::sync::Arc<dyn NotmuchClient>,
@@ -209,7 +209,7 @@ async fn test_compose_enter_behavior_in_non_body_fields() {
 #[tokio::test]
 async fn test_compose_backspace_with_multiline_content() {
     let client = std::sync::Arc::new(MockNotmuchClient);
-    let mail_sender = Some(Box::new(MockMailSender) as Box<dyn MailSender>);
+    let mail_sender = Some(std::sync::Arc::new(MockMailSender) as std::sync::Arc<dyn MailSender>);
     let config = Config::default();
     let mut app = App::new(
         client as std::sync::Arc<dyn NotmuchClient>,
@@ -47,6 +47,7 @@ async fn test_tui_html_conversion_basic() {
             content: BodyContent::Text(html_content.to_string()),
             content_disposition: None,
             content_id: None,
+            content_charset: None,
             filename: None,
             content_transfer_encoding: None,
             content_length: None,
@@ -113,6 +114,7 @@ async fn test_tui_plain_text_passthrough() {
             content: BodyContent::Text(plain_text.to_string()),
             content_disposition: None,
             content_id: None,
+            content_charset: None,
             filename: None,
             content_transfer_encoding: None,
             content_length: None,
@@ -189,6 +191,7 @@ async fn test_tui_multipart_mixed_html_content() {
                     content: BodyContent::Text(html_content.to_string()),
                     content_disposition: None,
                     content_id: None,
+                    content_charset: None,
                     filename: None,
                     content_transfer_encoding: None,
                     content_length: None,
@@ -199,6 +202,7 @@ async fn test_tui_multipart_mixed_html_content() {
                     content: BodyContent::Empty,
                     content_disposition: Some("attachment".to_string()),
                     content_id: None,
+                    content_charset: None,
                     filename: Some("receipt (SE42324486337).pdf".to_string()),
                     content_transfer_encoding: Some("base64".to_string()),
                     content_length: Some(60574),
@@ -206,6 +210,7 @@ async fn test_tui_multipart_mixed_html_content() {
             ]),
             content_disposition: None,
             content_id: None,
+            content_charset: None,
             filename: None,
             content_transfer_encoding: None,
             content_length: None,
@@ -269,6 +274,7 @@ async fn test_tui_mixed_content_prefers_plain_text() {
                 content: BodyContent::Text(plain_text.to_string()),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
@@ -279,6 +285,7 @@ async fn test_tui_mixed_content_prefers_plain_text() {
                 content: BodyContent::Text(html_content.to_string()),
                 content_disposition: None,
                 content_id: None,
+                content_charset: None,
                 filename: None,
                 content_transfer_encoding: None,
                 content_length: None,
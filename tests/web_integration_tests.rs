@@ -12,6 +12,7 @@ async fn spawn_test_server() -> (SocketAddr, AppState) {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -68,6 +69,21 @@ async fn test_inbox_returns_ok() {
     assert!(body.contains("Inbox"));
 }
 
+#[tokio::test]
+async fn test_archive_requires_configured_folder() {
+    let (addr, _state) = spawn_test_server().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/thread/0000000000000001/archive", addr))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Archiving is not configured"));
+}
+
 #[cfg(feature = "test-utils")]
 #[tokio::test]
 async fn test_inbox_displays_messages() {
@@ -105,6 +121,7 @@ async fn test_inbox_displays_messages() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {
@@ -375,6 +392,7 @@ async fn test_thread_view_displays_message_content() {
         initial_page_size: 20,
         pagination_size: 10,
         infinite_scroll_enabled: true,
+        ..Default::default()
     };
 
     let state = AppState {